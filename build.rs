@@ -0,0 +1,15 @@
+use std::process::Command;
+
+fn main() {
+    // The compiler version is only known while building, so it is handed to the engine through the environment.
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_else(|| "unknown rustc".to_string());
+
+    println!("cargo:rustc-env=SACRE_DIEU_RUSTC_VERSION={}", version.trim());
+    println!("cargo:rerun-if-changed=build.rs");
+}
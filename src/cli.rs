@@ -0,0 +1,139 @@
+use std::{process::ExitCode, str::FromStr};
+
+/// The arguments following a subcommand on the command line: positional arguments first,
+/// then options, which are either flags or followed by a value.
+pub struct Args<'a> {
+    /// The subcommand the arguments belong to, for error messages.
+    subcommand: &'a Subcommand,
+    /// The arguments which haven't been consumed yet.
+    args: &'a [String]
+}
+
+impl<'a> Args<'a> {
+    /// Wraps the arguments following a subcommand.
+    pub fn new(subcommand: &'a Subcommand, args: &'a [String]) -> Self {
+        Args { subcommand, args }
+    }
+
+    /// Consumes the next argument, if any is left.
+    fn next(&mut self) -> Option<&'a str> {
+        let (arg, rest) = self.args.split_first()?;
+        self.args = rest;
+        Some(arg.as_str())
+    }
+
+    /// Consumes a positional argument which has to be given.
+    pub fn required(&mut self, name: &str) -> Result<&'a str, String> {
+        self.next().ok_or_else(|| format!("missing {} argument", name))
+    }
+
+    /// Consumes a positional argument which has to be given, parsed into a value.
+    pub fn required_value<T: FromStr>(&mut self, name: &str) -> Result<T, String> {
+        let arg = self.required(name)?;
+        arg.parse::<T>().map_err(|_| format!("failed to parse {} argument {}", name, arg))
+    }
+
+    /// Consumes the next argument as the name of an option, if any is left.
+    pub fn option(&mut self) -> Option<&'a str> {
+        self.next()
+    }
+
+    /// Consumes the argument following an option.
+    pub fn string(&mut self, option: &str) -> Result<&'a str, String> {
+        self.next().ok_or_else(|| format!("missing {} argument", option))
+    }
+
+    /// Consumes the argument following an option, parsed into a value.
+    pub fn value<T: FromStr>(&mut self, option: &str) -> Result<T, String> {
+        let arg = self.string(option)?;
+        arg.parse::<T>().map_err(|_| format!("failed to parse {} argument {}", option, arg))
+    }
+
+    /// Consumes every argument left, which some subcommands take as a single space separated argument (such as a FEN).
+    pub fn rest(&mut self) -> Option<String> {
+        let rest = (!self.args.is_empty()).then(|| self.args.join(" "));
+        self.args = &[];
+        rest
+    }
+
+    /// The error for an option the subcommand doesn't know.
+    pub fn unknown(&self, option: &str) -> String {
+        format!("unknown {} option {}", self.subcommand.name, option)
+    }
+}
+
+/// A subcommand the engine runs instead of the UCI loop, for scripting and CI-style usage.
+pub struct Subcommand {
+    /// The name of the subcommand, which is the first argument.
+    pub name: &'static str,
+    /// The arguments the subcommand takes.
+    pub usage: &'static str,
+    /// What the subcommand does, in a few words.
+    pub description: &'static str,
+    /// Runs the subcommand. Errors in its arguments are reported along with its usage.
+    pub run: fn(&mut Args) -> Result<ExitCode, String>
+}
+
+/// The usage of every subcommand, one per line.
+pub fn usage(subcommands: &[Subcommand]) -> String {
+    let width = subcommands.iter().map(|subcommand| subcommand.name.len()).max().unwrap_or_default();
+    let lines: Vec<String> = subcommands
+        .iter()
+        .map(|subcommand| format!("  {:width$}  {}\n  {:width$}    {} {}", subcommand.name, subcommand.description, "", subcommand.name, subcommand.usage))
+        .collect();
+
+    format!("usage: sacre_dieu [subcommand] [arguments]\n\nwithout a subcommand, the engine speaks UCI on stdin.\n\nsubcommands:\n{}", lines.join("\n"))
+}
+
+/// Runs the subcommand named by the first argument. Unknown subcommands and invalid arguments
+/// are reported with the usage and exit with a code of 2, so scripts can tell them apart from failures.
+pub fn run(subcommands: &[Subcommand], args: &[String]) -> ExitCode {
+    let Some((name, rest)) = args.split_first() else {
+        eprintln!("{}", usage(subcommands));
+        return ExitCode::from(2);
+    };
+
+    if matches!(name.as_str(), "help" | "--help" | "-h") {
+        println!("{}", usage(subcommands));
+        return ExitCode::SUCCESS;
+    }
+
+    let Some(subcommand) = subcommands.iter().find(|subcommand| subcommand.name == name) else {
+        eprintln!("unknown subcommand {}\n\n{}", name, usage(subcommands));
+        return ExitCode::from(2);
+    };
+
+    match (subcommand.run)(&mut Args::new(subcommand, rest)) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{}\nusage: {} {}", error, subcommand.name, subcommand.usage);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitCode;
+
+    use super::{Args, Subcommand};
+
+    #[test]
+    fn test_args() {
+        let subcommand = Subcommand { name: "perft", usage: "<depth> [fen]", description: "", run: |_| Ok(ExitCode::SUCCESS) };
+        let args: Vec<String> = ["5", "nodes", "100", "nodes", "x", "flag"].iter().map(|arg| arg.to_string()).collect();
+        let mut args = Args::new(&subcommand, &args);
+
+        assert_eq!(args.required_value::<usize>("depth"), Ok(5));
+        assert_eq!(args.option(), Some("nodes"));
+        assert_eq!(args.value::<usize>("nodes"), Ok(100));
+
+        // Errors name the argument, so they can be reported along with the usage.
+        assert_eq!(args.option(), Some("nodes"));
+        assert_eq!(args.value::<usize>("nodes"), Err("failed to parse nodes argument x".to_string()));
+        assert_eq!(args.option(), Some("flag"));
+        assert_eq!(args.unknown("flag"), "unknown perft option flag");
+        assert_eq!(args.string("flag"), Err("missing flag argument".to_string()));
+        assert_eq!(args.rest(), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! The board format of the bullet trainer, which stores positions from the perspective of
+//! the side to move: a position with black to move is flipped, so "white" is always the side
+//! to move. Castling rights, en passant and the move counters are not stored.
+
+use strum::IntoEnumIterator;
+
+use crate::utils::{board::Bitboard, consts::PACKED_POSITION_SIZE, piece::{Piece, PieceColor, PieceType, Tile}};
+use super::{board_from_pieces, text::TrainingPosition, PackedPosition};
+
+/// A position in bulletformat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BulletFormat {
+    /// The occupied tiles.
+    occupancy: u64,
+    /// The pieces in order of the occupied tiles, as nibbles of the piece type (with bit 3 set for the opponent).
+    pieces: [u8; 16],
+    /// The score relative to the side to move, in centipawns.
+    score: i16,
+    /// The result relative to the side to move (0 for a loss, 1 for a draw, 2 for a win).
+    result: u8,
+    /// The tile of the king of the side to move.
+    king_tile: u8,
+    /// The tile of the opponent's king, flipped vertically.
+    opponent_king_tile: u8
+}
+
+impl PackedPosition for BulletFormat {
+    fn pack(position: &TrainingPosition) -> Self {
+        let board = &position.board;
+        let side = board.side_to_move;
+
+        // Flipping a tile vertically is the same as flipping its rank bits.
+        let flip = |index: usize| if side == PieceColor::Black { index ^ 56 } else { index };
+        let occupancy = if side == PieceColor::Black { board.occupied().board.swap_bytes() } else { board.occupied().board };
+
+        let score = position.score.unwrap_or(0).clamp(-(i16::MAX as i32), i16::MAX as i32) as i16;
+        let mut packed = BulletFormat {
+            occupancy,
+            pieces: [0; 16],
+            score: if side == PieceColor::White { score } else { -score },
+            result: ((if side == PieceColor::White { position.result } else { 1.0 - position.result }) * 2.0).round() as u8,
+            king_tile: flip(board.king_tile(side).index()) as u8,
+            opponent_king_tile: flip(board.king_tile(!side).index()) as u8 ^ 56
+        };
+
+        let mut tiles = Bitboard::new(occupancy);
+        let mut index = 0;
+        while tiles != Bitboard::ZERO {
+            let tile = tiles.pop_lsb();
+            let piece = board.piece_at(Tile::from(flip(tile.index()) as u8)).expect("occupied tile should have a piece");
+
+            let nibble = piece.piece_type as u8 | ((piece.piece_color != side) as u8) << 3;
+            packed.pieces[index / 2] |= nibble << (4 * (index % 2));
+            index += 1;
+        }
+
+        packed
+    }
+
+    fn unpack(&self) -> TrainingPosition {
+        let mut pieces = Vec::new();
+        let mut tiles = Bitboard::new(self.occupancy);
+
+        while tiles != Bitboard::ZERO {
+            let nibble = self.pieces[pieces.len() / 2] >> (4 * (pieces.len() % 2)) & 0xF;
+            let piece_type = PieceType::iter().nth((nibble & 0b111) as usize).expect("invalid piece in bulletformat");
+            let piece_color = if nibble & 0b1000 == 0 { PieceColor::White } else { PieceColor::Black };
+
+            pieces.push((tiles.pop_lsb(), Piece::new(piece_type, piece_color)));
+        }
+
+        let board = board_from_pieces(&pieces, PieceColor::White, "", None, 0);
+        TrainingPosition { board, ply: 0, score: Some(self.score as i32), result: self.result as f32 / 2.0 }
+    }
+
+    fn to_bytes(&self) -> [u8; PACKED_POSITION_SIZE] {
+        let mut bytes = [0; PACKED_POSITION_SIZE];
+
+        bytes[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces);
+        bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+        bytes[26] = self.result;
+        bytes[27] = self.king_tile;
+        bytes[28] = self.opponent_king_tile;
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; PACKED_POSITION_SIZE]) -> Self {
+        BulletFormat {
+            occupancy: u64::from_le_bytes(bytes[0..8].try_into().expect("slice should be 8 bytes")),
+            pieces: bytes[8..24].try_into().expect("slice should be 16 bytes"),
+            score: i16::from_le_bytes([bytes[24], bytes[25]]),
+            result: bytes[26],
+            king_tile: bytes[27],
+            opponent_king_tile: bytes[28]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{text::TrainingPosition, PackedPosition};
+
+    use super::BulletFormat;
+
+    #[test]
+    fn test_bulletformat() {
+        let start = TrainingPosition::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 | 20 | 1-0").expect("expected a valid line");
+        let bytes = BulletFormat::pack(&start).to_bytes();
+
+        // Occupancy, then a1 rook and b1 knight in the first byte of pieces, then the score, result and kings.
+        assert_eq!(&bytes[0..9], &[0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0x13]);
+        assert_eq!(&bytes[24..32], &[20, 0, 2, 4, 4, 0, 0, 0]);
+
+        // Positions with black to move are flipped, with the score and result relative to black.
+        let position = TrainingPosition::parse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1 | 30 | 1-0").expect("expected a valid line");
+        let unpacked = BulletFormat::from_bytes(&BulletFormat::pack(&position).to_bytes()).unpack();
+        assert_eq!(unpacked.to_line(), "rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w - - 0 1 | -30 | 0.0");
+    }
+}
@@ -0,0 +1,251 @@
+//! Self-play data generation, run with `sacre_dieu datagen <output> [options]`.
+//!
+//! Games start with a few random plies and are then played out with node limited searches,
+//! recording every quiet position with its score. Games whose outcome is already clear are
+//! adjudicated early, which spends far fewer nodes on positions that add little information.
+
+use std::sync::{atomic::AtomicBool, Arc};
+
+use arrayvec::ArrayVec;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{engine::{search::{SearchLimits, Searcher}, time_manager::TimeManager}, utils::{board::Board, consts::{ADJUDICATION_DRAW_MIN_PLY, ADJUDICATION_DRAW_PLIES, ADJUDICATION_DRAW_SCORE, ADJUDICATION_WIN_PLIES, ADJUDICATION_WIN_SCORE, DATAGEN_DEFAULT_NODES, DATAGEN_DEFAULT_RANDOM_PLIES, DEEPEST_PROVEN_WIN}, pgn::{GameResult, PgnGame}, piece::PieceColor, piece_move::MoveArray}};
+use super::text::TrainingPosition;
+
+/// When a game may be ended before it is decided on the board.
+#[derive(Debug, Clone, Copy)]
+pub struct AdjudicationRules {
+    /// The score (in centipawns) beyond which a side is considered winning.
+    pub win_score: i32,
+    /// The number of consecutive plies both sides have to agree on a winner for.
+    pub win_plies: usize,
+    /// The score (in centipawns) within which a position is considered drawn.
+    pub draw_score: i32,
+    /// The number of consecutive plies the score has to stay drawn for.
+    pub draw_plies: usize,
+    /// The ply before which games are never adjudicated as draws.
+    pub draw_min_ply: usize
+}
+
+impl Default for AdjudicationRules {
+    fn default() -> Self {
+        AdjudicationRules {
+            win_score: ADJUDICATION_WIN_SCORE,
+            win_plies: ADJUDICATION_WIN_PLIES,
+            draw_score: ADJUDICATION_DRAW_SCORE,
+            draw_plies: ADJUDICATION_DRAW_PLIES,
+            draw_min_ply: ADJUDICATION_DRAW_MIN_PLY
+        }
+    }
+}
+
+/// Tracks the scores of a game to decide when it can be adjudicated.
+#[derive(Debug, Clone)]
+pub struct Adjudicator {
+    rules: AdjudicationRules,
+    /// The side the last scores favored, and for how many plies.
+    win_streak: (PieceColor, usize),
+    /// The number of plies the score has been drawn for.
+    draw_streak: usize
+}
+
+impl Adjudicator {
+    /// Creates an adjudicator for a new game.
+    pub fn new(rules: AdjudicationRules) -> Self {
+        Adjudicator { rules, win_streak: (PieceColor::White, 0), draw_streak: 0 }
+    }
+
+    /// Records the score (from white's perspective) of the search at a ply, returning the result if the game can be adjudicated.
+    pub fn update(&mut self, ply: usize, score: i32) -> Option<GameResult> {
+        if score.abs() >= self.rules.win_score {
+            let winner = if score > 0 { PieceColor::White } else { PieceColor::Black };
+            self.win_streak = (winner, if self.win_streak.0 == winner { self.win_streak.1 + 1 } else { 1 });
+        } else {
+            self.win_streak.1 = 0;
+        }
+
+        if score.abs() <= self.rules.draw_score && ply >= self.rules.draw_min_ply {
+            self.draw_streak += 1;
+        } else {
+            self.draw_streak = 0;
+        }
+
+        match self.win_streak {
+            (PieceColor::White, streak) if streak >= self.rules.win_plies => Some(GameResult::WhiteWin),
+            (PieceColor::Black, streak) if streak >= self.rules.win_plies => Some(GameResult::BlackWin),
+            _ if self.draw_streak >= self.rules.draw_plies => Some(GameResult::Draw),
+            _ => None
+        }
+    }
+}
+
+/// The configuration of a data generation run.
+#[derive(Debug, Clone)]
+pub struct DatagenConfig {
+    /// The number of nodes searched for every move.
+    pub nodes: usize,
+    /// The number of random plies every game starts with.
+    pub random_plies: usize,
+    /// When games are adjudicated, or nothing to always play them out.
+    pub adjudication: Option<AdjudicationRules>,
+    /// The seed of the random openings.
+    pub seed: u64
+}
+
+impl Default for DatagenConfig {
+    fn default() -> Self {
+        DatagenConfig { nodes: DATAGEN_DEFAULT_NODES, random_plies: DATAGEN_DEFAULT_RANDOM_PLIES, adjudication: Some(AdjudicationRules::default()), seed: 0 }
+    }
+}
+
+/// Plays self-play games, recording the positions of each one.
+pub struct DataGenerator {
+    config: DatagenConfig,
+    searcher: Searcher,
+    rng: StdRng,
+    /// The moves of the last game, annotated with the search behind each of them.
+    game: PgnGame
+}
+
+impl DataGenerator {
+    /// Creates a generator with its own searcher.
+    pub fn new(config: DatagenConfig) -> Self {
+        let searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let rng = StdRng::seed_from_u64(config.seed);
+
+        DataGenerator { config, searcher, rng, game: PgnGame::default() }
+    }
+
+    /// Generates every legal move in a position.
+    fn legal_moves(board: &Board) -> MoveArray {
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+        moves.retain(|piece_move| board.is_legal(*piece_move));
+
+        moves
+    }
+
+    /// The last game played, with every searched move commented with its score, depth and principal variation.
+    pub fn last_game(&self) -> &PgnGame {
+        &self.game
+    }
+
+    /// Plays a game, returning its positions labelled with the result and whether it was adjudicated.
+    ///
+    /// Openings which end the game during the random plies produce no positions.
+    pub fn play_game(&mut self) -> (Vec<TrainingPosition>, bool) {
+        let mut board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        self.searcher.reset_heuristics();
+        self.searcher.past_boards = vec![board.zobrist_key];
+        self.game = PgnGame::default();
+
+        for _ in 0..self.config.random_plies {
+            let moves = Self::legal_moves(&board);
+            let Some(piece_move) = moves.choose(&mut self.rng) else { return (Vec::new(), false); };
+
+            self.game.push_move(piece_move.to_san(&board), Some("random".to_string()));
+            board.play(piece_move, &mut self.searcher.past_boards);
+        }
+
+        let limits = SearchLimits { nodes: Some(self.config.nodes), ..Default::default() };
+        let mut adjudicator = self.config.adjudication.map(Adjudicator::new);
+        let (mut positions, mut ply) = (Vec::new(), self.config.random_plies);
+
+        let (result, adjudicated) = loop {
+            let side = board.side_to_move;
+            if let Some(winner) = board.variant_winner() {
+                break (GameResult::win_for(winner), false);
+            }
+
+            if Self::legal_moves(&board).is_empty() {
+                let result = if board.in_check(side) { GameResult::win_for(!side) } else { GameResult::Draw };
+                break (result, false);
+            }
+
+            let repetitions = self.searcher.past_boards.iter().filter(|key| **key == board.zobrist_key).count();
+            if board.half_move_counter >= 100 || repetitions >= 3 {
+                break (GameResult::Draw, false);
+            }
+
+            let eval = self.searcher.start(&board, &limits);
+            let score = eval * if side == PieceColor::White { 1 } else { -1 };
+            if let Some(adjudicator) = adjudicator.as_mut() && let Some(result) = adjudicator.update(ply, score) {
+                break (result, true);
+            }
+
+            // Positions in check or with a forced mate teach little about static evaluation.
+            if !board.in_check(side) && score.abs() < DEEPEST_PROVEN_WIN {
+                positions.push(TrainingPosition { board: board.clone(), ply, score: Some(score), result: 0.5 });
+            }
+
+            let best_move = self.searcher.best_move.expect("search should return a move");
+            self.game.push_move(best_move.to_san(&board), Some(self.searcher.pgn_comment(&board, eval)));
+            board.play(&best_move, &mut self.searcher.past_boards);
+            ply += 1;
+        };
+
+        self.game.result = result;
+        self.game.headers = vec![
+            ("Event".to_string(), "sacre_dieu datagen".to_string()),
+            ("White".to_string(), "Sacre Dieu".to_string()),
+            ("Black".to_string(), "Sacre Dieu".to_string()),
+            ("Result".to_string(), result.to_pgn().to_string())
+        ];
+
+        if adjudicated {
+            self.game.headers.push(("Termination".to_string(), "adjudication".to_string()));
+        }
+
+        let result = match result {
+            GameResult::WhiteWin => 1.0,
+            GameResult::BlackWin => 0.0,
+            _ => 0.5
+        };
+
+        positions.iter_mut().for_each(|position| position.result = result);
+        (positions, adjudicated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::pgn::GameResult;
+
+    use super::{AdjudicationRules, Adjudicator, DataGenerator, DatagenConfig};
+
+    #[test]
+    fn test_adjudication() {
+        let rules = AdjudicationRules { win_score: 500, win_plies: 3, draw_score: 10, draw_plies: 4, draw_min_ply: 20 };
+
+        // Both sides have to agree on the winner for consecutive plies.
+        let mut adjudicator = Adjudicator::new(rules);
+        assert_eq!([600, -700, 600, 800].map(|score| adjudicator.update(10, score)), [None, None, None, None]);
+        assert_eq!(adjudicator.update(11, 900), Some(GameResult::WhiteWin));
+
+        let mut adjudicator = Adjudicator::new(rules);
+        assert_eq!([-600, -550, 100, -600, -600].map(|score| adjudicator.update(10, score)), [None; 5]);
+        assert_eq!(adjudicator.update(11, -600), Some(GameResult::BlackWin));
+
+        // Drawn scores only count past the minimum ply.
+        let mut adjudicator = Adjudicator::new(rules);
+        assert_eq!((16..23).map(|ply| adjudicator.update(ply, 0)).collect::<Vec<_>>(), vec![None; 7]);
+        assert_eq!(adjudicator.update(23, 5), Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn test_datagen() {
+        let mut generator = DataGenerator::new(DatagenConfig { nodes: 500, ..Default::default() });
+        let (positions, _) = generator.play_game();
+
+        assert!(!positions.is_empty());
+        assert!(positions.iter().all(|position| position.result == positions[0].result && position.score.is_some()));
+        assert!(positions.windows(2).all(|pair| pair[0].ply < pair[1].ply));
+
+        // Every searched move carries its evaluation, the random opening moves are only marked as such.
+        let game = generator.last_game();
+        assert_eq!(game.moves.len(), game.comments.len());
+        assert!(game.comments.iter().take(DatagenConfig::default().random_plies).all(|comment| comment.as_deref() == Some("random")));
+        assert!(game.comments.iter().skip(DatagenConfig::default().random_plies).all(|comment| comment.as_ref().is_some_and(|comment| comment.starts_with("[%eval "))));
+        assert!(game.to_pgn().trim_end().ends_with(game.result.to_pgn()));
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::utils::consts::{FILTER_DEFAULT_EXIT_PLIES, FILTER_DEFAULT_SCORE_BOUND, FILTER_DEFAULT_SEED};
+use super::text::TrainingPosition;
+
+/// The rules positions have to pass to be kept in a dataset.
+#[derive(Debug, Clone)]
+pub struct DataFilter {
+    /// The ply at which the opening book the games were played from ends.
+    pub book_plies: usize,
+    /// The number of plies after the book exit whose positions are dropped.
+    pub exit_plies: usize,
+    /// The largest absolute score (in centipawns) a position may have.
+    pub score_bound: i32,
+    /// The seed of the shuffle.
+    pub seed: u64
+}
+
+impl Default for DataFilter {
+    fn default() -> Self {
+        DataFilter { book_plies: 0, exit_plies: FILTER_DEFAULT_EXIT_PLIES, score_bound: FILTER_DEFAULT_SCORE_BOUND, seed: FILTER_DEFAULT_SEED }
+    }
+}
+
+/// The number of positions dropped by each rule.
+#[derive(Debug, Clone, Default)]
+pub struct FilterStats {
+    /// The number of positions read.
+    pub read: usize,
+    /// Positions seen earlier in the dataset.
+    pub duplicates: usize,
+    /// Positions too close to the opening book.
+    pub opening: usize,
+    /// Positions with the side to move in check.
+    pub in_check: usize,
+    /// Positions with a score beyond the bound.
+    pub score: usize,
+    /// The number of positions kept.
+    pub kept: usize
+}
+
+impl DataFilter {
+    /// Filters a set of positions, keeping the first occurrence of every position and shuffling the result.
+    pub fn apply(&self, positions: impl Iterator<Item = TrainingPosition>) -> (Vec<TrainingPosition>, FilterStats) {
+        let mut stats = FilterStats::default();
+        let mut seen = HashSet::new();
+
+        let mut kept: Vec<TrainingPosition> = positions.filter(|position| {
+            stats.read += 1;
+
+            if position.ply < self.book_plies + self.exit_plies {
+                stats.opening += 1;
+            } else if position.board.in_check(position.board.side_to_move) {
+                stats.in_check += 1;
+            } else if position.score.is_some_and(|score| score.abs() > self.score_bound) {
+                stats.score += 1;
+            } else if !seen.insert(position.board.zobrist_key) {
+                stats.duplicates += 1;
+            } else {
+                return true;
+            }
+
+            false
+        }).collect();
+
+        kept.shuffle(&mut StdRng::seed_from_u64(self.seed));
+        stats.kept = kept.len();
+
+        (kept, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::text::TrainingPosition;
+
+    use super::DataFilter;
+
+    #[test]
+    fn test_data_filter() {
+        let lines = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 | 30 | 0.5",
+            "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14 | 40 | 1.0",
+            "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 20 | 45 | 1.0",
+            "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24 | -50 | 0-1",
+            "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44 | 3500 | 1.0",
+            "4k3/8/8/8/8/8/4R3/4K3 b - - 0 40 | 600 | 1.0",
+            "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54 [0.5]"
+        ];
+
+        let filter = DataFilter { book_plies: 4, exit_plies: 8, ..Default::default() };
+        let (kept, stats) = filter.apply(lines.iter().filter_map(|line| TrainingPosition::parse(line)));
+
+        assert_eq!((stats.read, stats.opening, stats.duplicates, stats.score, stats.in_check, stats.kept), (7, 1, 1, 1, 1, 3));
+
+        let mut kept: Vec<String> = kept.iter().map(TrainingPosition::to_line).collect();
+        kept.sort();
+        assert_eq!(kept, vec![
+            "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24 | -50 | 0.0",
+            "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54 | 0.5",
+            "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14 | 40 | 1.0"
+        ]);
+    }
+}
@@ -0,0 +1,160 @@
+//! The board format of marlinflow, which stores the full position with the score and result
+//! relative to white. Rooks which can still castle are stored as a separate piece type.
+
+use strum::IntoEnumIterator;
+
+use crate::utils::{board::Bitboard, consts::PACKED_POSITION_SIZE, piece::{CastleRights, Piece, PieceColor, PieceType, Tile}};
+use super::{board_from_pieces, text::TrainingPosition, PackedPosition};
+
+/// The piece type nibble of a rook which can still castle.
+const UNMOVED_ROOK: u8 = 6;
+/// The en passant tile stored when there is none.
+const NO_EN_PASSANT: u8 = 64;
+
+/// A position in marlinformat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarlinFormat {
+    /// The occupied tiles.
+    occupancy: u64,
+    /// The pieces in order of the occupied tiles, as nibbles of the piece type (with bit 3 set for black).
+    pieces: u128,
+    /// The en passant tile, with bit 7 set if black is to move.
+    stm_en_passant: u8,
+    /// The half move counter.
+    half_move_counter: u8,
+    /// The full move number.
+    full_moves: u16,
+    /// The score relative to white, in centipawns.
+    score: i16,
+    /// The result relative to white (0 for a loss, 1 for a draw, 2 for a win).
+    result: u8
+}
+
+impl PackedPosition for MarlinFormat {
+    fn pack(position: &TrainingPosition) -> Self {
+        let board = &position.board;
+
+        let mut packed = MarlinFormat {
+            occupancy: board.occupied().board,
+            pieces: 0,
+            stm_en_passant: board.en_passant.map_or(NO_EN_PASSANT, |tile| tile.index() as u8) | ((board.side_to_move == PieceColor::Black) as u8) << 7,
+            half_move_counter: board.half_move_counter,
+            full_moves: (position.ply / 2 + 1) as u16,
+            score: position.score.unwrap_or(0).clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            result: (position.result * 2.0).round() as u8
+        };
+
+        let mut tiles = board.occupied();
+        let mut index = 0;
+        while tiles != Bitboard::ZERO {
+            let tile = tiles.pop_lsb();
+            let piece = board.piece_at(tile).expect("occupied tile should have a piece");
+
+            let home_rank = if piece.piece_color == PieceColor::White { 0 } else { 7 };
+            let castling_side = match tile.file {
+                0 => Some(CastleRights::QueenSide),
+                7 => Some(CastleRights::KingSide),
+                _ => None
+            };
+
+            let rights = board.castle_rights[piece.piece_color.to_index()];
+            let unmoved = piece.piece_type == PieceType::Rook && tile.rank == home_rank
+                && castling_side.is_some_and(|side| rights == side || rights == CastleRights::Both);
+
+            let piece_type = if unmoved { UNMOVED_ROOK } else { piece.piece_type as u8 };
+            packed.pieces |= ((piece_type | ((piece.piece_color == PieceColor::Black) as u8) << 3) as u128) << (4 * index);
+            index += 1;
+        }
+
+        packed
+    }
+
+    fn unpack(&self) -> TrainingPosition {
+        let mut pieces = Vec::new();
+        let mut castle_rights = String::new();
+        let mut tiles = Bitboard::new(self.occupancy);
+
+        while tiles != Bitboard::ZERO {
+            let nibble = (self.pieces >> (4 * pieces.len()) & 0xF) as u8;
+            let tile = tiles.pop_lsb();
+            let piece_color = if nibble & 0b1000 == 0 { PieceColor::White } else { PieceColor::Black };
+
+            let piece_type = if nibble & 0b111 == UNMOVED_ROOK {
+                let right = if tile.file == 7 { 'k' } else { 'q' };
+                castle_rights.push(if piece_color == PieceColor::White { right.to_ascii_uppercase() } else { right });
+
+                PieceType::Rook
+            } else {
+                PieceType::iter().nth((nibble & 0b111) as usize).expect("invalid piece in marlinformat")
+            };
+
+            pieces.push((tile, Piece::new(piece_type, piece_color)));
+        }
+
+        // Castling rights are written in the usual KQkq order.
+        let mut castle_rights: Vec<char> = castle_rights.chars().collect();
+        castle_rights.sort_by_key(|right| "KQkq".find(*right));
+
+        let side_to_move = if self.stm_en_passant >> 7 == 0 { PieceColor::White } else { PieceColor::Black };
+        let en_passant = Tile::from_index(self.stm_en_passant & 0x7F);
+
+        let board = board_from_pieces(&pieces, side_to_move, &castle_rights.iter().collect::<String>(), en_passant, self.half_move_counter);
+        let ply = (self.full_moves.max(1) as usize - 1) * 2 + (side_to_move == PieceColor::Black) as usize;
+
+        TrainingPosition { board, ply, score: Some(self.score as i32), result: self.result as f32 / 2.0 }
+    }
+
+    fn to_bytes(&self) -> [u8; PACKED_POSITION_SIZE] {
+        let mut bytes = [0; PACKED_POSITION_SIZE];
+
+        bytes[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces.to_le_bytes());
+        bytes[24] = self.stm_en_passant;
+        bytes[25] = self.half_move_counter;
+        bytes[26..28].copy_from_slice(&self.full_moves.to_le_bytes());
+        bytes[28..30].copy_from_slice(&self.score.to_le_bytes());
+        bytes[30] = self.result;
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; PACKED_POSITION_SIZE]) -> Self {
+        MarlinFormat {
+            occupancy: u64::from_le_bytes(bytes[0..8].try_into().expect("slice should be 8 bytes")),
+            pieces: u128::from_le_bytes(bytes[8..24].try_into().expect("slice should be 16 bytes")),
+            stm_en_passant: bytes[24],
+            half_move_counter: bytes[25],
+            full_moves: u16::from_le_bytes([bytes[26], bytes[27]]),
+            score: i16::from_le_bytes([bytes[28], bytes[29]]),
+            result: bytes[30]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{text::TrainingPosition, PackedPosition};
+
+    use super::MarlinFormat;
+
+    #[test]
+    fn test_marlinformat() {
+        let start = TrainingPosition::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 | 20 | 1-0").expect("expected a valid line");
+        let bytes = MarlinFormat::pack(&start).to_bytes();
+
+        // Occupancy, then an unmoved a1 rook and b1 knight in the first byte of pieces, then the state, score and result.
+        assert_eq!(&bytes[0..9], &[0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0x16]);
+        assert_eq!(&bytes[24..32], &[64, 0, 1, 0, 20, 0, 2, 0]);
+
+        // Everything but the score perspective round trips.
+        for line in [
+            "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w Kq a6 0 14 | 40 | 0.5",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1 | 30 | 0.0",
+            "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54 | -12 | 1.0"
+        ] {
+            let position = TrainingPosition::parse(line).expect("expected a valid line");
+            let unpacked = MarlinFormat::from_bytes(&MarlinFormat::pack(&position).to_bytes()).unpack();
+            assert_eq!(unpacked.to_line(), line);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::utils::{board::Board, consts::PACKED_POSITION_SIZE, piece::{Piece, PieceColor, Tile}};
+use text::TrainingPosition;
+
+pub mod bulletformat;
+pub mod datagen;
+pub mod filter;
+pub mod marlinformat;
+pub mod text;
+
+/// A fixed size binary record of a training position.
+pub trait PackedPosition: Sized {
+    /// Packs a labelled position.
+    fn pack(position: &TrainingPosition) -> Self;
+    /// Unpacks the labelled position.
+    fn unpack(&self) -> TrainingPosition;
+    /// Serializes the record.
+    fn to_bytes(&self) -> [u8; PACKED_POSITION_SIZE];
+    /// Deserializes a record.
+    fn from_bytes(bytes: &[u8; PACKED_POSITION_SIZE]) -> Self;
+}
+
+/// The formats training data can be stored in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    /// One `<fen> | <score> | <result>` line per position.
+    Text,
+    /// The side to move relative format of the bullet trainer.
+    Bullet,
+    /// The white relative format of marlinflow, which keeps the full position.
+    Marlin
+}
+
+impl DataFormat {
+    /// Parses the name of a format.
+    pub fn from_name(name: &str) -> Option<DataFormat> {
+        match name {
+            "text" => Some(DataFormat::Text),
+            "bullet" | "bulletformat" => Some(DataFormat::Bullet),
+            "marlin" | "marlinformat" => Some(DataFormat::Marlin),
+            _ => None
+        }
+    }
+
+    /// Reads every position of a file in this format.
+    pub fn read(&self, path: &str) -> Box<dyn Iterator<Item = TrainingPosition>> {
+        let input = BufReader::new(std::fs::File::open(path).expect("failed to open dataset"));
+
+        match self {
+            DataFormat::Text => Box::new(text::read_positions(input)),
+            DataFormat::Bullet => Box::new(read_packed::<bulletformat::BulletFormat, _>(input)),
+            DataFormat::Marlin => Box::new(read_packed::<marlinformat::MarlinFormat, _>(input))
+        }
+    }
+
+    /// Writes positions to a file in this format, returning how many were written.
+    pub fn write(&self, path: &str, positions: impl Iterator<Item = TrainingPosition>) -> std::io::Result<usize> {
+        let mut output = BufWriter::new(std::fs::File::create(path)?);
+        let mut count = 0;
+
+        for position in positions {
+            match self {
+                DataFormat::Text => writeln!(output, "{}", position.to_line())?,
+                DataFormat::Bullet => output.write_all(&bulletformat::BulletFormat::pack(&position).to_bytes())?,
+                DataFormat::Marlin => output.write_all(&marlinformat::MarlinFormat::pack(&position).to_bytes())?
+            }
+
+            count += 1;
+        }
+
+        output.flush()?;
+        Ok(count)
+    }
+}
+
+/// Reads every record of a binary dataset, ignoring a truncated record at the end.
+pub fn read_packed<P: PackedPosition, R: Read>(mut reader: R) -> impl Iterator<Item = TrainingPosition> {
+    std::iter::from_fn(move || {
+        let mut bytes = [0; PACKED_POSITION_SIZE];
+        reader.read_exact(&mut bytes).ok()?;
+
+        Some(P::from_bytes(&bytes).unpack())
+    })
+}
+
+/// Builds a board from its pieces and state, as packed formats store them.
+fn board_from_pieces(pieces: &[(Tile, Piece)], side_to_move: PieceColor, castle_rights: &str, en_passant: Option<Tile>, half_move_counter: u8) -> Board {
+    let mut mailbox = [None; 64];
+    for (tile, piece) in pieces {
+        mailbox[tile.index()] = Some(*piece);
+    }
+
+    let ranks: Vec<String> = (0..8).rev().map(|rank| {
+        let mut fen_rank = String::new();
+        let mut empty = 0;
+
+        for piece in mailbox[rank * 8..rank * 8 + 8].iter() {
+            match piece {
+                Some(piece) => {
+                    if empty > 0 {
+                        fen_rank += &empty.to_string();
+                        empty = 0;
+                    }
+
+                    fen_rank.push(piece.to_char());
+                },
+                None => empty += 1
+            }
+        }
+
+        if empty > 0 {
+            fen_rank += &empty.to_string();
+        }
+
+        fen_rank
+    }).collect();
+
+    let side = if side_to_move == PieceColor::White { "w" } else { "b" };
+    let castle_rights = if castle_rights.is_empty() { "-" } else { castle_rights };
+    let en_passant = en_passant.map_or("-".to_string(), |tile| tile.get_code());
+
+    Board::new(&format!("{} {} {} {} {}", ranks.join("/"), side, castle_rights, en_passant, half_move_counter))
+}
@@ -0,0 +1,88 @@
+use std::io::BufRead;
+
+use crate::utils::{board::Board, piece::PieceColor};
+
+/// A labelled position of a training dataset.
+#[derive(Debug, Clone)]
+pub struct TrainingPosition {
+    /// The position.
+    pub board: Board,
+    /// The number of plies played in the game before the position, taken from the move number of the FEN.
+    pub ply: usize,
+    /// The score of the position from white's perspective, in centipawns, if the dataset has one.
+    pub score: Option<i32>,
+    /// The result of the game from white's perspective (1 for a win, 0.5 for a draw).
+    pub result: f32
+}
+
+impl TrainingPosition {
+    /// Parses a dataset line, which holds a FEN followed by the result, such as
+    /// `<fen> [0.5]`, `<fen> | <score> | 1-0` or `<fen> c9 "1/2-1/2";`.
+    pub fn parse(line: &str) -> Option<TrainingPosition> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 5 {
+            return None;
+        }
+
+        let result = match tokens.last()?.trim_matches(|c| matches!(c, '[' | ']' | '"' | ';')) {
+            "1-0" | "1.0" | "1" => 1.0,
+            "0-1" | "0.0" | "0" => 0.0,
+            "1/2-1/2" | "0.5" => 0.5,
+            _ => return None
+        };
+
+        // The FEN is everything before the first token which can't be part of one.
+        let fen_length = tokens.iter().take(6).position(|token| token.starts_with(['[', '|', '"', ';']) || *token == "c9").unwrap_or(6.min(tokens.len() - 1)).max(4);
+        let board = Board::new(&tokens[..fen_length].join(" "));
+
+        let full_moves = tokens.get(5).filter(|_| fen_length == 6).and_then(|token| token.parse::<usize>().ok()).unwrap_or(1);
+        let ply = (full_moves.max(1) - 1) * 2 + (board.side_to_move == PieceColor::Black) as usize;
+
+        // Only the `<fen> | <score> | <result>` layout carries a score.
+        let fields: Vec<&str> = line.split('|').collect();
+        let score = if fields.len() == 3 { fields[1].trim().parse::<i32>().ok() } else { None };
+
+        Some(TrainingPosition { board, ply, score, result })
+    }
+
+    /// Formats the position as a `<fen> | <score> | <result>` line, leaving out the score if there is none.
+    pub fn to_line(&self) -> String {
+        // The move number is not tracked by the board, so it is restored from the ply.
+        let fen = self.board.to_fen();
+        let fen = format!("{} {}", fen.rsplit_once(' ').map_or(fen.as_str(), |(fen, _)| fen), self.ply / 2 + 1);
+
+        match self.score {
+            Some(score) => format!("{} | {} | {:.1}", fen, score, self.result),
+            None => format!("{} | {:.1}", fen, self.result)
+        }
+    }
+}
+
+/// Reads every valid position of a dataset, skipping lines which can't be parsed.
+pub fn read_positions<R: BufRead>(reader: R) -> impl Iterator<Item = TrainingPosition> {
+    reader.lines()
+        .map(|line| line.expect("failed to read dataset line"))
+        .filter_map(|line| TrainingPosition::parse(&line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrainingPosition;
+
+    #[test]
+    fn test_text_positions() {
+        let position = TrainingPosition::parse("4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24 | -50 | 0-1").expect("expected a valid line");
+        assert_eq!((position.ply, position.score, position.result), (47, Some(-50), 0.0));
+        assert_eq!(position.to_line(), "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24 | -50 | 0.0");
+
+        let position = TrainingPosition::parse("2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - c9 \"1/2-1/2\";").expect("expected a valid line");
+        assert_eq!((position.ply, position.score, position.result), (0, None, 0.5));
+        assert_eq!(position.to_line(), "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 1 | 0.5");
+
+        let position = TrainingPosition::parse("8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54 [1.0]").expect("expected a valid line");
+        assert_eq!((position.ply, position.score, position.result), (106, None, 1.0));
+
+        assert!(TrainingPosition::parse("not a position").is_none());
+        assert!(TrainingPosition::parse("8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54 [draw]").is_none());
+    }
+}
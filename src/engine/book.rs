@@ -0,0 +1,417 @@
+use std::{collections::HashMap, io::Write};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::utils::{board::Board, consts::{BOOK_DEFAULT_MAX_PLIES, BOOK_DRAW_SCORE, BOOK_IMPORT_MAX_WEIGHT, BOOK_LEARN_MAX_SCORE, BOOK_LEARN_SCALE, BOOK_WIN_SCORE}, pgn::{GameResult, PgnGame}, piece::PieceColor, piece_move::Move, polyglot::{decode_move, encode_move, polyglot_key, PolyglotEntry}};
+
+/// The criteria a game must meet to be included in an opening book.
+#[derive(Debug, Clone)]
+pub struct BookFilter {
+    /// The minimum rating of both players, if any. Games without ratings are skipped when set.
+    pub min_rating: Option<u32>,
+    /// The number of plies from the start of each game which are added to the book.
+    pub max_plies: usize,
+    /// The minimum number of games a move must be played in to be kept.
+    pub min_games: u32,
+    /// The results which are accepted.
+    pub results: Vec<GameResult>
+}
+
+impl Default for BookFilter {
+    fn default() -> Self {
+        BookFilter {
+            min_rating: None,
+            max_plies: BOOK_DEFAULT_MAX_PLIES,
+            min_games: 1,
+            results: vec![GameResult::WhiteWin, GameResult::BlackWin, GameResult::Draw]
+        }
+    }
+}
+
+impl BookFilter {
+    /// Whether or not a game passes the result and rating criteria.
+    pub fn accepts(&self, game: &PgnGame) -> bool {
+        if !self.results.contains(&game.result) {
+            return false;
+        }
+
+        let Some(min_rating) = self.min_rating else { return true; };
+        ["WhiteElo", "BlackElo"].iter().all(|tag| {
+            game.header(tag).and_then(|rating| rating.parse::<u32>().ok()).is_some_and(|rating| rating >= min_rating)
+        })
+    }
+}
+
+/// The statistics of a single move in a single position.
+#[derive(Debug, Clone, Copy, Default)]
+struct BookMove {
+    /// The score of the move from the perspective of the side who played it.
+    score: u32,
+    /// The number of games the move was played in.
+    games: u32
+}
+
+/// Collects moves from games into a polyglot opening book.
+pub struct BookBuilder {
+    /// The criteria games and moves must meet.
+    pub filter: BookFilter,
+    /// The number of games offered to the builder.
+    pub games_read: usize,
+    /// The number of games which passed the filter.
+    pub games_used: usize,
+    /// The statistics of every move, indexed by polyglot key and encoded move.
+    moves: HashMap<(u64, u16), BookMove>
+}
+
+impl BookBuilder {
+    /// Creates an empty book builder.
+    pub fn new(filter: BookFilter) -> Self {
+        BookBuilder { filter, games_read: 0, games_used: 0, moves: HashMap::new() }
+    }
+
+    /// Adds the opening moves of a game, if the game passes the filter.
+    ///
+    /// Moves after an illegal or unreadable move are ignored.
+    pub fn add_game(&mut self, game: &PgnGame) {
+        self.games_read += 1;
+        if !self.filter.accepts(game) {
+            return;
+        }
+
+        self.games_used += 1;
+
+        let mut board = Board::new(game.header("FEN").unwrap_or("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+        for san in game.moves.iter().take(self.filter.max_plies) {
+            let Some(piece_move) = Move::from_san(&board, san) else { break; };
+
+            let score = match (game.result, board.side_to_move) {
+                (GameResult::Draw, _) => BOOK_DRAW_SCORE,
+                (GameResult::WhiteWin, PieceColor::White) | (GameResult::BlackWin, PieceColor::Black) => BOOK_WIN_SCORE,
+                _ => 0
+            };
+
+            let book_move = self.moves.entry((polyglot_key(&board), encode_move(&piece_move))).or_default();
+            book_move.score += score;
+            book_move.games += 1;
+
+            board = board.make_move(&piece_move, false).expect("expected a legal move");
+        }
+    }
+
+    /// Produces the book entries, sorted by key and then by descending weight.
+    ///
+    /// Moves which never scored are dropped, and weights are scaled down
+    /// proportionally if they would not fit in polyglot's 16 bits.
+    pub fn entries(&self) -> Vec<PolyglotEntry> {
+        let moves: Vec<_> = self.moves.iter()
+            .filter(|(_, book_move)| book_move.games >= self.filter.min_games && book_move.score > 0)
+            .collect();
+
+        let max_score = moves.iter().map(|(_, book_move)| book_move.score).max().unwrap_or(0) as u64;
+        let scale = |score: u32| if max_score > u16::MAX as u64 { (score as u64 * u16::MAX as u64 / max_score).max(1) as u16 } else { score as u16 };
+
+        let mut entries: Vec<PolyglotEntry> = moves.iter()
+            .map(|((key, piece_move), book_move)| PolyglotEntry { key: *key, piece_move: *piece_move, weight: scale(book_move.score), learn: 0 })
+            .collect();
+
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)).then(a.piece_move.cmp(&b.piece_move)));
+        entries
+    }
+}
+
+/// The magic bytes at the start of a native book file.
+const NATIVE_BOOK_MAGIC: &[u8; 4] = b"SDBK";
+/// The version of the native book format.
+const NATIVE_BOOK_VERSION: u32 = 1;
+/// The size of a single move in a native book file.
+const NATIVE_ENTRY_SIZE: usize = 24;
+
+/// A move in the engine's own book, along with what was learned about it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LearnedMove {
+    /// The move, in polyglot's encoding.
+    pub piece_move: u16,
+    /// The weight of the move relative to the other moves in the position.
+    pub weight: u16,
+    /// The number of games the move was learned from.
+    pub games: u32,
+    /// The sum of the scores (in cp) the engine found after leaving the book.
+    pub total_score: i64
+}
+
+/// The engine's own opening book, which adjusts its weights based on how
+/// the engine fared after leaving the book (book learning).
+///
+/// Positions are keyed like polyglot books, so polyglot books convert losslessly.
+/// The file is a magic number and version, followed by big-endian records of
+/// key, move, weight, games and total score.
+pub struct LearningBook {
+    /// The file the book is loaded from and saved to.
+    pub path: String,
+    /// The moves of every position, indexed by polyglot key.
+    positions: HashMap<u64, Vec<LearnedMove>>,
+    /// The book moves played by the engine which have not been learned from yet.
+    line: Vec<(u64, u16)>,
+    /// The source of randomness when picking between book moves.
+    rng: StdRng
+}
+
+impl LearningBook {
+    /// Creates a book from polyglot entries, scaling the weights up so learning can adjust small weights.
+    pub fn from_polyglot(path: &str, entries: &[PolyglotEntry]) -> Self {
+        let max_weight = entries.iter().map(|entry| entry.weight).max().unwrap_or(1).max(1) as u32;
+        let scale = (BOOK_IMPORT_MAX_WEIGHT as u32 / max_weight).max(1);
+
+        let mut positions: HashMap<u64, Vec<LearnedMove>> = HashMap::new();
+        for entry in entries {
+            let weight = (entry.weight as u32 * scale).min(u16::MAX as u32) as u16;
+            positions.entry(entry.key).or_default().push(LearnedMove { piece_move: entry.piece_move, weight, games: 0, total_score: 0 });
+        }
+
+        LearningBook { path: path.to_string(), positions, line: Vec::new(), rng: StdRng::from_entropy() }
+    }
+
+    /// Loads a native book file.
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+
+        if bytes.len() < 8 || &bytes[0..4] != NATIVE_BOOK_MAGIC {
+            return Err(invalid("not a native book file"));
+        }
+
+        if u32::from_be_bytes(bytes[4..8].try_into().expect("expected four bytes")) != NATIVE_BOOK_VERSION {
+            return Err(invalid("unsupported book version"));
+        }
+
+        if (bytes.len() - 8) % NATIVE_ENTRY_SIZE != 0 {
+            return Err(invalid("truncated book file"));
+        }
+
+        let mut positions: HashMap<u64, Vec<LearnedMove>> = HashMap::new();
+        for record in bytes[8..].chunks_exact(NATIVE_ENTRY_SIZE) {
+            let key = u64::from_be_bytes(record[0..8].try_into().expect("expected eight bytes"));
+            positions.entry(key).or_default().push(LearnedMove {
+                piece_move: u16::from_be_bytes(record[8..10].try_into().expect("expected two bytes")),
+                weight: u16::from_be_bytes(record[10..12].try_into().expect("expected two bytes")),
+                games: u32::from_be_bytes(record[12..16].try_into().expect("expected four bytes")),
+                total_score: i64::from_be_bytes(record[16..24].try_into().expect("expected eight bytes"))
+            });
+        }
+
+        Ok(LearningBook { path: path.to_string(), positions, line: Vec::new(), rng: StdRng::from_entropy() })
+    }
+
+    /// Saves the book to its file, sorted by key.
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&self.path)?);
+        writer.write_all(NATIVE_BOOK_MAGIC)?;
+        writer.write_all(&NATIVE_BOOK_VERSION.to_be_bytes())?;
+
+        let mut keys: Vec<&u64> = self.positions.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            for learned_move in self.positions[key].iter() {
+                writer.write_all(&key.to_be_bytes())?;
+                writer.write_all(&learned_move.piece_move.to_be_bytes())?;
+                writer.write_all(&learned_move.weight.to_be_bytes())?;
+                writer.write_all(&learned_move.games.to_be_bytes())?;
+                writer.write_all(&learned_move.total_score.to_be_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// The moves stored for a position.
+    pub fn moves(&self, board: &Board) -> &[LearnedMove] {
+        self.positions.get(&polyglot_key(board)).map_or(&[], Vec::as_slice)
+    }
+
+    /// Picks a legal book move for a position, at random in proportion to the weights,
+    /// or the heaviest move when the choice must be reproducible.
+    pub fn probe(&mut self, board: &Board, deterministic: bool) -> Option<Move> {
+        let candidates: Vec<(Move, u16)> = self.moves(board).iter()
+            .filter(|learned_move| learned_move.weight > 0)
+            .filter_map(|learned_move| decode_move(board, learned_move.piece_move).map(|piece_move| (piece_move, learned_move.weight)))
+            .collect();
+
+        if deterministic {
+            // The first heaviest move, so ties are broken by the order in the file.
+            return candidates.iter().rev().max_by_key(|(_, weight)| *weight).map(|(piece_move, _)| *piece_move);
+        }
+
+        let total: u32 = candidates.iter().map(|(_, weight)| *weight as u32).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = self.rng.gen_range(0..total);
+        for (piece_move, weight) in candidates {
+            if pick < weight as u32 {
+                return Some(piece_move);
+            }
+
+            pick -= weight as u32;
+        }
+
+        None
+    }
+
+    /// Remembers that the engine played a book move, so it can be learned from later.
+    pub fn record(&mut self, board: &Board, piece_move: &Move) {
+        self.line.push((polyglot_key(board), encode_move(piece_move)));
+    }
+
+    /// Forgets the book moves of the current game without learning from them.
+    pub fn new_game(&mut self) {
+        self.line.clear();
+    }
+
+    /// Learns from the score (from the engine's perspective) of the first search after leaving
+    /// the book, adjusting every book move which led there. Returns the number of moves adjusted.
+    pub fn learn(&mut self, score: i32) -> usize {
+        let score = score.clamp(-BOOK_LEARN_MAX_SCORE, BOOK_LEARN_MAX_SCORE);
+        let factor = 2.0_f64.powf(score as f64 / BOOK_LEARN_SCALE);
+        let mut adjusted = 0;
+
+        for (key, piece_move) in std::mem::take(&mut self.line) {
+            let Some(learned_move) = self.positions.get_mut(&key).and_then(|moves| moves.iter_mut().find(|learned_move| learned_move.piece_move == piece_move)) else {
+                continue;
+            };
+
+            learned_move.games += 1;
+            learned_move.total_score += score as i64;
+            learned_move.weight = (learned_move.weight as f64 * factor).round().clamp(1.0, u16::MAX as f64) as u16;
+            adjusted += 1;
+        }
+
+        adjusted
+    }
+
+    /// Removes every learned move whose average score is below `-threshold`,
+    /// returning the number of moves removed.
+    pub fn prune(&mut self, threshold: i32) -> usize {
+        let mut removed = 0;
+
+        for moves in self.positions.values_mut() {
+            let before = moves.len();
+            moves.retain(|learned_move| learned_move.games == 0 || learned_move.total_score / learned_move.games as i64 >= -threshold as i64);
+            removed += before - moves.len();
+        }
+
+        self.positions.retain(|_, moves| !moves.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::Board, pgn::{GameResult, PgnReader}, polyglot::{encode_move, polyglot_key, PolyglotEntry}, piece_move::Move};
+
+    use super::{BookBuilder, BookFilter, LearningBook};
+
+    const GAMES: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[WhiteElo "2500"]
+[BlackElo "2400"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 3. Bb5 a6 ; the Ruy Lopez
+4. Ba4 1-0
+
+[Event "Test"]
+[WhiteElo "2000"]
+[BlackElo "2600"]
+[Result "0-1"]
+
+1.e4 e5 2.Nf3 Nf6 0-1
+
+[Event "Test"]
+[Result "1/2-1/2"]
+
+1. d4 d5 1/2-1/2
+
+[Event "Test"]
+[Result "*"]
+
+1. c4 *
+"#;
+
+    #[test]
+    fn test_pgn_reader() {
+        let games: Vec<_> = PgnReader::new(GAMES.as_bytes()).collect();
+
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].moves, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]);
+        assert_eq!(games[0].header("WhiteElo"), Some("2500"));
+        assert_eq!(games[1].moves, ["e4", "e5", "Nf3", "Nf6"]);
+        assert_eq!(games.iter().map(|game| game.result).collect::<Vec<_>>(), [GameResult::WhiteWin, GameResult::BlackWin, GameResult::Draw, GameResult::Unknown]);
+    }
+
+    #[test]
+    fn test_book_builder() {
+        let build = |filter: BookFilter| {
+            let mut builder = BookBuilder::new(filter);
+            PgnReader::new(GAMES.as_bytes()).for_each(|game| builder.add_game(&game));
+            builder
+        };
+
+        // Unfinished games are skipped, and moves which only lost are dropped.
+        let builder = build(BookFilter::default());
+        assert_eq!((builder.games_read, builder.games_used), (4, 3));
+
+        let entries = builder.entries();
+        let e4 = encode_move(&Move::from_uci("e2e4"));
+        let start: Vec<_> = entries.iter().filter(|entry| entry.key == 0x463b96181691fc9c).collect();
+        assert_eq!(start.iter().map(|entry| (entry.piece_move, entry.weight)).collect::<Vec<_>>(), [(e4, 2), (encode_move(&Move::from_uci("d2d4")), 1)]);
+        assert!(entries.windows(2).all(|pair| pair[0].key <= pair[1].key));
+
+        // Rating, ply and game count filters.
+        let builder = build(BookFilter { min_rating: Some(2100), max_plies: 2, ..Default::default() });
+        assert_eq!(builder.games_used, 1);
+        assert_eq!(builder.entries().len(), 1);
+
+        let builder = build(BookFilter { min_games: 2, ..Default::default() });
+        let mut moves: Vec<_> = builder.entries().iter().map(|entry| entry.piece_move).collect();
+        moves.sort();
+        assert_eq!(moves, [encode_move(&Move::from_uci("g1f3")), e4, encode_move(&Move::from_uci("e7e5"))]);
+    }
+
+    #[test]
+    fn test_book_learning() {
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let (e4, d4) = (Move::from_uci("e2e4"), Move::from_uci("d2d4"));
+
+        let key = polyglot_key(&board);
+        let entries = [
+            PolyglotEntry { key, piece_move: encode_move(&e4), weight: 2, learn: 0 },
+            PolyglotEntry { key, piece_move: encode_move(&d4), weight: 2, learn: 0 }
+        ];
+
+        let path = std::env::temp_dir().join(format!("sacre_dieu_book_{}.sdb", std::process::id()));
+        let mut book = LearningBook::from_polyglot(path.to_str().unwrap(), &entries);
+
+        // Ties are broken by file order when picking deterministically.
+        assert_eq!(book.probe(&board, true).map(|piece_move| piece_move.to_uci()), Some("e2e4".to_string()));
+
+        // A bad result after e4 moves the weight towards d4.
+        book.record(&board, &e4);
+        assert_eq!(book.learn(-200), 1);
+        assert_eq!(book.learn(-200), 0);
+        assert_eq!(book.probe(&board, true).map(|piece_move| piece_move.to_uci()), Some("d2d4".to_string()));
+
+        // The learned state survives a round trip through the file.
+        book.save().unwrap();
+        let mut loaded = LearningBook::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.moves(&board), book.moves(&board));
+
+        // Pruning only removes learned moves which scored badly enough.
+        assert_eq!(loaded.prune(300), 0);
+        assert_eq!(loaded.prune(100), 1);
+        assert_eq!(loaded.moves(&board).len(), 1);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::utils::{board::{material_key_from_counts, Bitboard, Board, MATERIAL_PIECE_TYPES}, consts::{KNOWN_WIN, LIGHT_TILES}, piece::{PieceColor, PieceType, Tile}};
+
+/// A function which scores an endgame from the perspective of the strong side.
+type EndgameEvaluator = fn(&Board, PieceColor) -> i32;
+
+/// An endgame with a dedicated scoring function, identified by its material signature.
+struct Endgame {
+    /// The pawns, knights, bishops, rooks, and queens of the strong side.
+    strong: [u32; MATERIAL_PIECE_TYPES],
+    /// The pawns, knights, bishops, rooks, and queens of the weak side.
+    weak: [u32; MATERIAL_PIECE_TYPES],
+    /// The scoring function of the endgame.
+    evaluate: EndgameEvaluator
+}
+
+/// Every recognized endgame.
+const ENDGAMES: [Endgame; 8] = [
+    Endgame { strong: [0, 0, 0, 0, 0], weak: [0, 0, 0, 0, 0], evaluate: evaluate_draw },
+    Endgame { strong: [0, 1, 0, 0, 0], weak: [0, 0, 0, 0, 0], evaluate: evaluate_draw },
+    Endgame { strong: [0, 0, 1, 0, 0], weak: [0, 0, 0, 0, 0], evaluate: evaluate_draw },
+    Endgame { strong: [0, 2, 0, 0, 0], weak: [0, 0, 0, 0, 0], evaluate: evaluate_draw },
+    Endgame { strong: [0, 1, 1, 0, 0], weak: [0, 0, 0, 0, 0], evaluate: evaluate_kbnk },
+    Endgame { strong: [0, 0, 0, 1, 0], weak: [0, 0, 0, 0, 0], evaluate: evaluate_mate_with_major },
+    Endgame { strong: [0, 0, 0, 0, 1], weak: [0, 0, 0, 0, 0], evaluate: evaluate_mate_with_major },
+    Endgame { strong: [0, 0, 0, 1, 0], weak: [0, 0, 1, 0, 0], evaluate: evaluate_krkb }
+];
+
+/// Scores the position with a dedicated endgame function if its material is recognized,
+/// from the perspective of the side to move.
+pub fn probe(board: &Board) -> Option<i32> {
+    // Every recognized endgame is pawnless.
+    if board.piece(PieceType::Pawn) != Bitboard::ZERO {
+        return None;
+    }
+
+    let material_key = board.material_key();
+
+    for endgame in ENDGAMES.iter() {
+        let strong_side = if material_key == material_key_from_counts(endgame.strong, endgame.weak) {
+            PieceColor::White
+        } else if material_key == material_key_from_counts(endgame.weak, endgame.strong) {
+            PieceColor::Black
+        } else {
+            continue;
+        };
+
+        let score = (endgame.evaluate)(board, strong_side);
+        return Some(if board.side_to_move == strong_side { score } else { -score });
+    }
+
+    None
+}
+
+/// The Chebyshev distance between two tiles.
+pub fn distance(a: Tile, b: Tile) -> i32 {
+    (a.rank as i32 - b.rank as i32).abs().max((a.file as i32 - b.file as i32).abs())
+}
+
+/// A bonus for a tile being far from the center of the board.
+fn push_to_edge(tile: Tile) -> i32 {
+    ((2 * tile.rank as i32 - 7).abs() + (2 * tile.file as i32 - 7).abs()) * 10
+}
+
+/// A bonus for two tiles being close together.
+fn push_close(a: Tile, b: Tile) -> i32 {
+    (7 - distance(a, b)) * 20
+}
+
+/// Insufficient material to checkmate.
+fn evaluate_draw(_board: &Board, _strong_side: PieceColor) -> i32 {
+    0
+}
+
+/// A lone rook or queen against a king, won by driving the king to the edge.
+fn evaluate_mate_with_major(board: &Board, strong_side: PieceColor) -> i32 {
+    let strong_king = board.king_tile(strong_side);
+    let weak_king = board.king_tile(!strong_side);
+
+    KNOWN_WIN + push_to_edge(weak_king) + push_close(strong_king, weak_king)
+}
+
+/// Bishop and knight against a king, won by driving the king to a corner of the bishop's color.
+fn evaluate_kbnk(board: &Board, strong_side: PieceColor) -> i32 {
+    let strong_king = board.king_tile(strong_side);
+    let weak_king = board.king_tile(!strong_side);
+
+    let light_bishop = board.colored_piece(PieceType::Bishop, strong_side).board & LIGHT_TILES != 0;
+    let corners = if light_bishop { [Tile::A8, Tile::H1] } else { [Tile::A1, Tile::H8] };
+
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| distance(weak_king, corner))
+        .min()
+        .expect("expected a corner");
+
+    KNOWN_WIN + (7 - corner_distance) * 40 + push_close(strong_king, weak_king)
+}
+
+/// Rook against bishop, which is usually drawn unless the defending king is cornered.
+fn evaluate_krkb(board: &Board, strong_side: PieceColor) -> i32 {
+    push_to_edge(board.king_tile(!strong_side)) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::Board, consts::KNOWN_WIN};
+
+    use super::probe;
+
+    #[test]
+    fn test_endgame_recognizers() {
+        // Insufficient material is always a draw.
+        assert_eq!(probe(&Board::new("8/8/4k3/8/8/2NN4/4K3/8 w - - 0 1")), Some(0));
+        assert_eq!(probe(&Board::new("8/8/4k3/8/8/2b5/4K3/8 b - - 0 1")), Some(0));
+
+        // Scores are from the perspective of the side to move.
+        assert!(probe(&Board::new("8/8/4k3/8/8/2R5/4K3/8 w - - 0 1")).is_some_and(|score| score > KNOWN_WIN));
+        assert!(probe(&Board::new("8/8/4k3/8/8/2R5/4K3/8 b - - 0 1")).is_some_and(|score| score < -KNOWN_WIN));
+
+        // The weak king in the right corner scores higher than in the wrong corner.
+        let right_corner = probe(&Board::new("k7/8/2K5/8/8/8/2BN4/8 w - - 0 1")).expect("expected KBNvK");
+        let wrong_corner = probe(&Board::new("7k/8/5K2/8/8/8/2BN4/8 w - - 0 1")).expect("expected KBNvK");
+        assert!(right_corner > wrong_corner);
+
+        // Positions with pawns or unrecognized material aren't probed.
+        assert_eq!(probe(&Board::new("8/8/4k3/8/8/2R5/4KP2/8 w - - 0 1")), None);
+        assert_eq!(probe(&Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")), None);
+    }
+}
@@ -1,48 +1,119 @@
 use strum::IntoEnumIterator;
 
-use crate::utils::{board::{Bitboard, Board}, consts::{self, PIECE_SQUARE_TABLE}, piece::{PieceColor, PieceType}};
+use crate::utils::{board::{Attacks, Bitboard, Board}, consts::{A_FILE, DARK_TILES, FIFTY_MOVE_SCALE_BASE, H_FILE, KING_MASKS, LAZY_EVAL_MARGIN, LIGHT_TILES, MAX_PHASE, SCALE_DRAW, SCALE_NORMAL, SCALE_OPPOSITE_BISHOPS, SCALE_OPPOSITE_BISHOPS_WITH_PIECES, SCALE_ROOK_PAWN_UP, SPACE_MASK, SPACE_PIECE_DIVISOR, STRANDED_KING_FILES}, piece::{CastleRights, PieceColor, PieceType, Tile}};
+use super::{endgame::{self, distance}, eval_params::EvalParams};
 
-/// Evaluates the board, where negative values represent a black advantage and positive
-/// values represent a white advantage.
-pub fn evaluate_board(board: &Board) -> i32 {
-    let (mut mg, mut eg) = (0_i32, 0_i32);
-    
-    let material_eval = count_material(board, PieceColor::White) as i32 - count_material(board, PieceColor::Black) as i32;
-    mg += material_eval;
-    eg += material_eval;
+/// A static evaluation of a board, from the perspective of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evaluation {
+    /// Every term was evaluated.
+    Full(i32),
+    /// Only material and piece-square tables were evaluated, as they put the score far outside the window.
+    Lazy(i32)
+}
+
+impl Evaluation {
+    /// The score of the evaluation, however it was reached.
+    pub fn score(self) -> i32 {
+        match self {
+            Evaluation::Full(score) | Evaluation::Lazy(score) => score
+        }
+    }
+}
+
+/// Evaluates the board, where positive values represent an advantage for the side to move.
+/// 
+/// Given an alpha-beta window, the expensive terms (mobility, king safety, pawn structure and so on)
+/// are skipped when material and piece-square tables alone are `LAZY_EVAL_MARGIN` outside of it.
+pub fn evaluate_board(board: &Board, params: &EvalParams, window: Option<(i32, i32)>) -> Evaluation {
+    if let Some(score) = endgame::probe(board) {
+        return Evaluation::Full(score);
+    }
+
+    let (mut mg, mut eg) = evaluate_base(board, params);
+
+    if let Some((alpha, beta)) = window {
+        let estimate = taper(board, mg, eg);
+
+        if estimate - LAZY_EVAL_MARGIN >= beta || estimate + LAZY_EVAL_MARGIN <= alpha {
+            return Evaluation::Lazy(estimate);
+        }
+    }
+
+    let white_attacks = board.attacks_by(PieceColor::White);
+    let black_attacks = board.attacks_by(PieceColor::Black);
+
+    let (mobility_white_mg, mobility_white_eg) = evaluate_mobility(board, PieceColor::White, &white_attacks, &black_attacks, params);
+    let (mobility_black_mg, mobility_black_eg) = evaluate_mobility(board, PieceColor::Black, &black_attacks, &white_attacks, params);
+
+    mg += mobility_white_mg - mobility_black_mg;
+    eg += mobility_white_eg - mobility_black_eg;
+
+    mg += evaluate_king_safety(board, PieceColor::White, &black_attacks, params) - evaluate_king_safety(board, PieceColor::Black, &white_attacks, params);
+    mg += evaluate_king_files(board, PieceColor::White, params) - evaluate_king_files(board, PieceColor::Black, params);
+    mg += evaluate_castling(board, PieceColor::White, params) - evaluate_castling(board, PieceColor::Black, params);
+
+    let (minors_white_mg, minors_white_eg) = evaluate_minor_pieces(board, PieceColor::White, &white_attacks, params);
+    let (minors_black_mg, minors_black_eg) = evaluate_minor_pieces(board, PieceColor::Black, &black_attacks, params);
+
+    mg += minors_white_mg - minors_black_mg;
+    eg += minors_white_eg - minors_black_eg;
+
+    let (proximity_white_mg, proximity_white_eg) = evaluate_king_proximity(board, PieceColor::White, params);
+    let (proximity_black_mg, proximity_black_eg) = evaluate_king_proximity(board, PieceColor::Black, params);
+
+    mg += proximity_white_mg - proximity_black_mg;
+    eg += proximity_white_eg - proximity_black_eg;
+
+    mg += evaluate_space(board, PieceColor::White, &black_attacks, params) - evaluate_space(board, PieceColor::Black, &white_attacks, params);
+
+    // Drawish endgames are scaled towards zero, in favor of the weaker side.
+    let strong_side = if eg > 0 { PieceColor::White } else { PieceColor::Black };
+    eg = eg * endgame_scale(board, strong_side) / SCALE_NORMAL;
+
+    Evaluation::Full(taper(board, mg, eg))
+}
 
-    let (psqt_white_mg, psqt_white_eg) = evaluate_piece_square_score(board, PieceColor::White);
-    let (psqt_black_mg, psqt_black_eg) = evaluate_piece_square_score(board, PieceColor::Black);
+/// Blends middlegame and endgame scores by the phase of the board, from the perspective of the side to move.
+fn taper(board: &Board, mg: i32, eg: i32) -> i32 {
+    let phase = board.phase() as i32;
+    let mut eval = (mg * phase + eg * (MAX_PHASE as i32 - phase)) / MAX_PHASE as i32;
 
-    mg += psqt_white_mg - psqt_black_mg;
-    eg += psqt_white_eg - psqt_black_eg;
+    // Progress towards the fifty move rule makes any advantage less likely to be converted.
+    eval = eval * (FIFTY_MOVE_SCALE_BASE - board.half_move_counter as i32).max(0) / FIFTY_MOVE_SCALE_BASE;
 
-    let phase = (board.phase() as i32).min(24);
-    let eval = (mg * phase + eg * (24 - phase)) / 24;
     let perspective = if board.side_to_move == PieceColor::White { 1 } else { -1 };
 
     eval * perspective
 }
 
-/// Counts the material for a side of the board.
-pub fn count_material(board: &Board, side: PieceColor) -> u32 {
-    let pawn_bitboard = board.colored_piece(PieceType::Pawn, side);
-    let knight_bitboard = board.colored_piece(PieceType::Knight, side);
-    let bishop_bitboard = board.colored_piece(PieceType::Bishop, side);
-    let rook_bitboard = board.colored_piece(PieceType::Rook, side);
-    let queen_bitboard = board.colored_piece(PieceType::Queen, side);
+/// Evaluates the material and piece-square score of the board, which the rest of the evaluation builds on.
+pub fn evaluate_base(board: &Board, params: &EvalParams) -> (i32, i32) {
+    let (material_white_mg, material_white_eg) = evaluate_material(board, PieceColor::White, params);
+    let (material_black_mg, material_black_eg) = evaluate_material(board, PieceColor::Black, params);
+
+    let (psqt_white_mg, psqt_white_eg) = evaluate_piece_square_score(board, PieceColor::White, params);
+    let (psqt_black_mg, psqt_black_eg) = evaluate_piece_square_score(board, PieceColor::Black, params);
+
+    (material_white_mg - material_black_mg + psqt_white_mg - psqt_black_mg, material_white_eg - material_black_eg + psqt_white_eg - psqt_black_eg)
+}
+
+/// Evaluates the material for a side of the board.
+pub fn evaluate_material(board: &Board, side: PieceColor, params: &EvalParams) -> (i32, i32) {
+    let (mut mg, mut eg) = (0, 0);
+    for piece_type in PieceType::iter() {
+        let count = board.colored_piece(piece_type, side).board.count_ones() as i32;
+        let (mg_value, eg_value) = params.material_values[piece_type as usize];
 
-    let pawn_material = pawn_bitboard.board.count_ones() * consts::PAWN_VALUE as u32;
-    let knight_material = knight_bitboard.board.count_ones() * consts::KNIGHT_VALUE as u32;
-    let bishop_material = bishop_bitboard.board.count_ones() * consts::BISHOP_VALUE as u32;
-    let rook_material = rook_bitboard.board.count_ones() * consts::ROOK_VALUE as u32;
-    let queen_material = queen_bitboard.board.count_ones() * consts::QUEEN_VALUE as u32;
+        mg += count * mg_value;
+        eg += count * eg_value;
+    }
 
-    pawn_material + knight_material + bishop_material + rook_material + queen_material
+    (mg, eg)
 }
 
 /// Evaluates a piece square score for a certain side.
-pub fn evaluate_piece_square_score(board: &Board, side: PieceColor) -> (i32, i32) {
+pub fn evaluate_piece_square_score(board: &Board, side: PieceColor, params: &EvalParams) -> (i32, i32) {
     let mut mg = 0_i32;
     let mut eg = 0_i32;
 
@@ -54,11 +125,532 @@ pub fn evaluate_piece_square_score(board: &Board, side: PieceColor) -> (i32, i32
             let tile = piece_bitboard.pop_lsb();
             let tile_index = if side == PieceColor::White { tile.index() ^ 56 } else { tile.index() };
 
-            let (opening_eval, endgame_eval) = PIECE_SQUARE_TABLE[piece_index][tile_index];
+            let (opening_eval, endgame_eval) = params.piece_square_table[piece_index][tile_index];
             mg += opening_eval;
             eg += endgame_eval;
         }
     }
 
     (mg, eg)
+}
+
+/// Evaluates the mobility of a side's pieces, counting the tiles they attack
+/// which are neither occupied by friendly pieces nor attacked by enemy pawns.
+pub fn evaluate_mobility(board: &Board, side: PieceColor, attacks: &Attacks, enemy_attacks: &Attacks, params: &EvalParams) -> (i32, i32) {
+    let safe_tiles = !board.color(side) & !enemy_attacks.piece(PieceType::Pawn);
+
+    let (mut mg, mut eg) = (0, 0);
+    for piece_type in PieceType::iter() {
+        let mobility = (attacks.piece(piece_type) & safe_tiles).board.count_ones() as i32;
+        let (mg_weight, eg_weight) = params.mobility_weights[piece_type as usize];
+
+        mg += mobility * mg_weight;
+        eg += mobility * eg_weight;
+    }
+
+    (mg, eg)
+}
+
+/// Evaluates the safety of a side's king (as a middlegame penalty), based
+/// on how many tiles around it are attacked by enemy pieces.
+pub fn evaluate_king_safety(board: &Board, side: PieceColor, enemy_attacks: &Attacks, params: &EvalParams) -> i32 {
+    let king_tile = board.king_tile(side);
+    let king_zone = Bitboard::new(KING_MASKS[king_tile.index()]);
+
+    let mut penalty = 0;
+    for piece_type in PieceType::iter() {
+        let attacked_tiles = (enemy_attacks.piece(piece_type) & king_zone).board.count_ones() as i32;
+        penalty += attacked_tiles * params.king_zone_attack_weights[piece_type as usize];
+    }
+
+    -penalty
+}
+
+/// The pawn structure around a side's king, on the king's file and the files next to it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KingFiles {
+    /// The number of files without friendly pawns, but with enemy pawns.
+    pub semi_open: i32,
+    /// The number of files without any pawns.
+    pub open: i32,
+    /// The number of storming enemy pawns free to advance, indexed by their relative rank.
+    pub storms: [i32; 8],
+    /// The number of storming enemy pawns blocked by a friendly pawn, indexed by their relative rank.
+    pub blocked_storms: [i32; 8]
+}
+
+impl KingFiles {
+    /// Finds the pawn structure around a side's king, where the most advanced enemy
+    /// pawn in front of the king on each file leads the storm on that file.
+    pub fn new(board: &Board, side: PieceColor) -> Self {
+        let king_tile = board.king_tile(side);
+
+        let our_pawns = board.colored_piece(PieceType::Pawn, side);
+        let enemy_pawns = board.colored_piece(PieceType::Pawn, !side);
+
+        let mut king_files = KingFiles::default();
+        for file in king_tile.file.saturating_sub(1)..=(king_tile.file + 1).min(7) {
+            let file_mask = A_FILE << file;
+            let our_file_pawns = our_pawns.board & file_mask;
+            let enemy_file_pawns = enemy_pawns.board & file_mask;
+
+            if our_file_pawns == 0 {
+                if enemy_file_pawns == 0 {
+                    king_files.open += 1;
+                } else {
+                    king_files.semi_open += 1;
+                }
+            }
+
+            if enemy_file_pawns == 0 {
+                continue;
+            }
+
+            let index = if side == PieceColor::White { enemy_file_pawns.trailing_zeros() } else { 63 - enemy_file_pawns.leading_zeros() } as u8;
+            let storm_tile = Tile::from(index);
+            let storm_rank = storm_tile.relative_rank(side);
+
+            if storm_rank <= king_tile.relative_rank(side) {
+                continue;
+            }
+
+            let blocker_rank = if side == PieceColor::White { storm_tile.rank - 1 } else { storm_tile.rank + 1 };
+            if Tile::new(blocker_rank, file).is_some_and(|tile| our_pawns.get_bit(tile)) {
+                king_files.blocked_storms[storm_rank as usize] += 1;
+            } else {
+                king_files.storms[storm_rank as usize] += 1;
+            }
+        }
+
+        king_files
+    }
+}
+
+/// Evaluates the pawns around a side's king (as a middlegame penalty), penalizing
+/// files without friendly pawns and enemy pawns storming towards the king.
+pub fn evaluate_king_files(board: &Board, side: PieceColor, params: &EvalParams) -> i32 {
+    let king_files = KingFiles::new(board, side);
+
+    let mut penalty = king_files.semi_open * params.king_semi_open_file_penalty + king_files.open * params.king_open_file_penalty;
+    for rank in 0..8 {
+        penalty += king_files.storms[rank] * params.pawn_storm_penalty[rank] + king_files.blocked_storms[rank] * params.blocked_pawn_storm_penalty[rank];
+    }
+
+    -penalty
+}
+
+/// How far a side has come towards getting its king to safety by castling.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Castling {
+    /// The number of castling rights the side keeps (up to two).
+    pub rights: i32,
+    /// Whether or not the side has castled (as 0 or 1).
+    pub castled: i32,
+    /// Whether or not the king is stuck on the central files without having castled or kept any rights (as 0 or 1).
+    pub stranded: i32
+}
+
+impl Castling {
+    /// Finds how a side's king stands with respect to castling. A king which castled before the position
+    /// was set up from a FEN can't be told apart from one which walked there, so it only counts as not stranded.
+    pub fn new(board: &Board, side: PieceColor) -> Self {
+        let rights = match board.castle_rights[side.to_index()] {
+            CastleRights::None => 0,
+            CastleRights::KingSide | CastleRights::QueenSide => 1,
+            CastleRights::Both => 2
+        };
+        let castled = board.has_castled[side.to_index()];
+        let central = STRANDED_KING_FILES & 1 << board.king_tile(side).index() != 0;
+
+        Castling { rights, castled: castled as i32, stranded: (rights == 0 && !castled && central) as i32 }
+    }
+}
+
+/// Evaluates a side's castling (as a middlegame bonus), rewarding the rights it keeps and having castled,
+/// and penalizing a king stranded in the center, which the piece square tables can't tell apart from one about to castle.
+pub fn evaluate_castling(board: &Board, side: PieceColor, params: &EvalParams) -> i32 {
+    let castling = Castling::new(board, side);
+    castling.rights * params.castling_right_bonus + castling.castled * params.castled_bonus - castling.stranded * params.stranded_king_penalty
+}
+
+/// The files next to a file.
+fn adjacent_files(file: u8) -> u64 {
+    let file_mask = A_FILE << file;
+    (file_mask << 1 & !A_FILE) | (file_mask >> 1 & !H_FILE)
+}
+
+/// The ranks in front of a tile, from a side's point of view.
+fn ranks_ahead(tile: Tile, side: PieceColor) -> u64 {
+    if side == PieceColor::White {
+        u64::MAX.checked_shl(8 * (tile.rank as u32 + 1)).unwrap_or(0)
+    } else {
+        (1 << (8 * tile.rank)) - 1
+    }
+}
+
+/// Finds a side's passed pawns, which no enemy pawn can stop or capture on their way to promotion.
+pub fn passed_pawns(board: &Board, side: PieceColor) -> Bitboard {
+    let enemy_pawns = board.colored_piece(PieceType::Pawn, !side);
+
+    let mut passed = Bitboard::ZERO;
+    let mut pawns = board.colored_piece(PieceType::Pawn, side);
+    while pawns != Bitboard::ZERO {
+        let tile = pawns.pop_lsb();
+        let front_span = ((A_FILE << tile.file) | adjacent_files(tile.file)) & ranks_ahead(tile, side);
+
+        if enemy_pawns.board & front_span == 0 {
+            passed.set_bit(tile);
+        }
+    }
+
+    passed
+}
+
+/// The placement of a side's knights and bishops relative to the pawns.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MinorPieces {
+    /// The number of knights on outposts.
+    pub knight_outposts: i32,
+    /// The number of bishops on outposts.
+    pub bishop_outposts: i32,
+    /// The number of friendly pawns on the same color complex as each bishop, summed over the bishops.
+    pub bad_bishop_pawns: i32
+}
+
+impl MinorPieces {
+    /// Finds the outposts and bad bishops of a side, given its attacks.
+    pub fn new(board: &Board, side: PieceColor, attacks: &Attacks) -> Self {
+        let our_pawns = board.colored_piece(PieceType::Pawn, side);
+        let enemy_pawns = board.colored_piece(PieceType::Pawn, !side);
+        let pawn_protected = attacks.piece(PieceType::Pawn);
+
+        let is_outpost = |tile: Tile| {
+            if !(3..=5).contains(&tile.relative_rank(side)) || !pawn_protected.get_bit(tile) {
+                return false;
+            }
+
+            // Enemy pawns on the neighbouring files which haven't passed the tile yet could still attack it.
+            enemy_pawns.board & adjacent_files(tile.file) & ranks_ahead(tile, side) == 0
+        };
+
+        let mut minor_pieces = MinorPieces::default();
+
+        let mut knights = board.colored_piece(PieceType::Knight, side);
+        while knights != Bitboard::ZERO {
+            minor_pieces.knight_outposts += is_outpost(knights.pop_lsb()) as i32;
+        }
+
+        let mut bishops = board.colored_piece(PieceType::Bishop, side);
+        while bishops != Bitboard::ZERO {
+            let tile = bishops.pop_lsb();
+            minor_pieces.bishop_outposts += is_outpost(tile) as i32;
+
+            let color_complex = if Bitboard::new(LIGHT_TILES).get_bit(tile) { LIGHT_TILES } else { DARK_TILES };
+            minor_pieces.bad_bishop_pawns += (our_pawns.board & color_complex).count_ones() as i32;
+        }
+
+        minor_pieces
+    }
+}
+
+/// Evaluates the placement of a side's knights and bishops, rewarding outposts and
+/// penalizing bishops hemmed in by their own pawns.
+pub fn evaluate_minor_pieces(board: &Board, side: PieceColor, attacks: &Attacks, params: &EvalParams) -> (i32, i32) {
+    let minor_pieces = MinorPieces::new(board, side, attacks);
+
+    let mg = minor_pieces.knight_outposts * params.knight_outpost_bonus.0 + minor_pieces.bishop_outposts * params.bishop_outpost_bonus.0 - minor_pieces.bad_bishop_pawns * params.bad_bishop_penalty.0;
+    let eg = minor_pieces.knight_outposts * params.knight_outpost_bonus.1 + minor_pieces.bishop_outposts * params.bishop_outpost_bonus.1 - minor_pieces.bad_bishop_pawns * params.bad_bishop_penalty.1;
+
+    (mg, eg)
+}
+
+/// The distances between a side's pieces and the kings.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KingProximity {
+    /// How much closer than 7 tiles each piece is to the enemy king, summed and indexed by piece type.
+    pub tropism: [i32; 6],
+    /// How much closer the king is to the center than to the edge, from 0 to 3.
+    pub centralization: i32,
+    /// The distance of the king from each of its passed pawns, summed.
+    pub passed_pawn_distance: i32,
+    /// The distance of the enemy king from each of our passed pawns, summed.
+    pub enemy_passed_pawn_distance: i32
+}
+
+impl KingProximity {
+    /// Measures the distances between a side's pieces and the kings.
+    pub fn new(board: &Board, side: PieceColor) -> Self {
+        let king_tile = board.king_tile(side);
+        let enemy_king_tile = board.king_tile(!side);
+
+        let mut king_proximity = KingProximity::default();
+        for piece_type in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+            let mut pieces = board.colored_piece(piece_type, side);
+            while pieces != Bitboard::ZERO {
+                king_proximity.tropism[piece_type as usize] += 7 - distance(pieces.pop_lsb(), enemy_king_tile);
+            }
+        }
+
+        let edge_distance = |coordinate: u8| coordinate.min(7 - coordinate) as i32;
+        king_proximity.centralization = edge_distance(king_tile.rank).min(edge_distance(king_tile.file));
+
+        let mut passed = passed_pawns(board, side);
+        while passed != Bitboard::ZERO {
+            let tile = passed.pop_lsb();
+            king_proximity.passed_pawn_distance += distance(king_tile, tile);
+            king_proximity.enemy_passed_pawn_distance += distance(enemy_king_tile, tile);
+        }
+
+        king_proximity
+    }
+}
+
+/// Evaluates how close a side's pieces are to the enemy king in the middlegame, and
+/// how active its king is (in the center and near the passed pawns) in the endgame.
+pub fn evaluate_king_proximity(board: &Board, side: PieceColor, params: &EvalParams) -> (i32, i32) {
+    let king_proximity = KingProximity::new(board, side);
+
+    let mg = PieceType::iter().map(|piece_type| king_proximity.tropism[piece_type as usize] * params.king_tropism_weights[piece_type as usize]).sum();
+    let eg = king_proximity.centralization * params.king_centralization_bonus
+        - king_proximity.passed_pawn_distance * params.king_passed_pawn_distance_penalty
+        + king_proximity.enemy_passed_pawn_distance * params.enemy_king_passed_pawn_distance_bonus;
+
+    (mg, eg)
+}
+
+/// Measures the space a side controls, see `SPACE_WEIGHT` and `SPACE_PIECE_DIVISOR`.
+///
+/// Only the central files of the 2nd to 4th ranks count, where tiles which aren't attacked by enemy pawns
+/// (or blocked by our own) give our pieces room to maneuver, especially behind a pawn chain.
+pub fn space(board: &Board, side: PieceColor, enemy_attacks: &Attacks) -> i32 {
+    let our_pawns = board.colored_piece(PieceType::Pawn, side).board;
+    let safe = SPACE_MASK[side.to_index()] & !our_pawns & !enemy_attacks.piece(PieceType::Pawn).board;
+
+    // Up to three tiles behind each of our pawns.
+    let behind = if side == PieceColor::White {
+        our_pawns >> 8 | our_pawns >> 16 | our_pawns >> 24
+    } else {
+        our_pawns << 8 | our_pawns << 16 | our_pawns << 24
+    };
+
+    let area = (safe.count_ones() + (safe & behind).count_ones()) as i32;
+    let pieces = (board.color(side).board & !our_pawns).count_ones() as i32 - 1;
+
+    area * pieces / SPACE_PIECE_DIVISOR
+}
+
+/// Evaluates the space a side controls (as a middlegame bonus).
+pub fn evaluate_space(board: &Board, side: PieceColor, enemy_attacks: &Attacks, params: &EvalParams) -> i32 {
+    space(board, side, enemy_attacks) * params.space_weight
+}
+
+/// Calculates how much of the endgame evaluation should be kept (out of `SCALE_NORMAL`),
+/// given the side which the evaluation favors.
+pub fn endgame_scale(board: &Board, strong_side: PieceColor) -> i32 {
+    let weak_side = !strong_side;
+
+    let count = |piece_type: PieceType, side: PieceColor| board.colored_piece(piece_type, side).board.count_ones();
+    let minors_and_majors = |side: PieceColor| count(PieceType::Knight, side) + count(PieceType::Rook, side) + count(PieceType::Queen, side);
+
+    let strong_pawns = board.colored_piece(PieceType::Pawn, strong_side);
+    let strong_bishops = board.colored_piece(PieceType::Bishop, strong_side);
+
+    // Wrong rook pawn: a bishop which can't control the promotion tile of rook pawns,
+    // with the defending king already in the corner.
+    if strong_bishops.board.count_ones() == 1 && minors_and_majors(strong_side) == 0 && strong_pawns != Bitboard::ZERO {
+        let file = if strong_pawns.board & !A_FILE == 0 {
+            Some(0)
+        } else if strong_pawns.board & !H_FILE == 0 {
+            Some(7)
+        } else {
+            None
+        };
+
+        if let Some(file) = file {
+            let promotion_tile = Tile::at(if strong_side == PieceColor::White { 7 } else { 0 }, file);
+            let bishop_on_light = strong_bishops.board & LIGHT_TILES != 0;
+            let promotion_on_light = Bitboard::new(LIGHT_TILES).get_bit(promotion_tile);
+
+            let weak_king = board.king_tile(weak_side);
+            let king_distance = (weak_king.rank as i8 - promotion_tile.rank as i8).abs().max((weak_king.file as i8 - promotion_tile.file as i8).abs());
+
+            if bishop_on_light != promotion_on_light && king_distance <= 1 {
+                return SCALE_DRAW;
+            }
+        }
+    }
+
+    // Opposite colored bishops: the defending bishop can often blockade the pawns on its color.
+    let weak_bishops = board.colored_piece(PieceType::Bishop, weak_side);
+    if strong_bishops.board.count_ones() == 1 && weak_bishops.board.count_ones() == 1 {
+        let opposite_colors = (strong_bishops.board & LIGHT_TILES != 0) != (weak_bishops.board & LIGHT_TILES != 0);
+
+        if opposite_colors {
+            if minors_and_majors(strong_side) == 0 && minors_and_majors(weak_side) == 0 {
+                return SCALE_OPPOSITE_BISHOPS;
+            }
+
+            return SCALE_OPPOSITE_BISHOPS_WITH_PIECES;
+        }
+    }
+
+    // Rook endgames with an extra pawn are frequently drawn.
+    let only_rooks = |side: PieceColor| count(PieceType::Rook, side) == 1 && count(PieceType::Knight, side) + count(PieceType::Bishop, side) + count(PieceType::Queen, side) == 0;
+    if only_rooks(strong_side) && only_rooks(weak_side) && strong_pawns.board.count_ones() == count(PieceType::Pawn, weak_side) + 1 {
+        return SCALE_ROOK_PAWN_UP;
+    }
+
+    SCALE_NORMAL
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::{Bitboard, Board}, consts::{SCALE_DRAW, SCALE_NORMAL, SCALE_OPPOSITE_BISHOPS, SCALE_OPPOSITE_BISHOPS_WITH_PIECES, SCALE_ROOK_PAWN_UP}, piece::{PieceColor, PieceType}, piece_move::Move};
+
+    use crate::engine::eval_params::EvalParams;
+
+    use super::{endgame_scale, evaluate_board, evaluate_king_files, passed_pawns, space, Castling, Evaluation, KingProximity, MinorPieces};
+
+    #[test]
+    fn test_king_files() {
+        let params = EvalParams::default();
+
+        // A castled king behind an intact shield has nothing to fear.
+        let castled = Board::new("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQ1RK1 w - - 0 1");
+        assert_eq!(evaluate_king_files(&castled, PieceColor::White, &params), 0);
+        assert_eq!(evaluate_king_files(&castled, PieceColor::Black, &params), 0);
+
+        // Enemy pawns advancing towards the king hurt more the closer they get.
+        let far_storm = Board::new("2kr3r/ppp2pp1/7p/8/8/8/PPP2PPP/R4RK1 w - - 0 1");
+        let near_storm = Board::new("2kr3r/ppp2p2/8/8/6pp/8/PPP2PPP/R4RK1 w - - 0 1");
+        assert!(evaluate_king_files(&near_storm, PieceColor::White, &params) < evaluate_king_files(&far_storm, PieceColor::White, &params));
+
+        // Open files next to the king are worse than semi-open ones, which are worse than closed ones.
+        let open = Board::new("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1");
+        let semi_open = Board::new("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1");
+        assert!(evaluate_king_files(&open, PieceColor::White, &params) < evaluate_king_files(&semi_open, PieceColor::White, &params));
+        assert!(evaluate_king_files(&semi_open, PieceColor::White, &params) < evaluate_king_files(&semi_open, PieceColor::Black, &params));
+    }
+
+    #[test]
+    fn test_castling() {
+        let board = Board::new("r3k2r/pppq1ppp/2npbn2/2b1p3/2B1P3/2NPBN2/PPPQ1PPP/R3K2R w Kq - 0 1");
+        assert_eq!(Castling::new(&board, PieceColor::White), Castling { rights: 1, castled: 0, stranded: 0 });
+
+        // Castling trades the rights for having castled, while walking the king gives them up for nothing.
+        let castled = board.make_move(&Move::from_uci_on(&board, "e1g1").expect("expected castling to be legal"), false).unwrap();
+        assert_eq!(Castling::new(&castled, PieceColor::White), Castling { rights: 0, castled: 1, stranded: 0 });
+
+        let walked = board.make_move(&Move::from_uci_on(&board, "e1e2").expect("expected a legal king move"), false).unwrap();
+        assert_eq!(Castling::new(&walked, PieceColor::White), Castling { rights: 0, castled: 0, stranded: 1 });
+
+        // A king tucked away on the wing isn't stranded, even without having castled.
+        let wing = Board::new("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(Castling::new(&wing, PieceColor::Black), Castling::default());
+    }
+
+    #[test]
+    fn test_minor_pieces() {
+        let minor_pieces = |fen: &str, side: PieceColor| {
+            let board = Board::new(fen);
+            MinorPieces::new(&board, side, &board.attacks_by(side))
+        };
+
+        // A knight on d5 protected by the e4 pawn, which no black pawn can chase away.
+        let outpost = minor_pieces("4k3/p4ppp/3p4/3N4/4P3/8/8/4K3 w - - 0 1", PieceColor::White);
+        assert_eq!(outpost.knight_outposts, 1);
+
+        // The c7 pawn could still kick the knight with c6.
+        let contested = minor_pieces("4k3/2p2ppp/3p4/3N4/4P3/8/8/4K3 w - - 0 1", PieceColor::White);
+        assert_eq!(contested.knight_outposts, 0);
+
+        // Black's outposts are mirrored, and a bishop counts its own pawns on its color.
+        let black = minor_pieces("4k3/8/8/4p3/3b4/8/P4PPP/4K3 b - - 0 1", PieceColor::Black);
+        assert_eq!(black.bishop_outposts, 1);
+        assert_eq!(black.bad_bishop_pawns, 1);
+    }
+
+    #[test]
+    fn test_king_proximity() {
+        // The d5 pawn is passed, while the a2 and b7 pawns stand in each other's way.
+        let board = Board::new("8/1p6/4k3/3P4/8/8/P7/4K3 w - - 0 1");
+        assert_eq!(passed_pawns(&board, PieceColor::White).board.count_ones(), 1);
+        assert_eq!(passed_pawns(&board, PieceColor::Black), Bitboard::ZERO);
+
+        let white = KingProximity::new(&board, PieceColor::White);
+        assert_eq!(white.centralization, 0);
+        assert_eq!(white.passed_pawn_distance, 4);
+        assert_eq!(white.enemy_passed_pawn_distance, 1);
+
+        // A queen next to the enemy king is close, one across the board isn't.
+        let near = KingProximity::new(&Board::new("6k1/8/5Q2/8/8/8/8/K7 w - - 0 1"), PieceColor::White);
+        let far = KingProximity::new(&Board::new("6k1/8/8/8/8/8/8/KQ6 w - - 0 1"), PieceColor::White);
+        assert!(near.tropism[PieceType::Queen as usize] > far.tropism[PieceType::Queen as usize]);
+    }
+
+    #[test]
+    fn test_space() {
+        let space_of = |fen: &str, side: PieceColor| {
+            let board = Board::new(fen);
+            space(&board, side, &board.attacks_by(!side))
+        };
+
+        // Advanced central pawns claim room behind them, which matters more with more pieces around.
+        let cramped = "r1bqkb1r/pppppppp/2n2n2/8/8/2N2N2/PPPPPPPP/R1BQKB1R w KQkq - 0 1";
+        let spacious = "r1bqkb1r/pppppppp/2n2n2/8/2PPP3/2N2N2/PP3PPP/R1BQKB1R w KQkq - 0 1";
+        assert!(space_of(spacious, PieceColor::White) > space_of(cramped, PieceColor::White));
+        assert_eq!(space_of(cramped, PieceColor::White), space_of(cramped, PieceColor::Black));
+
+        let endgame = "4k3/pppppppp/8/8/2PPP3/8/PP3PPP/4K3 w - - 0 1";
+        assert_eq!(space_of(endgame, PieceColor::White), 0);
+    }
+
+    #[test]
+    fn test_lazy_evaluation() {
+        let params = EvalParams::default();
+
+        // White is a queen up, which no positional term could make up for.
+        let board = Board::new("rnb1kbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3");
+        let Evaluation::Full(full) = evaluate_board(&board, &params, None) else {
+            panic!("expected a full evaluation without a window");
+        };
+
+        assert_eq!(evaluate_board(&board, &params, Some((full - 50, full + 50))), Evaluation::Full(full));
+        assert!(matches!(evaluate_board(&board, &params, Some((-100, 0))), Evaluation::Lazy(score) if score > 0));
+
+        // The side a queen down fails low just as lazily.
+        let board = Board::new("rnb1kbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3");
+        assert!(matches!(evaluate_board(&board, &params, Some((0, 100))), Evaluation::Lazy(score) if score < 0));
+    }
+
+    #[test]
+    fn test_endgame_scale() {
+        // Two pawns up with opposite colored bishops is a textbook draw, so the endgame score is halved.
+        let opposite = Board::new("8/4k1b1/8/3P4/2P5/3B4/4K3/8 w - - 0 1");
+        assert_eq!(endgame_scale(&opposite, PieceColor::White), SCALE_OPPOSITE_BISHOPS);
+
+        // With the bishops on the same color, the same material is scaled no further than usual.
+        let same = Board::new("8/4k3/4b3/3P4/2P5/3B4/4K3/8 w - - 0 1");
+        assert_eq!(endgame_scale(&same, PieceColor::White), SCALE_NORMAL);
+
+        // Other pieces on the board leave the side ahead more to play with.
+        let with_rooks = Board::new("r7/4k1b1/8/3P4/2P5/3B4/4K3/7R w - - 0 1");
+        assert_eq!(endgame_scale(&with_rooks, PieceColor::White), SCALE_OPPOSITE_BISHOPS_WITH_PIECES);
+
+        // A rook pawn with the wrong bishop can't be promoted past a king in the corner, so the endgame score is dropped.
+        let wrong_bishop = Board::new("k7/8/8/8/8/8/P7/B3K3 w - - 0 1");
+        assert_eq!(endgame_scale(&wrong_bishop, PieceColor::White), SCALE_DRAW);
+
+        let right_bishop = Board::new("k7/8/8/8/8/8/P7/1B2K3 w - - 0 1");
+        assert_eq!(endgame_scale(&right_bishop, PieceColor::White), SCALE_NORMAL);
+
+        // A rook endgame a pawn up is scaled down, but not two pawns up.
+        assert_eq!(endgame_scale(&Board::new("8/5k2/8/4p3/3P1P2/8/r7/4K2R w - - 0 1"), PieceColor::White), SCALE_ROOK_PAWN_UP);
+        assert_eq!(endgame_scale(&Board::new("8/5k2/8/8/3P1P2/8/r7/4K2R w - - 0 1"), PieceColor::White), SCALE_NORMAL);
+
+        // A lone knight isn't scaled here, since the endgame recognizers already score it as a dead draw.
+        let knight = Board::new("8/8/4k3/8/8/3NK3/8/8 w - - 0 1");
+        assert_eq!(endgame_scale(&knight, PieceColor::White), SCALE_NORMAL);
+        assert_eq!(evaluate_board(&knight, &EvalParams::default(), None), Evaluation::Full(0));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,195 @@
+use serde_json::Value;
+
+use crate::utils::consts::{BAD_BISHOP_PENALTY, BISHOP_OUTPOST_BONUS, BLOCKED_PAWN_STORM_PENALTY, CASTLED_BONUS, CASTLING_RIGHT_BONUS, ENEMY_KING_PASSED_PAWN_DISTANCE_BONUS, KING_CENTRALIZATION_BONUS, KING_OPEN_FILE_PENALTY, KING_PASSED_PAWN_DISTANCE_PENALTY, KING_SEMI_OPEN_FILE_PENALTY, KING_TROPISM_WEIGHTS, KING_ZONE_ATTACK_WEIGHTS, KNIGHT_OUTPOST_BONUS, MATERIAL_VALUES, MOBILITY_WEIGHTS, PAWN_STORM_PENALTY, PIECE_SQUARE_TABLE, SPACE_WEIGHT, STRANDED_KING_PENALTY};
+
+/// The weights of the evaluation, which default to the constants of the same name.
+///
+/// Parameters can be overridden from a JSON object keyed by the constant names, such as
+/// `{ "SPACE_WEIGHT": 3, "KNIGHT_OUTPOST_BONUS": [30, 20] }`, where (middlegame, endgame)
+/// pairs are written as two element arrays. Parameters which are left out keep their defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    pub material_values: [(i32, i32); 6],
+    pub piece_square_table: [[(i32, i32); 64]; 6],
+    pub mobility_weights: [(i32, i32); 6],
+    pub king_zone_attack_weights: [i32; 6],
+    pub king_semi_open_file_penalty: i32,
+    pub king_open_file_penalty: i32,
+    pub pawn_storm_penalty: [i32; 8],
+    pub blocked_pawn_storm_penalty: [i32; 8],
+    pub knight_outpost_bonus: (i32, i32),
+    pub bishop_outpost_bonus: (i32, i32),
+    pub bad_bishop_penalty: (i32, i32),
+    pub king_tropism_weights: [i32; 6],
+    pub king_centralization_bonus: i32,
+    pub king_passed_pawn_distance_penalty: i32,
+    pub enemy_king_passed_pawn_distance_bonus: i32,
+    pub space_weight: i32,
+    pub castling_right_bonus: i32,
+    pub castled_bonus: i32,
+    pub stranded_king_penalty: i32
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            material_values: MATERIAL_VALUES,
+            piece_square_table: PIECE_SQUARE_TABLE,
+            mobility_weights: MOBILITY_WEIGHTS,
+            king_zone_attack_weights: KING_ZONE_ATTACK_WEIGHTS,
+            king_semi_open_file_penalty: KING_SEMI_OPEN_FILE_PENALTY,
+            king_open_file_penalty: KING_OPEN_FILE_PENALTY,
+            pawn_storm_penalty: PAWN_STORM_PENALTY,
+            blocked_pawn_storm_penalty: BLOCKED_PAWN_STORM_PENALTY,
+            knight_outpost_bonus: KNIGHT_OUTPOST_BONUS,
+            bishop_outpost_bonus: BISHOP_OUTPOST_BONUS,
+            bad_bishop_penalty: BAD_BISHOP_PENALTY,
+            king_tropism_weights: KING_TROPISM_WEIGHTS,
+            king_centralization_bonus: KING_CENTRALIZATION_BONUS,
+            king_passed_pawn_distance_penalty: KING_PASSED_PAWN_DISTANCE_PENALTY,
+            enemy_king_passed_pawn_distance_bonus: ENEMY_KING_PASSED_PAWN_DISTANCE_BONUS,
+            space_weight: SPACE_WEIGHT,
+            castling_right_bonus: CASTLING_RIGHT_BONUS,
+            castled_bonus: CASTLED_BONUS,
+            stranded_king_penalty: STRANDED_KING_PENALTY
+        }
+    }
+}
+
+/// A parameter which can be read from (and written to) JSON.
+trait Param {
+    /// Replaces the parameter with a JSON value, returning whether or not it was valid.
+    fn set(&mut self, value: &Value) -> bool;
+    /// The parameter as a JSON value.
+    fn get(&self) -> Value;
+}
+
+impl Param for i32 {
+    fn set(&mut self, value: &Value) -> bool {
+        match value.as_i64().and_then(|value| i32::try_from(value).ok()) {
+            Some(parsed) => {
+                *self = parsed;
+                true
+            },
+            None => false
+        }
+    }
+
+    fn get(&self) -> Value {
+        Value::from(*self)
+    }
+}
+
+impl Param for (i32, i32) {
+    fn set(&mut self, value: &Value) -> bool {
+        let mut pair = [self.0, self.1];
+        let valid = pair.set(value);
+        (self.0, self.1) = (pair[0], pair[1]);
+
+        valid
+    }
+
+    fn get(&self) -> Value {
+        [self.0, self.1].get()
+    }
+}
+
+impl<T: Param, const N: usize> Param for [T; N] {
+    fn set(&mut self, value: &Value) -> bool {
+        value.as_array()
+            .filter(|values| values.len() == N)
+            .is_some_and(|values| self.iter_mut().zip(values).all(|(param, value)| param.set(value)))
+    }
+
+    fn get(&self) -> Value {
+        Value::Array(self.iter().map(Param::get).collect())
+    }
+}
+
+impl EvalParams {
+    /// Every parameter, paired with the name of its constant.
+    fn slots(&mut self) -> [(&'static str, &mut dyn Param); 19] {
+        [
+            ("MATERIAL_VALUES", &mut self.material_values),
+            ("PIECE_SQUARE_TABLE", &mut self.piece_square_table),
+            ("MOBILITY_WEIGHTS", &mut self.mobility_weights),
+            ("KING_ZONE_ATTACK_WEIGHTS", &mut self.king_zone_attack_weights),
+            ("KING_SEMI_OPEN_FILE_PENALTY", &mut self.king_semi_open_file_penalty),
+            ("KING_OPEN_FILE_PENALTY", &mut self.king_open_file_penalty),
+            ("PAWN_STORM_PENALTY", &mut self.pawn_storm_penalty),
+            ("BLOCKED_PAWN_STORM_PENALTY", &mut self.blocked_pawn_storm_penalty),
+            ("KNIGHT_OUTPOST_BONUS", &mut self.knight_outpost_bonus),
+            ("BISHOP_OUTPOST_BONUS", &mut self.bishop_outpost_bonus),
+            ("BAD_BISHOP_PENALTY", &mut self.bad_bishop_penalty),
+            ("KING_TROPISM_WEIGHTS", &mut self.king_tropism_weights),
+            ("KING_CENTRALIZATION_BONUS", &mut self.king_centralization_bonus),
+            ("KING_PASSED_PAWN_DISTANCE_PENALTY", &mut self.king_passed_pawn_distance_penalty),
+            ("ENEMY_KING_PASSED_PAWN_DISTANCE_BONUS", &mut self.enemy_king_passed_pawn_distance_bonus),
+            ("SPACE_WEIGHT", &mut self.space_weight),
+            ("CASTLING_RIGHT_BONUS", &mut self.castling_right_bonus),
+            ("CASTLED_BONUS", &mut self.castled_bonus),
+            ("STRANDED_KING_PENALTY", &mut self.stranded_king_penalty)
+        ]
+    }
+
+    /// Overrides the parameters found in a JSON object. Nothing is changed if any of them are unknown or malformed.
+    pub fn apply_json(&mut self, json: &Value) -> Result<(), String> {
+        let object = json.as_object().ok_or("expected a JSON object of parameters")?;
+
+        let mut overridden = self.clone();
+        let mut slots = overridden.slots();
+        for (name, value) in object {
+            let (_, slot) = slots.iter_mut().find(|(slot_name, _)| slot_name == name).ok_or_else(|| format!("unknown parameter {}", name))?;
+
+            if !slot.set(value) {
+                return Err(format!("invalid value for {}", name));
+            }
+        }
+
+        *self = overridden;
+        Ok(())
+    }
+
+    /// Loads parameters from a JSON file, starting from the defaults.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let json: Value = serde_json::from_str(&contents).map_err(|error| error.to_string())?;
+
+        let mut params = EvalParams::default();
+        params.apply_json(&json)?;
+
+        Ok(params)
+    }
+
+    /// Writes every parameter to a JSON object, which can be loaded again.
+    pub fn to_json(&self) -> Value {
+        let object = self.clone().slots().into_iter().map(|(name, slot)| (name.to_string(), slot.get())).collect();
+        Value::Object(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::EvalParams;
+
+    #[test]
+    fn test_eval_params() {
+        let mut params = EvalParams::default();
+        params.apply_json(&json!({ "SPACE_WEIGHT": 5, "KNIGHT_OUTPOST_BONUS": [30, 20] })).expect("expected valid parameters");
+        assert_eq!(params.space_weight, 5);
+        assert_eq!(params.knight_outpost_bonus, (30, 20));
+        assert_eq!(params.material_values, EvalParams::default().material_values);
+
+        // Bad files change nothing.
+        assert!(params.apply_json(&json!({ "SPACE_WEIGHT": 1, "NOT_A_WEIGHT": 1 })).unwrap_err().contains("NOT_A_WEIGHT"));
+        assert!(params.apply_json(&json!({ "MOBILITY_WEIGHTS": [1, 2, 3] })).is_err());
+        assert_eq!(params.space_weight, 5);
+
+        // Every parameter survives a round trip.
+        let mut round_trip = EvalParams { space_weight: 0, ..EvalParams::default() };
+        round_trip.apply_json(&params.to_json()).expect("expected valid parameters");
+        assert_eq!(round_trip, params);
+    }
+}
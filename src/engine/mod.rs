@@ -1,2 +1,10 @@
+pub mod book;
+pub mod endgame;
 pub mod eval;
-pub mod search;
\ No newline at end of file
+pub mod eval_params;
+pub mod pn_search;
+pub mod position_store;
+pub mod root_split;
+pub mod search;
+pub mod stats;
+pub mod time_manager;
\ No newline at end of file
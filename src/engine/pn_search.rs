@@ -0,0 +1,241 @@
+use arrayvec::ArrayVec;
+
+use crate::utils::{board::Board, piece_move::{Move, MoveArray}};
+
+/// The proof or disproof number of a node which can never be proven or disproven.
+const INFINITY: u32 = u32::MAX;
+
+/// A node in the proof-number search tree.
+struct PNNode {
+    /// The move which leads from the parent to this node.
+    piece_move: Option<Move>,
+    /// The index of the parent node.
+    parent: Option<usize>,
+    /// The indices of the child nodes, empty until the node is expanded.
+    children: Vec<usize>,
+    /// The minimum number of leaves which must be proven to prove this node.
+    proof: u32,
+    /// The minimum number of leaves which must be disproven to disprove this node.
+    disproof: u32,
+    /// Whether or not the attacking side is to move (an OR node).
+    attacker_to_move: bool,
+    /// The distance of the node from the root.
+    ply: usize
+}
+
+/// The outcome of a proof-number search.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveResult {
+    /// The attacking side forces mate, along with the mating line.
+    Proven(Vec<Move>),
+    /// The attacking side cannot force mate (within the ply limit).
+    Disproven,
+    /// The node budget ran out before the position was solved.
+    Unknown
+}
+
+/// A proof-number search solver, which tries to prove a forced mate for the side to move.
+///
+/// This is independent of the alpha-beta search, and keeps its own tree in memory.
+pub struct ProofNumberSearch {
+    /// Every node in the tree, where the root is the first node.
+    nodes: Vec<PNNode>,
+    /// The maximum number of nodes in the tree.
+    max_nodes: usize,
+    /// The number of plies within which mate must be delivered, if any.
+    max_plies: Option<usize>
+}
+
+impl ProofNumberSearch {
+    /// Creates a new solver with a node budget and an optional ply limit.
+    pub fn new(max_nodes: usize, max_plies: Option<usize>) -> Self {
+        ProofNumberSearch {
+            nodes: Vec::new(),
+            max_nodes,
+            max_plies
+        }
+    }
+
+    /// The number of nodes in the tree.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Tries to prove that the side to move can force mate.
+    pub fn solve(&mut self, board: &Board) -> SolveResult {
+        self.nodes.clear();
+        self.nodes.push(PNNode { piece_move: None, parent: None, children: Vec::new(), proof: 1, disproof: 1, attacker_to_move: true, ply: 0 });
+        self.evaluate(0, board);
+
+        while self.nodes[0].proof != 0 && self.nodes[0].disproof != 0 && self.nodes.len() < self.max_nodes {
+            let (index, node_board) = self.select_most_proving(board);
+
+            self.expand(index, &node_board);
+            self.update_ancestors(index);
+        }
+
+        if self.nodes[0].proof == 0 {
+            SolveResult::Proven(self.principal_variation())
+        } else if self.nodes[0].disproof == 0 {
+            SolveResult::Disproven
+        } else {
+            SolveResult::Unknown
+        }
+    }
+
+    /// Generates every legal move in a position.
+    fn legal_moves(board: &Board) -> MoveArray {
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+        moves.retain(|piece_move| board.make_move(piece_move, true).is_some());
+
+        moves
+    }
+
+    /// Initializes the proof and disproof numbers of a new leaf.
+    fn evaluate(&mut self, index: usize, board: &Board) {
+        let moves = Self::legal_moves(board);
+        let node = &mut self.nodes[index];
+
+        let (proof, disproof) = if moves.is_empty() {
+            // A side which is mated loses, and a stalemate is never a proof.
+            if board.in_check(board.side_to_move) && !node.attacker_to_move {
+                (0, INFINITY)
+            } else {
+                (INFINITY, 0)
+            }
+        } else if self.max_plies.is_some_and(|max_plies| node.ply >= max_plies) {
+            (INFINITY, 0)
+        } else if node.attacker_to_move {
+            (1, moves.len() as u32)
+        } else {
+            (moves.len() as u32, 1)
+        };
+
+        node.proof = proof;
+        node.disproof = disproof;
+    }
+
+    /// Adds every child of a leaf to the tree.
+    fn expand(&mut self, index: usize, board: &Board) {
+        let (attacker_to_move, ply) = (self.nodes[index].attacker_to_move, self.nodes[index].ply);
+
+        for piece_move in Self::legal_moves(board) {
+            let child_board = board.make_move(&piece_move, true).expect("expected a legal move");
+            let child_index = self.nodes.len();
+
+            self.nodes.push(PNNode {
+                piece_move: Some(piece_move),
+                parent: Some(index),
+                children: Vec::new(),
+                proof: 1,
+                disproof: 1,
+                attacker_to_move: !attacker_to_move,
+                ply: ply + 1
+            });
+
+            self.evaluate(child_index, &child_board);
+            self.nodes[index].children.push(child_index);
+        }
+    }
+
+    /// Recomputes the proof and disproof numbers of a node and all of its ancestors.
+    fn update_ancestors(&mut self, index: usize) {
+        let mut current = Some(index);
+
+        while let Some(index) = current {
+            let node = &self.nodes[index];
+            let proofs = node.children.iter().map(|&child| self.nodes[child].proof);
+            let disproofs = node.children.iter().map(|&child| self.nodes[child].disproof);
+
+            let (proof, disproof) = if node.attacker_to_move {
+                (proofs.min().unwrap_or(INFINITY), disproofs.fold(0, u32::saturating_add))
+            } else {
+                (proofs.fold(0, u32::saturating_add), disproofs.min().unwrap_or(INFINITY))
+            };
+
+            let node = &mut self.nodes[index];
+            node.proof = proof;
+            node.disproof = disproof;
+
+            current = node.parent;
+        }
+    }
+
+    /// Finds the leaf which would contribute the most to solving the root,
+    /// along with the position at that leaf.
+    fn select_most_proving(&self, root_board: &Board) -> (usize, Board) {
+        let mut index = 0;
+        let mut board = root_board.clone();
+
+        while !self.nodes[index].children.is_empty() {
+            let node = &self.nodes[index];
+
+            index = if node.attacker_to_move {
+                *node.children.iter().find(|&&child| self.nodes[child].proof == node.proof).expect("expected a child with the same proof number")
+            } else {
+                *node.children.iter().find(|&&child| self.nodes[child].disproof == node.disproof).expect("expected a child with the same disproof number")
+            };
+
+            let piece_move = self.nodes[index].piece_move.expect("expected a move for a child node");
+            board = board.make_move(&piece_move, true).expect("expected a legal move");
+        }
+
+        (index, board)
+    }
+
+    /// Extracts the mating line from a proven tree.
+    fn principal_variation(&self) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut index = 0;
+
+        loop {
+            let node = &self.nodes[index];
+
+            // The attacker follows a proven move, and the defender resists with the longest proof.
+            let next = if node.attacker_to_move {
+                node.children.iter().copied().find(|&child| self.nodes[child].proof == 0)
+            } else {
+                node.children.iter().copied().max_by_key(|&child| self.subtree_size(child))
+            };
+
+            let Some(next) = next else { break; };
+
+            line.push(self.nodes[next].piece_move.expect("expected a move for a child node"));
+            index = next;
+        }
+
+        line
+    }
+
+    /// The number of nodes under (and including) a node.
+    fn subtree_size(&self, index: usize) -> usize {
+        1 + self.nodes[index].children.iter().map(|&child| self.subtree_size(child)).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::Board, piece_move::Move};
+
+    use super::{ProofNumberSearch, SolveResult};
+
+    #[test]
+    fn test_proof_number_search() {
+        // Back rank mate in two with doubled rooks.
+        let board = Board::new("r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1");
+
+        let mut solver = ProofNumberSearch::new(1_000_000, Some(3));
+        match solver.solve(&board) {
+            SolveResult::Proven(line) => {
+                assert_eq!(line.first().map(Move::to_uci), Some("e2e8".to_string()));
+                assert_eq!(line.len(), 3);
+            },
+            result => panic!("expected a proven mate, got {:?}", result)
+        }
+
+        // There is no mate in one.
+        let mut solver = ProofNumberSearch::new(1_000_000, Some(1));
+        assert_eq!(solver.solve(&board), SolveResult::Disproven);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::utils::{board::Board, consts::{DEEPEST_PROVEN_LOSS, DEEPEST_PROVEN_WIN, POSITION_STORE_BIAS_DIVISOR, POSITION_STORE_MAX_BIAS}};
+
+/// The magic bytes at the start of a position store file.
+const POSITION_STORE_MAGIC: &[u8; 4] = b"SDPS";
+/// The version of the position store format.
+const POSITION_STORE_VERSION: u32 = 1;
+/// The size of a single position in a position store file.
+const POSITION_ENTRY_SIZE: usize = 14;
+
+/// What a deep analysis found out about a position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StoredScore {
+    /// The score of the position in internal evaluation units, from the perspective of the side to move.
+    pub score: i32,
+    /// The depth the position was analysed to.
+    pub depth: u16
+}
+
+/// A persistent store of positions and the scores deep analyses found for them, which
+/// gives the engine a memory of its analysis across sessions.
+///
+/// Analysis writes to the store, and games probe it to bias the root moves towards
+/// positions which analysis liked. The file is a magic number and version, followed
+/// by big-endian records of zobrist key, score and depth.
+pub struct PositionStore {
+    /// The file the store is loaded from and saved to.
+    pub path: String,
+    /// The deepest score found for every position, indexed by zobrist key.
+    positions: HashMap<u64, StoredScore>
+}
+
+impl PositionStore {
+    /// Creates an empty store, which is saved to a file.
+    pub fn new(path: &str) -> Self {
+        PositionStore { path: path.to_string(), positions: HashMap::new() }
+    }
+
+    /// Loads a position store file, or starts an empty store if the file doesn't exist yet.
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(PositionStore::new(path)),
+            Err(error) => return Err(error)
+        };
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+
+        if bytes.len() < 8 || &bytes[0..4] != POSITION_STORE_MAGIC {
+            return Err(invalid("not a position store file"));
+        }
+
+        if u32::from_be_bytes(bytes[4..8].try_into().expect("expected four bytes")) != POSITION_STORE_VERSION {
+            return Err(invalid("unsupported position store version"));
+        }
+
+        if (bytes.len() - 8) % POSITION_ENTRY_SIZE != 0 {
+            return Err(invalid("truncated position store file"));
+        }
+
+        let positions = bytes[8..].chunks_exact(POSITION_ENTRY_SIZE)
+            .map(|record| (
+                u64::from_be_bytes(record[0..8].try_into().expect("expected eight bytes")),
+                StoredScore {
+                    score: i32::from_be_bytes(record[8..12].try_into().expect("expected four bytes")),
+                    depth: u16::from_be_bytes(record[12..14].try_into().expect("expected two bytes"))
+                }
+            ))
+            .collect();
+
+        Ok(PositionStore { path: path.to_string(), positions })
+    }
+
+    /// Saves the store to its file, sorted by key.
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&self.path)?);
+        writer.write_all(POSITION_STORE_MAGIC)?;
+        writer.write_all(&POSITION_STORE_VERSION.to_be_bytes())?;
+
+        let mut keys: Vec<&u64> = self.positions.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let stored = self.positions[key];
+            writer.write_all(&key.to_be_bytes())?;
+            writer.write_all(&stored.score.to_be_bytes())?;
+            writer.write_all(&stored.depth.to_be_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    /// The number of positions in the store.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// The score stored for a position, if any.
+    pub fn probe(&self, board: &Board) -> Option<StoredScore> {
+        self.positions.get(&board.zobrist_key).copied()
+    }
+
+    /// Stores the score (from the perspective of the side to move) an analysis found for a position,
+    /// unless a deeper analysis is already stored. Proven scores depend on the path to the position,
+    /// so they aren't stored. Returns whether or not the score was stored.
+    pub fn store(&mut self, board: &Board, score: i32, depth: usize) -> bool {
+        if score <= DEEPEST_PROVEN_LOSS || score >= DEEPEST_PROVEN_WIN {
+            return false;
+        }
+
+        let depth = depth.min(u16::MAX as usize) as u16;
+        if self.positions.get(&board.zobrist_key).is_some_and(|stored| stored.depth > depth) {
+            return false;
+        }
+
+        self.positions.insert(board.zobrist_key, StoredScore { score, depth });
+        true
+    }
+
+    /// The bias of a root move leading to `child_board`, if analysis went deeper than `depth` there.
+    ///
+    /// The bias is a fraction of the stored score (from the perspective of the side playing the move),
+    /// so moves analysis found good are preferred among moves of similar score, without overruling the search.
+    pub fn root_bias(&self, child_board: &Board, depth: usize) -> i32 {
+        match self.probe(child_board) {
+            Some(stored) if stored.depth as usize > depth => (-stored.score / POSITION_STORE_BIAS_DIVISOR).clamp(-POSITION_STORE_MAX_BIAS, POSITION_STORE_MAX_BIAS),
+            _ => 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::Board, consts::{POSITION_STORE_MAX_BIAS, SHALLOWEST_PROVEN_WIN}};
+
+    use super::{PositionStore, StoredScore};
+
+    #[test]
+    fn test_position_store() {
+        let path = std::env::temp_dir().join(format!("sacre_dieu_store_{}.sds", std::process::id())).to_string_lossy().to_string();
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+
+        // A missing file is an empty store.
+        let mut store = PositionStore::load(&path).expect("failed to start an empty store");
+        assert_eq!(store.len(), 0);
+
+        // Deeper analyses replace shallower ones, but not the other way around, and mates aren't stored.
+        assert!(store.store(&board, -30, 10));
+        assert!(!store.store(&board, 50, 6));
+        assert!(!store.store(&board, SHALLOWEST_PROVEN_WIN - 3, 20));
+        assert_eq!(store.probe(&board), Some(StoredScore { score: -30, depth: 10 }));
+
+        // Only analyses deeper than the search bias the move leading to the position, from the mover's perspective.
+        assert!(store.root_bias(&board, 6) > 0);
+        assert_eq!(store.root_bias(&board, 10), 0);
+
+        store.store(&board, -10_000, 12);
+        assert_eq!(store.root_bias(&board, 6), POSITION_STORE_MAX_BIAS);
+
+        store.save().expect("failed to save store");
+        let loaded = PositionStore::load(&path).expect("failed to load store");
+        std::fs::remove_file(&path).expect("failed to remove store");
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.probe(&board), Some(StoredScore { score: -10_000, depth: 12 }));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::utils::{board::Board, consts::{DEEPEST_PROVEN_LOSS, DEEPEST_PROVEN_WIN, ROOT_SPLIT_HASH_MB}, piece_move::MoveArray, transposition_table::TranspositionTable};
+
+use super::{search::{RootMove, SearchLimits, Searcher}, time_manager::TimeManager};
+
+/// Analyses a position at a fixed depth by splitting its root moves across threads, each with its own searcher.
+///
+/// Unlike a regular search, every root move gets an exact score and a full principal variation,
+/// which is what an analysis of all the moves of a position wants. Each move is searched from
+/// a clean slate, so the result is the same no matter how many threads share the work.
+pub struct RootSplit {
+    /// The depth every root move is searched to, counting the root move itself.
+    pub depth: usize,
+    /// The number of threads sharing the root moves.
+    pub threads: usize
+}
+
+impl RootSplit {
+    /// Creates an analysis of a fixed depth, split across a number of threads.
+    pub fn new(depth: usize, threads: usize) -> Self {
+        RootSplit { depth: depth.max(1), threads: threads.max(1) }
+    }
+
+    /// Searches every legal move of a position, configured like `searcher` (evaluation weights, search parameters and
+    /// game history). The moves are returned best first, with ties kept in move generation order.
+    pub fn analyse(&self, searcher: &Searcher, board: &Board) -> Vec<RootMove> {
+        let mut moves = MoveArray::new();
+        board.generate_moves(&mut moves, false);
+
+        let root_moves: Vec<RootMove> = moves
+            .into_iter()
+            .filter(|piece_move| board.make_move(piece_move, true).is_some())
+            .map(RootMove::new)
+            .collect();
+
+        // Moves are dealt out in order, so every thread gets the same moves whatever the timing.
+        let mut results: Vec<(usize, RootMove)> = std::thread::scope(|scope| {
+            let handles = (0..self.threads.min(root_moves.len()))
+                .map(|thread| {
+                    let assigned = root_moves.iter().enumerate().skip(thread).step_by(self.threads);
+                    let mut worker = self.worker(searcher);
+
+                    scope.spawn(move || {
+                        assigned.map(|(index, root_move)| (index, self.search_move(&mut worker, board, root_move.clone()))).collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles.into_iter().flat_map(|handle| handle.join().expect("root split thread panicked")).collect()
+        });
+
+        results.sort_by_key(|(index, root_move)| (std::cmp::Reverse(root_move.score), *index));
+        results.into_iter().map(|(_, root_move)| root_move).collect()
+    }
+
+    /// Builds a searcher for a single thread, with the same configuration as `searcher`.
+    fn worker(&self, searcher: &Searcher) -> Searcher {
+        let mut worker = Searcher::new(TimeManager::default(), self.depth, searcher.stop_signal.clone());
+
+        worker.transposition_table = TranspositionTable::from_mb(ROOT_SPLIT_HASH_MB);
+        worker.past_boards = searcher.past_boards.clone();
+        worker.eval_params = searcher.eval_params.clone();
+        worker.lmp_table = searcher.lmp_table;
+        worker.history_pruning_margin = searcher.history_pruning_margin;
+        worker.rfp_margins = searcher.rfp_margins;
+        worker.qsearch_check_plies = searcher.qsearch_check_plies;
+        worker.features = searcher.features;
+        worker.draw_jitter = searcher.draw_jitter;
+
+        // Every search starts from a clean slate, which makes the results independent of the order of the moves.
+        worker.deterministic = true;
+        worker
+    }
+
+    /// Searches the position after a root move to one ply less than the analysis depth.
+    fn search_move(&self, worker: &mut Searcher, board: &Board, mut root_move: RootMove) -> RootMove {
+        let child_board = board.make_move(&root_move.piece_move, false).expect("root move should be legal");
+        root_move.pv.clear();
+        root_move.pv.push(root_move.piece_move);
+
+        if worker.is_draw(&child_board, 1) {
+            root_move.score = worker.draw_score(&child_board);
+            return root_move;
+        }
+
+        // A depth of zero is searched as a single ply, which only makes the score more accurate.
+        let eval = worker.start(&child_board, &SearchLimits { depth: Some(self.depth - 1), ..Default::default() });
+
+        // Proven scores count the plies until mate, which is one more from the root.
+        root_move.score = match -eval {
+            score if score >= DEEPEST_PROVEN_WIN => score - 1,
+            score if score <= DEEPEST_PROVEN_LOSS => score + 1,
+            score => score
+        };
+        root_move.nodes = worker.nodes;
+        root_move.pv.extend(worker.principal_variation.iter().copied().take(root_move.pv.remaining_capacity()));
+
+        root_move
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use crate::{engine::{search::{RootMove, Searcher}, time_manager::TimeManager}, utils::{board::Board, piece_move::MoveArray}};
+
+    use super::RootSplit;
+
+    #[test]
+    fn test_root_split() {
+        let searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let board = Board::new("k7/8/2K5/8/8/8/8/1Q6 w - - 0 1");
+
+        // The same analysis comes out of any number of threads.
+        let single = RootSplit::new(3, 1).analyse(&searcher, &board);
+        let split = RootSplit::new(3, 4).analyse(&searcher, &board);
+
+        let summary = |root_moves: &[RootMove]| root_moves.iter().map(|root_move| (root_move.piece_move, root_move.score, root_move.pv.clone())).collect::<Vec<_>>();
+        assert_eq!(summary(&single), summary(&split));
+
+        // Every legal move is analysed, the mates first.
+        let mut moves = MoveArray::new();
+        board.generate_moves(&mut moves, false);
+        assert_eq!(split.len(), moves.iter().filter(|piece_move| board.make_move(piece_move, true).is_some()).count());
+
+        assert_eq!(Searcher::uci_score(split[0].score), "mate 1");
+        assert!(split.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    }
+}
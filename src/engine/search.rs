@@ -1,17 +1,118 @@
-use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::{Duration, Instant}};
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
 
 use arrayvec::ArrayVec;
 
-use crate::utils::{board::Board, consts::{BEST_EVAL, LMR_MOVE_THRESHOLD, LMR_REDUCTION_BASE, LMR_REDUCTION_DIVISOR, LMR_REDUCTION_TABLE, MAX_DEPTH, RFP_DEPTH, RFP_THRESHOLD, SHALLOWEST_PROVEN_LOSS, WORST_EVAL}, piece_move::{Move, MoveArray, MoveFlags, MoveSorter}, transposition_table::{EvaluationType, TTEntry, TranspositionTable}};
-use super::eval;
+use crate::utils::{board::{Bitboard, Board}, piece::{Piece, PieceColor}, consts::{ASPIRATION_DELTA, ASPIRATION_GROWTH, ASPIRATION_MAX_FAILURES, ASPIRATION_MIN_DEPTH, BEST_EVAL, DEEPEST_PROVEN_LOSS, DEEPEST_PROVEN_WIN, DEFAULT_HASH_MB, DETERMINISTIC_NODES_PER_MS, ELO_MIN, ELO_MIN_NPS, ELO_PER_NPS_DOUBLING, EVAL_CACHE_MB, EVAL_GRAIN, FIFTY_MOVE_TT_CUTOFF_PLIES, FP_BASE, FP_DEPTH, FP_MARGIN, HP_DEPTH, HP_MARGIN, LMP_BASE, LMP_DEPTH, LMP_SCALE, LMR_CHECK_ADJUSTMENT, LMR_HISTORY_DIVISOR, LMR_KILLER_ADJUSTMENT, LMR_MOVE_THRESHOLD, LMR_NOT_IMPROVING_ADJUSTMENT, LMR_PV_ADJUSTMENT, LMR_REDUCTION_BASE, LMR_REDUCTION_DIVISOR, LMR_REDUCTION_TABLE, MAX_DEPTH, MAX_EXTENSIONS, NORMALIZE_PAWN_VALUE, NPS_GOVERNOR_SLICES, QS_CHECK_PLIES, RFP_DEPTH, RFP_IMPROVING_MARGIN, RFP_MARGIN, RFP_QUADRATIC_MARGIN, SHALLOWEST_PROVEN_LOSS, SHALLOWEST_PROVEN_WIN, STOP_CHECK_INTERVAL, VARIETY_NOISE, WORST_EVAL}, eval_cache::EvalCache, piece_move::{Move, MoveArray, MoveFlags, MoveSorter}, transposition_table::{EvaluationType, TTEntry, TranspositionTable}};
+use super::{eval::{self, Evaluation}, eval_params::EvalParams, position_store::PositionStore, stats::{SearchStats, SearchSummary}, time_manager::{Clock, PositionClass, SystemClock, TimeManager}};
+
+/// A line of moves the search expects to be played.
+pub type PrincipalVariation = ArrayVec<Move, MAX_DEPTH>;
+
+/// Late move pruning thresholds, indexed by whether or not the position is improving, then by depth.
+pub type LmpTable = [[usize; LMP_DEPTH + 1]; 2];
+
+/// A legal move at the root of the search, along with what the search found out about it.
+#[derive(Debug, Clone)]
+pub struct RootMove {
+    /// The move played from the root.
+    pub piece_move: Move,
+    /// The score of the move from the last iteration, or `WORST_EVAL` if it failed low.
+    pub score: i32,
+    /// The principal variation starting with the move.
+    pub pv: PrincipalVariation,
+    /// The number of nodes spent searching the move.
+    pub nodes: usize,
+    /// The time spent searching the move, only measured when `Searcher::show_refutations` is set.
+    pub time: Duration,
+    /// The line which refuted the move the last time it failed low, starting with the move.
+    pub refutation: PrincipalVariation
+}
+
+impl RootMove {
+    /// Creates a new, unsearched root move.
+    pub fn new(piece_move: Move) -> Self {
+        RootMove { piece_move, score: WORST_EVAL, pv: ArrayVec::new(), nodes: 0, time: Duration::ZERO, refutation: ArrayVec::new() }
+    }
+}
 
 /// An entry in the search stack.
 #[derive(Debug, Default, Clone)]
 pub struct SearchEntry {
     /// The killer move at the ply.
     pub killer_move: Option<Move>,
-    /// The static evaluation at the ply.
-    pub static_eval: i32
+    /// The static evaluation at the ply, or None if the side to move was in check.
+    pub static_eval: Option<i32>,
+    /// Whether or not the side to move at the ply is in check.
+    pub in_check: bool,
+    /// The move played at the ply. The move which reached a node is the one at the ply before it.
+    pub played_move: Option<Move>,
+    /// The piece which made the move played at the ply.
+    pub moved_piece: Option<Piece>,
+    /// Whether or not the move played at the ply was a capture.
+    pub played_capture: bool,
+    /// The number of plies the line leading up to the ply was extended by.
+    pub extensions: usize,
+    /// The number of moves on the line leading up to the ply which were extended by more than one ply.
+    pub double_extensions: usize
+}
+
+/// The limits of a single search, as given by a `go` command.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    /// A fixed amount of time (in ms) to search for.
+    pub movetime: Option<u64>,
+    /// The time (in ms) left on each side's clock, indexed by color.
+    pub time_left: [Option<u64>; 2],
+    /// The increment (in ms) of each side's clock, indexed by color.
+    pub increment: [u64; 2],
+    /// The number of moves until the next time control.
+    pub moves_to_go: Option<u64>,
+    /// The maximum depth to search to.
+    pub depth: Option<usize>,
+    /// The maximum number of nodes to search.
+    pub nodes: Option<usize>,
+    /// The number of moves within which a forced mate should be found.
+    pub mate: Option<usize>,
+    /// Whether or not to search until `stop` is sent, regardless of the other limits.
+    pub infinite: bool,
+    /// Whether or not the search is pondering on the opponent's time. A pondering search still stops within
+    /// the clock limits, as if the expected move was played, but its `bestmove` waits for `ponderhit` or `stop`.
+    pub ponder: bool
+}
+
+/// Switches for the selective search techniques, so one suspected of causing a blunder can be ruled out without recompiling.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchFeatures {
+    pub null_move: bool,
+    pub lmr: bool,
+    pub rfp: bool,
+    pub lmp: bool,
+    pub aspiration: bool,
+    pub lazy_eval: bool
+}
+
+impl Default for SearchFeatures {
+    fn default() -> Self {
+        SearchFeatures { null_move: true, lmr: true, rfp: true, lmp: true, aspiration: true, lazy_eval: true }
+    }
+}
+
+impl SearchFeatures {
+    /// Switches a technique by the name of its UCI option, returning whether or not the name is known.
+    pub fn set(&mut self, option: &str, enabled: bool) -> bool {
+        let feature = match option.to_lowercase().as_str() {
+            "usenullmove" => &mut self.null_move,
+            "uselmr" => &mut self.lmr,
+            "userfp" => &mut self.rfp,
+            "uselmp" => &mut self.lmp,
+            "useaspiration" => &mut self.aspiration,
+            "uselazyeval" => &mut self.lazy_eval,
+            _ => return false
+        };
+
+        *feature = enabled;
+        true
+    }
 }
 
 pub struct Searcher {
@@ -19,59 +120,256 @@ pub struct Searcher {
     pub past_boards: Vec<u64>,
     /// A table of previous searches and their evaluations.
     pub transposition_table: TranspositionTable,
+    /// The cache of static evaluations.
+    pub eval_cache: EvalCache,
+    /// The weights of the evaluation.
+    pub eval_params: EvalParams,
     /// A table of search information, indexed by ply.
     pub search_stack: [SearchEntry; MAX_DEPTH + 4],
+    /// A triangular table of principal variations, indexed by ply.
+    pub pv_table: Vec<PrincipalVariation>,
+    /// The legal moves at the root, ordered best first after each iteration.
+    pub root_moves: Vec<RootMove>,
     /// A struct which sorts moves.
     pub move_sorter: MoveSorter,
+    /// A boolean signalling when to stop a search.
+    pub stop_signal: Arc<AtomicBool>,
+    /// Whether or not the clock is ignored, so searches only stop on depth or node limits.
+    pub deterministic: bool,
+    /// The number of quiet moves searched before the rest are pruned, see `Searcher::lmp_table`.
+    pub lmp_table: LmpTable,
+    /// The history (per ply of depth) below which quiet moves are pruned.
+    pub history_pruning_margin: i32,
+    /// The linear, quadratic and improving terms of the reverse futility margin, see `Searcher::rfp_margin`.
+    pub rfp_margins: (i32, i32, i32),
+    /// The number of plies into quiescence search in which quiet checks are searched.
+    pub qsearch_check_plies: usize,
+    /// Which selective search techniques are enabled.
+    pub features: SearchFeatures,
+    /// The clock the search is timed with.
+    pub clock: Box<dyn Clock>,
+    /// Whether or not draws inside the search are nudged away from zero, see `Searcher::draw_score`.
+    pub draw_jitter: bool,
+    /// The seed of the noise added to root move scores for opening variety, if any, see `Searcher::variety_noise`.
+    pub variety_seed: Option<u64>,
+    /// The scores of earlier analyses, which bias the root moves, see `PositionStore::root_bias`.
+    pub position_store: Option<PositionStore>,
+    /// The largest number of nodes searched per second, which limits the strength of the engine, see `Searcher::throttle`.
+    pub nps_limit: Option<usize>,
+    /// Whether or not the time spent on each root move and the lines refuting them are tracked, see `RootMove::refutation`.
+    pub show_refutations: bool,
     
-    /// The soft time constraint of the search.
-    pub soft_tm: Duration,
-    /// The hard time constraint of the search.
-    pub hard_tm: Duration,
-    /// The timer associated with the search.
-    pub timer: Instant,
+    // Everything below describes a single search, and is reset by `start`.
+
+    /// The time constraints of the search.
+    pub time_manager: TimeManager,
+    /// The reading of the clock when the search started.
+    pub start_time: Duration,
     /// The current depth of the search.
     pub depth: usize,
     /// The maximum depth of the search.
     pub max_depth: usize,
-    /// The maximum number of nodes to search (-1 represents infinity).
-    pub max_nodes: isize,
-    /// A boolean signalling when to stop a search.
-    pub stop_signal: Arc<AtomicBool>,
-    
+    /// The maximum number of nodes to search, if any.
+    pub max_nodes: Option<usize>,
+    /// The number of moves within which a forced mate is being searched for, if any.
+    pub mate_limit: Option<usize>,
     /// The number of nodes searched.
     pub nodes: usize,
+    /// Whether or not the stop signal or the hard time limit was noticed.
+    pub stopped: bool,
+    /// Counters describing the last search, collected with the `stats` feature.
+    pub stats: SearchStats,
+    /// How the last search spent its nodes and time, see `SearchSummary`.
+    pub summary: SearchSummary,
     /// The best move searched.
-    pub best_move: Option<Move>
+    pub best_move: Option<Move>,
+    /// The principal variation of the last completed iteration.
+    pub principal_variation: PrincipalVariation,
+    /// The zobrist keys of the positions along the principal variation, paired
+    /// with the move expected to be played in each of them.
+    pub predicted_line: Vec<(u64, Move)>
 }
 
 impl Searcher {
     /// Initializes a new searcher.
-    pub fn new(soft_tm: Duration, hard_tm: Duration, max_depth: usize, stop_signal: Arc<AtomicBool>) -> Self {
+    pub fn new(time_manager: TimeManager, max_depth: usize, stop_signal: Arc<AtomicBool>) -> Self {
         Searcher {
             past_boards: Vec::new(),
-            transposition_table: TranspositionTable::from_mb(16),
+            transposition_table: TranspositionTable::from_mb(DEFAULT_HASH_MB),
+            eval_cache: EvalCache::from_mb(EVAL_CACHE_MB),
+            eval_params: EvalParams::default(),
             search_stack: std::array::from_fn(|_| SearchEntry::default()),
+            pv_table: vec![ArrayVec::new(); MAX_DEPTH + 4],
+            root_moves: Vec::new(),
             move_sorter: MoveSorter::new(),
-
-            soft_tm,
-            hard_tm,
-            timer: Instant::now(),
+            stop_signal,
+            deterministic: false,
+            lmp_table: Self::lmp_table(LMP_BASE, LMP_SCALE),
+            history_pruning_margin: HP_MARGIN,
+            rfp_margins: (RFP_MARGIN, RFP_QUADRATIC_MARGIN, RFP_IMPROVING_MARGIN),
+            qsearch_check_plies: QS_CHECK_PLIES,
+            features: SearchFeatures::default(),
+            clock: Box::new(SystemClock::default()),
+            draw_jitter: true,
+            variety_seed: None,
+            position_store: None,
+            nps_limit: None,
+            show_refutations: false,
+
+            time_manager,
+            start_time: Duration::ZERO,
             depth: 0,
             max_depth,
-            max_nodes: -1,
-            stop_signal,
-
+            max_nodes: None,
+            mate_limit: None,
             nodes: 0,
-            best_move: None
+            stopped: false,
+            stats: SearchStats::default(),
+            summary: SearchSummary::default(),
+            best_move: None,
+            principal_variation: ArrayVec::new(),
+            predicted_line: Vec::new()
         }
     }
 
+    /// Builds the late move pruning thresholds: quiet moves are pruned once `base + scale * depth`
+    /// moves have been searched, or half as many when the position isn't improving.
+    pub fn lmp_table(base: usize, scale: usize) -> LmpTable {
+        std::array::from_fn(|improving| std::array::from_fn(|depth| (base + scale * depth) * (1 + improving) / 2))
+    }
+
     /// Whether or not the search has been cancelled.
     pub fn search_cancelled(&self) -> bool {
-        (self.max_nodes > 0 && self.nodes >= self.max_nodes as usize)
-        || self.stop_signal.load(Ordering::Relaxed) 
-        || self.timer.elapsed() > self.hard_tm
+        self.stopped || self.max_nodes.is_some_and(|max_nodes| self.nodes >= max_nodes)
+    }
+
+    /// Counts a searched node. The stop signal and the clock are only polled every
+    /// `STOP_CHECK_INTERVAL` nodes, which bounds how far past the hard limit a search can run.
+    fn count_node(&mut self) {
+        self.nodes += 1;
+
+        // A throttled search polls whenever it may have slept, so sleeping can't run past the hard limit.
+        let throttled = self.nps_limit.is_some_and(|nps_limit| self.nodes.is_multiple_of((nps_limit / NPS_GOVERNOR_SLICES).clamp(1, STOP_CHECK_INTERVAL)));
+        if throttled {
+            self.throttle();
+        }
+
+        if throttled || self.nodes.is_multiple_of(STOP_CHECK_INTERVAL) {
+            self.stopped |= self.stop_signal.load(Ordering::Relaxed)
+                || (!self.deterministic && self.elapsed() > self.time_manager.hard_limit);
+        }
+    }
+
+    /// Sleeps for as long as the search is ahead of its node rate, but never more than one slice of a second,
+    /// so a stop is noticed in time. Unlike noise in the evaluation, a slower search plays weaker moves in the same style.
+    fn throttle(&mut self) {
+        let Some(nps_limit) = self.nps_limit else {
+            return;
+        };
+
+        let due = Duration::from_secs_f64(self.nodes as f64 / nps_limit as f64);
+        let ahead = due.saturating_sub(self.elapsed());
+        std::thread::sleep(ahead.min(Duration::from_secs(1) / NPS_GOVERNOR_SLICES as u32));
+    }
+
+    /// The node rate which plays at roughly a UCI_Elo rating, doubling every `ELO_PER_NPS_DOUBLING` Elo.
+    pub fn elo_nps(elo: usize) -> usize {
+        let doublings = elo.saturating_sub(ELO_MIN) as f64 / ELO_PER_NPS_DOUBLING;
+        (ELO_MIN_NPS as f64 * doublings.exp2()) as usize
+    }
+
+    /// The time passed since the search started.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.elapsed(self.start_time)
+    }
+
+    /// Starts a new search from a position within the given limits, returning its evaluation.
+    /// 
+    /// Long-lived state (the transposition table and move ordering heuristics) carries
+    /// over from earlier searches, while everything describing the last search is reset.
+    pub fn start(&mut self, board: &Board, limits: &SearchLimits) -> i32 {
+        self.reset_search_state();
+
+        // Every search starts from a clean slate, so it can be reproduced from the position alone.
+        if self.deterministic {
+            self.reset_heuristics();
+        }
+
+        self.follow_pv(board);
+
+        if let Some(mate) = limits.mate {
+            // A mate in N moves is found within 2N - 1 plies.
+            self.mate_limit = Some(mate);
+            self.max_depth = (2 * mate - 1).min(MAX_DEPTH);
+        }
+
+        // Exactly one limit applies, in order of priority: movetime, clock, depth, nodes, infinite.
+        // Nonsensical values (such as an empty clock or a depth of zero) are clamped to
+        // the smallest meaningful search, which is still guaranteed to return a move.
+        let color = board.side_to_move.to_index();
+        if let Some(movetime) = limits.movetime {
+            // Iterative deepening until time limit reached.
+            self.time_manager.set_movetime(movetime);
+            self.limit_nodes_by_time();
+        } else if let Some(time_left) = limits.time_left[color] {
+            // Iterative deepening using soft and hard time limits.
+            self.time_manager.set_clock(time_left, limits.increment[color], limits.moves_to_go);
+            self.limit_nodes_by_time();
+        } else if let Some(depth) = limits.depth {
+            // Search up to a specified depth.
+            self.max_depth = depth.clamp(1, MAX_DEPTH);
+        } else if let Some(nodes) = limits.nodes {
+            // Search up to a specified node count.
+            self.max_nodes = Some(nodes.max(1));
+        } else if limits.infinite {
+            // Search until `stop` is sent (or the maximum depth is reached).
+            self.time_manager.set_infinite();
+        }
+
+        // Without any limit at all, the search is just as infinite.
+        self.search_timed(board)
+    }
+
+    /// Resets everything describing the last search.
+    fn reset_search_state(&mut self) {
+        self.time_manager.set_infinite();
+        self.start_time = self.clock.now();
+        self.depth = 0;
+        self.max_depth = MAX_DEPTH;
+        self.max_nodes = None;
+        self.mate_limit = None;
+        self.nodes = 0;
+        self.stopped = false;
+        self.stats.clear();
+        self.summary.clear();
+        self.best_move = None;
+        self.principal_variation.clear();
+    }
+
+    /// Replaces the allocated time with a proportional node limit when searching
+    /// deterministically, so the same search always visits the same nodes.
+    pub fn limit_nodes_by_time(&mut self) {
+        if self.deterministic {
+            let time = self.time_manager.soft_limit.as_millis() as u64;
+            self.max_nodes = Some(time.saturating_mul(DETERMINISTIC_NODES_PER_MS) as usize);
+        }
+    }
+
+    /// Forgets everything learned from previous searches: the transposition
+    /// table, the move ordering heuristics and the predicted line.
+    pub fn reset_heuristics(&mut self) {
+        self.transposition_table.clear();
+        self.search_stack = std::array::from_fn(|_| SearchEntry::default());
+        self.principal_variation.clear();
+        self.predicted_line.clear();
+        self.move_sorter = MoveSorter::new();
+    }
+
+    /// Replaces the weights of the evaluation, forgetting every score found with the old ones.
+    pub fn set_eval_params(&mut self, eval_params: EvalParams) {
+        self.eval_params = eval_params;
+        self.eval_cache = EvalCache::from_mb(EVAL_CACHE_MB);
+        self.reset_heuristics();
     }
 
     /// Gets an entry at a ply in the search stack.
@@ -79,141 +377,663 @@ impl Searcher {
         self.search_stack.get(ply).cloned()
     }
 
+    /// Gets the entry `offset` plies away from a ply in the search stack, if it exists.
+    pub fn relative_search_entry(&self, ply: usize, offset: isize) -> Option<&SearchEntry> {
+        ply.checked_add_signed(offset).and_then(|ply| self.search_stack.get(ply))
+    }
+
+    /// Whether or not the static evaluation at a ply is better than the most recent
+    /// evaluation of the same side, two or four plies earlier.
+    /// 
+    /// Positions without an earlier evaluation to compare against count as improving.
+    pub fn is_improving(&self, ply: usize) -> bool {
+        let Some(static_eval) = self.search_stack[ply].static_eval else {
+            return false;
+        };
+
+        [-2, -4].iter()
+            .filter_map(|&offset| self.relative_search_entry(ply, offset))
+            .find_map(|entry| entry.static_eval)
+            .is_none_or(|previous_eval| static_eval > previous_eval)
+    }
+
     /// Updates a killer move at a ply in the search stack.
     pub fn update_killer(&mut self, killer_move: Option<Move>, ply: usize) {
         self.search_stack[ply].killer_move = killer_move;
     }
 
     /// Updates a static eval at a ply in the search stack.
-    pub fn update_static_eval(&mut self, eval: i32, ply: usize) {
+    pub fn update_static_eval(&mut self, eval: Option<i32>, ply: usize) {
         self.search_stack[ply].static_eval = eval;
     }
 
+    /// Updates the principal variation at a ply with a new best move, followed
+    /// by the principal variation of the child node.
+    pub fn update_pv(&mut self, piece_move: Move, ply: usize) {
+        let (parent, child) = self.pv_table.split_at_mut(ply + 1);
+        let pv = &mut parent[ply];
+
+        pv.clear();
+        pv.push(piece_move);
+
+        if let Some(child_pv) = child.first() {
+            for child_move in child_pv.iter() {
+                if pv.try_push(*child_move).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The move the opponent is expected to reply with, if any.
+    pub fn predicted_reply(&self) -> Option<Move> {
+        self.principal_variation.get(1).copied()
+    }
+
+    /// Prepares the searcher for a new root position.
+    /// 
+    /// If the game followed the principal variation of the previous search, the moves of the
+    /// remaining line are written back into whichever of its entries the transposition table still holds.
+    /// Either way, the search stack is shifted by the plies played since the previous
+    /// search, so killers line up with their new plies.
+    pub fn follow_pv(&mut self, board: &Board) {
+        let predicted_line = std::mem::take(&mut self.predicted_line);
+        let Some(plies) = predicted_line.iter().skip(1).position(|(key, _)| *key == board.zobrist_key).map(|index| index + 1) else {
+            // Off the predicted line, the game history tells how far the game moved on. Once an irreversible move has
+            // cleared the previous root from it, the game has usually moved on by the engine's move and the opponent's reply.
+            if let Some((root_key, _)) = predicted_line.first() {
+                let plies = self.past_boards.iter().rev().position(|key| key == root_key).unwrap_or(2);
+                self.shift_search_stack(plies);
+            }
+
+            return;
+        };
+
+        for (key, piece_move) in predicted_line[plies..].iter().copied() {
+            self.transposition_table.store_move(key, piece_move.pack());
+        }
+
+        self.shift_search_stack(plies);
+    }
+
+    /// Moves every entry of the search stack a number of plies closer to the root, clearing the entries at the end.
+    fn shift_search_stack(&mut self, plies: usize) {
+        let plies = plies.min(self.search_stack.len());
+        self.search_stack.rotate_left(plies);
+
+        let length = self.search_stack.len();
+        self.search_stack[length - plies..].iter_mut().for_each(|entry| *entry = SearchEntry::default());
+    }
+
+    /// Follows the best moves stored in the transposition table from a position,
+    /// returning the key and entry (if any) of every position along the line.
+    pub fn tt_line(&self, board: &Board) -> Vec<(u64, Option<TTEntry>)> {
+        let mut line = Vec::new();
+        let mut board = board.clone();
+
+        while line.len() < MAX_DEPTH && !line.iter().any(|(key, _)| *key == board.zobrist_key) {
+            let entry = self.transposition_table.get(board.zobrist_key).filter(|entry| entry.zobrist_key == board.zobrist_key).cloned();
+            let best_move = entry.as_ref().and_then(|entry| entry.best_move.unpack());
+            line.push((board.zobrist_key, entry));
+
+            match best_move.filter(|&piece_move| board.is_legal(piece_move)) {
+                Some(piece_move) => board = board.make_move(&piece_move, false).expect("expected a legal move"),
+                None => break
+            }
+        }
+
+        line
+    }
+
+    /// Records the move played at a ply from a board, and how far the line is extended after it.
+    pub fn update_played_move(&mut self, board: &Board, piece_move: Option<Move>, is_capture: bool, extension: usize, ply: usize) {
+        let (parent, child) = self.search_stack.split_at_mut(ply + 1);
+        let (entry, child) = (&mut parent[ply], &mut child[0]);
+
+        entry.played_move = piece_move;
+        entry.moved_piece = piece_move.and_then(|piece_move| board.piece_at(piece_move.initial));
+        entry.played_capture = is_capture;
+
+        child.extensions = entry.extensions + extension;
+        child.double_extensions = entry.double_extensions + (extension > 1) as usize;
+    }
+
+    /// The search stack from the root up to a ply, ending with the entry of the ply itself.
+    ///
+    /// Children are searched through `&mut self`, so rather than handing them a window of
+    /// the stack, each node reads the entries before it through this.
+    pub fn stack_window(&self, ply: usize) -> &[SearchEntry] {
+        &self.search_stack[..=ply]
+    }
+
+    /// The margin by which the static evaluation has to beat beta for reverse futility pruning, which
+    /// grows with the depth and shrinks when the position is improving.
+    pub fn rfp_margin(&self, depth: usize, improving: bool) -> i32 {
+        let (linear, quadratic, improving_margin) = self.rfp_margins;
+        let depth = depth as i32;
+
+        linear * depth + quadratic * depth * depth - improving_margin * improving as i32
+    }
+
+    /// Calculates how many plies a move should be extended by, as long
+    /// as the line has not used up its extension budget.
+    pub fn extension(&self, board: &Board, piece_move: Move, is_capture: bool, single_reply: bool, depth: usize, ply: usize) -> usize {
+        if self.search_stack[ply].extensions >= MAX_EXTENSIONS || ply + depth >= MAX_DEPTH {
+            return 0;
+        }
+
+        // Check Extension
+        if board.in_check(board.side_to_move) {
+            return 1;
+        }
+
+        // Single Reply Extension
+        if single_reply {
+            return 1;
+        }
+
+        // Recapture Extension
+        if is_capture && let [.., previous, _] = self.stack_window(ply) && previous.played_capture && previous.played_move.is_some_and(|previous_move| previous_move.end == piece_move.end) {
+            return 1;
+        }
+
+        0
+    }
+
     /// Searches for a move with a time constraint.
     pub fn search_timed(&mut self, board: &Board) -> i32 {
-        self.timer = std::time::Instant::now();
+        self.start_time = self.clock.now();
+        self.stopped = false;
         let (mut eval, mut best_move) = (0, None);
 
+        self.move_sorter.age_history();
+        self.transposition_table.new_search();
+        self.stats.clear();
+        self.summary.clear();
+
+        let (checkers, pinned) = board.checkers_and_pinned();
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves_with(&mut moves, false, checkers, pinned);
+        self.move_sorter.order_moves(board, self, &mut moves, 0, false);
+
+        let root_eval = (checkers == Bitboard::ZERO).then(|| self.evaluate(board));
+        self.update_static_eval(root_eval, 0);
+        self.search_stack[0].in_check = checkers != Bitboard::ZERO;
+
+        self.root_moves = moves
+            .iter()
+            .filter(|piece_move| board.make_move(piece_move, true).is_some())
+            .map(|piece_move| RootMove::new(*piece_move))
+            .collect();
+
+        let legal_moves = self.root_moves.len();
+        if legal_moves == 0 {
+            self.best_move = None;
+            self.principal_variation.clear();
+            self.predicted_line.clear();
+
+            return match Self::variant_score(board, 0) {
+                Some(score) => score,
+                None if checkers != Bitboard::ZERO => SHALLOWEST_PROVEN_LOSS,
+                None => 0
+            };
+        }
+
+        let candidates: Vec<Move> = self.root_moves.iter().map(|root_move| root_move.piece_move).collect();
+        self.time_manager.set_position_class(PositionClass::classify(board, &candidates));
+
         self.depth = 0;
         for _ in 0..self.max_depth {
             // Soft Time Control
-            if self.timer.elapsed() >= self.soft_tm {
+            if !self.deterministic && self.elapsed() >= self.time_manager.soft_limit {
                 break;
             }
 
             self.depth += 1;
-            let score = self.aspiration_windows(board, self.depth, eval);
+            let score = match self.mate_limit {
+                // Only a mate within the limit can beat the window, so everything else fails low quickly.
+                Some(mate_limit) => self.root_search(board, self.depth, Self::mate_score(mate_limit) - 1, BEST_EVAL),
+                None => self.aspiration_windows(board, self.depth, eval)
+            };
             // let score = self.search::<true>(board, self.depth, 0, WORST_EVAL, BEST_EVAL);
 
             if self.search_cancelled() {
                 break;
             } else {
+                if self.depth > 1 {
+                    self.time_manager.adjust_soft_limit(eval, score, legal_moves);
+                }
+
                 eval = score;
-                best_move = self.best_move;
-            } 
+                best_move = Some(self.root_moves[0].piece_move);
+                self.summary.end_iteration(self.depth, self.nodes);
+                self.principal_variation = self.root_moves[0].pv.clone();
+            }
+
+            if let Some(mate_limit) = self.mate_limit && eval >= Self::mate_score(mate_limit) {
+                break;
+            }
+
+            // A single legal move is played right away, the first iteration only provides a score and a ponder move.
+            if self.time_manager.instant_move() {
+                break;
+            }
         }
 
-        self.best_move = best_move;
+        // A search which was cut off before finishing a single iteration still has to play something. Whatever
+        // it ends up being, it has to come from the root moves, which were generated and checked for legality.
+        let is_root_move = |piece_move: &Move| self.root_moves.iter().any(|root_move| root_move.piece_move == *piece_move);
+        self.best_move = best_move.filter(is_root_move).or(Some(self.root_moves[0].piece_move));
+
+        let mut line_board = board.clone();
+        self.predicted_line.clear();
+
+        for piece_move in self.principal_variation.iter() {
+            self.predicted_line.push((line_board.zobrist_key, *piece_move));
+
+            match line_board.make_move(piece_move, false) {
+                Some(new_board) => line_board = new_board,
+                None => break
+            }
+        }
 
         eval
     }
 
+    /// Statically evaluates a position, going through the evaluation cache.
+    pub fn evaluate(&mut self, board: &Board) -> i32 {
+        self.evaluate_within(board, None)
+    }
+
+    /// Statically evaluates a position, which only has to be exact inside of the window, if any.
+    /// Lazy evaluations are too rough to be cached, so only full ones are stored.
+    pub fn evaluate_within(&mut self, board: &Board, window: Option<(i32, i32)>) -> i32 {
+        if let Some(evaluation) = self.eval_cache.get(board) {
+            return evaluation;
+        }
+
+        match eval::evaluate_board(board, &self.eval_params, window.filter(|_| self.features.lazy_eval)) {
+            Evaluation::Full(evaluation) => {
+                self.eval_cache.store(board, evaluation);
+                evaluation
+            },
+            Evaluation::Lazy(evaluation) => evaluation
+        }
+    }
+
+    /// The score of delivering mate in a number of moves.
+    pub fn mate_score(moves: usize) -> i32 {
+        SHALLOWEST_PROVEN_WIN - (2 * moves as i32 - 1)
+    }
+
+    /// Whether or not a position inside the search (past the root) is drawn by threefold repetition or the fifty move rule.
+    /// 
+    /// A move which delivers mate as the fifty move rule runs out still wins, so the rule only
+    /// draws positions with a legal move or without check.
+    pub fn is_draw(&self, board: &Board, ply: usize) -> bool {
+        if ply == 0 {
+            return false;
+        }
+
+        // Positions after a null move cannot repeat the game history.
+        let repetition = !board.null_move_within(ply) && self.past_boards.iter().filter(|key| **key == board.zobrist_key).count() == 2;
+        if repetition {
+            return true;
+        }
+
+        if board.half_move_counter < 100 {
+            return false;
+        }
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+        !board.in_check(board.side_to_move) || moves.iter().any(|piece_move| board.make_move(piece_move, true).is_some())
+    }
+
+    /// The score of a drawn position found inside the search.
+    /// 
+    /// A flat zero makes every drawing line look the same, so the search happily shuffles between them.
+    /// Moving it by a centipawn in either direction, depending on the position, breaks those ties.
+    pub fn draw_score(&self, board: &Board) -> i32 {
+        if self.draw_jitter {
+            (board.zobrist_key & 2) as i32 - 1
+        } else {
+            0
+        }
+    }
+
+    /// The score of a position whose game was won by the rules of its variant, scored like a mate `ply` plies from the root.
+    pub fn variant_score(board: &Board, ply: usize) -> Option<i32> {
+        board.variant_winner().map(|winner| if winner == board.side_to_move {
+            SHALLOWEST_PROVEN_WIN - ply as i32
+        } else {
+            SHALLOWEST_PROVEN_LOSS + ply as i32
+        })
+    }
+
+    /// The noise added to the score of a root move leading to `child_board`, within `VARIETY_NOISE`.
+    /// 
+    /// The noise only depends on the seed and the position, so a seed always plays the same game,
+    /// while a new seed picks between moves of (nearly) equal score differently.
+    pub fn variety_noise(&self, child_board: &Board) -> i32 {
+        let Some(seed) = self.variety_seed else {
+            return 0;
+        };
+
+        // The SplitMix64 finalizer.
+        let mut hash = seed ^ child_board.zobrist_key;
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D049BB133111EB);
+        hash ^= hash >> 31;
+
+        (hash % (2 * VARIETY_NOISE as u64 + 1)) as i32 - VARIETY_NOISE
+    }
+
+    /// The number of moves until mate for a proven score, negative when getting mated.
+    /// 
+    /// Scores beyond the shallowest mate bound, such as a bound truncated by the transposition table,
+    /// are clamped to it so neither a winning nor a losing score ever reports a mate in zero.
+    pub fn mate_in(eval: i32) -> Option<i32> {
+        if eval >= DEEPEST_PROVEN_WIN {
+            let plies = (SHALLOWEST_PROVEN_WIN - eval).max(1);
+            Some((plies + 1) / 2)
+        } else if eval <= DEEPEST_PROVEN_LOSS {
+            let plies = (eval - SHALLOWEST_PROVEN_LOSS).max(1);
+            Some(-(plies + 1) / 2)
+        } else {
+            None
+        }
+    }
+
+    /// Describes the last search of a position as a PGN comment on the move it played: the score (from white's
+    /// perspective) and depth as an `[%eval]` command, followed by the principal variation in SAN.
+    pub fn pgn_comment(&self, board: &Board, eval: i32) -> String {
+        let eval = if board.side_to_move == PieceColor::White { eval } else { -eval };
+        let score = match Self::mate_in(eval) {
+            Some(moves) => format!("#{}", moves),
+            None => format!("{:.2}", Self::normalized_cp(eval) as f64 / 100.0)
+        };
+
+        let mut comment = format!("[%eval {},{}]", score, self.depth);
+        let mut line_board = board.clone();
+
+        for piece_move in self.principal_variation.iter() {
+            comment += &format!(" {}", piece_move.to_san(&line_board));
+
+            match line_board.make_move(piece_move, false) {
+                Some(new_board) => line_board = new_board,
+                None => break
+            }
+        }
+
+        comment
+    }
+
+    /// Converts an internal score to normalized centipawns, see `NORMALIZE_PAWN_VALUE`.
+    pub fn normalized_cp(eval: i32) -> i32 {
+        let cp = eval as f64 * 100.0 / NORMALIZE_PAWN_VALUE as f64;
+        (cp / EVAL_GRAIN as f64).round() as i32 * EVAL_GRAIN
+    }
+
+    /// Formats a score the way it appears after `score` in a UCI info line.
+    pub fn uci_score(eval: i32) -> String {
+        match Self::mate_in(eval) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", Self::normalized_cp(eval))
+        }
+    }
+
+    /// Formats a score the way it appears in JSON output, as `{ "mate": N }` or `{ "cp": N }`.
+    pub fn json_score(eval: i32) -> serde_json::Value {
+        match Self::mate_in(eval) {
+            Some(moves) => serde_json::json!({ "mate": moves }),
+            None => serde_json::json!({ "cp": Self::normalized_cp(eval) })
+        }
+    }
+
     /// Iteratively reduces the window for the search to yield more cutoffs.
+    /// 
+    /// The window starts around the previous score, and whichever bound fails is widened
+    /// geometrically until the score fits, falling back to a full window if it keeps failing.
     pub fn aspiration_windows(&mut self, board: &Board, depth: usize, prev_score: i32) -> i32 {
-        let mut delta = 25;
+        let mut delta = ASPIRATION_DELTA;
         let (mut alpha, mut beta) = (WORST_EVAL, BEST_EVAL);
 
-        if depth >= 4 {
-            alpha = prev_score - delta;
-            beta = prev_score + delta;
+        if self.features.aspiration && depth >= ASPIRATION_MIN_DEPTH {
+            alpha = prev_score.saturating_sub(delta).max(WORST_EVAL);
+            beta = prev_score.saturating_add(delta);
         }
 
+        let mut failures = 0;
+
         loop {
-            let search_score = self.search::<true>(board, depth, 0, alpha, beta);
+            let search_score = self.root_search(board, depth, alpha, beta);
             if self.search_cancelled() {
                 return search_score;
             }
 
+            if search_score > alpha && search_score < beta {
+                return search_score;
+            }
+
+            failures += 1;
+            delta = (delta as f32 * ASPIRATION_GROWTH) as i32;
+
             if search_score <= alpha {
-                alpha -= delta;
-            } else if search_score >= beta {
-                beta += delta;
+                self.time_manager.report_fail_low();
+            }
+
+            (alpha, beta) = Self::widen_window(alpha, beta, search_score, delta);
+
+            // Unstable scores are resolved with a full window instead of more re-searches.
+            if failures >= ASPIRATION_MAX_FAILURES {
+                (alpha, beta) = (WORST_EVAL, BEST_EVAL);
+            }
+        }
+    }
+
+    /// Widens an aspiration window which a search failed with a score outside of. Only the bound
+    /// which failed is moved, to `delta` past the fail-soft score.
+    fn widen_window(alpha: i32, beta: i32, score: i32, delta: i32) -> (i32, i32) {
+        if score <= alpha {
+            (score.saturating_sub(delta).max(WORST_EVAL), beta)
+        } else {
+            (alpha, score.saturating_add(delta))
+        }
+    }
+
+    /// Searches every root move, using a full window for the first move and
+    /// null window scouts for the rest. Root moves are sorted by their scores
+    /// afterwards, so the best move is searched first in the next iteration.
+    pub fn root_search(&mut self, board: &Board, depth: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.update_killer(None, 2);
+        self.pv_table[0].clear();
+
+        let mut best_score = WORST_EVAL;
+        let mut evaluation_type = EvaluationType::UpperBound;
+
+        // Like the rest of the tree, only a lone evasion from check counts as a single reply.
+        let single_reply = board.in_check(board.side_to_move) && self.root_moves.len() == 1;
+
+        for index in 0..self.root_moves.len() {
+            let piece_move = self.root_moves[index].piece_move;
+            let child_board = board.make_move(&piece_move, false).expect("root move should be legal");
+
+            let nodes_before = self.nodes;
+            let time_before = if self.show_refutations { self.elapsed() } else { Duration::ZERO };
+            self.count_node();
+            self.summary.node(1, false);
+
+            let is_capture = piece_move.flags == MoveFlags::EnPassant || !board.is_empty(piece_move.end);
+            let extension = self.extension(&child_board, piece_move, is_capture, single_reply, depth, 0);
+            self.update_played_move(board, Some(piece_move), is_capture, extension, 0);
+
+            // Adding a bias to the score of a move is the same as searching it with a window shifted the other way.
+            let bias = self.variety_noise(&child_board) + self.position_store.as_ref().map_or(0, |store| store.root_bias(&child_board, depth));
+            let (biased_alpha, biased_beta) = (alpha.saturating_sub(bias).clamp(WORST_EVAL, BEST_EVAL), beta.saturating_sub(bias).clamp(WORST_EVAL, BEST_EVAL));
+
+            let mut score = 0;
+            if index == 0 {
+                // Full Window Search
+                score = -self.search::<true>(&child_board, depth - 1 + extension, 1, -biased_beta, -biased_alpha);
             } else {
-                return search_score;
+                // Null Window Search
+                score = -self.search::<false>(&child_board, depth - 1 + extension, 1, -biased_alpha - 1, -biased_alpha);
+
+                if score > biased_alpha && score < biased_beta {
+                    // Null Window Search failed, resort to Full Window Search
+                    score = -self.search::<true>(&child_board, depth - 1 + extension, 1, -biased_beta, -biased_alpha);
+                }
+            }
+
+            // Proven scores are left alone, so mates are still reported exactly.
+            if score > DEEPEST_PROVEN_LOSS && score < DEEPEST_PROVEN_WIN {
+                score += bias;
+            }
+
+            if self.search_cancelled() {
+                return best_score;
+            }
+
+            let root_move = &mut self.root_moves[index];
+            root_move.nodes += self.nodes - nodes_before;
+            root_move.score = WORST_EVAL;
+
+            if self.show_refutations {
+                let time = self.elapsed().saturating_sub(time_before);
+                self.root_moves[index].time += time;
+
+                // The child's principal variation starts with the reply which refuted the move, unless the
+                // child was cut off by the transposition table, which still remembers the reply.
+                if score <= alpha {
+                    let line: PrincipalVariation = if self.pv_table[1].is_empty() {
+                        self.tt_line(&child_board).iter().map_while(|(_, entry)| entry.as_ref().and_then(|entry| entry.best_move.unpack())).take(MAX_DEPTH - 1).collect()
+                    } else {
+                        self.pv_table[1].iter().copied().take(MAX_DEPTH - 1).collect()
+                    };
+
+                    if !line.is_empty() {
+                        let root_move = &mut self.root_moves[index];
+                        root_move.refutation.clear();
+                        root_move.refutation.push(piece_move);
+                        root_move.refutation.extend(line);
+                    }
+                }
             }
 
-            delta *= 2;
+            if score > best_score {
+                best_score = score;
+            }
+
+            if score > alpha {
+                evaluation_type = EvaluationType::Exact;
+                alpha = score;
+
+                self.update_pv(piece_move, 0);
+                self.root_moves[index].score = score;
+                self.root_moves[index].pv = self.pv_table[0].clone();
+            }
+
+            if score >= beta {
+                evaluation_type = EvaluationType::LowerBound;
+                break;
+            }
         }
+
+        // Fail-low moves keep their relative order, since they all score `WORST_EVAL`.
+        self.root_moves.sort_by_key(|root_move| std::cmp::Reverse(root_move.score));
+
+        let best_move = (evaluation_type != EvaluationType::UpperBound).then_some(self.root_moves[0].piece_move);
+        self.transposition_table.store(board.zobrist_key, TTEntry { zobrist_key: board.zobrist_key, depth, evaluation: best_score, evaluation_type, best_move: best_move.into(), generation: self.transposition_table.generation() });
+
+        best_score
     }
 
     /// Searches for a move with the highest evaluation with a fixed depth and a hard time limit.
     pub fn search<const PV: bool>(&mut self, old_board: &Board, depth: usize, ply: usize, mut alpha: i32, beta: i32) -> i32 {
-        if self.max_nodes > 0 && self.nodes >= self.max_nodes as usize {
+        if self.max_nodes.is_some_and(|max_nodes| self.nodes >= max_nodes) {
             if old_board.in_check(old_board.side_to_move) {
                 return 0;
             } else {
-                return eval::evaluate_board(old_board);
+                return self.evaluate(old_board);
             }
         }
 
         self.update_killer(None, ply + 2);
+        self.pv_table[ply].clear();
+
+        if let Some(score) = Self::variant_score(old_board, ply) {
+            return score;
+        }
 
-        if ply > 0 && (old_board.half_move_counter >= 100 || self.past_boards.iter().filter(|p| **p == old_board.zobrist_key).count() == 2) {
-            return 0; // 50 move repetition or threefold repetition.
+        if self.is_draw(old_board, ply) {
+            return self.draw_score(old_board);
         }
 
         if depth == 0 {
-            return self.quiescence_search(old_board, ply, alpha, beta);
+            return self.quiescence_search(old_board, ply, 0, alpha, beta);
         }
 
-        if !PV && ply > 0 {
-            if let Some(entry) = self.transposition_table.get(old_board.zobrist_key) {
-                if entry.zobrist_key == old_board.zobrist_key && entry.depth >= depth {
-                    match entry.evaluation_type {
-                        EvaluationType::Exact => return entry.evaluation,
-                        EvaluationType::UpperBound if entry.evaluation <= alpha => return entry.evaluation,
-                        EvaluationType::LowerBound if entry.evaluation >= beta => return entry.evaluation,
-                        _ => {}
-                    }
-                }
+        // Every node probes the table, but only non-PV nodes take cutoffs from it, so the PV is always searched out.
+        let tt_entry = self.transposition_table.get(old_board.zobrist_key).filter(|entry| entry.zobrist_key == old_board.zobrist_key).cloned();
+        self.stats.tt_probe(old_board, tt_entry.as_ref());
+        self.summary.tt_probe(tt_entry.is_some());
+
+        // Transpositions don't share the half move counter, so their scores can't be trusted near a fifty move draw.
+        if !PV && ply > 0 && old_board.half_move_counter < FIFTY_MOVE_TT_CUTOFF_PLIES && let Some(entry) = &tt_entry && entry.depth >= depth {
+            match entry.evaluation_type {
+                EvaluationType::Exact => return entry.evaluation,
+                EvaluationType::UpperBound if entry.evaluation <= alpha => return entry.evaluation,
+                EvaluationType::LowerBound if entry.evaluation >= beta => return entry.evaluation,
+                _ => {}
             }
         }
 
-        let in_check = old_board.in_check(old_board.side_to_move);
-        let static_eval = eval::evaluate_board(old_board);
-
-        self.update_static_eval(static_eval, ply);
-
-        let improving = if in_check {
-            false
-        } else {
-            let current_ply = self.get_search_entry(ply).expect("couldnt find search entry tf").static_eval;
-            let two_ply_back = self.get_search_entry(ply - 2).unwrap_or(SearchEntry { static_eval: WORST_EVAL, killer_move: None }).static_eval;
-            let four_ply_back = self.get_search_entry(ply - 2).unwrap_or(SearchEntry { static_eval: WORST_EVAL, killer_move: None }).static_eval;
-
-            current_ply > two_ply_back || current_ply > four_ply_back
+        let (checkers, pinned) = old_board.checkers_and_pinned();
+        let in_check = checkers != Bitboard::ZERO;
+        let raw_eval = self.evaluate(old_board);
+
+        self.update_static_eval((!in_check).then_some(raw_eval), ply);
+        self.search_stack[ply].in_check = in_check;
+        let improving = self.is_improving(ply);
+
+        // A searched score is a better guess than the static evaluation whenever its bound points the right way.
+        let static_eval = match &tt_entry {
+            Some(entry) if !in_check && entry.evaluation.abs() < DEEPEST_PROVEN_WIN && old_board.half_move_counter < FIFTY_MOVE_TT_CUTOFF_PLIES => match entry.evaluation_type {
+                EvaluationType::Exact => entry.evaluation,
+                EvaluationType::LowerBound => entry.evaluation.max(raw_eval),
+                EvaluationType::UpperBound => entry.evaluation.min(raw_eval)
+            },
+            _ => raw_eval
         };
 
         // Reverse Futility Pruning
-        if !PV && !in_check && depth < RFP_DEPTH && static_eval - (RFP_THRESHOLD * (depth - improving as usize)) as i32 >= beta {
+        if self.features.rfp && !PV && !in_check && depth < RFP_DEPTH && static_eval - self.rfp_margin(depth, improving) >= beta {
             return static_eval;
         }
 
-        // Null Move Pruning
-        if !PV && !in_check && static_eval >= beta {
+        // Null Move Pruning (skipped with only pawns left, where zugzwang is likely)
+        if self.features.null_move && !PV && !in_check && static_eval >= beta && old_board.non_pawn_material(old_board.side_to_move) > 0 {
             let depth = (depth as isize - 3) - (depth as isize / 3);
 
+            self.update_played_move(old_board, None, false, 0, ply);
+
             let nmp_board = old_board.make_null_move();
             let nmp_score = -self.search::<false>(&nmp_board, depth.max(0) as usize, ply + 1, -beta, -alpha);
+            self.stats.null_move(nmp_score >= beta);
+
             if nmp_score >= beta {
                 return nmp_score;
             }
         }
 
         let mut moves = ArrayVec::new();
-        old_board.generate_moves(&mut moves, false);
+        old_board.generate_moves_with(&mut moves, false, checkers, pinned);
         self.move_sorter.order_moves(old_board, self, &mut moves, ply, false);
 
+        let single_reply = in_check && moves.iter().filter(|piece_move| old_board.make_move(piece_move, true).is_some()).count() == 1;
+
         let mut quiet_moves: MoveArray = ArrayVec::new();
         let mut num_moves = 0;
 
@@ -221,10 +1041,22 @@ impl Searcher {
         let mut evaluation_type = EvaluationType::UpperBound;
 
         for piece_move in moves.iter() {
-            let is_quiet = piece_move.flags != MoveFlags::EnPassant && old_board.board[piece_move.end.index()].is_none();
+            let is_quiet = piece_move.flags != MoveFlags::EnPassant && old_board.is_empty(piece_move.end);
 
             // Late Move Pruning
-            if !PV && is_quiet && depth <= 5 && num_moves >= (8 * depth) / (2 - improving as usize) {
+            if self.features.lmp && !PV && is_quiet && depth <= LMP_DEPTH && num_moves >= self.lmp_table[improving as usize][depth] {
+                continue;
+            }
+
+            // History Pruning
+            if !PV && !in_check && is_quiet && !piece_move.flags.is_promotion() && num_moves > 0 && depth <= HP_DEPTH
+                && self.move_sorter.get_history(old_board, *piece_move) < -self.history_pruning_margin * depth as i32 {
+                continue;
+            }
+
+            // Futility Pruning
+            if !PV && !in_check && is_quiet && num_moves > 0 && depth <= FP_DEPTH
+                && static_eval + FP_BASE + FP_MARGIN * (depth + improving as usize) as i32 <= alpha {
                 continue;
             }
 
@@ -235,10 +1067,13 @@ impl Searcher {
 
             let Some(board) = old_board.make_move(piece_move, false) else { continue; };
 
-            self.nodes += 1;
+            self.count_node();
+            self.summary.node(ply + 1, false);
             num_moves += 1;
 
-            let extension = if depth < 127 && board.in_check(board.side_to_move) { 1 } else { 0 };
+            let gives_check = board.in_check(board.side_to_move);
+            let extension = self.extension(&board, *piece_move, !is_quiet, single_reply, depth, ply);
+            self.update_played_move(old_board, Some(*piece_move), !is_quiet, extension, ply);
 
             let mut score = 0;
 
@@ -246,8 +1081,23 @@ impl Searcher {
                 // Full Window Search
                 score = -self.search::<PV>(&board, depth - 1 + extension, ply + 1, -beta, -alpha);
             } else {
-                let reduction = if !in_check && num_moves > LMR_MOVE_THRESHOLD {
-                    LMR_REDUCTION_TABLE[depth][num_moves] - PV as u32 as f32 - improving as u32 as f32
+                let reduction = if self.features.lmr && !in_check && num_moves > LMR_MOVE_THRESHOLD {
+                    let mut reduction = LMR_REDUCTION_TABLE[depth][num_moves];
+
+                    // Reduce less in PV nodes, when giving check, and for killer moves.
+                    reduction -= PV as u32 as f32 * LMR_PV_ADJUSTMENT;
+                    reduction -= gives_check as u32 as f32 * LMR_CHECK_ADJUSTMENT;
+                    reduction -= (self.search_stack[ply].killer_move == Some(*piece_move)) as u32 as f32 * LMR_KILLER_ADJUSTMENT;
+
+                    // Reduce more when the position isn't improving.
+                    reduction += (!improving) as u32 as f32 * LMR_NOT_IMPROVING_ADJUSTMENT;
+
+                    // Reduce quiet moves based off their history; good history reduces less, bad history reduces more.
+                    if is_quiet {
+                        reduction -= self.move_sorter.get_history(old_board, *piece_move) as f32 / LMR_HISTORY_DIVISOR;
+                    }
+
+                    reduction.max(0.0)
                 } else {
                     0_f32
                 };
@@ -255,6 +1105,10 @@ impl Searcher {
                 // Null Window Search
                 score = -self.search::<false>(&board, (depth as f32 - 1.0 - reduction + extension as f32).max(0.0) as usize, ply + 1, -alpha - 1, -alpha);
 
+                if reduction > 0.0 {
+                    self.stats.late_move_reduction(score > alpha);
+                }
+
                 if score > alpha && (score < beta || reduction > 0.0) {
                     // Null Window Search failed, resort to Full Window Search
                     score = -self.search::<PV>(&board, depth - 1 + extension, ply + 1, -beta, -alpha);
@@ -274,10 +1128,7 @@ impl Searcher {
 
                 alpha = score;
                 best_move = Some(*piece_move);
-
-                if ply == 0 {
-                    self.best_move = Some(*piece_move);
-                }
+                self.update_pv(*piece_move, ply);
             }
 
             if score >= beta {
@@ -294,6 +1145,8 @@ impl Searcher {
                     self.update_killer(Some(*piece_move), ply);
                 }
 
+                self.stats.beta_cutoff(num_moves);
+
                 evaluation_type = EvaluationType::LowerBound;
                 break;
             }
@@ -304,7 +1157,7 @@ impl Searcher {
         }
 
         if num_moves == 0 {
-            if old_board.in_check(old_board.side_to_move) {
+            if in_check {
                 return SHALLOWEST_PROVEN_LOSS + ply as i32; // Checkmate.
             } else {
                 return 0; // Stalemate.
@@ -312,35 +1165,75 @@ impl Searcher {
         }
 
         if !self.search_cancelled() {
-            self.transposition_table.store(old_board.zobrist_key, TTEntry { zobrist_key: old_board.zobrist_key, depth, evaluation: best_score, evaluation_type, best_move });
+            self.transposition_table.store(old_board.zobrist_key, TTEntry { zobrist_key: old_board.zobrist_key, depth, evaluation: best_score, evaluation_type, best_move: best_move.into(), generation: self.transposition_table.generation() });
         }
 
         best_score
     }
 
-    pub fn quiescence_search(&mut self, board: &Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
-        let eval = eval::evaluate_board(board);
-        if eval >= beta {
-            return eval;
+    /// Searches captures and promotions until the position is quiet, along with quiet checks in the
+    /// first `qsearch_check_plies` plies (counted by `qsearch_ply`) to catch short mating attacks.
+    pub fn quiescence_search(&mut self, board: &Board, ply: usize, qsearch_ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if let Some(pv) = self.pv_table.get_mut(ply) {
+            pv.clear();
+        }
+
+        if let Some(score) = Self::variant_score(board, ply) {
+            return score;
+        }
+
+        if self.is_draw(board, ply) {
+            return self.draw_score(board);
         }
 
-        alpha = alpha.max(eval);
+        let (checkers, pinned) = board.checkers_and_pinned();
+        let in_check = checkers != Bitboard::ZERO;
+
+        if ply >= MAX_DEPTH {
+            return if in_check { 0 } else { self.evaluate(board) };
+        }
+
+        // Standing pat is not an option when in check, as every evasion has to be searched.
+        let mut best_score = if in_check {
+            SHALLOWEST_PROVEN_LOSS + ply as i32
+        } else {
+            let eval = self.evaluate_within(board, Some((alpha, beta)));
+            if eval >= beta {
+                return eval;
+            }
+
+            alpha = alpha.max(eval);
+            eval
+        };
+
+        let search_checks = !in_check && qsearch_ply < self.qsearch_check_plies;
 
         let mut moves = ArrayVec::new();
-        board.generate_moves(&mut moves, true);
+        board.generate_moves_with(&mut moves, !in_check && !search_checks, checkers, pinned);
         self.move_sorter.order_moves(board, self, &mut moves, ply, true);
 
-        let mut best_score = eval;
+        let mut legal_moves = 0;
 
         for piece_move in moves.iter() {
-            if !MoveSorter::static_exchange_evaluation(board, *piece_move, -20) {
+            let is_quiet = piece_move.flags != MoveFlags::EnPassant && board.is_empty(piece_move.end) && !piece_move.flags.is_promotion();
+
+            // Quiet checks have to survive on their own, as a checking piece which is just taken threatens nothing.
+            let threshold = if is_quiet { 0 } else { -20 };
+            if !in_check && !MoveSorter::static_exchange_evaluation(board, *piece_move, threshold) {
                 continue;
             }
 
             let Some(board) = board.make_move(piece_move, false) else { continue; };
-            self.nodes += 1;
+            if !in_check && is_quiet && !board.in_check(board.side_to_move) {
+                continue;
+            }
+
+            self.count_node();
+            self.stats.qsearch_node();
+            self.summary.node(ply + 1, true);
+            legal_moves += 1;
 
-            let score = -self.quiescence_search(&board, ply + 1, -beta, -alpha);
+            let score = -self.quiescence_search(&board, ply + 1, qsearch_ply + 1, -beta, -alpha);
 
             if score > best_score {
                 best_score = score;
@@ -355,6 +1248,456 @@ impl Searcher {
             }
         }
 
+        // Without pieces, running out of captures may mean running out of moves entirely.
+        if !in_check && legal_moves == 0 && board.non_pawn_material(board.side_to_move) == 0 {
+            let mut all_moves = ArrayVec::new();
+            board.generate_moves_with(&mut all_moves, false, checkers, pinned);
+
+            if !all_moves.iter().any(|piece_move| board.make_move(piece_move, true).is_some()) {
+                return 0; // Stalemate.
+            }
+        }
+
         best_score
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::{sync::{atomic::AtomicBool, Arc}, time::Duration};
+
+    use crate::{engine::time_manager::{MockClock, TimeManager}, utils::{board::{Board, Variant}, consts::{ASPIRATION_DELTA, ASPIRATION_MIN_DEPTH, BEST_EVAL, DEEPEST_PROVEN_WIN, ELO_MAX, ELO_MIN, ELO_MIN_NPS, EVAL_GRAIN, KOTH_HILL, LMP_BASE, LMP_SCALE, MAX_DEPTH, NORMALIZE_PAWN_VALUE, RFP_DEPTH, SHALLOWEST_PROVEN_LOSS, SHALLOWEST_PROVEN_WIN, VARIETY_NOISE, WORST_EVAL}, piece::{Piece, PieceColor, PieceType}, piece_move::Move, transposition_table::{EvaluationType, TTEntry}}};
+
+    use super::{SearchLimits, Searcher};
+
+    #[test]
+    fn test_improving() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        // Without earlier evaluations (including near the root), positions count as improving.
+        searcher.update_static_eval(Some(10), 0);
+        searcher.update_static_eval(Some(-10), 1);
+        assert!(searcher.is_improving(0));
+        assert!(searcher.is_improving(1));
+
+        searcher.update_static_eval(Some(5), 2);
+        assert!(!searcher.is_improving(2));
+
+        // Being in check is never improving, and skips back to four plies earlier.
+        searcher.update_static_eval(None, 3);
+        assert!(!searcher.is_improving(3));
+
+        searcher.update_static_eval(None, 4);
+        searcher.update_static_eval(Some(0), 5);
+        searcher.update_static_eval(Some(20), 6);
+        assert!(searcher.is_improving(5));
+        assert!(searcher.is_improving(6));
+        assert!(searcher.relative_search_entry(1, -2).is_none());
+    }
+
+    #[test]
+    fn test_search_stack() {
+        let board = Board::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        let piece_move = Move::from_uci_on(&board, "f3e5").unwrap();
+        searcher.update_played_move(&board, Some(piece_move), true, 2, 0);
+        searcher.update_played_move(&board, None, false, 1, 1);
+
+        let window = searcher.stack_window(2);
+        assert_eq!(window.len(), 3);
+        assert_eq!(window[0].moved_piece, Some(Piece::new(PieceType::Knight, PieceColor::White)));
+        assert!(window[0].played_capture);
+        assert_eq!(window[1].moved_piece, None);
+
+        // Extensions add up along the line, and only the double extension is counted as one.
+        assert_eq!((window[1].extensions, window[1].double_extensions), (2, 1));
+        assert_eq!((window[2].extensions, window[2].double_extensions), (3, 1));
+    }
+
+    #[test]
+    fn test_killer_shift() {
+        let root = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let (mut board, mut past_boards) = (root.clone(), vec![root.zobrist_key]);
+        for uci_move in ["g1f3", "g8f6"] {
+            board.play(&Move::from_uci_on(&board, uci_move).unwrap(), &mut past_boards);
+        }
+
+        let killers = [Move::from_uci("b1c3"), Move::from_uci("b8c6")];
+        let searcher_after = |past_boards: &[u64], board: &Board| {
+            let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+            searcher.predicted_line = vec![(root.zobrist_key, Move::from_uci("e2e4"))];
+            searcher.past_boards = past_boards.to_vec();
+            searcher.update_killer(Some(killers[0]), 2);
+            searcher.update_killer(Some(killers[1]), 3);
+
+            searcher.follow_pv(board);
+            searcher.search_stack.iter().take(4).map(|entry| entry.killer_move).collect::<Vec<_>>()
+        };
+
+        // The opponent left the predicted line, and the killers move two plies closer to the root with the game.
+        assert_eq!(searcher_after(&past_boards, &board), vec![Some(killers[0]), Some(killers[1]), None, None]);
+
+        // An irreversible move cleared the previous root from the history, which is assumed to be two plies ago.
+        let board = board.make_move(&Move::from_uci_on(&board, "e2e4").unwrap(), false).unwrap();
+        assert_eq!(searcher_after(&[board.zobrist_key], &board), vec![Some(killers[0]), Some(killers[1]), None, None]);
+
+        // Searching the same root again keeps the stack in place.
+        assert_eq!(searcher_after(&[root.zobrist_key], &root), vec![None, None, Some(killers[0]), Some(killers[1])]);
+    }
+
+    #[test]
+    fn test_garbage_root_entry() {
+        let board = Board::new("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        // A colliding entry claims a move which isn't even pseudo-legal at the root.
+        let garbage = Move::from_uci("a1h8");
+        searcher.transposition_table.store(board.zobrist_key, TTEntry {
+            zobrist_key: board.zobrist_key, depth: MAX_DEPTH, evaluation: 0, evaluation_type: EvaluationType::Exact, best_move: garbage.pack(), generation: 0
+        });
+
+        for depth in [1, 4] {
+            searcher.start(&board, &SearchLimits { depth: Some(depth), ..Default::default() });
+
+            let best_move = searcher.best_move.unwrap();
+            assert_ne!(best_move, garbage);
+            assert!(board.is_legal(best_move));
+        }
+    }
+
+    #[test]
+    fn test_draw_score() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let boards = ["8/8/4k3/8/8/4K3/8/8 w - - 0 1", "8/8/4k3/8/8/3K4/8/8 b - - 0 1", "8/8/4k3/8/8/5K2/8/8 w - - 0 1", "8/8/4k3/8/8/2K5/8/8 w - - 0 1"].map(Board::new);
+
+        for board in &boards {
+            assert_eq!(searcher.draw_score(board).abs(), 1);
+            assert_eq!(searcher.draw_score(board), searcher.draw_score(board));
+        }
+
+        // Both directions show up, or the jitter would just be a constant offset.
+        let scores: Vec<_> = boards.iter().map(|board| searcher.draw_score(board)).collect();
+        assert!(scores.contains(&1) && scores.contains(&-1), "{:?}", scores);
+
+        searcher.draw_jitter = false;
+        assert!(boards.iter().all(|board| searcher.draw_score(board) == 0));
+    }
+
+    #[test]
+    fn test_variety_noise() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(searcher.variety_noise(&board), 0);
+
+        searcher.variety_seed = Some(1);
+        let noise = searcher.variety_noise(&board);
+        assert!(noise.abs() <= VARIETY_NOISE);
+        assert_eq!(searcher.variety_noise(&board), noise);
+
+        // Some other seed shifts the same position differently.
+        assert!((2..10).any(|seed| {
+            searcher.variety_seed = Some(seed);
+            searcher.variety_noise(&board) != noise
+        }));
+
+        // Noisy searches still find forced mates.
+        let board = Board::new("k7/8/2K5/8/8/8/8/1Q6 w - - 0 1");
+        let eval = searcher.start(&board, &SearchLimits { depth: Some(4), ..Default::default() });
+        assert_eq!(Searcher::uci_score(eval), "mate 1");
+    }
+
+    #[test]
+    fn test_search_summary() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let board = Board::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        searcher.start(&board, &SearchLimits { depth: Some(5), ..Default::default() });
+
+        // Every iteration is recorded, and together they account for every node.
+        let summary = &searcher.summary;
+        assert_eq!(summary.iterations.iter().map(|iteration| iteration.depth).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(summary.iterations.iter().map(|iteration| iteration.nodes).sum::<usize>(), searcher.nodes);
+        assert!(summary.iterations.iter().all(|iteration| iteration.average_ply >= 1.0));
+
+        assert!(summary.branching_factor() > 1.0);
+        assert!(summary.qsearch_nodes < searcher.nodes && summary.tt_hits <= summary.tt_probes);
+
+        let root_nodes: Vec<_> = searcher.root_moves.iter().map(|root_move| (root_move.piece_move, root_move.nodes)).collect();
+        assert_eq!(summary.summary(searcher.nodes, &root_nodes).len(), 5);
+    }
+
+    #[test]
+    fn test_refutations() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let board = Board::new("4k3/8/8/r7/8/8/8/3QK3 w - - 0 1");
+        let hanging_queen = Move::from_uci_on(&board, "d1a4");
+
+        // Nothing is tracked unless asked for.
+        searcher.start(&board, &SearchLimits { depth: Some(4), ..Default::default() });
+        assert!(searcher.root_moves.iter().all(|root_move| root_move.refutation.is_empty()));
+
+        searcher.show_refutations = true;
+        searcher.start(&board, &SearchLimits { depth: Some(4), ..Default::default() });
+
+        let root_move = searcher.root_moves.iter().find(|root_move| Some(root_move.piece_move) == hanging_queen).expect("expected Qa4+ to be a root move");
+        assert_eq!(root_move.refutation.iter().take(2).map(|piece_move| piece_move.to_uci()).collect::<Vec<_>>(), vec!["d1a4", "a5a4"]);
+        assert!(searcher.root_moves.iter().all(|root_move| root_move.refutation.first().is_none_or(|first| *first == root_move.piece_move)));
+    }
+
+    #[test]
+    fn test_variant_search() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let limits = SearchLimits { depth: Some(4), ..Default::default() };
+
+        // Walking the king to the hill wins like a mate in one.
+        let mut board = Board::new("4k3/8/8/8/8/3K4/8/8 w - - 0 1");
+        board.set_variant(Variant::KingOfTheHill);
+        let eval = searcher.start(&board, &limits);
+        assert_eq!(Searcher::uci_score(eval), "mate 1");
+        assert!(searcher.best_move.is_some_and(|best_move| KOTH_HILL & best_move.end.bit() != 0));
+
+        // A third check wins, even one which could be answered.
+        let board = Board::new("rnbq1bnr/ppp2k1p/3p2p1/4p2Q/4P3/8/PPPP1PPP/RNB1K1NR w KQ - 1+3 0 5");
+        let eval = searcher.start(&board, &limits);
+        assert_eq!(Searcher::uci_score(eval), "mate 1");
+
+        // A game which is already over has no moves to search.
+        let mut board = Board::new("8/8/8/3k4/8/8/8/4K3 w - - 0 1");
+        board.set_variant(Variant::KingOfTheHill);
+        assert_eq!(searcher.start(&board, &limits), SHALLOWEST_PROVEN_LOSS);
+        assert_eq!(searcher.best_move, None);
+    }
+
+    #[test]
+    fn test_uci_score() {
+        for moves in 1..=5 {
+            assert_eq!(Searcher::mate_in(Searcher::mate_score(moves)), Some(moves as i32));
+        }
+
+        // Getting mated on the next move is two plies away.
+        assert_eq!(Searcher::uci_score(SHALLOWEST_PROVEN_LOSS + 2), "mate -1");
+        assert_eq!(Searcher::uci_score(SHALLOWEST_PROVEN_LOSS + 4), "mate -2");
+        assert_eq!(Searcher::uci_score(SHALLOWEST_PROVEN_WIN - 1), "mate 1");
+        assert_eq!(Searcher::uci_score(SHALLOWEST_PROVEN_WIN), "mate 1");
+        assert_eq!(Searcher::uci_score(BEST_EVAL), "mate 1");
+        assert_eq!(Searcher::uci_score(SHALLOWEST_PROVEN_LOSS), "mate -1");
+        assert_eq!(Searcher::uci_score(WORST_EVAL), "mate -1");
+        assert_eq!(Searcher::uci_score(-NORMALIZE_PAWN_VALUE), "cp -100");
+
+        // Normalized scores keep their sign and are rounded to the grain, the same way for both sides.
+        for eval in [-501, -35, -1, 0, 1, 35, 501] {
+            let cp = Searcher::normalized_cp(eval);
+            assert_eq!(cp % EVAL_GRAIN, 0);
+            assert_eq!(Searcher::normalized_cp(-eval), -cp);
+            assert!((cp - eval * 100 / NORMALIZE_PAWN_VALUE).abs() <= EVAL_GRAIN);
+        }
+
+        // The mated side of a position reports a negative mate.
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let board = Board::new("k7/8/2K5/8/8/8/8/1Q6 b - - 0 1");
+        let eval = searcher.start(&board, &SearchLimits { depth: Some(4), ..Default::default() });
+        assert_eq!(Searcher::uci_score(eval), "mate -1");
+    }
+
+    #[test]
+    fn test_forced_move() {
+        // Taking the undefended rook is the only legal move.
+        let board = Board::new("k7/8/8/8/8/8/6r1/7K w - - 0 1");
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        let limits = SearchLimits { time_left: [Some(60_000), Some(60_000)], ..Default::default() };
+        searcher.start(&board, &limits);
+
+        assert_eq!(searcher.depth, 1);
+        assert_eq!(searcher.best_move, Move::from_uci_on(&board, "h1g2"));
+    }
+
+    #[test]
+    fn test_qsearch_in_check() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        // White is a queen for a rook up, but mated on the back rank: standing pat would fail high.
+        let board = Board::new("6k1/1Q6/8/8/8/8/5PPP/3r2K1 w - - 0 1");
+        assert!(searcher.evaluate(&board) >= 50);
+        assert_eq!(searcher.quiescence_search(&board, 0, 0, -50, 50), SHALLOWEST_PROVEN_LOSS);
+
+        // With an evasion available, it is searched even though it isn't a capture.
+        let board = Board::new("6k1/1Q6/8/8/8/8/5PP1/3r2K1 w - - 0 1");
+        assert!(searcher.quiescence_search(&board, 0, 0, WORST_EVAL, BEST_EVAL) > SHALLOWEST_PROVEN_LOSS + MAX_DEPTH as i32);
+    }
+
+    #[test]
+    fn test_qsearch_draws() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        searcher.draw_jitter = false;
+
+        // A rook up, but the fifty move rule is about to run out.
+        let board = Board::new("6k1/8/8/8/8/8/8/R5K1 b - - 100 80");
+        assert_eq!(searcher.quiescence_search(&board, 1, 0, WORST_EVAL, BEST_EVAL), 0);
+
+        // Mate on the move which runs out the clock still counts.
+        let board = Board::new("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80");
+        assert_eq!(searcher.quiescence_search(&board, 1, 0, WORST_EVAL, BEST_EVAL), SHALLOWEST_PROVEN_LOSS + 1);
+
+        // The third occurrence of a position is a draw.
+        let board = Board::new("6k1/8/8/8/8/8/8/R5K1 b - - 10 80");
+        searcher.past_boards = vec![board.zobrist_key, 0, board.zobrist_key];
+        assert_eq!(searcher.quiescence_search(&board, 1, 0, WORST_EVAL, BEST_EVAL), 0);
+    }
+
+    #[test]
+    fn test_qsearch_checks() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let board = Board::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+
+        // The back rank mate is a quiet move, so only quiescence search with checks sees it.
+        assert_eq!(searcher.quiescence_search(&board, 0, 0, WORST_EVAL, BEST_EVAL), SHALLOWEST_PROVEN_WIN - 1);
+
+        searcher.qsearch_check_plies = 0;
+        assert!(searcher.quiescence_search(&board, 0, 0, WORST_EVAL, BEST_EVAL) < DEEPEST_PROVEN_WIN);
+    }
+
+    #[test]
+    fn test_search_features() {
+        let board = Board::new("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+        let limits = SearchLimits { depth: Some(6), ..Default::default() };
+
+        // Each search gets a fresh searcher, so neither one is helped by the table or history of the other.
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        searcher.start(&board, &limits);
+        let nodes = searcher.nodes;
+
+        // Without the selective techniques, the same depth takes a lot more nodes, but still finds a move.
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        for option in ["UseNullMove", "UseLMR", "UseRFP", "UseLMP", "UseAspiration", "UseLazyEval"] {
+            assert!(searcher.features.set(option, false));
+        }
+        assert!(!searcher.features.set("UseEverything", false));
+
+        searcher.start(&board, &limits);
+        assert!(searcher.nodes > nodes);
+        assert!(searcher.best_move.is_some_and(|best_move| board.is_legal(best_move)));
+    }
+
+    #[test]
+    fn test_aspiration_windows() {
+        // Only the failed bound moves, past the score and never past the eval range.
+        assert_eq!(Searcher::widen_window(-25, 25, -40, 37), (-77, 25));
+        assert_eq!(Searcher::widen_window(-25, 25, 60, 37), (-25, 97));
+        assert_eq!(Searcher::widen_window(-25, 25, WORST_EVAL + 10, 37), (WORST_EVAL, 25));
+        assert_eq!(Searcher::widen_window(-25, 25, BEST_EVAL - 10, 37), (-25, BEST_EVAL));
+
+        let board = Board::new("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+        let depth = ASPIRATION_MIN_DEPTH + 1;
+
+        // A previous score far above and far below the real one fails low and high respectively, and the
+        // window keeps widening until the score fits. What comes out is exact rather than a bound, so a
+        // search with a window just around it finds the same score.
+        for prev_score in [1000, -1000] {
+            let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+            searcher.start(&board, &SearchLimits { depth: Some(1), ..Default::default() });
+
+            let score = searcher.aspiration_windows(&board, depth, prev_score);
+            assert!((score - prev_score).abs() > ASPIRATION_DELTA, "{} should have failed the window around {}", score, prev_score);
+            assert!(score > WORST_EVAL && score < BEST_EVAL);
+            assert_eq!(searcher.root_search(&board, depth, score - 1, score + 1), score);
+        }
+    }
+
+    #[test]
+    fn test_rfp_margin() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        // The default margins match the old `200 * (depth - improving)` rule, without going through unsigned depths.
+        for depth in 1..RFP_DEPTH {
+            assert_eq!(searcher.rfp_margin(depth, false), 200 * depth as i32);
+            assert_eq!(searcher.rfp_margin(depth, true), 200 * (depth as i32 - 1));
+        }
+
+        searcher.rfp_margins = (50, 25, 100);
+        assert_eq!(searcher.rfp_margin(1, true), -25);
+        assert_eq!(searcher.rfp_margin(4, false), 50 * 4 + 25 * 16);
+    }
+
+    #[test]
+    fn test_lmp_table() {
+        // The default coefficients match the old `8 * depth / (2 - improving)` rule.
+        let table = Searcher::lmp_table(LMP_BASE, LMP_SCALE);
+        for (depth, (&not_improving, &improving)) in table[0].iter().zip(table[1].iter()).enumerate() {
+            assert_eq!(not_improving, 8 * depth / 2);
+            assert_eq!(improving, 8 * depth);
+        }
+
+        let table = Searcher::lmp_table(3, 2);
+        assert_eq!(table[1][2], 7);
+        assert_eq!(table[0][2], 3);
+    }
+
+    #[test]
+    fn test_degenerate_limits() {
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        // Empty clocks, a depth of zero and a node limit of zero should all still produce a move.
+        let clock = SearchLimits { time_left: [Some(0), Some(0)], depth: Some(0), ..Default::default() };
+        let depth = SearchLimits { depth: Some(0), nodes: Some(0), ..Default::default() };
+        let nodes = SearchLimits { nodes: Some(0), ..Default::default() };
+
+        for limits in [clock, depth, nodes] {
+            searcher.start(&board, &limits);
+            assert!(searcher.best_move.is_some(), "no move for {:?}", limits);
+        }
+
+        // Depth takes priority over nodes, and any limit over an infinite search.
+        searcher.start(&board, &SearchLimits { depth: Some(2), nodes: Some(1), ..Default::default() });
+        assert_eq!(searcher.depth, 2);
+
+        searcher.start(&board, &SearchLimits { depth: Some(3), infinite: true, ..Default::default() });
+        assert_eq!(searcher.depth, 3);
+    }
+
+    #[test]
+    fn test_nps_governor() {
+        let board = Board::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        searcher.nps_limit = Some(50_000);
+
+        // Every slice of nodes is held back until it is due, so the search can't beat its node rate.
+        let start = std::time::Instant::now();
+        searcher.start(&board, &SearchLimits { nodes: Some(3_000), ..Default::default() });
+        let slices = searcher.nodes / 500;
+        assert!(start.elapsed() >= Duration::from_millis(10) * slices as u32);
+
+        // Stronger ratings search faster.
+        assert_eq!(Searcher::elo_nps(ELO_MIN), ELO_MIN_NPS);
+        assert!(Searcher::elo_nps(ELO_MAX) > Searcher::elo_nps(2000));
+    }
+
+    #[test]
+    fn test_time_limits() {
+        let board = Board::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+
+        // A clock which ticks on every reading makes timed searches reproducible.
+        let timed_search = |limits: &SearchLimits| {
+            let mut searcher = Searcher::new(TimeManager::new(0), 1, Arc::new(AtomicBool::new(false)));
+            searcher.clock = Box::new(MockClock::ticking(Duration::from_millis(1)));
+            searcher.start(&board, limits);
+
+            assert!(searcher.best_move.is_some());
+            (searcher.nodes, searcher.elapsed())
+        };
+
+        // A fixed move time is a hard limit, which the search stops at once the clock is next polled.
+        let movetime = SearchLimits { movetime: Some(20), ..Default::default() };
+        let (nodes, elapsed) = timed_search(&movetime);
+        assert!(elapsed > Duration::from_millis(20) && elapsed <= Duration::from_millis(23));
+        assert_eq!(timed_search(&movetime).0, nodes);
+
+        // With a clock, no new iteration starts past the soft limit, and no search runs past the hard limit.
+        let clock = SearchLimits { time_left: [Some(1_000), Some(1_000)], ..Default::default() };
+        let mut time_manager = TimeManager::new(0);
+        time_manager.set_clock(1_000, 0, None);
+
+        let (_, elapsed) = timed_search(&clock);
+        assert!(elapsed <= time_manager.hard_limit + Duration::from_millis(3));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::utils::{board::Board, piece_move::Move, transposition_table::TTEntry};
+
+/// The number of move indices beta cutoffs are counted for; later cutoffs share the last slot.
+const CUTOFF_SLOTS: usize = 8;
+
+/// The number of root moves the time distribution is listed for in a search summary.
+const ROOT_MOVE_SLOTS: usize = 5;
+
+/// Counters which describe how the search spent its nodes.
+///
+/// Counting only happens when the engine is built with the `stats` feature,
+/// so the hooks compile away entirely otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct SearchStats {
+    /// The number of transposition table lookups.
+    pub tt_probes: usize,
+    /// The number of lookups which found an entry for the same position.
+    pub tt_hits: usize,
+    /// The number of hits whose best move isn't pseudo legal, meaning two positions share a key.
+    pub tt_collisions: usize,
+    /// The number of beta cutoffs, indexed by the number of moves searched before the cutoff.
+    pub beta_cutoffs: [usize; CUTOFF_SLOTS],
+    /// The number of null move searches.
+    pub null_move_searches: usize,
+    /// The number of null move searches which failed high.
+    pub null_move_cutoffs: usize,
+    /// The number of reduced late move searches.
+    pub lmr_searches: usize,
+    /// The number of reduced late move searches which had to be searched again.
+    pub lmr_researches: usize,
+    /// The number of nodes visited in quiescence search.
+    pub qsearch_nodes: usize
+}
+
+impl SearchStats {
+    /// Whether or not statistics are being collected.
+    pub const ENABLED: bool = cfg!(feature = "stats");
+
+    /// Resets every counter.
+    pub fn clear(&mut self) {
+        if Self::ENABLED {
+            *self = SearchStats::default();
+        }
+    }
+
+    /// Records a transposition table lookup, given the entry found for the position (if any).
+    pub fn tt_probe(&mut self, board: &Board, entry: Option<&TTEntry>) {
+        if Self::ENABLED {
+            self.tt_probes += 1;
+            self.tt_hits += entry.is_some() as usize;
+            self.tt_collisions += entry.and_then(|entry| entry.best_move.unpack()).is_some_and(|best_move| !board.is_pseudo_legal(best_move)) as usize;
+        }
+    }
+
+    /// Records a beta cutoff after searching `num_moves` moves.
+    pub fn beta_cutoff(&mut self, num_moves: usize) {
+        if Self::ENABLED {
+            self.beta_cutoffs[num_moves.saturating_sub(1).min(CUTOFF_SLOTS - 1)] += 1;
+        }
+    }
+
+    /// Records a null move search, and whether or not it failed high.
+    pub fn null_move(&mut self, cutoff: bool) {
+        if Self::ENABLED {
+            self.null_move_searches += 1;
+            self.null_move_cutoffs += cutoff as usize;
+        }
+    }
+
+    /// Records a reduced late move search, and whether or not it was searched again.
+    pub fn late_move_reduction(&mut self, research: bool) {
+        if Self::ENABLED {
+            self.lmr_searches += 1;
+            self.lmr_researches += research as usize;
+        }
+    }
+
+    /// Records a node visited in quiescence search.
+    pub fn qsearch_node(&mut self) {
+        if Self::ENABLED {
+            self.qsearch_nodes += 1;
+        }
+    }
+
+    /// Formats the counters as a JSON object, given the total number of nodes searched.
+    pub fn to_json(&self, nodes: usize) -> serde_json::Value {
+        serde_json::json!({
+            "nodes": nodes,
+            "tt_probes": self.tt_probes,
+            "tt_hits": self.tt_hits,
+            "tt_collisions": self.tt_collisions,
+            "beta_cutoffs": self.beta_cutoffs,
+            "null_move_searches": self.null_move_searches,
+            "null_move_cutoffs": self.null_move_cutoffs,
+            "lmr_searches": self.lmr_searches,
+            "lmr_researches": self.lmr_researches,
+            "qsearch_nodes": self.qsearch_nodes
+        })
+    }
+
+    /// Formats the counters as a list of lines, given the total number of nodes searched.
+    pub fn summary(&self, nodes: usize) -> Vec<String> {
+        let percentage = |part: usize, total: usize| if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 };
+
+        let total_cutoffs: usize = self.beta_cutoffs.iter().sum();
+        let cutoffs = self.beta_cutoffs.iter()
+            .enumerate()
+            .map(|(index, count)| format!("{}{}:{:.1}%", index + 1, if index == CUTOFF_SLOTS - 1 { "+" } else { "" }, percentage(*count, total_cutoffs)))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        vec![
+            format!("tt probes {} hits {} ({:.1}%) collisions {}", self.tt_probes, self.tt_hits, percentage(self.tt_hits, self.tt_probes), self.tt_collisions),
+            format!("beta cutoffs {} by move {}", total_cutoffs, cutoffs),
+            format!("null move searches {} cutoffs {} ({:.1}%)", self.null_move_searches, self.null_move_cutoffs, percentage(self.null_move_cutoffs, self.null_move_searches)),
+            format!("lmr searches {} researches {} ({:.1}%)", self.lmr_searches, self.lmr_researches, percentage(self.lmr_researches, self.lmr_searches)),
+            format!("qsearch nodes {} ({:.1}% of {})", self.qsearch_nodes, percentage(self.qsearch_nodes, nodes), nodes)
+        ]
+    }
+}
+
+/// What a single completed iteration of iterative deepening searched.
+#[derive(Debug, Clone)]
+pub struct IterationSummary {
+    /// The depth of the iteration.
+    pub depth: usize,
+    /// The number of nodes searched during the iteration.
+    pub nodes: usize,
+    /// The average ply of the nodes searched during the iteration.
+    pub average_ply: f64
+}
+
+/// A summary of how a search spent its nodes and time, printed after the search when requested.
+///
+/// Unlike `SearchStats`, these counters are cheap enough to always be collected.
+#[derive(Debug, Clone, Default)]
+pub struct SearchSummary {
+    /// Every completed iteration, in order.
+    pub iterations: Vec<IterationSummary>,
+    /// The number of nodes visited in quiescence search.
+    pub qsearch_nodes: usize,
+    /// The number of transposition table lookups.
+    pub tt_probes: usize,
+    /// The number of lookups which found an entry for the same position.
+    pub tt_hits: usize,
+    /// The sum of the plies of every node searched.
+    pub ply_sum: usize,
+    /// The totals (nodes and ply sum) when the last iteration was recorded.
+    last_totals: (usize, usize)
+}
+
+impl SearchSummary {
+    /// Resets every counter.
+    pub fn clear(&mut self) {
+        *self = SearchSummary::default();
+    }
+
+    /// Records a node searched at `ply`.
+    pub fn node(&mut self, ply: usize, qsearch: bool) {
+        self.ply_sum += ply;
+        self.qsearch_nodes += qsearch as usize;
+    }
+
+    /// Records a transposition table lookup, and whether or not it found an entry for the same position.
+    pub fn tt_probe(&mut self, hit: bool) {
+        self.tt_probes += 1;
+        self.tt_hits += hit as usize;
+    }
+
+    /// Records a completed iteration, given the total number of nodes searched so far.
+    pub fn end_iteration(&mut self, depth: usize, nodes: usize) {
+        let (last_nodes, last_ply_sum) = self.last_totals;
+        let iteration_nodes = nodes - last_nodes;
+
+        self.iterations.push(IterationSummary {
+            depth,
+            nodes: iteration_nodes,
+            average_ply: if iteration_nodes == 0 { 0.0 } else { (self.ply_sum - last_ply_sum) as f64 / iteration_nodes as f64 }
+        });
+        self.last_totals = (nodes, self.ply_sum);
+    }
+
+    /// The effective branching factor: the geometric mean of the growth in nodes from one iteration to the next.
+    pub fn branching_factor(&self) -> f64 {
+        let nodes: Vec<usize> = self.iterations.iter().map(|iteration| iteration.nodes).filter(|nodes| *nodes > 0).collect();
+
+        match (nodes.first(), nodes.last()) {
+            (Some(first), Some(last)) if nodes.len() > 1 => (*last as f64 / *first as f64).powf(1.0 / (nodes.len() - 1) as f64),
+            _ => 0.0
+        }
+    }
+
+    /// Formats the summary as a JSON object, given the total number of nodes searched
+    /// and the moves searched from the root with the nodes spent on each.
+    pub fn to_json(&self, nodes: usize, root_moves: &[(Move, usize)]) -> serde_json::Value {
+        let iterations = self.iterations.iter()
+            .map(|iteration| serde_json::json!({ "depth": iteration.depth, "nodes": iteration.nodes, "average_ply": iteration.average_ply }))
+            .collect::<Vec<_>>();
+        let root_moves = root_moves.iter()
+            .map(|(piece_move, nodes)| serde_json::json!({ "move": piece_move.to_uci(), "nodes": nodes }))
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "nodes": nodes,
+            "branching_factor": self.branching_factor(),
+            "iterations": iterations,
+            "qsearch_nodes": self.qsearch_nodes,
+            "tt_probes": self.tt_probes,
+            "tt_hits": self.tt_hits,
+            "root_moves": root_moves
+        })
+    }
+
+    /// Formats the summary as a list of lines, given the total number of nodes searched
+    /// and the moves searched from the root with the nodes spent on each.
+    pub fn summary(&self, nodes: usize, root_moves: &[(Move, usize)]) -> Vec<String> {
+        let percentage = |part: f64, total: f64| if total == 0.0 { 0.0 } else { part * 100.0 / total };
+
+        let average_ply = self.iterations.iter().map(|iteration| iteration.average_ply).sum::<f64>() / self.iterations.len().max(1) as f64;
+        let depths = self.iterations.iter()
+            .map(|iteration| format!("{}:{:.1}", iteration.depth, iteration.average_ply))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // The search runs at a steady speed, so the share of the nodes spent on a root move is its share of the time.
+        // Only the moves which took a noticeable share are worth listing.
+        let total_nodes = root_moves.iter().map(|(_, nodes)| *nodes).sum::<usize>() as f64;
+        let mut shares: Vec<(Move, f64)> = root_moves.iter().map(|(piece_move, nodes)| (*piece_move, percentage(*nodes as f64, total_nodes))).collect();
+        shares.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let times = shares.iter()
+            .take(ROOT_MOVE_SLOTS)
+            .map(|(piece_move, share)| format!("{}:{:.1}%", piece_move.to_uci(), share))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        vec![
+            format!("branching factor {:.2} over {} iterations", self.branching_factor(), self.iterations.len()),
+            format!("average ply {:.1} by depth {}", average_ply, depths),
+            format!("qsearch nodes {} ({:.1}% of {})", self.qsearch_nodes, percentage(self.qsearch_nodes as f64, nodes as f64), nodes),
+            format!("tt probes {} hits {} ({:.1}%)", self.tt_probes, self.tt_hits, percentage(self.tt_hits as f64, self.tt_probes as f64)),
+            format!("root move time {}", times)
+        ]
+    }
+}
@@ -0,0 +1,365 @@
+use std::time::{Duration, Instant};
+#[cfg(test)]
+use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+
+use crate::utils::{board::{Bitboard, Board}, consts::{DEFAULT_MOVES_TO_GO, EMERGENCY_TIME, KING_MASKS, MIN_THINK_TIME, MOVE_OVERHEAD, PAWN_VALUE, TM_COMPLEX_PHASE, TM_COMPLEX_SCALE, TM_DECIDED_EVAL, TM_DECIDED_SCALE, TM_FAIL_LOW_WEIGHT, TM_KING_DANGER_TILES, TM_MAX_SCALE, TM_MAX_SCORE_DROP_SCALE, TM_MIN_SCALE, TM_RECAPTURE_DEFICIT, TM_SCORE_DROP_WEIGHT, TM_SIMPLE_PHASE, TM_SIMPLE_SCALE}, piece::{PieceColor, PieceType}, piece_move::{Move, MoveFlags}};
+
+/// A source of time for the search, so time management can be tested without waiting on a real clock.
+pub trait Clock: Send {
+    /// The time passed since an arbitrary, fixed starting point.
+    fn now(&self) -> Duration;
+
+    /// The time passed since an earlier reading of the clock.
+    fn elapsed(&self, since: Duration) -> Duration {
+        self.now().saturating_sub(since)
+    }
+}
+
+/// The wall clock.
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    /// The moment the clock was created.
+    origin: Instant
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock { origin: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A clock which only moves when told to, or by a fixed step every time it is read.
+///
+/// Clones share the same time, so a test can keep a handle on a clock owned by a searcher.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    /// The current time, in microseconds.
+    time: Arc<AtomicU64>,
+    /// The time (in microseconds) which passes with every reading.
+    step: u64
+}
+
+#[cfg(test)]
+impl MockClock {
+    /// Creates a clock which advances by a fixed step every time it is read.
+    pub fn ticking(step: Duration) -> Self {
+        MockClock { time: Arc::default(), step: step.as_micros() as u64 }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, time: Duration) {
+        self.time.fetch_add(time.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_micros(self.time.fetch_add(self.step, Ordering::Relaxed))
+    }
+}
+
+/// A rough description of how much thought a root position deserves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionClass {
+    /// There is only one legal move, so there is nothing to think about.
+    Forced,
+    /// A simple endgame, or the middle of an exchange where the obvious recapture is available.
+    Simple,
+    Normal,
+    /// A middlegame where either king is under pressure.
+    Complex
+}
+
+impl PositionClass {
+    /// Classifies a root position by its phase, material balance and king danger, given its legal moves.
+    pub fn classify(board: &Board, legal_moves: &[Move]) -> Self {
+        if legal_moves.len() == 1 {
+            return PositionClass::Forced;
+        }
+
+        let (us, them) = (board.side_to_move, !board.side_to_move);
+        let material = |side: PieceColor| board.non_pawn_material(side) + board.colored_piece(PieceType::Pawn, side).board.count_ones() as i32 * PAWN_VALUE;
+        let deficit = material(them) - material(us);
+
+        // Being a piece down with a capture which wins it back is almost always an exchange in progress.
+        let recapture = deficit >= TM_RECAPTURE_DEFICIT && legal_moves.iter().any(|piece_move| {
+            piece_move.flags != MoveFlags::EnPassant
+                && board.piece_at(piece_move.end).is_some_and(|piece| piece.piece_type.get_value() + TM_RECAPTURE_DEFICIT > deficit)
+        });
+
+        let phase = board.phase();
+        if recapture || phase <= TM_SIMPLE_PHASE {
+            return PositionClass::Simple;
+        }
+
+        let king_danger = |side: PieceColor| {
+            let king_zone = Bitboard::new(KING_MASKS[board.king_tile(side).index()]);
+            (board.attacks_by(!side).all & king_zone).board.count_ones()
+        };
+
+        if phase >= TM_COMPLEX_PHASE && king_danger(us).max(king_danger(them)) >= TM_KING_DANGER_TILES {
+            PositionClass::Complex
+        } else {
+            PositionClass::Normal
+        }
+    }
+
+    /// The factor the allocated time is scaled by.
+    pub fn time_scale(&self) -> f64 {
+        match self {
+            PositionClass::Forced => 0.0,
+            PositionClass::Simple => TM_SIMPLE_SCALE,
+            PositionClass::Normal => 1.0,
+            PositionClass::Complex => TM_COMPLEX_SCALE
+        }
+    }
+}
+
+/// A struct which allocates the time the engine is allowed to spend on a move.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    /// The time after which no new iteration should be started.
+    pub soft_limit: Duration,
+    /// The time after which the search must be aborted.
+    pub hard_limit: Duration,
+    /// The time (in ms) subtracted from the clock to compensate for communication lag.
+    pub move_overhead: u64,
+
+    /// The soft limit allocated from the clock, before any scaling.
+    base_soft_limit: Duration,
+    /// Whether or not the soft limit may be scaled by the search.
+    adjustable: bool,
+    /// The class of the root position, if it was classified.
+    position_class: Option<PositionClass>,
+    /// The number of times the root search failed low in the current iteration.
+    root_fail_lows: usize
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        TimeManager::new(MOVE_OVERHEAD)
+    }
+}
+
+impl TimeManager {
+    /// Creates a new time manager with no time constraints.
+    pub fn new(move_overhead: u64) -> Self {
+        TimeManager {
+            soft_limit: Duration::MAX,
+            hard_limit: Duration::MAX,
+            move_overhead,
+
+            base_soft_limit: Duration::MAX,
+            adjustable: false,
+            position_class: None,
+            root_fail_lows: 0
+        }
+    }
+
+    /// Removes all time constraints.
+    pub fn set_infinite(&mut self) {
+        self.soft_limit = Duration::MAX;
+        self.hard_limit = Duration::MAX;
+        self.base_soft_limit = Duration::MAX;
+        self.adjustable = false;
+        self.position_class = None;
+        self.root_fail_lows = 0;
+    }
+
+    /// Allocates a fixed amount of time (in ms) for the move.
+    pub fn set_movetime(&mut self, movetime: u64) {
+        let time = movetime.saturating_sub(self.move_overhead).max(MIN_THINK_TIME);
+
+        self.soft_limit = Duration::from_millis(time);
+        self.hard_limit = Duration::from_millis(time);
+        self.base_soft_limit = self.soft_limit;
+        self.adjustable = false;
+        self.position_class = None;
+        self.root_fail_lows = 0;
+    }
+
+    /// Allocates time for the move given the time left on the clock, the increment,
+    /// and optionally the number of moves until the next time control (all in ms).
+    pub fn set_clock(&mut self, time_left: u64, increment: u64, moves_to_go: Option<u64>) {
+        let usable_time = time_left.saturating_sub(self.move_overhead).max(MIN_THINK_TIME);
+        let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, DEFAULT_MOVES_TO_GO);
+
+        // Never allow a single move to take up the whole clock, and be
+        // especially careful when there is barely any time left.
+        let maximum_time = if time_left < EMERGENCY_TIME { usable_time / 8 } else { usable_time * 3 / 4 };
+
+        let soft_time = usable_time / moves_to_go + increment * 3 / 4;
+        let hard_time = (soft_time * 4).min(maximum_time).max(MIN_THINK_TIME);
+
+        self.soft_limit = Duration::from_millis(soft_time.min(hard_time).max(MIN_THINK_TIME));
+        self.hard_limit = Duration::from_millis(hard_time);
+        self.base_soft_limit = self.soft_limit;
+        self.adjustable = true;
+        self.position_class = None;
+        self.root_fail_lows = 0;
+    }
+
+    /// Scales the allocated time by the class of the root position.
+    /// 
+    /// Only clock based allocations are scaled, since a fixed move time or an infinite search was asked for explicitly.
+    pub fn set_position_class(&mut self, position_class: PositionClass) {
+        self.position_class = Some(position_class);
+        if !self.adjustable {
+            return;
+        }
+
+        let soft_limit = self.base_soft_limit.mul_f64(position_class.time_scale()).min(self.hard_limit);
+        self.base_soft_limit = soft_limit.max(Duration::from_millis(MIN_THINK_TIME));
+        self.soft_limit = self.base_soft_limit;
+    }
+
+    /// Whether or not the move should be played as soon as the search has one, without thinking further.
+    pub fn instant_move(&self) -> bool {
+        self.adjustable && self.position_class == Some(PositionClass::Forced)
+    }
+
+    /// Records that the root search failed low in the current iteration.
+    pub fn report_fail_low(&mut self) {
+        self.root_fail_lows += 1;
+    }
+
+    /// Scales the soft limit after an iteration, given the score of the previous and
+    /// current iteration and the number of legal moves at the root.
+    /// 
+    /// More time is given when the score drops or the root fails low, and less
+    /// time is given when the position is trivially decided.
+    pub fn adjust_soft_limit(&mut self, previous_score: i32, score: i32, legal_moves: usize) {
+        let fail_lows = std::mem::take(&mut self.root_fail_lows);
+        if !self.adjustable {
+            return;
+        }
+
+        let mut scale = 1.0;
+
+        let score_drop = (previous_score - score).max(0) as f64;
+        scale += (score_drop * TM_SCORE_DROP_WEIGHT).min(TM_MAX_SCORE_DROP_SCALE);
+        scale += fail_lows as f64 * TM_FAIL_LOW_WEIGHT;
+
+        if legal_moves <= 1 || score.abs() >= TM_DECIDED_EVAL {
+            scale *= TM_DECIDED_SCALE;
+        }
+
+        let scale = scale.clamp(TM_MIN_SCALE, TM_MAX_SCALE);
+        self.soft_limit = self.base_soft_limit.mul_f64(scale).min(self.hard_limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::utils::{board::Board, piece_move::MoveArray};
+
+    use super::{Clock, MockClock, PositionClass, TimeManager};
+
+    #[test]
+    fn test_time_allocation() {
+        let mut time_manager = TimeManager::new(10);
+
+        // Soft limit should never exceed the hard limit, which should never exceed the clock.
+        for time_left in [0, 5, 50, 500, 5_000, 60_000, 3_600_000] {
+            for increment in [0, 100, 1_000] {
+                for moves_to_go in [None, Some(1), Some(10), Some(40)] {
+                    time_manager.set_clock(time_left, increment, moves_to_go);
+
+                    assert!(time_manager.soft_limit <= time_manager.hard_limit);
+                    assert!(time_left < 10 || time_manager.hard_limit <= Duration::from_millis(time_left - 10));
+                }
+            }
+        }
+
+        // Move overhead is subtracted from fixed move times.
+        time_manager.set_movetime(1_000);
+        assert_eq!(time_manager.hard_limit, Duration::from_millis(990));
+    }
+
+    #[test]
+    fn test_soft_limit_scaling() {
+        let mut time_manager = TimeManager::new(0);
+        time_manager.set_clock(60_000, 0, None);
+        let base_limit = time_manager.soft_limit;
+
+        // A dropping score extends the search.
+        time_manager.adjust_soft_limit(50, -100, 20);
+        assert!(time_manager.soft_limit > base_limit);
+        assert!(time_manager.soft_limit <= time_manager.hard_limit);
+
+        // A decided position shortens the search.
+        time_manager.adjust_soft_limit(2000, 2000, 20);
+        assert!(time_manager.soft_limit < base_limit);
+
+        // Fixed move times are never scaled.
+        time_manager.set_movetime(1_000);
+        time_manager.report_fail_low();
+        time_manager.adjust_soft_limit(500, -500, 1);
+        assert_eq!(time_manager.soft_limit, time_manager.hard_limit);
+    }
+
+    #[test]
+    fn test_position_class() {
+        let classify = |fen: &str| {
+            let board = Board::new(fen);
+            let mut moves = MoveArray::new();
+            board.generate_moves(&mut moves, false);
+
+            let legal_moves: Vec<_> = moves.into_iter().filter(|piece_move| board.make_move(piece_move, true).is_some()).collect();
+            PositionClass::classify(&board, &legal_moves)
+        };
+
+        assert_eq!(classify("k7/8/8/8/8/8/6r1/7K w - - 0 1"), PositionClass::Forced);
+        assert_eq!(classify("8/5k2/8/3p4/3P4/8/5K2/8 w - - 0 1"), PositionClass::Simple);
+        assert_eq!(classify("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), PositionClass::Normal);
+        // White just took a knight on f6 with the bishop, which black takes back.
+        assert_eq!(classify("r1bqkb1r/pppp1ppp/2n2B2/4p3/4P3/2N5/PPPP1PPP/R2QKBNR b KQkq - 0 4"), PositionClass::Simple);
+        // The white pieces are aimed at the black king.
+        assert_eq!(classify("r4rk1/pp3p1p/2n3pQ/q5N1/2B5/8/PB3PPP/R5K1 w - - 0 1"), PositionClass::Complex);
+
+        // The allocation follows the class, except for a single legal move, which is played immediately.
+        let mut time_manager = TimeManager::new(0);
+        time_manager.set_clock(60_000, 0, None);
+        let base_limit = time_manager.soft_limit;
+
+        time_manager.set_position_class(PositionClass::Complex);
+        assert!(time_manager.soft_limit > base_limit && time_manager.soft_limit <= time_manager.hard_limit);
+        assert!(!time_manager.instant_move());
+
+        time_manager.set_clock(60_000, 0, None);
+        time_manager.set_position_class(PositionClass::Forced);
+        assert!(time_manager.instant_move());
+
+        // Explicit limits are left alone.
+        time_manager.set_movetime(1_000);
+        time_manager.set_position_class(PositionClass::Forced);
+        assert_eq!(time_manager.soft_limit, time_manager.hard_limit);
+        assert!(!time_manager.instant_move());
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::default();
+        let handle = clock.clone();
+
+        let start = clock.now();
+        handle.advance(Duration::from_millis(15));
+        assert_eq!(clock.elapsed(start), Duration::from_millis(15));
+
+        // A ticking clock moves on every reading.
+        let clock = MockClock::ticking(Duration::from_millis(1));
+        assert_eq!(clock.now(), Duration::ZERO);
+        assert_eq!(clock.now(), Duration::from_millis(1));
+        assert_eq!(clock.elapsed(Duration::ZERO), Duration::from_millis(2));
+    }
+}
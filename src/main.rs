@@ -5,15 +5,24 @@
 #![allow(clippy::wrong_self_convention)]
 #![allow(unused_assignments)]
 
-use std::{sync::{atomic::AtomicBool, mpsc::channel, Arc}, time::Duration};
+use std::{io::Write, process::ExitCode, sync::{atomic::AtomicBool, mpsc::channel, Arc}};
 use colored::Colorize;
 
-use engine::search::Searcher;
-use utils::{board::Board, consts::{BEST_EVAL, LMR_REDUCTION_BASE, LMR_REDUCTION_DIVISOR, MAX_DEPTH, MAX_LEGAL_MOVES, WORST_EVAL}, piece::Tile, piece_move::{Move, MoveSorter}};
+use cli::{Args, Subcommand};
+use data::{datagen::{AdjudicationRules, DataGenerator, DatagenConfig}, filter::DataFilter, DataFormat};
+use tuner::{OptimizerKind, Schedule, TunerConfig, TunerState};
+use engine::{book::{BookBuilder, BookFilter, LearningBook}, eval, eval_params::EvalParams, search::{SearchLimits, Searcher}, time_manager::TimeManager};
+use utils::{board::Board, consts::{BENCH_NODES, BEST_EVAL, DATAGEN_DEFAULT_GAMES, LMR_REDUCTION_BASE, LMR_REDUCTION_DIVISOR, MAX_DEPTH, MAX_LEGAL_MOVES, STARTING_FEN, TEST_SUITE_DEFAULT_MOVETIME, TEST_SUITE_HASH_MB, TUNER_DEFAULT_CHECKPOINT_INTERVAL, TUNER_DEFAULT_EPOCHS, TUNER_DEFAULT_LEARNING_RATE, TUNER_DEFAULT_REPORT_INTERVAL, WORST_EVAL}, epd::EpdEntry, magics::{self, Slider}, pgn::{GameResult, PgnReader}, piece::Tile, piece_move::{Move, MoveArray, MoveSorter}, polyglot, transposition_table::TranspositionTable};
 
+mod cli;
+mod data;
 mod engine;
 mod utils;
 mod uci;
+mod tuner;
+
+/// The depth each bench position is searched to.
+const BENCH_DEPTH: usize = 5;
 
 const POSITIONS: &[&str] = &[
     "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14",
@@ -183,42 +192,546 @@ go nodes 145269
 position fen rn1qk2r/ppp1bppp/4pn2/6Bb/2BP4/2N2N1P/PPP2PP1/R2QK2R w KQkq - 0 1 moves g5f6 e7f6 d4d5 e6d5 d1d5 f6c3 b2c3 d8d5 c4d5 b8c6 d5c6 b7c6 e1c1 h5f3 h1e1 e8f8 g2f3 a8e8 e1g1 g7g6 d1d7 e8e7 g1d1 h7h5 d1d4 a7a5 c1d2 h5h4 d7d8 e7e8 d4d7 e8d8 d7d8 f8g7 d8h8 g7h8 d2e3 h8g7 e3f4 g7f6 a2a4 g6g5 f4g4 f6g6 f3f4 f7f5 g4f3 g5f4 f3f4 g6f6 c3c4 c6c5 c2c3 c7c6 f2f3 f6e6 f4e3
 go nodes 110949";
 
-fn main() {
-    std::env::set_var("RUST_BACKTRACE", "1");
+/// Every subcommand, in the order the usage lists them.
+const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand { name: "bench", usage: "[json]", description: "searches the bench positions and reports the node count", run: run_bench },
+    Subcommand { name: "perft", usage: "<depth> [json] [fen]", description: "counts the leaf nodes of the move tree, divided by root move", run: run_perft },
+    Subcommand { name: "testsuite", usage: "<epd> [depth N] [nodes N] [movetime N] [hash N] [minsolved N] [json]", description: "searches every position of an EPD test suite and counts the solved ones", run: run_test_suite },
+    Subcommand { name: "datagen", usage: "<output> [games N] [nodes N] [randomplies N] [seed N] [format text|bullet|marlin] [noadjudication] [winscore N] [winplies N] [drawscore N] [drawplies N] [drawply N] [pgn <path>]", description: "plays games against itself and writes their positions as training data", run: run_datagen },
+    Subcommand { name: "selfplay", usage: "[games N] [nodes N] [randomplies N] [seed N] [noadjudication] [pgn <path>]", description: "plays games against itself and reports the results", run: run_selfplay },
+    Subcommand { name: "genmagics", usage: "[seed N] [out <dir>]", description: "searches magics for the slider attack tables", run: run_genmagics },
+    Subcommand { name: "tune", usage: "<dataset> [threads N] [epochs N] [optimizer adam|gd] [lr X] [schedule constant|step|cosine] [stepsize N] [gamma X] [k X|auto] [validation N] [report N] [checkpoint <path>] [checkpointevery N] [resume <path>] [params <path>]", description: "tunes the evaluation parameters on a dataset", run: run_tune },
+    Subcommand { name: "makebook", usage: "<pgn> <book> [minelo N] [maxply N] [mingames N] [results 1-0,0-1,1/2-1/2] [format polyglot|native]", description: "builds an opening book from a PGN file", run: run_makebook },
+    Subcommand { name: "filterdata", usage: "<input> <output> [bookplies N] [exitplies N] [maxscore N] [seed N]", description: "filters training data", run: run_filterdata },
+    Subcommand { name: "convertdata", usage: "<input> <output> [from text|bullet|marlin] [to text|bullet|marlin]", description: "converts training data between formats", run: run_convertdata },
+    Subcommand { name: "symmetry", usage: "[epd]", description: "checks that positions evaluate the same as their mirror images", run: run_symmetry },
+    Subcommand { name: "evalparams", usage: "[path]", description: "prints the default evaluation parameters, or speaks UCI with the parameters of a file", run: run_evalparams }
+];
+
+/// Searches every bench position to a fixed depth, returning the total
+/// node count and the average nodes per second.
+fn bench() -> (usize, f64) {
+    let mut searcher = Searcher::new(TimeManager::default(), 5, Arc::new(AtomicBool::new(false)));
+    let mut npsa = vec![];
+    let mut nodes = 0;
+
+    let limits = SearchLimits { depth: Some(BENCH_DEPTH), ..Default::default() };
+
+    for pos in POSITIONS.iter() {
+        let board = Board::new(pos);
+
+        let time = std::time::Instant::now();
+        searcher.start(&board, &limits);
+        let _end = time.elapsed();
+        let end = _end.as_secs_f64();
+
+        nodes += searcher.nodes;
+
+        let nps = searcher.nodes as f64 / (if end == 0.0 { 1.0 } else { end });
+        npsa.push(nps);
+    }
+
+    let avg_nps = npsa.iter().sum::<f64>() / npsa.len() as f64;
+    (nodes, avg_nps)
+}
+
+/// Evaluates every position and its mirror image, returning the positions whose scores differ, along with both scores.
+/// Evaluations are relative to the side to move, so an asymmetric evaluation is always a bug.
+fn symmetry<'a>(positions: impl Iterator<Item = &'a str>, params: &EvalParams) -> Vec<(&'a str, i32, i32)> {
+    positions
+        .map(|fen| {
+            let board = Board::new(fen);
+            (fen, eval::evaluate_board(&board, params, None).score(), eval::evaluate_board(&board.mirror(), params, None).score())
+        })
+        .filter(|(_, eval, mirrored)| eval != mirrored)
+        .collect()
+}
+
+/// A seed which differs between runs, for subcommands which weren't given one.
+fn time_seed() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+}
+
+fn run_bench(args: &mut Args) -> Result<ExitCode, String> {
+    let json = match args.option() {
+        Some("json") => true,
+        Some(option) => return Err(args.unknown(option)),
+        None => false
+    };
 
-    let args: Vec<String> = std::env::args().collect();
-    if args.get(1) == Some(&"bench".to_string()) {
-        let mut searcher = Searcher::new(Duration::MAX, Duration::MAX, 5, Arc::new(AtomicBool::new(false)));
-        let mut npsa = vec![];
-        let mut nodes = 0;
-    
-        for pos in POSITIONS.iter() {
-            searcher.nodes = 0;
-    
-            let board = Board::new(pos);
-    
-            let time = std::time::Instant::now();
-            searcher.search_timed(&board);
-            let _end = time.elapsed();
-            let end = _end.as_secs_f64();
-    
-            nodes += searcher.nodes;
-    
-            let nps = searcher.nodes as f64 / (if end == 0.0 { 1.0 } else { end });
-            npsa.push(nps);
-        }
-    
-        let avg_nps = npsa.iter().sum::<f64>() / npsa.len() as f64;
+    let (nodes, avg_nps) = bench();
+
+    if json {
+        println!("{}", serde_json::json!({ "type": "bench", "nodes": nodes, "nps": avg_nps as u64, "signature": BENCH_NODES }));
+    } else {
         println!("{} nodes {} nps", nodes, avg_nps as u64);
 
-        std::process::exit(0);
+        if nodes != BENCH_NODES {
+            println!("bench signature mismatch, expected {} nodes", BENCH_NODES);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_perft(args: &mut Args) -> Result<ExitCode, String> {
+    let depth = args.required_value::<usize>("depth")?;
+    if depth == 0 {
+        return Err("depth should be at least 1".to_string());
+    }
+
+    // A leading `json` switches the output format, and whatever follows it is the FEN.
+    let rest = args.rest().unwrap_or_default();
+    let (json, fen) = match rest.strip_prefix("json") {
+        Some(fen) if fen.is_empty() || fen.starts_with(' ') => (true, fen.trim()),
+        _ => (false, rest.as_str())
+    };
+
+    let board = Board::from_fen(if fen.is_empty() { STARTING_FEN } else { fen })?;
+    let start = std::time::Instant::now();
+
+    let mut moves = MoveArray::new();
+    board.generate_moves(&mut moves, false);
+
+    // Every root move is counted separately, which narrows a wrong count down to the move generation of a single move.
+    let mut total = 0;
+    for piece_move in moves.iter() {
+        if let Some(child_board) = board.make_move(piece_move, true) {
+            let nodes = child_board.perft(depth - 1);
+            if json {
+                println!("{}", serde_json::json!({ "type": "divide", "move": piece_move.to_uci(), "nodes": nodes }));
+            } else {
+                println!("{}: {}", piece_move.to_uci(), nodes);
+            }
+
+            total += nodes;
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let (ms, nps) = ((elapsed * 1000.0) as u64, (total as f64 / elapsed.max(1e-9)) as u64);
+    if json {
+        println!("{}", serde_json::json!({ "type": "perft", "depth": depth, "nodes": total, "time": ms, "nps": nps }));
+    } else {
+        println!("\n{} nodes {} ms {} nps", total, ms, nps);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_test_suite(args: &mut Args) -> Result<ExitCode, String> {
+    let epd_path = args.required("epd")?;
+
+    let mut limits = SearchLimits::default();
+    let (mut hash, mut min_solved, mut json) = (TEST_SUITE_HASH_MB, None, false);
+    while let Some(option) = args.option() {
+        match option {
+            "depth" => limits.depth = Some(args.value::<usize>("depth")?),
+            "nodes" => limits.nodes = Some(args.value::<usize>("nodes")?),
+            "movetime" => limits.movetime = Some(args.value::<u64>("movetime")?),
+            "hash" => hash = args.value::<usize>("hash")?,
+            "minsolved" => min_solved = Some(args.value::<usize>("minsolved")?),
+            "json" => json = true,
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    if limits.depth.is_none() && limits.nodes.is_none() && limits.movetime.is_none() {
+        limits.movetime = Some(TEST_SUITE_DEFAULT_MOVETIME);
+    }
+
+    let contents = std::fs::read_to_string(epd_path).map_err(|error| format!("failed to read test suite {}: {}", epd_path, error))?;
+    let entries = contents
+        .lines()
+        .filter_map(EpdEntry::parse)
+        .collect::<Result<Vec<EpdEntry>, String>>()?;
+
+    let mut searcher = Searcher::new(TimeManager::default(), MAX_DEPTH, Arc::new(AtomicBool::new(false)));
+    searcher.transposition_table = TranspositionTable::from_mb(hash);
+
+    let mut solved = 0;
+    for (index, entry) in entries.iter().enumerate() {
+        // Every position is searched from an empty table, so the results don't depend on the order of the suite.
+        searcher.transposition_table.clear();
+        let eval = searcher.start(&entry.board, &limits);
+
+        let best_move = searcher.best_move;
+        let is_solved = best_move.is_some_and(|best_move| entry.is_solved_by(best_move));
+        solved += is_solved as usize;
+
+        let id = entry.id.clone().unwrap_or_else(|| (index + 1).to_string());
+        let best_move = best_move.map(|best_move| best_move.to_san(&entry.board));
+        let expected: Vec<String> = entry.best_moves.iter().map(|piece_move| piece_move.to_san(&entry.board))
+            .chain(entry.avoid_moves.iter().map(|piece_move| format!("!{}", piece_move.to_san(&entry.board))))
+            .collect();
+
+        if json {
+            println!("{}", serde_json::json!({
+                "type": "position", "id": id, "solved": is_solved, "move": best_move, "expected": expected,
+                "score": Searcher::json_score(eval), "nodes": searcher.nodes
+            }));
+        } else {
+            println!(
+                "{} {} {} (expected {}) {} {} nodes",
+                id,
+                if is_solved { "solved" } else { "failed" },
+                best_move.unwrap_or_else(|| "none".to_string()),
+                expected.join(" "),
+                Searcher::uci_score(eval),
+                searcher.nodes
+            );
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::json!({ "type": "testsuite", "solved": solved, "total": entries.len() }));
+    } else {
+        println!("{} of {} solved", solved, entries.len());
+    }
+
+    Ok(if min_solved.is_some_and(|min_solved| solved < min_solved) { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
+
+/// Parses the options shared by the subcommands playing games against the engine itself, returning whether the option was one of them.
+fn parse_game_option(option: &str, args: &mut Args, config: &mut DatagenConfig, rules: &mut AdjudicationRules) -> Result<bool, String> {
+    match option {
+        "nodes" => config.nodes = args.value::<usize>("nodes")?,
+        "randomplies" => config.random_plies = args.value::<usize>("randomplies")?,
+        "seed" => config.seed = args.value::<u64>("seed")?,
+        "noadjudication" => config.adjudication = None,
+        "winscore" => rules.win_score = args.value::<i32>("winscore")?,
+        "winplies" => rules.win_plies = args.value::<usize>("winplies")?,
+        "drawscore" => rules.draw_score = args.value::<i32>("drawscore")?,
+        "drawplies" => rules.draw_plies = args.value::<usize>("drawplies")?,
+        "drawply" => rules.draw_min_ply = args.value::<usize>("drawply")?,
+        _ => return Ok(false)
+    }
+
+    Ok(true)
+}
+
+fn run_datagen(args: &mut Args) -> Result<ExitCode, String> {
+    let output_path = args.required("output")?;
+
+    let (mut games, mut format) = (DATAGEN_DEFAULT_GAMES, DataFormat::Text);
+    let mut pgn_path = None;
+    let mut config = DatagenConfig { seed: time_seed(), ..Default::default() };
+    let mut rules = config.adjudication.expect("adjudication should be enabled by default");
+
+    while let Some(option) = args.option() {
+        match option {
+            "games" => games = args.value::<usize>("games")?,
+            "format" => format = DataFormat::from_name(args.string("format")?).ok_or("unknown data format")?,
+            "pgn" => pgn_path = Some(args.string("pgn")?),
+            _ if parse_game_option(option, args, &mut config, &mut rules)? => {},
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    config.adjudication = config.adjudication.map(|_| rules);
+
+    let mut generator = DataGenerator::new(config);
+    let (mut adjudicated, start) = (0, std::time::Instant::now());
+    let mut pgn = pgn_path.map(|path| std::io::BufWriter::new(std::fs::File::create(path).expect("failed to create PGN file")));
+
+    let positions = (0..games).flat_map(|game| {
+        let (positions, was_adjudicated) = generator.play_game();
+        adjudicated += was_adjudicated as usize;
+
+        // Games which ended during the random opening were never played out.
+        if let Some(pgn) = pgn.as_mut() && generator.last_game().result != GameResult::Unknown {
+            let mut game_pgn = generator.last_game().clone();
+            game_pgn.headers.insert(1, ("Round".to_string(), (game + 1).to_string()));
+            writeln!(pgn, "{}", game_pgn.to_pgn()).expect("failed to write PGN game");
+        }
+
+        if (game + 1) % 10 == 0 || game + 1 == games {
+            println!("{} games, {} adjudicated, {:.1}s", game + 1, adjudicated, start.elapsed().as_secs_f64());
+        }
+
+        positions
+    });
+
+    let count = format.write(output_path, positions).expect("failed to write positions");
+    if let Some(pgn) = pgn.as_mut() {
+        pgn.flush().expect("failed to write PGN file");
     }
 
+    println!("{} positions written to {}", count, output_path);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_selfplay(args: &mut Args) -> Result<ExitCode, String> {
+    let mut games = DATAGEN_DEFAULT_GAMES;
+    let mut pgn_path = None;
+    let mut config = DatagenConfig { seed: time_seed(), ..Default::default() };
+    let mut rules = config.adjudication.expect("adjudication should be enabled by default");
+
+    while let Some(option) = args.option() {
+        match option {
+            "games" => games = args.value::<usize>("games")?,
+            "pgn" => pgn_path = Some(args.string("pgn")?),
+            _ if parse_game_option(option, args, &mut config, &mut rules)? => {},
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    config.adjudication = config.adjudication.map(|_| rules);
+
+    let mut generator = DataGenerator::new(config);
+    let mut pgn = pgn_path.map(|path| std::io::BufWriter::new(std::fs::File::create(path).expect("failed to create PGN file")));
+    let (mut white_wins, mut black_wins, mut draws, mut unfinished) = (0, 0, 0, 0);
+
+    for game in 0..games {
+        generator.play_game();
+
+        let mut game_pgn = generator.last_game().clone();
+        match game_pgn.result {
+            GameResult::WhiteWin => white_wins += 1,
+            GameResult::BlackWin => black_wins += 1,
+            GameResult::Draw => draws += 1,
+            GameResult::Unknown => unfinished += 1
+        }
+
+        println!("game {} {}", game + 1, game_pgn.result.to_pgn());
+
+        // Games which ended during the random opening were never played out.
+        if let Some(pgn) = pgn.as_mut() && game_pgn.result != GameResult::Unknown {
+            game_pgn.headers.insert(1, ("Round".to_string(), (game + 1).to_string()));
+            writeln!(pgn, "{}", game_pgn.to_pgn()).expect("failed to write PGN game");
+        }
+    }
+
+    if let Some(pgn) = pgn.as_mut() {
+        pgn.flush().expect("failed to write PGN file");
+    }
+
+    println!(
+        "{} games, {} white wins, {} black wins, {} draws, {} unfinished",
+        games, white_wins, black_wins, draws, unfinished
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_genmagics(args: &mut Args) -> Result<ExitCode, String> {
+    let (mut seed, mut output_dir) = (time_seed(), None);
+
+    while let Some(option) = args.option() {
+        match option {
+            "seed" => seed = args.value::<u64>("seed")?,
+            "out" => output_dir = Some(args.string("out")?),
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    for slider in [Slider::Rook, Slider::Bishop] {
+        let (entries, attacks) = magics::generate_magics(slider, seed);
+        println!("{}", magics::format_magics(slider, &entries));
+
+        if let Some(output_dir) = output_dir {
+            let path = std::path::Path::new(output_dir).join(format!("{}.bin", slider.name()));
+            magics::write_attacks(&path.to_string_lossy(), &attacks).expect("failed to write attack table");
+            eprintln!("{} attacks written to {}", attacks.len(), path.display());
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_tune(args: &mut Args) -> Result<ExitCode, String> {
+    let dataset_path = args.required("dataset")?;
+
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut config = TunerConfig {
+        threads, epochs: TUNER_DEFAULT_EPOCHS, optimizer: OptimizerKind::Adam, learning_rate: TUNER_DEFAULT_LEARNING_RATE, schedule: Schedule::Constant,
+        report_interval: TUNER_DEFAULT_REPORT_INTERVAL, checkpoint_interval: TUNER_DEFAULT_CHECKPOINT_INTERVAL, checkpoint_path: None
+    };
+    let (mut schedule, mut step_size, mut gamma) = ("constant", 100, 0.5);
+    let (mut k, mut validation_interval, mut resume_path) = (None, 0, None);
+    let mut params = EvalParams::default();
+
+    while let Some(option) = args.option() {
+        match option {
+            "threads" => config.threads = args.value::<usize>("threads")?.max(1),
+            "epochs" => config.epochs = args.value::<usize>("epochs")?,
+            "optimizer" => config.optimizer = match args.string("optimizer")? {
+                "adam" => OptimizerKind::Adam,
+                "gd" => OptimizerKind::GradientDescent,
+                optimizer => return Err(format!("unknown optimizer {}", optimizer))
+            },
+            "lr" => config.learning_rate = args.value::<f64>("lr")?,
+            "schedule" => schedule = args.string("schedule")?,
+            "stepsize" => step_size = args.value::<usize>("stepsize")?,
+            "gamma" => gamma = args.value::<f64>("gamma")?,
+            "k" => k = match args.string("k")? {
+                "auto" => None,
+                k => Some(k.parse::<f64>().map_err(|_| format!("failed to parse k argument {}", k))?)
+            },
+            "validation" => validation_interval = args.value::<usize>("validation")?,
+            "report" => config.report_interval = args.value::<usize>("report")?,
+            "checkpoint" => config.checkpoint_path = Some(args.string("checkpoint")?.to_string()),
+            "checkpointevery" => config.checkpoint_interval = args.value::<usize>("checkpointevery")?,
+            "resume" => resume_path = Some(args.string("resume")?),
+            "params" => params = EvalParams::load(args.string("params")?).expect("failed to load evaluation parameters"),
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    config.schedule = match schedule {
+        "constant" => Schedule::Constant,
+        "step" => Schedule::Step { every: step_size, gamma },
+        "cosine" => Schedule::Cosine,
+        schedule => return Err(format!("unknown schedule {}", schedule))
+    };
+
+    let initial_parameters = tuner::initial_parameters(&params);
+    let (positions, validation) = tuner::split_validation(tuner::load_dataset(dataset_path, &initial_parameters), validation_interval);
+    println!("{} training positions, {} validation positions", positions.len(), validation.len());
+
+    // A resumed run keeps the K it was started with, so its loss stays comparable.
+    let state = match resume_path {
+        Some(path) => {
+            let state = TunerState::load(path).expect("failed to read checkpoint");
+            println!("resuming from epoch {} with k {:.4}", state.epoch, state.k);
+            state
+        },
+        None => {
+            let k = k.unwrap_or_else(|| tuner::optimize_k(&positions, &initial_parameters, config.threads));
+            println!("k {:.4} (normalize pawn value {})", k, tuner::normalize_pawn_value(k));
+            TunerState::new(initial_parameters, k)
+        }
+    };
+
+    println!("initial loss {:.6}", tuner::loss(&positions, &state.parameters, state.k, config.threads));
+    let state = tuner::tune(&positions, &validation, state, &config);
+    println!("final loss {:.6}\n", tuner::loss(&positions, &state.parameters, state.k, config.threads));
+    println!("{}", tuner::format_parameters(&state.parameters));
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_makebook(args: &mut Args) -> Result<ExitCode, String> {
+    let pgn_path = args.required("pgn")?;
+    let book_path = args.required("book")?;
+
+    let mut filter = BookFilter::default();
+    let mut native = false;
+
+    while let Some(option) = args.option() {
+        match option {
+            "minelo" => filter.min_rating = Some(args.value::<u32>("minelo")?),
+            "maxply" => filter.max_plies = args.value::<usize>("maxply")?,
+            "mingames" => filter.min_games = args.value::<u32>("mingames")?,
+            "format" => native = args.string("format")? == "native",
+            "results" => filter.results = args.string("results")?
+                .split(',')
+                .map(|result| GameResult::from_pgn(result).ok_or(format!("failed to parse result {}", result)))
+                .collect::<Result<_, String>>()?,
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    let pgn = std::fs::File::open(pgn_path).expect("failed to open PGN file");
+    let mut builder = BookBuilder::new(filter);
+    PgnReader::new(std::io::BufReader::new(pgn)).for_each(|game| builder.add_game(&game));
+
+    let entries = builder.entries();
+    if native {
+        LearningBook::from_polyglot(book_path, &entries).save().expect("failed to write book");
+    } else {
+        polyglot::write_book(book_path, &entries).expect("failed to write book");
+    }
+
+    println!("{} of {} games used, {} entries written to {}", builder.games_used, builder.games_read, entries.len(), book_path);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_filterdata(args: &mut Args) -> Result<ExitCode, String> {
+    let input_path = args.required("input")?;
+    let output_path = args.required("output")?;
+
+    let mut filter = DataFilter::default();
+
+    while let Some(option) = args.option() {
+        match option {
+            "bookplies" => filter.book_plies = args.value::<usize>("bookplies")?,
+            "exitplies" => filter.exit_plies = args.value::<usize>("exitplies")?,
+            "maxscore" => filter.score_bound = args.value::<i32>("maxscore")?,
+            "seed" => filter.seed = args.value::<u64>("seed")?,
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    let (positions, stats) = filter.apply(DataFormat::Text.read(input_path));
+    DataFormat::Text.write(output_path, positions.into_iter()).expect("failed to write positions");
+
+    println!(
+        "{} positions read, {} duplicates, {} in the opening, {} in check, {} beyond the score bound, {} written to {}",
+        stats.read, stats.duplicates, stats.opening, stats.in_check, stats.score, stats.kept, output_path
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_convertdata(args: &mut Args) -> Result<ExitCode, String> {
+    let input_path = args.required("input")?;
+    let output_path = args.required("output")?;
+
+    let (mut from, mut to) = (DataFormat::Text, DataFormat::Bullet);
+
+    while let Some(option) = args.option() {
+        match option {
+            "from" => from = DataFormat::from_name(args.string("from")?).ok_or("unknown data format")?,
+            "to" => to = DataFormat::from_name(args.string("to")?).ok_or("unknown data format")?,
+            _ => return Err(args.unknown(option))
+        }
+    }
+
+    let count = to.write(output_path, from.read(input_path)).expect("failed to write positions");
+    println!("{} positions written to {}", count, output_path);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_symmetry(args: &mut Args) -> Result<ExitCode, String> {
+    // Positions come from an EPD file, one per line, or from the bench when no file is given.
+    let contents = args.option().map(|path| std::fs::read_to_string(path).expect("failed to read positions"));
+    let positions: Vec<&str> = match contents.as_ref() {
+        Some(contents) => contents.lines().map(|line| line.split(';').next().unwrap_or_default().trim()).filter(|fen| !fen.is_empty()).collect(),
+        None => POSITIONS.to_vec()
+    };
+
+    let asymmetric = symmetry(positions.iter().copied(), &EvalParams::default());
+    for (fen, eval, mirrored) in asymmetric.iter() {
+        println!("{} eval {} mirrored {}", fen, eval, mirrored);
+    }
+
+    println!("{} positions, {} asymmetric", positions.len(), asymmetric.len());
+    Ok(if asymmetric.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+fn run_evalparams(args: &mut Args) -> Result<ExitCode, String> {
+    match args.option() {
+        // With a path, the engine starts with the evaluation parameters from that file.
+        Some(path) => uci_loop(Some(path)),
+        // Without a path, the default evaluation parameters are printed, as a template for a parameter file.
+        None => println!("{}", serde_json::to_string_pretty(&EvalParams::default().to_json()).expect("failed to format evaluation parameters"))
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Speaks UCI on stdin until it is closed, optionally with the evaluation parameters of a file.
+fn uci_loop(eval_params_path: Option<&str>) {
     let stop_signal = Arc::new(AtomicBool::new(false));
     let stop_signal_clone = stop_signal.clone();
+    let state = Arc::new(uci::SearchState::default());
+    let state_clone = state.clone();
 
     let (sender, receiver) = channel();
-    let _ = std::thread::spawn(move || uci::handle_board(receiver, stop_signal));
+    let _ = std::thread::spawn(move || uci::handle_board(receiver, stop_signal, state));
+
+    if let Some(path) = eval_params_path {
+        sender.send(uci::UCICommands::SetEvalParams(path.to_string())).expect("failed to send eval params cmd");
+    }
 
     // let cmds = commands.split("\n");
     // for cmd in cmds {
@@ -229,7 +742,39 @@ fn main() {
 
     let mut buffer = String::new();
     while std::io::stdin().read_line(&mut buffer).unwrap() > 0 {
-        uci::handle_command(buffer.trim(), sender.clone(), stop_signal_clone.clone());
+        uci::handle_command(buffer.trim(), sender.clone(), stop_signal_clone.clone(), state_clone.clone());
         buffer.clear();
     }
+}
+
+fn main() -> ExitCode {
+    std::env::set_var("RUST_BACKTRACE", "1");
+
+    // Without arguments the engine speaks UCI, otherwise the first argument names a subcommand.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        uci_loop(None);
+        return ExitCode::SUCCESS;
+    }
+
+    cli::run(SUBCOMMANDS, &args)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{engine::eval_params::EvalParams, utils::consts::BENCH_NODES};
+
+    use super::{bench, symmetry, POSITIONS};
+
+    #[test]
+    fn test_bench_signature() {
+        // Any change to the search behaviour has to come with an updated signature.
+        assert_eq!(bench().0, BENCH_NODES);
+    }
+
+    #[test]
+    fn test_eval_symmetry() {
+        // Every evaluation term has to score both colors the same way.
+        assert_eq!(symmetry(POSITIONS.iter().copied(), &EvalParams::default()), vec![]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,690 @@
+//! Texel tuning of the evaluation parameters, run with `sacre_dieu tune <dataset> [options]`.
+//!
+//! The evaluation is linear in its parameters once the game phase, endgame scaling and
+//! fifty move scaling of a position are fixed, so every position is reduced to a sparse
+//! list of feature counts up front, and the parameters are fitted to the game results
+//! with gradient descent.
+
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::{data::DataFormat, engine::{endgame, eval::{self, Castling, KingFiles, KingProximity, MinorPieces}, eval_params::EvalParams}, utils::{board::{Bitboard, Board}, consts::{FIFTY_MOVE_SCALE_BASE, KING_MASKS, MAX_PHASE, SCALE_NORMAL, TUNER_ADAM_BETA1, TUNER_ADAM_BETA2, TUNER_ADAM_EPSILON, TUNER_K_ITERATIONS, TUNER_K_MAX, TUNER_K_MIN}, piece::{PieceColor, PieceType}}};
+
+/// The index of the first material parameter (pawn to queen).
+const MATERIAL_OFFSET: usize = 0;
+/// The index of the first piece square parameter, indexed by piece type and then tile (A8 = 0).
+const PSQT_OFFSET: usize = MATERIAL_OFFSET + PieceType::COUNT - 1;
+/// The index of the first mobility parameter, indexed by piece type.
+const MOBILITY_OFFSET: usize = PSQT_OFFSET + PieceType::COUNT * 64;
+/// The index of the first king zone attack parameter, indexed by piece type.
+const KING_ZONE_OFFSET: usize = MOBILITY_OFFSET + PieceType::COUNT;
+/// The index of the semi-open king file parameter, followed by the open king file parameter.
+const KING_FILE_OFFSET: usize = KING_ZONE_OFFSET + PieceType::COUNT;
+/// The index of the first pawn storm parameter, indexed by relative rank.
+const PAWN_STORM_OFFSET: usize = KING_FILE_OFFSET + 2;
+/// The index of the first blocked pawn storm parameter, indexed by relative rank.
+const BLOCKED_PAWN_STORM_OFFSET: usize = PAWN_STORM_OFFSET + 8;
+/// The index of the knight outpost parameter, followed by the bishop outpost and bad bishop parameters.
+const MINOR_PIECE_OFFSET: usize = BLOCKED_PAWN_STORM_OFFSET + 8;
+/// The index of the first king tropism parameter, indexed by piece type.
+const TROPISM_OFFSET: usize = MINOR_PIECE_OFFSET + 3;
+/// The index of the king centralization parameter, followed by the passed pawn distance parameters.
+const KING_ACTIVITY_OFFSET: usize = TROPISM_OFFSET + PieceType::COUNT;
+/// The index of the space parameter.
+const SPACE_OFFSET: usize = KING_ACTIVITY_OFFSET + 3;
+/// The index of the castling right parameter, followed by the castled and stranded king parameters.
+const CASTLING_OFFSET: usize = SPACE_OFFSET + 1;
+/// The total number of (middlegame, endgame) parameter pairs.
+const PARAMETER_COUNT: usize = CASTLING_OFFSET + 3;
+
+/// A (middlegame, endgame) pair of values.
+type Pair = [f64; 2];
+
+/// How the two halves of a parameter pair relate in the evaluation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Term {
+    /// The middlegame and endgame values are independent.
+    Tapered,
+    /// Only the middlegame value is used.
+    MiddlegameOnly,
+    /// Only the endgame value is used.
+    EndgameOnly
+}
+
+/// The term of a parameter, given its index.
+fn term(index: usize) -> Term {
+    if (KING_ZONE_OFFSET..MINOR_PIECE_OFFSET).contains(&index) || (TROPISM_OFFSET..KING_ACTIVITY_OFFSET).contains(&index) || index >= SPACE_OFFSET {
+        Term::MiddlegameOnly
+    } else if (KING_ACTIVITY_OFFSET..SPACE_OFFSET).contains(&index) {
+        Term::EndgameOnly
+    } else {
+        Term::Tapered
+    }
+}
+
+/// A position reduced to the features the evaluation depends on.
+#[derive(Debug, Clone)]
+pub struct TuningPosition {
+    /// The parameter indices and their counts (white minus black).
+    features: Vec<(u16, i8)>,
+    /// The weight of the middlegame values.
+    mg_weight: f32,
+    /// The weight of the endgame values.
+    eg_weight: f32,
+    /// The result of the game from white's perspective (1 for a win, 0.5 for a draw).
+    result: f32
+}
+
+impl TuningPosition {
+    /// Extracts the features of a position, or nothing if the position is scored by a dedicated endgame function.
+    pub fn new(board: &Board, result: f32, parameters: &[Pair]) -> Option<Self> {
+        if endgame::probe(board).is_some() {
+            return None;
+        }
+
+        let mut features: Vec<(u16, i8)> = Vec::new();
+        let sign = |side: PieceColor| if side == PieceColor::White { 1 } else { -1 };
+
+        let attacks = [board.attacks_by(PieceColor::White), board.attacks_by(PieceColor::Black)];
+        for side in [PieceColor::White, PieceColor::Black] {
+            let (own_attacks, enemy_attacks) = (&attacks[side.to_index()], &attacks[(!side).to_index()]);
+            let safe_tiles = !board.color(side) & !enemy_attacks.piece(PieceType::Pawn);
+            let king_zone = Bitboard::new(KING_MASKS[board.king_tile(side).index()]);
+
+            for piece_type in PieceType::iter() {
+                let mut pieces = board.colored_piece(piece_type, side);
+
+                if piece_type != PieceType::King {
+                    features.push(((MATERIAL_OFFSET + piece_type as usize) as u16, sign(side) * pieces.board.count_ones() as i8));
+                }
+
+                while pieces != Bitboard::ZERO {
+                    let tile = pieces.pop_lsb();
+                    let tile_index = if side == PieceColor::White { tile.index() ^ 56 } else { tile.index() };
+                    features.push(((PSQT_OFFSET + piece_type as usize * 64 + tile_index) as u16, sign(side)));
+                }
+
+                let mobility = (own_attacks.piece(piece_type) & safe_tiles).board.count_ones() as i8;
+                features.push(((MOBILITY_OFFSET + piece_type as usize) as u16, sign(side) * mobility));
+
+                // Enemy attacks on the king zone, whose weights are stored as penalties.
+                let king_attacks = (enemy_attacks.piece(piece_type) & king_zone).board.count_ones() as i8;
+                features.push(((KING_ZONE_OFFSET + piece_type as usize) as u16, sign(side) * king_attacks));
+            }
+
+            // Weak king files and pawn storms, whose weights are stored as penalties.
+            let king_files = KingFiles::new(board, side);
+            features.push((KING_FILE_OFFSET as u16, sign(side) * king_files.semi_open as i8));
+            features.push(((KING_FILE_OFFSET + 1) as u16, sign(side) * king_files.open as i8));
+            for rank in 0..8 {
+                features.push(((PAWN_STORM_OFFSET + rank) as u16, sign(side) * king_files.storms[rank] as i8));
+                features.push(((BLOCKED_PAWN_STORM_OFFSET + rank) as u16, sign(side) * king_files.blocked_storms[rank] as i8));
+            }
+
+            // Outposts, and bad bishops whose weights are stored as penalties.
+            let minor_pieces = MinorPieces::new(board, side, own_attacks);
+            features.push((MINOR_PIECE_OFFSET as u16, sign(side) * minor_pieces.knight_outposts as i8));
+            features.push(((MINOR_PIECE_OFFSET + 1) as u16, sign(side) * minor_pieces.bishop_outposts as i8));
+            features.push(((MINOR_PIECE_OFFSET + 2) as u16, sign(side) * minor_pieces.bad_bishop_pawns as i8));
+
+            // King tropism, and king activity whose passed pawn distance weight is stored as a penalty.
+            let king_proximity = KingProximity::new(board, side);
+            for piece_type in PieceType::iter() {
+                features.push(((TROPISM_OFFSET + piece_type as usize) as u16, sign(side) * king_proximity.tropism[piece_type as usize] as i8));
+            }
+            features.push((KING_ACTIVITY_OFFSET as u16, sign(side) * king_proximity.centralization as i8));
+            features.push(((KING_ACTIVITY_OFFSET + 1) as u16, sign(side) * king_proximity.passed_pawn_distance as i8));
+            features.push(((KING_ACTIVITY_OFFSET + 2) as u16, sign(side) * king_proximity.enemy_passed_pawn_distance as i8));
+
+            features.push((SPACE_OFFSET as u16, sign(side) * eval::space(board, side, enemy_attacks) as i8));
+
+            // Castling, whose stranded king weight is stored as a penalty.
+            let castling = Castling::new(board, side);
+            features.push((CASTLING_OFFSET as u16, sign(side) * castling.rights as i8));
+            features.push(((CASTLING_OFFSET + 1) as u16, sign(side) * castling.castled as i8));
+            features.push(((CASTLING_OFFSET + 2) as u16, sign(side) * castling.stranded as i8));
+        }
+
+        // Merge duplicate features, and drop the ones which cancel out.
+        features.sort_unstable_by_key(|(index, _)| *index);
+        features.dedup_by(|(index, count), (kept_index, kept_count)| {
+            let duplicate = index == kept_index;
+            if duplicate {
+                *kept_count += *count;
+            }
+
+            duplicate
+        });
+        features.retain(|(_, count)| *count != 0);
+
+        // The endgame scale depends on which side the endgame evaluation favors,
+        // which is decided with the parameters at the time of extraction.
+        let eg: f64 = features.iter().map(|(index, count)| *count as f64 * value(parameters[*index as usize], *index as usize, 1)).sum();
+        let strong_side = if eg > 0.0 { PieceColor::White } else { PieceColor::Black };
+        let scale = eval::endgame_scale(board, strong_side) as f32 / SCALE_NORMAL as f32;
+
+        let phase = board.phase() as f32 / MAX_PHASE as f32;
+        let fifty = (FIFTY_MOVE_SCALE_BASE - board.half_move_counter as i32).max(0) as f32 / FIFTY_MOVE_SCALE_BASE as f32;
+
+        Some(TuningPosition { features, mg_weight: phase * fifty, eg_weight: (1.0 - phase) * scale * fifty, result })
+    }
+
+    /// Evaluates the position from white's perspective.
+    fn evaluate(&self, parameters: &[Pair]) -> f64 {
+        self.features.iter().map(|(index, count)| {
+            let index = *index as usize;
+            *count as f64 * (value(parameters[index], index, 0) * self.mg_weight as f64 + value(parameters[index], index, 1) * self.eg_weight as f64)
+        }).sum()
+    }
+}
+
+/// The value of one phase (0 for the middlegame, 1 for the endgame) of a parameter pair.
+fn value(pair: Pair, index: usize, phase: usize) -> f64 {
+    match term(index) {
+        Term::MiddlegameOnly if phase == 1 => 0.0,
+        Term::EndgameOnly if phase == 0 => 0.0,
+        _ => pair[phase]
+    }
+}
+
+/// The predicted score (between 0 and 1) of an evaluation, given the scaling constant.
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10.0_f64.powf(-k * eval / 400.0))
+}
+
+/// Reads the tunable parameters from the weights of the evaluation.
+pub fn initial_parameters(params: &EvalParams) -> Vec<Pair> {
+    let mut parameters = vec![[0.0; 2]; PARAMETER_COUNT];
+    let pair = |(mg, eg): (i32, i32)| [mg as f64, eg as f64];
+
+    for (piece_type, material) in params.material_values.iter().take(PieceType::COUNT - 1).enumerate() {
+        parameters[MATERIAL_OFFSET + piece_type] = pair(*material);
+    }
+
+    for piece_type in 0..PieceType::COUNT {
+        for tile in 0..64 {
+            parameters[PSQT_OFFSET + piece_type * 64 + tile] = pair(params.piece_square_table[piece_type][tile]);
+        }
+
+        parameters[MOBILITY_OFFSET + piece_type] = pair(params.mobility_weights[piece_type]);
+
+        // King zone weights are stored as penalties.
+        parameters[KING_ZONE_OFFSET + piece_type] = [-params.king_zone_attack_weights[piece_type] as f64, 0.0];
+    }
+
+    // So are king file and pawn storm weights.
+    parameters[KING_FILE_OFFSET] = [-params.king_semi_open_file_penalty as f64, 0.0];
+    parameters[KING_FILE_OFFSET + 1] = [-params.king_open_file_penalty as f64, 0.0];
+    for rank in 0..8 {
+        parameters[PAWN_STORM_OFFSET + rank] = [-params.pawn_storm_penalty[rank] as f64, 0.0];
+        parameters[BLOCKED_PAWN_STORM_OFFSET + rank] = [-params.blocked_pawn_storm_penalty[rank] as f64, 0.0];
+    }
+
+    parameters[MINOR_PIECE_OFFSET] = pair(params.knight_outpost_bonus);
+    parameters[MINOR_PIECE_OFFSET + 1] = pair(params.bishop_outpost_bonus);
+    parameters[MINOR_PIECE_OFFSET + 2] = pair(params.bad_bishop_penalty).map(|value| -value);
+
+    for piece_type in 0..PieceType::COUNT {
+        parameters[TROPISM_OFFSET + piece_type] = [params.king_tropism_weights[piece_type] as f64, 0.0];
+    }
+
+    parameters[KING_ACTIVITY_OFFSET] = [0.0, params.king_centralization_bonus as f64];
+    parameters[KING_ACTIVITY_OFFSET + 1] = [0.0, -params.king_passed_pawn_distance_penalty as f64];
+    parameters[KING_ACTIVITY_OFFSET + 2] = [0.0, params.enemy_king_passed_pawn_distance_bonus as f64];
+    parameters[SPACE_OFFSET] = [params.space_weight as f64, 0.0];
+
+    parameters[CASTLING_OFFSET] = [params.castling_right_bonus as f64, 0.0];
+    parameters[CASTLING_OFFSET + 1] = [params.castled_bonus as f64, 0.0];
+    parameters[CASTLING_OFFSET + 2] = [-params.stranded_king_penalty as f64, 0.0];
+
+    parameters
+}
+
+/// Loads every usable position of a dataset.
+pub fn load_dataset(path: &str, parameters: &[Pair]) -> Vec<TuningPosition> {
+    DataFormat::Text.read(path)
+        .filter_map(|position| TuningPosition::new(&position.board, position.result, parameters))
+        .collect()
+}
+
+/// The mean squared error of the predictions over a set of positions, computed on every thread.
+pub fn loss(positions: &[TuningPosition], parameters: &[Pair], k: f64, threads: usize) -> f64 {
+    let chunk_size = positions.len().div_ceil(threads.max(1)).max(1);
+
+    let total: f64 = std::thread::scope(|scope| {
+        let handles: Vec<_> = positions.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|position| (sigmoid(position.evaluate(parameters), k) - position.result as f64).powi(2)).sum::<f64>()))
+            .collect();
+
+        handles.into_iter().map(|handle| handle.join().expect("tuner thread panicked")).sum()
+    });
+
+    total / positions.len().max(1) as f64
+}
+
+/// The gradient of the loss with respect to every parameter, computed in chunks on every thread and then summed.
+fn gradient(positions: &[TuningPosition], parameters: &[Pair], k: f64, threads: usize) -> Vec<Pair> {
+    let chunk_size = positions.len().div_ceil(threads.max(1)).max(1);
+    let derivative_scale = k * std::f64::consts::LN_10 / 400.0;
+
+    let partials: Vec<Vec<Pair>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = positions.chunks(chunk_size).map(|chunk| scope.spawn(move || {
+            let mut partial = vec![[0.0; 2]; PARAMETER_COUNT];
+
+            for position in chunk {
+                let prediction = sigmoid(position.evaluate(parameters), k);
+                let error = 2.0 * (prediction - position.result as f64) * prediction * (1.0 - prediction) * derivative_scale;
+
+                for (index, count) in position.features.iter() {
+                    let gradient = &mut partial[*index as usize];
+                    gradient[0] += error * *count as f64 * position.mg_weight as f64;
+                    gradient[1] += error * *count as f64 * position.eg_weight as f64;
+                }
+            }
+
+            partial
+        })).collect();
+
+        handles.into_iter().map(|handle| handle.join().expect("tuner thread panicked")).collect()
+    });
+
+    let mut total = vec![[0.0; 2]; PARAMETER_COUNT];
+    for partial in partials {
+        for (total, partial) in total.iter_mut().zip(partial) {
+            total[0] += partial[0];
+            total[1] += partial[1];
+        }
+    }
+
+    // Unused halves never move.
+    for (index, pair) in total.iter_mut().enumerate() {
+        match term(index) {
+            Term::MiddlegameOnly => pair[1] = 0.0,
+            Term::EndgameOnly => pair[0] = 0.0,
+            Term::Tapered => {}
+        }
+
+        pair[0] /= positions.len().max(1) as f64;
+        pair[1] /= positions.len().max(1) as f64;
+    }
+
+    total
+}
+
+/// The method used to follow the gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptimizerKind {
+    /// Plain gradient descent.
+    GradientDescent,
+    /// Adam, which adapts the step size of each parameter.
+    Adam
+}
+
+/// How the learning rate changes over the epochs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    /// The learning rate never changes.
+    Constant,
+    /// The learning rate is multiplied by `gamma` every `every` epochs.
+    Step { every: usize, gamma: f64 },
+    /// The learning rate follows half a cosine wave down to zero.
+    Cosine
+}
+
+impl Schedule {
+    /// The learning rate of an epoch.
+    pub fn rate(&self, base: f64, epoch: usize, epochs: usize) -> f64 {
+        match *self {
+            Schedule::Constant => base,
+            Schedule::Step { every, gamma } => base * gamma.powi((epoch / every.max(1)) as i32),
+            Schedule::Cosine => base * 0.5 * (1.0 + (std::f64::consts::PI * epoch as f64 / epochs.max(1) as f64).cos())
+        }
+    }
+}
+
+/// The configuration of a tuning run.
+#[derive(Debug, Clone)]
+pub struct TunerConfig {
+    /// The number of threads computing the gradient.
+    pub threads: usize,
+    /// The number of passes over the dataset.
+    pub epochs: usize,
+    /// The method used to follow the gradient.
+    pub optimizer: OptimizerKind,
+    /// The base learning rate, which is scaled by the schedule.
+    pub learning_rate: f64,
+    /// How the learning rate changes over the epochs.
+    pub schedule: Schedule,
+    /// The number of epochs between loss reports, or 0 to never report.
+    pub report_interval: usize,
+    /// The number of epochs between checkpoints, or 0 to never checkpoint.
+    pub checkpoint_interval: usize,
+    /// The file checkpoints are written to.
+    pub checkpoint_path: Option<String>
+}
+
+/// The parameters and optimizer state of a tuning run, which is everything needed to resume it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TunerState {
+    /// The number of epochs already run.
+    pub epoch: usize,
+    /// The sigmoid scaling constant the run was started with.
+    pub k: f64,
+    /// The current parameters.
+    pub parameters: Vec<Pair>,
+    /// The first moment estimates of Adam.
+    momentum: Vec<Pair>,
+    /// The second moment estimates of Adam.
+    velocity: Vec<Pair>
+}
+
+impl TunerState {
+    /// Starts a run from a set of parameters.
+    pub fn new(parameters: Vec<Pair>, k: f64) -> Self {
+        TunerState { epoch: 0, k, parameters, momentum: vec![[0.0; 2]; PARAMETER_COUNT], velocity: vec![[0.0; 2]; PARAMETER_COUNT] }
+    }
+
+    /// Writes the state to a file, as one parameter per line.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut output = format!("epoch {}\nk {}\n", self.epoch, self.k);
+        for index in 0..PARAMETER_COUNT {
+            let ([mg, eg], [mg_momentum, eg_momentum], [mg_velocity, eg_velocity]) = (self.parameters[index], self.momentum[index], self.velocity[index]);
+            output += &format!("{} {} {} {} {} {}\n", mg, eg, mg_momentum, eg_momentum, mg_velocity, eg_velocity);
+        }
+
+        // Write to a temporary file first, so an interrupted write never loses the previous checkpoint.
+        let temporary_path = format!("{}.tmp", path);
+        std::fs::write(&temporary_path, output)?;
+        std::fs::rename(temporary_path, path)
+    }
+
+    /// Reads a state written by `save`.
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+        let contents = std::fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+
+        let mut header = |name: &str| lines.next()
+            .and_then(|line| line.strip_prefix(name))
+            .map(|value| value.trim().to_string())
+            .ok_or_else(|| invalid(&format!("missing {} in checkpoint", name)));
+
+        let epoch = header("epoch")?.parse::<usize>().map_err(|_| invalid("invalid epoch in checkpoint"))?;
+        let k = header("k")?.parse::<f64>().map_err(|_| invalid("invalid k in checkpoint"))?;
+
+        let mut state = TunerState::new(Vec::with_capacity(PARAMETER_COUNT), k);
+        state.epoch = epoch;
+
+        for line in lines {
+            let values = line.split_whitespace().map(str::parse::<f64>).collect::<Result<Vec<_>, _>>().map_err(|_| invalid("invalid parameter in checkpoint"))?;
+            let [mg, eg, mg_momentum, eg_momentum, mg_velocity, eg_velocity] = values[..] else {
+                return Err(invalid("invalid parameter in checkpoint"));
+            };
+
+            if state.parameters.len() == PARAMETER_COUNT {
+                return Err(invalid("too many parameters in checkpoint"));
+            }
+
+            state.parameters.push([mg, eg]);
+            state.momentum[state.parameters.len() - 1] = [mg_momentum, eg_momentum];
+            state.velocity[state.parameters.len() - 1] = [mg_velocity, eg_velocity];
+        }
+
+        if state.parameters.len() != PARAMETER_COUNT {
+            return Err(invalid("too few parameters in checkpoint"));
+        }
+
+        Ok(state)
+    }
+}
+
+/// Finds the sigmoid scaling constant which best fits the current evaluation to the results,
+/// with a ternary search (the loss is unimodal in K).
+pub fn optimize_k(positions: &[TuningPosition], parameters: &[Pair], threads: usize) -> f64 {
+    let (mut low, mut high) = (TUNER_K_MIN, TUNER_K_MAX);
+
+    for _ in 0..TUNER_K_ITERATIONS {
+        let (lower_third, upper_third) = (low + (high - low) / 3.0, high - (high - low) / 3.0);
+
+        if loss(positions, parameters, lower_third, threads) < loss(positions, parameters, upper_third, threads) {
+            high = upper_third;
+        } else {
+            low = lower_third;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+/// The internal score of a one pawn advantage for a fitted sigmoid scaling constant,
+/// which reported scores are normalized by (see `NORMALIZE_PAWN_VALUE`).
+pub fn normalize_pawn_value(k: f64) -> i32 {
+    (100.0 / k).round() as i32
+}
+
+/// Splits off every `interval`th position into a validation set, which the parameters are not fitted to.
+pub fn split_validation(positions: Vec<TuningPosition>, interval: usize) -> (Vec<TuningPosition>, Vec<TuningPosition>) {
+    if interval == 0 {
+        return (positions, Vec::new());
+    }
+
+    let (mut training, mut validation) = (Vec::new(), Vec::new());
+    for (index, position) in positions.into_iter().enumerate() {
+        if index % interval == interval - 1 {
+            validation.push(position);
+        } else {
+            training.push(position);
+        }
+    }
+
+    (training, validation)
+}
+
+/// Continues a run until the configured number of epochs, returning the final state.
+///
+/// The loss over the training (and validation) positions is reported, and the state is
+/// checkpointed, at the configured intervals.
+pub fn tune(positions: &[TuningPosition], validation: &[TuningPosition], mut state: TunerState, config: &TunerConfig) -> TunerState {
+    let start = std::time::Instant::now();
+    let every = |interval: usize, epoch: usize| interval != 0 && (epoch.is_multiple_of(interval) || epoch == config.epochs);
+
+    while state.epoch < config.epochs {
+        let epoch = state.epoch;
+        let gradient = gradient(positions, &state.parameters, state.k, config.threads);
+        let rate = config.schedule.rate(config.learning_rate, epoch, config.epochs);
+
+        for index in 0..PARAMETER_COUNT {
+            for phase in 0..2 {
+                let gradient = gradient[index][phase];
+
+                state.parameters[index][phase] -= match config.optimizer {
+                    OptimizerKind::GradientDescent => rate * gradient,
+                    OptimizerKind::Adam => {
+                        let (momentum, velocity) = (&mut state.momentum[index][phase], &mut state.velocity[index][phase]);
+                        *momentum = TUNER_ADAM_BETA1 * *momentum + (1.0 - TUNER_ADAM_BETA1) * gradient;
+                        *velocity = TUNER_ADAM_BETA2 * *velocity + (1.0 - TUNER_ADAM_BETA2) * gradient * gradient;
+
+                        let momentum = *momentum / (1.0 - TUNER_ADAM_BETA1.powi(epoch as i32 + 1));
+                        let velocity = *velocity / (1.0 - TUNER_ADAM_BETA2.powi(epoch as i32 + 1));
+                        rate * momentum / (velocity.sqrt() + TUNER_ADAM_EPSILON)
+                    }
+                };
+            }
+        }
+
+        state.epoch += 1;
+
+        if every(config.report_interval, state.epoch) {
+            let mut report = format!("epoch {} lr {:.6} loss {:.6}", state.epoch, rate, loss(positions, &state.parameters, state.k, config.threads));
+            if !validation.is_empty() {
+                report += &format!(" validation {:.6}", loss(validation, &state.parameters, state.k, config.threads));
+            }
+
+            println!("{} time {:.1}s", report, start.elapsed().as_secs_f64());
+        }
+
+        if let Some(path) = &config.checkpoint_path && every(config.checkpoint_interval, state.epoch) {
+            state.save(path).expect("failed to write checkpoint");
+        }
+    }
+
+    state
+}
+
+/// Formats the parameters as the constants they were read from.
+pub fn format_parameters(parameters: &[Pair]) -> String {
+    let round = |value: f64| value.round() as i32;
+    let mut output = String::new();
+
+    let material = (0..PieceType::COUNT).map(|piece_type| {
+        let [mg, eg] = if piece_type == PieceType::King as usize { [0.0; 2] } else { parameters[MATERIAL_OFFSET + piece_type] };
+        format!("({}, {})", round(mg), round(eg))
+    }).collect::<Vec<_>>().join(", ");
+    output += &format!("pub const MATERIAL_VALUES: [(i32, i32); 6] = [{}];\n", material);
+
+    let mobility = (0..PieceType::COUNT).map(|piece_type| {
+        let [mg, eg] = parameters[MOBILITY_OFFSET + piece_type];
+        format!("({}, {})", round(mg), round(eg))
+    }).collect::<Vec<_>>().join(", ");
+    output += &format!("\npub const MOBILITY_WEIGHTS: [(i32, i32); 6] = [{}];\n", mobility);
+
+    let king_zone = (0..PieceType::COUNT).map(|piece_type| (-round(parameters[KING_ZONE_OFFSET + piece_type][0])).to_string()).collect::<Vec<_>>().join(", ");
+    output += &format!("\npub const KING_ZONE_ATTACK_WEIGHTS: [i32; 6] = [{}];\n", king_zone);
+
+    output += &format!("\npub const KING_SEMI_OPEN_FILE_PENALTY: i32 = {};\n", -round(parameters[KING_FILE_OFFSET][0]));
+    output += &format!("pub const KING_OPEN_FILE_PENALTY: i32 = {};\n", -round(parameters[KING_FILE_OFFSET + 1][0]));
+
+    let storms = |offset: usize| (0..8).map(|rank| (-round(parameters[offset + rank][0])).to_string()).collect::<Vec<_>>().join(", ");
+    output += &format!("\npub const PAWN_STORM_PENALTY: [i32; 8] = [{}];\n", storms(PAWN_STORM_OFFSET));
+    output += &format!("pub const BLOCKED_PAWN_STORM_PENALTY: [i32; 8] = [{}];\n", storms(BLOCKED_PAWN_STORM_OFFSET));
+
+    let pair = |[mg, eg]: Pair| format!("({}, {})", round(mg), round(eg));
+    output += &format!("\npub const KNIGHT_OUTPOST_BONUS: (i32, i32) = {};\n", pair(parameters[MINOR_PIECE_OFFSET]));
+    output += &format!("pub const BISHOP_OUTPOST_BONUS: (i32, i32) = {};\n", pair(parameters[MINOR_PIECE_OFFSET + 1]));
+    output += &format!("pub const BAD_BISHOP_PENALTY: (i32, i32) = {};\n", pair(parameters[MINOR_PIECE_OFFSET + 2].map(|value| -value)));
+
+    let tropism = (0..PieceType::COUNT).map(|piece_type| round(parameters[TROPISM_OFFSET + piece_type][0]).to_string()).collect::<Vec<_>>().join(", ");
+    output += &format!("\npub const KING_TROPISM_WEIGHTS: [i32; 6] = [{}];\n", tropism);
+
+    output += &format!("\npub const KING_CENTRALIZATION_BONUS: i32 = {};\n", round(parameters[KING_ACTIVITY_OFFSET][1]));
+    output += &format!("pub const KING_PASSED_PAWN_DISTANCE_PENALTY: i32 = {};\n", -round(parameters[KING_ACTIVITY_OFFSET + 1][1]));
+    output += &format!("pub const ENEMY_KING_PASSED_PAWN_DISTANCE_BONUS: i32 = {};\n", round(parameters[KING_ACTIVITY_OFFSET + 2][1]));
+
+    output += &format!("\npub const SPACE_WEIGHT: i32 = {};\n", round(parameters[SPACE_OFFSET][0]));
+
+    output += &format!("\npub const CASTLING_RIGHT_BONUS: i32 = {};\n", round(parameters[CASTLING_OFFSET][0]));
+    output += &format!("pub const CASTLED_BONUS: i32 = {};\n", round(parameters[CASTLING_OFFSET + 1][0]));
+    output += &format!("pub const STRANDED_KING_PENALTY: i32 = {};\n", -round(parameters[CASTLING_OFFSET + 2][0]));
+
+    output += "\npub const PIECE_SQUARE_TABLE: [[(i32, i32); 64]; PieceType::COUNT] = [\n";
+    for piece_type in PieceType::iter() {
+        output += &format!("    // {:?}\n    [\n", piece_type);
+
+        for rank in 0..8 {
+            let row = (0..8).map(|file| {
+                let [mg, eg] = parameters[PSQT_OFFSET + piece_type as usize * 64 + rank * 8 + file];
+                format!("({}, {})", round(mg), round(eg))
+            }).collect::<Vec<_>>().join(", ");
+
+            output += &format!("        {},\n", row);
+        }
+
+        output += "    ],\n";
+    }
+    output += "];\n";
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::text::TrainingPosition, engine::{eval::evaluate_board, eval_params::EvalParams}, utils::{board::Board, piece::PieceColor}};
+
+    use super::{initial_parameters, loss, optimize_k, split_validation, tune, OptimizerKind, Schedule, TunerConfig, TunerState, TuningPosition};
+
+    const DATASET: &[&str] = &[
+        "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14 [1.0]",
+        "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24 | -50 | 0-1",
+        "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44 [0.5]",
+        "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54 [0.5]",
+        "r1bq1rk1/pp2b1pp/n1pp1n2/3P1p2/2P1p3/2N1P2N/PP2BPPP/R1BQ1RK1 b - - 2 10 [0.0]",
+        "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 42 c9 \"1-0\";",
+        "8/p2B4/PkP5/4p1pK/4Pb1p/5P2/8/8 w - - 29 68 [1.0]",
+        "r4qk1/6r1/1p4p1/2ppBbN1/1p5Q/P7/2P3PP/5RK1 w - - 2 25 [1.0]"
+    ];
+
+    /// A configuration which runs silently with Adam.
+    fn config(epochs: usize) -> TunerConfig {
+        TunerConfig { threads: 3, epochs, optimizer: OptimizerKind::Adam, learning_rate: 1.0, schedule: Schedule::Step { every: 10, gamma: 0.5 }, report_interval: 0, checkpoint_interval: 0, checkpoint_path: None }
+    }
+
+    #[test]
+    fn test_tuning_features() {
+        let parameters = initial_parameters(&EvalParams::default());
+
+        // With the parameters read from the constants, features reproduce the evaluation (up to rounding).
+        for line in DATASET {
+            let board = TrainingPosition::parse(line).expect("expected a valid dataset line").board;
+            let position = TuningPosition::new(&board, 0.5, &parameters).expect("expected a tunable position");
+
+            let perspective = if board.side_to_move == PieceColor::White { 1 } else { -1 };
+            let eval = evaluate_board(&board, &EvalParams::default(), None).score() * perspective;
+            assert!((position.evaluate(&parameters) - eval as f64).abs() <= 2.0, "{}: {} vs {}", line, position.evaluate(&parameters), eval);
+        }
+
+        assert!(TuningPosition::new(&Board::new("8/8/8/4k3/8/8/8/KQ6 w - - 0 1"), 1.0, &parameters).is_none());
+    }
+
+    #[test]
+    fn test_tuning_reduces_loss() {
+        let parameters = initial_parameters(&EvalParams::default());
+        let positions: Vec<_> = DATASET.iter()
+            .filter_map(|line| TrainingPosition::parse(line))
+            .filter_map(|position| TuningPosition::new(&position.board, position.result, &parameters))
+            .collect();
+
+        let before = loss(&positions, &parameters, 1.0, 3);
+        assert!((before - loss(&positions, &parameters, 1.0, 1)).abs() < 1e-12);
+
+        for (optimizer, learning_rate) in [(OptimizerKind::Adam, 1.0), (OptimizerKind::GradientDescent, 1e5)] {
+            let tuner_config = TunerConfig { optimizer, learning_rate, ..config(20) };
+            let tuned = tune(&positions, &[], TunerState::new(parameters.clone(), 1.0), &tuner_config);
+
+            assert_eq!(tuned.epoch, 20);
+            assert!(loss(&positions, &tuned.parameters, 1.0, 3) < before, "{:?} did not reduce the loss", optimizer);
+        }
+
+        // The fitted K should never be worse than the default.
+        let k = optimize_k(&positions, &parameters, 3);
+        assert!(loss(&positions, &parameters, k, 3) <= before);
+    }
+
+    #[test]
+    fn test_tuning_checkpoints() {
+        let parameters = initial_parameters(&EvalParams::default());
+        let positions: Vec<_> = DATASET.iter()
+            .filter_map(|line| TrainingPosition::parse(line))
+            .filter_map(|position| TuningPosition::new(&position.board, position.result, &parameters))
+            .collect();
+
+        let (training, validation) = split_validation(positions.clone(), 4);
+        assert_eq!((training.len(), validation.len()), (positions.len() - positions.len() / 4, positions.len() / 4));
+
+        // A run interrupted halfway and resumed from its checkpoint ends exactly where an uninterrupted run does.
+        let path = std::env::temp_dir().join(format!("sacre_dieu_checkpoint_{}", std::process::id())).to_string_lossy().to_string();
+        let uninterrupted = tune(&training, &validation, TunerState::new(parameters.clone(), 1.0), &config(20));
+
+        let checkpointed = TunerConfig { checkpoint_interval: 5, checkpoint_path: Some(path.clone()), ..config(10) };
+        tune(&training, &validation, TunerState::new(parameters, 1.0), &checkpointed);
+
+        let resumed = TunerState::load(&path).expect("expected a valid checkpoint");
+        assert_eq!(resumed.epoch, 10);
+        assert_eq!(tune(&training, &validation, resumed, &config(20)), uninterrupted);
+
+        // A checkpoint with more parameters than the evaluation has is rejected rather than read past the end.
+        let extra = std::fs::read_to_string(&path).expect("failed to read checkpoint") + "0 0 0 0 0 0\n";
+        std::fs::write(&path, extra).expect("failed to write checkpoint");
+        assert_eq!(TunerState::load(&path).err().map(|error| error.kind()), Some(std::io::ErrorKind::InvalidData));
+
+        std::fs::remove_file(path).expect("failed to remove checkpoint");
+    }
+}
@@ -1,7 +1,8 @@
-use std::{sync::{atomic::{AtomicBool, Ordering}, mpsc::{Receiver, Sender}, Arc}, time::{Duration, Instant}};
-use arrayvec::ArrayVec;
+use std::{sync::{atomic::{AtomicBool, AtomicU8, Ordering}, mpsc::{Receiver, Sender}, Arc}, time::Instant};
 
-use crate::{engine::search::{SearchEntry, Searcher}, utils::{board::Board, consts::{BEST_EVAL, DEEPEST_PROVEN_LOSS, DEEPEST_PROVEN_WIN, MAX_DEPTH, SHALLOWEST_PROVEN_LOSS, SHALLOWEST_PROVEN_WIN, WORST_EVAL}, piece::PieceColor, piece_move::{Move, MoveFlags, MoveSorter}}};
+use serde_json::json;
+
+use crate::{engine::{book::LearningBook, eval_params::EvalParams, pn_search::{ProofNumberSearch, SolveResult}, position_store::PositionStore, root_split::RootSplit, search::{SearchLimits, Searcher}, stats::SearchStats, time_manager::TimeManager}, utils::{board::{Board, Variant}, consts::{BENCH_NODES, BEST_EVAL, BOOK_DEFAULT_PRUNE_THRESHOLD, BOOK_LEARN_MIN_DEPTH, DEFAULT_HASH_MB, ELO_MAX, ELO_MIN, HP_MARGIN, LMP_BASE, LMP_SCALE, MAX_DEPTH, MAX_HASH_MB, MIN_HASH_MB, MOVE_OVERHEAD, NPS_MAX_LIMIT, PN_SEARCH_DEFAULT_NODES, QS_CHECK_PLIES, RFP_IMPROVING_MARGIN, RFP_MARGIN, RFP_QUADRATIC_MARGIN, ROOT_SPLIT_DEFAULT_DEPTH, STARTING_FEN, VARIETY_MAX_MOVES, WORST_EVAL}, piece::PieceColor, piece_move::Move, render::{render, RenderStyle}, transposition_table::{EvaluationType, TranspositionTable}}};
 
 #[derive(Debug)]
 pub enum UCICommands {
@@ -9,31 +10,296 @@ pub enum UCICommands {
     ForceMove(String),
     NewGame,
     ResizeTT(usize),
-    StartSearch(i64, i64, u64, u64, u64, u64, isize),
-    PrintBoard
+    SetMoveOverhead(u64),
+    SetDeterministic(bool),
+    SetDrawJitter(bool),
+    SetVariety(usize),
+    SetVarietySeed(u64),
+    SetNpsLimit(usize),
+    SetLimitStrength(bool),
+    SetElo(usize),
+    SetJsonOutput(bool),
+    SetSearchSummary(bool),
+    SetShowRefutations(bool),
+    SetVariant(Variant),
+    SetLmpBase(usize),
+    SetLmpScale(usize),
+    SetHistoryPruningMargin(i32),
+    SetRfpMargin(i32),
+    SetRfpQuadraticMargin(i32),
+    SetRfpImprovingMargin(i32),
+    SetQsearchCheckPlies(usize),
+    SetSearchFeature(String, bool),
+    SetBookFile(String),
+    SetEvalParams(String),
+    SetPositionStore(String),
+    SetBookLearning(bool),
+    SetBookPruneThreshold(i32),
+    PruneBook,
+    StartSearch(SearchLimits),
+    PrintBoard(RenderStyle),
+    ProbeTT,
+    Solve(usize, Option<usize>),
+    Analyse(usize, usize)
+}
+
+/// What the board thread is doing with searches, as far as the GUI is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineState {
+    /// No search is running or queued.
+    Idle,
+    /// A search is running or queued, and prints its `bestmove` as soon as it is done.
+    Searching,
+    /// A search on the opponent's time is running or queued, and holds its `bestmove` back until `ponderhit` or `stop`.
+    Pondering,
+    /// The search was told to stop, and is about to print its `bestmove`.
+    Stopping
+}
+
+impl EngineState {
+    const ALL: [EngineState; 4] = [EngineState::Idle, EngineState::Searching, EngineState::Pondering, EngineState::Stopping];
+}
+
+/// The state of the search, shared by the thread reading commands and the board thread.
+/// 
+/// The state only changes through the transitions below, which are atomic, so a command can't act on a state it
+/// doesn't expect (such as `ponderhit` resuming a search which was already stopped). Only the thread reading
+/// commands starts searches, and only the board thread finishes them.
+#[derive(Debug, Default)]
+pub struct SearchState(AtomicU8);
+
+impl SearchState {
+    /// The current state.
+    pub fn get(&self) -> EngineState {
+        EngineState::ALL[self.0.load(Ordering::Acquire) as usize]
+    }
+
+    /// Moves from one state to another, returning whether or not the state was the expected one.
+    fn transition(&self, from: EngineState, to: EngineState) -> bool {
+        self.0.compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+
+    /// Idle -> Searching (or Pondering), as a search is sent to the board thread.
+    pub fn start(&self, ponder: bool) {
+        let started = self.transition(EngineState::Idle, if ponder { EngineState::Pondering } else { EngineState::Searching });
+        assert!(started, "expected the previous search to be preempted");
+    }
+
+    /// Searching or Pondering -> Stopping, returning whether or not there was a search to stop.
+    pub fn stop(&self) -> bool {
+        self.transition(EngineState::Searching, EngineState::Stopping) || self.transition(EngineState::Pondering, EngineState::Stopping)
+    }
+
+    /// Pondering -> Searching, once the opponent played the expected move.
+    pub fn ponder_hit(&self) -> bool {
+        self.transition(EngineState::Pondering, EngineState::Searching)
+    }
+
+    /// Any state -> Idle, once the `bestmove` of the search is out.
+    pub fn finish(&self) {
+        self.0.store(EngineState::Idle as u8, Ordering::Release);
+    }
+
+    /// Blocks until the search is no longer pondering, so a finished search on the opponent's
+    /// time doesn't print its `bestmove` before the GUI knows whether the expected move was played.
+    pub fn wait_while_pondering(&self) {
+        while self.get() == EngineState::Pondering {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    /// Blocks until the board thread is idle.
+    pub fn wait_until_idle(&self) {
+        while self.get() != EngineState::Idle {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
 }
 
-pub fn handle_command(command: &str, sender: Sender<UCICommands>, stop_signal: Arc<AtomicBool>) {
+/// Stops the running search, if any, and waits until its `bestmove` is out, so a new position or
+/// search can't end up queued behind it (or have its stop signal swallowed by it).
+fn preempt_search(stop_signal: &AtomicBool, state: &SearchState) {
+    if state.stop() {
+        stop_signal.store(true, Ordering::Relaxed);
+    }
+
+    state.wait_until_idle();
+}
+
+/// Parses a command from the GUI, forwarding it to the board thread.
+/// 
+/// `state` leaves `EngineState::Idle` from the moment a search is sent until its `bestmove` is printed.
+pub fn handle_command(command: &str, sender: Sender<UCICommands>, stop_signal: Arc<AtomicBool>, state: Arc<SearchState>) {
     let mut args = command.split(' ');
     let command = args.next().expect("received empty UCI command");
 
     match command {
-        "uci" => reply("uciok"),
+        "uci" => {
+            reply(&format!("id name Sacre Dieu {}", env!("CARGO_PKG_VERSION")));
+            reply("id author Altanis");
+            reply(&format!("info string {}", build_info()));
+            reply(&format!("option name Hash type spin default {} min {} max {}", DEFAULT_HASH_MB, MIN_HASH_MB, MAX_HASH_MB));
+            reply(&format!("option name Move Overhead type spin default {} min 0 max 5000", MOVE_OVERHEAD));
+            reply("option name Deterministic type check default false");
+            reply("option name DrawJitter type check default true");
+            reply(&format!("option name Variety type spin default 0 min 0 max {}", VARIETY_MAX_MOVES));
+            reply("option name VarietySeed type spin default 0 min 0 max 2147483647");
+            reply(&format!("option name NodesPerSecond type spin default 0 min 0 max {}", NPS_MAX_LIMIT));
+            reply("option name UCI_LimitStrength type check default false");
+            reply(&format!("option name UCI_Elo type spin default {} min {} max {}", ELO_MAX, ELO_MIN, ELO_MAX));
+            reply("option name JSONOutput type check default false");
+            reply("option name SearchSummary type check default false");
+            reply("option name UCI_ShowRefutations type check default false");
+            reply(&format!("option name UCI_Variant type combo default chess{}", Variant::ALL.iter().map(|(_, name)| format!(" var {}", name)).collect::<String>()));
+            reply(&format!("option name LMPBase type spin default {} min 0 max 64", LMP_BASE));
+            reply(&format!("option name LMPScale type spin default {} min 0 max 64", LMP_SCALE));
+            reply(&format!("option name HistoryPruningMargin type spin default {} min 0 max 16384", HP_MARGIN));
+            reply(&format!("option name RFPMargin type spin default {} min 0 max 1000", RFP_MARGIN));
+            reply(&format!("option name RFPQuadraticMargin type spin default {} min 0 max 200", RFP_QUADRATIC_MARGIN));
+            reply(&format!("option name RFPImprovingMargin type spin default {} min 0 max 1000", RFP_IMPROVING_MARGIN));
+            reply(&format!("option name QSearchCheckPlies type spin default {} min 0 max 4", QS_CHECK_PLIES));
+            for feature in ["UseNullMove", "UseLMR", "UseRFP", "UseLMP", "UseAspiration", "UseLazyEval"] {
+                reply(&format!("option name {} type check default true", feature));
+            }
+            reply("option name BookFile type string default <empty>");
+            reply("option name EvalParams type string default <empty>");
+            reply("option name PositionStore type string default <empty>");
+            reply("option name BookLearning type check default false");
+            reply(&format!("option name BookPruneThreshold type spin default {} min 0 max 10000", BOOK_DEFAULT_PRUNE_THRESHOLD));
+            reply("option name PruneBook type button");
+            reply("uciok");
+        },
         "isready" => reply("readyok"),
         "setoption" => {
-            if args.next() == Some("name") && args.next() == Some("Hash") && args.next() == Some("value") {
-                let size = args.next().expect("failed to parse hash size").parse::<usize>().expect("failed to parse hash size");
-                sender.send(UCICommands::ResizeTT(size)).expect("failed to send resize cmd");
-            } else {
-                reply("setoption only supports setting hashsize.");
+            let tokens: Vec<&str> = args.collect();
+            let name_index = tokens.iter().position(|token| *token == "name");
+            let value_index = tokens.iter().position(|token| *token == "value");
+
+            let Some(name_index) = name_index else {
+                reply("setoption expects a name.");
+                return;
+            };
+
+            // Buttons are sent without a value.
+            let name = tokens[name_index + 1..value_index.unwrap_or(tokens.len())].join(" ");
+            let value = value_index.map_or(String::new(), |value_index| tokens[value_index + 1..].join(" "));
+
+            match name.to_lowercase().as_str() {
+                "hash" => {
+                    // Negative sizes are clamped up to the minimum along with any other out of range size.
+                    let size = value.parse::<i64>().expect("failed to parse hash size").max(0) as usize;
+                    sender.send(UCICommands::ResizeTT(size)).expect("failed to send resize cmd");
+                },
+                "move overhead" => {
+                    let overhead = value.parse::<u64>().expect("failed to parse move overhead");
+                    sender.send(UCICommands::SetMoveOverhead(overhead)).expect("failed to send move overhead cmd");
+                },
+                "deterministic" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse deterministic flag");
+                    sender.send(UCICommands::SetDeterministic(enabled)).expect("failed to send deterministic cmd");
+                },
+                "jsonoutput" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse json output flag");
+                    sender.send(UCICommands::SetJsonOutput(enabled)).expect("failed to send json output cmd");
+                },
+                "searchsummary" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse search summary flag");
+                    sender.send(UCICommands::SetSearchSummary(enabled)).expect("failed to send search summary cmd");
+                },
+                "uci_variant" => match Variant::from_uci(&value) {
+                    Some(variant) => sender.send(UCICommands::SetVariant(variant)).expect("failed to send variant cmd"),
+                    None => reply(&format!("info string unsupported variant {}", value))
+                },
+                "uci_showrefutations" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse show refutations flag");
+                    sender.send(UCICommands::SetShowRefutations(enabled)).expect("failed to send show refutations cmd");
+                },
+                "drawjitter" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse draw jitter flag");
+                    sender.send(UCICommands::SetDrawJitter(enabled)).expect("failed to send draw jitter cmd");
+                },
+                "variety" => {
+                    let moves = value.parse::<usize>().expect("failed to parse variety");
+                    sender.send(UCICommands::SetVariety(moves.min(VARIETY_MAX_MOVES))).expect("failed to send variety cmd");
+                },
+                "varietyseed" => {
+                    let seed = value.parse::<u64>().expect("failed to parse variety seed");
+                    sender.send(UCICommands::SetVarietySeed(seed)).expect("failed to send variety seed cmd");
+                },
+                "nodespersecond" => {
+                    let nps = value.parse::<usize>().expect("failed to parse nodes per second");
+                    sender.send(UCICommands::SetNpsLimit(nps.min(NPS_MAX_LIMIT))).expect("failed to send nps limit cmd");
+                },
+                "uci_limitstrength" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse limit strength flag");
+                    sender.send(UCICommands::SetLimitStrength(enabled)).expect("failed to send limit strength cmd");
+                },
+                "uci_elo" => {
+                    let elo = value.parse::<usize>().expect("failed to parse elo");
+                    sender.send(UCICommands::SetElo(elo.clamp(ELO_MIN, ELO_MAX))).expect("failed to send elo cmd");
+                },
+                "lmpbase" => {
+                    let base = value.parse::<usize>().expect("failed to parse lmp base");
+                    sender.send(UCICommands::SetLmpBase(base)).expect("failed to send lmp base cmd");
+                },
+                "lmpscale" => {
+                    let scale = value.parse::<usize>().expect("failed to parse lmp scale");
+                    sender.send(UCICommands::SetLmpScale(scale)).expect("failed to send lmp scale cmd");
+                },
+                "historypruningmargin" => {
+                    let margin = value.parse::<i32>().expect("failed to parse history pruning margin");
+                    sender.send(UCICommands::SetHistoryPruningMargin(margin)).expect("failed to send history pruning margin cmd");
+                },
+                "rfpmargin" => {
+                    let margin = value.parse::<i32>().expect("failed to parse rfp margin");
+                    sender.send(UCICommands::SetRfpMargin(margin)).expect("failed to send rfp margin cmd");
+                },
+                "rfpquadraticmargin" => {
+                    let margin = value.parse::<i32>().expect("failed to parse rfp quadratic margin");
+                    sender.send(UCICommands::SetRfpQuadraticMargin(margin)).expect("failed to send rfp quadratic margin cmd");
+                },
+                "rfpimprovingmargin" => {
+                    let margin = value.parse::<i32>().expect("failed to parse rfp improving margin");
+                    sender.send(UCICommands::SetRfpImprovingMargin(margin)).expect("failed to send rfp improving margin cmd");
+                },
+                "usenullmove" | "uselmr" | "userfp" | "uselmp" | "useaspiration" | "uselazyeval" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse search feature flag");
+                    sender.send(UCICommands::SetSearchFeature(name, enabled)).expect("failed to send search feature cmd");
+                },
+                "qsearchcheckplies" => {
+                    let plies = value.parse::<usize>().expect("failed to parse qsearch check plies");
+                    sender.send(UCICommands::SetQsearchCheckPlies(plies)).expect("failed to send qsearch check plies cmd");
+                },
+                "bookfile" => sender.send(UCICommands::SetBookFile(value)).expect("failed to send book file cmd"),
+                "evalparams" => sender.send(UCICommands::SetEvalParams(value)).expect("failed to send eval params cmd"),
+                "positionstore" => sender.send(UCICommands::SetPositionStore(value)).expect("failed to send position store cmd"),
+                "booklearning" => {
+                    let enabled = value.parse::<bool>().expect("failed to parse book learning flag");
+                    sender.send(UCICommands::SetBookLearning(enabled)).expect("failed to send book learning cmd");
+                },
+                "bookprunethreshold" => {
+                    let threshold = value.parse::<i32>().expect("failed to parse book prune threshold");
+                    sender.send(UCICommands::SetBookPruneThreshold(threshold)).expect("failed to send book prune threshold cmd");
+                },
+                "prunebook" => sender.send(UCICommands::PruneBook).expect("failed to send prune book cmd"),
+                _ => reply(&format!("unsupported option {}.", name))
             }
         },
         "ucinewgame" => {
+            preempt_search(&stop_signal, &state);
             sender.send(UCICommands::NewGame).expect("couldnt send ucinewgame");
-            stop_signal.store(true, Ordering::Relaxed);
         },
-        "stop" => stop_signal.store(true, Ordering::Relaxed),
+        // Stopping without a search (or one which is already stopping) does nothing, so it can't stop the next one.
+        "stop" => {
+            if state.stop() {
+                stop_signal.store(true, Ordering::Relaxed);
+            }
+        },
+        "ponderhit" => {
+            state.ponder_hit();
+        },
         "position" => {
+            preempt_search(&stop_signal, &state);
             let tokens: Vec<&str> = args.collect();
 
             // do later
@@ -44,7 +310,7 @@ pub fn handle_command(command: &str, sender: Sender<UCICommands>, stop_signal: A
                     moves_index = 1;
                 }
 
-                sender.send(UCICommands::SetPosition("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string())).expect("failed to send position cmd (startpos)");
+                sender.send(UCICommands::SetPosition(STARTING_FEN.to_string())).expect("failed to send position cmd (startpos)");
             } else if tokens[0] == "fen" {
                 let mut fen = String::new();
 
@@ -70,34 +336,62 @@ pub fn handle_command(command: &str, sender: Sender<UCICommands>, stop_signal: A
             }
         },
         "go" => {
-            let (
-                mut time,
-                mut depth,
-                mut wtime,
-                mut winc,
-                mut btime,
-                mut binc,
-                mut nodes
-            ) = (-1, -1, 0, 0, 0, 0, -1);
+            let limits = parse_search_limits(args);
+
+            preempt_search(&stop_signal, &state);
+            stop_signal.store(false, Ordering::Relaxed);
+            state.start(limits.ponder);
+
+            sender.send(UCICommands::StartSearch(limits)).expect("failed to send startsearch cmd");
+        },
+        "solve" => {
+            let (mut nodes, mut mate) = (PN_SEARCH_DEFAULT_NODES, None);
+
+            while let Some(token) = args.next() {
+                match token {
+                    "nodes" => nodes = args.next().expect("missing nodes argument").parse::<usize>().expect("failed to parse nodes argument"),
+                    "mate" => mate = Some(args.next().expect("missing mate argument").parse::<usize>().expect("failed to parse mate argument")).filter(|&moves| moves != 0).map(|moves| moves.min(MAX_DEPTH / 2)),
+                    _ => {}
+                }
+            }
+
+            sender.send(UCICommands::Solve(nodes, mate)).expect("failed to send solve cmd");
+        },
+        "analyse" => {
+            let mut depth = ROOT_SPLIT_DEFAULT_DEPTH;
+            let mut threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
 
             while let Some(token) = args.next() {
                 match token {
-                    "infinite" => (time, depth, wtime, winc, btime, binc, nodes) = (-1, -1, 0, 0, 0, 0, -1),
-                    "movetime" => time = args.next().expect("missing time argument").parse::<i64>().expect("failed to parse time argument"),
-                    "depth" => depth = args.next().expect("missing depth argument").parse::<i64>().expect("failed to parse depth argument"),
-                    "wtime" => wtime = args.next().expect("missing wtime arg").parse::<u64>().expect("failed to parse wtime"),
-                    "btime" => btime = args.next().expect("missing btime argument").parse::<u64>().expect("failed to parse btime argument"),
-                    "winc" => winc = args.next().expect("missing winc argument").parse::<u64>().expect("failed to parse winc argument"),
-                    "binc" => binc = args.next().expect("missing binc argument").parse::<u64>().expect("failed to parse binc argument"),
-                    "nodes" => nodes = args.next().expect("missing nodes argument").parse::<isize>().expect("failed to parse nodes argument"),
+                    "depth" => depth = args.next().expect("missing depth argument").parse::<usize>().expect("failed to parse depth argument"),
+                    "threads" => threads = args.next().expect("missing threads argument").parse::<usize>().expect("failed to parse threads argument"),
                     _ => {}
                 }
             }
 
-            // dbg!(time, depth, wtime, winc, btime, binc);
-            sender.send(UCICommands::StartSearch(time, depth, wtime, winc, btime, binc, nodes)).expect("failed to send startsearch cmd");
+            preempt_search(&stop_signal, &state);
+            stop_signal.store(false, Ordering::Relaxed);
+            state.start(false);
+
+            sender.send(UCICommands::Analyse(depth, threads)).expect("failed to send analyse cmd");
         },
-        "d" => sender.send(UCICommands::PrintBoard).expect("failed to send printboard cmd"),
+        "d" => {
+            let mut style = RenderStyle::detect();
+
+            for token in args {
+                match token {
+                    "unicode" => style.unicode = true,
+                    "ascii" => style.unicode = false,
+                    "color" => style.color = true,
+                    "nocolor" => style.color = false,
+                    _ => {}
+                }
+            }
+
+            sender.send(UCICommands::PrintBoard(style)).expect("failed to send printboard cmd");
+        },
+        "probe" => sender.send(UCICommands::ProbeTT).expect("failed to send probe cmd"),
+        "about" | "version" => reply(&format!("info string {}", build_info())),
         "quit" => {
             println!("asked to quit");
             std::process::exit(0);
@@ -106,119 +400,585 @@ pub fn handle_command(command: &str, sender: Sender<UCICommands>, stop_signal: A
     }
 }
 
-pub fn handle_board(receiver: Receiver<UCICommands>, stop_signal: Arc<AtomicBool>) {
+/// Parses the limits of a search from the arguments of a `go` command.
+pub fn parse_search_limits<'a>(mut args: impl Iterator<Item = &'a str>) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let (white, black) = (PieceColor::White.to_index(), PieceColor::Black.to_index());
+
+    while let Some(token) = args.next() {
+        match token {
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            "movetime" => limits.movetime = Some(parse_time(args.next().expect("missing time argument"))),
+            "depth" => limits.depth = Some(args.next().expect("missing depth argument").parse::<usize>().expect("failed to parse depth argument")),
+            "wtime" => limits.time_left[white] = Some(parse_time(args.next().expect("missing wtime argument"))),
+            "btime" => limits.time_left[black] = Some(parse_time(args.next().expect("missing btime argument"))),
+            "winc" => limits.increment[white] = parse_time(args.next().expect("missing winc argument")),
+            "binc" => limits.increment[black] = parse_time(args.next().expect("missing binc argument")),
+            "movestogo" => limits.moves_to_go = Some(args.next().expect("missing movestogo argument").parse::<u64>().expect("failed to parse movestogo argument")).filter(|&moves| moves != 0),
+            "nodes" => limits.nodes = Some(args.next().expect("missing nodes argument").parse::<usize>().expect("failed to parse nodes argument")),
+            // Mates longer than the deepest search could never be found, and would overflow the mate score.
+            "mate" => limits.mate = Some(args.next().expect("missing mate argument").parse::<usize>().expect("failed to parse mate argument")).filter(|&moves| moves != 0).map(|moves| moves.min(MAX_DEPTH / 2)),
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+/// Parses a time (in ms) from a `go` command. Some GUIs send negative times once a
+/// clock has run out, which are treated as an empty clock.
+fn parse_time(time: &str) -> u64 {
+    time.parse::<i64>().expect("failed to parse time argument").max(0) as u64
+}
+
+/// The final `info` line of a search, in the JSON output format.
+fn search_info_json(searcher: &Searcher, eval: i32, ms_time: u128, nps: u64) -> serde_json::Value {
+    let pv = searcher.principal_variation.iter().map(|piece_move| piece_move.to_uci()).collect::<Vec<_>>();
+
+    json!({
+        "type": "info", "depth": searcher.depth, "score": Searcher::json_score(eval), "time": ms_time as u64, "nodes": searcher.nodes,
+        "nps": nps, "pv": pv
+    })
+}
+
+/// Runs the commands forwarded by `handle_command` one at a time, finishing every search it is sent.
+pub fn handle_board(receiver: Receiver<UCICommands>, stop_signal: Arc<AtomicBool>, state: Arc<SearchState>) {
     let mut board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
-    let mut searcher = Searcher::new(Duration::MAX, Duration::MAX, 5, stop_signal.clone());
+    let mut searcher = Searcher::new(TimeManager::default(), 5, stop_signal.clone());
+    let mut last_move = None;
+    // Set when the last position command had a FEN which didn't parse, so its moves aren't played on the previous position.
+    let mut invalid_position = false;
+
+    let mut book: Option<LearningBook> = None;
+    let (mut book_learning, mut book_prune_threshold) = (false, BOOK_DEFAULT_PRUNE_THRESHOLD);
+    let (mut lmp_base, mut lmp_scale) = (LMP_BASE, LMP_SCALE);
+    let (mut json_output, mut search_summary, mut show_refutations) = (false, false, false);
+    let mut variant = Variant::Standard;
+
+    // Opening variety applies to the first `variety` moves of each side, with a seed which changes every game.
+    let (mut variety, mut variety_seed, mut games) = (0, 0, 0u64);
+    let mut game_ply = 0;
+
+    // The node rate is limited by NodesPerSecond (when non-zero) and by UCI_Elo (when strength is limited), whichever is lower.
+    let (mut nps_limit, mut limit_strength, mut elo) = (0, false, ELO_MAX);
 
     while let Ok(message) = receiver.recv() {
         match message {
             UCICommands::NewGame => {
-                searcher.transposition_table.clear();
+                searcher.reset_heuristics();
                 searcher.past_boards.clear();
-                searcher.search_stack = std::array::from_fn(|_| SearchEntry::default());
-                searcher.move_sorter = MoveSorter::new();
+                games = games.wrapping_add(1);
+
+                if let Some(book) = book.as_mut() {
+                    book.new_game();
+                }
             },
-            UCICommands::SetPosition(pos) => board = Board::new(pos.as_str()),
-            UCICommands::ForceMove(moves) => {
-                for uci_move in moves.split(' ') {
-                    let piece_move = Move::from_uci(uci_move);
-                    
-                    if piece_move.flags == MoveFlags::None {
-                        let piece = board.board[piece_move.initial.index()].as_ref().expect("no piece on initial move index");
-                    
-                        let mut moves = ArrayVec::new();
-                        piece.generate_moves(&board, piece_move.initial, false, &mut moves);
-
-                        let real_move = moves
-                            .iter()
-                            .find(|mv| mv.end.index() == piece_move.end.index())
-                            .expect("couldnt find move");
-
-                        board = board.make_move(real_move, false).unwrap();
-                    } else {
-                        board = board.make_move(&piece_move, false).unwrap();
+            // The game history is rebuilt from every position command, so it always matches the game the GUI
+            // is playing, whatever the engine searched (or pondered) on in the meantime.
+            UCICommands::SetPosition(pos) => {
+                invalid_position = false;
+                board = match Board::from_fen(pos.as_str()) {
+                    Ok(new_board) => new_board,
+                    Err(error) => {
+                        reply(&format!("info string invalid fen {}: {}, keeping the previous position", pos.trim(), error));
+                        invalid_position = true;
+                        continue;
                     }
+                };
+
+                // A FEN which counts checks can only be a three-check position, so its field wins over UCI_Variant.
+                if board.variant == Variant::ThreeCheck && variant != Variant::ThreeCheck {
+                    reply(&format!("info string fen counts checks, playing it as {} instead of {}", Variant::ThreeCheck.to_uci(), variant.to_uci()));
+                } else {
+                    board.set_variant(variant);
+                }
+
+                searcher.past_boards = vec![board.zobrist_key];
+                last_move = None;
+                game_ply = 0;
+            },
+            UCICommands::ForceMove(moves) => {
+                if invalid_position {
+                    continue;
                 }
+
+                let played = match board.apply_uci_moves(&moves, &mut searcher.past_boards) {
+                    Ok(played) => played,
+                    Err(error) => {
+                        reply(&format!("info string ignoring illegal move {} and the moves after it", error.illegal_move));
+                        error.played
+                    }
+                };
+
+                last_move = played.last().copied().or(last_move);
+                game_ply += played.len();
             },
             UCICommands::ResizeTT(mb) => {
-                searcher.transposition_table.resize_mb(mb);
-            },
-            UCICommands::StartSearch(time_limit, depth, white_time, winc, black_time, binc, max_nodes) => {
-                stop_signal.store(false, Ordering::Relaxed);
-
-                let engine_time_left = if board.side_to_move == PieceColor::White { white_time } else { black_time };
-                let engine_inc_left = if board.side_to_move == PieceColor::White { winc } else { binc };
-                
-                let mut eval = 0;
-                let mut timer: Instant = Instant::now();
-
-                searcher.soft_tm = Duration::MAX;
-                searcher.hard_tm = Duration::MAX;
-                searcher.timer = Instant::now();
-                searcher.max_depth = MAX_DEPTH;
-                searcher.nodes = 0;
-                searcher.best_move = None;
-
-                if time_limit != -1 {
-                    // Iterative deepening until time limit reached.
-                    searcher.hard_tm = Duration::from_millis(time_limit as u64);
-                    eval = searcher.search_timed(&board);
-                } else if engine_time_left != 0 {
-                    // Iterative deepening using soft and hard time limits.
-                    searcher.soft_tm = Duration::from_millis(engine_time_left / 20 + engine_inc_left / 2);
-                    searcher.hard_tm = Duration::from_millis(engine_time_left / 4);
-
-                    eval = searcher.search_timed(&board);
-                } else if depth != -1 {
-                    // Search up to a specified depth.
-                    searcher.max_depth = depth as usize;
-                    eval = searcher.search_timed(&board);
-                } else if max_nodes != -1 {
-                    // Search up to a specified node count.
-                    searcher.max_nodes = max_nodes;
-                    eval = searcher.search_timed(&board);
+                // The table size affects which entries survive, so it is pinned in deterministic mode.
+                if searcher.deterministic {
+                    reply("info string hash size is fixed in deterministic mode");
                 } else {
-                    // Iterative deepening until `stop` is sent (or depth 127 is reached).
-                    searcher.hard_tm = Duration::MAX;
-                    eval = searcher.search_timed(&board);
+                    let clamped = TranspositionTable::clamp_mb(mb);
+                    if clamped != mb {
+                        reply(&format!("info string hash size {} is out of range, using {}", mb, clamped));
+                    }
+
+                    searcher.transposition_table.resize_mb(clamped);
+                    if searcher.transposition_table.size_mb() < clamped {
+                        reply(&format!("info string not enough memory for {} MB of hash, using {}", clamped, searcher.transposition_table.size_mb()));
+                    }
                 }
+            },
+            UCICommands::SetDeterministic(enabled) => {
+                searcher.deterministic = enabled;
 
-                let (timer, nodes, depth) = (searcher.timer, searcher.nodes, searcher.depth);
+                if enabled {
+                    searcher.transposition_table.resize_mb(DEFAULT_HASH_MB);
+                }
+            },
+            UCICommands::SetDrawJitter(enabled) => searcher.draw_jitter = enabled,
+            UCICommands::SetVariety(moves) => variety = moves,
+            UCICommands::SetVarietySeed(seed) => variety_seed = seed,
+            UCICommands::SetNpsLimit(nps) => nps_limit = nps,
+            UCICommands::SetLimitStrength(enabled) => limit_strength = enabled,
+            UCICommands::SetElo(rating) => elo = rating,
+            UCICommands::SetJsonOutput(enabled) => json_output = enabled,
+            UCICommands::SetSearchSummary(enabled) => search_summary = enabled,
+            UCICommands::SetShowRefutations(enabled) => show_refutations = enabled,
+            // Scores found under other rules mean nothing in the new variant.
+            UCICommands::SetVariant(new_variant) => {
+                variant = new_variant;
+                board.set_variant(variant);
+                searcher.reset_heuristics();
+            },
+            UCICommands::SetLmpBase(base) => {
+                lmp_base = base;
+                searcher.lmp_table = Searcher::lmp_table(lmp_base, lmp_scale);
+            },
+            UCICommands::SetLmpScale(scale) => {
+                lmp_scale = scale;
+                searcher.lmp_table = Searcher::lmp_table(lmp_base, lmp_scale);
+            },
+            UCICommands::SetHistoryPruningMargin(margin) => searcher.history_pruning_margin = margin,
+            UCICommands::SetRfpMargin(margin) => searcher.rfp_margins.0 = margin,
+            UCICommands::SetRfpQuadraticMargin(margin) => searcher.rfp_margins.1 = margin,
+            UCICommands::SetRfpImprovingMargin(margin) => searcher.rfp_margins.2 = margin,
+            UCICommands::SetQsearchCheckPlies(plies) => searcher.qsearch_check_plies = plies,
+            UCICommands::SetSearchFeature(name, enabled) => {
+                searcher.features.set(&name, enabled);
+            },
+            UCICommands::SetMoveOverhead(overhead) => {
+                searcher.time_manager.move_overhead = overhead;
+            },
+            UCICommands::SetBookFile(path) => {
+                book = None;
 
-                let ms_time = timer.elapsed().as_millis();
-                let nps = nodes as f64 / (ms_time as f64 / 1000.0);
+                if !path.is_empty() && path != "<empty>" {
+                    match LearningBook::load(&path) {
+                        Ok(loaded) => book = Some(loaded),
+                        Err(error) => reply(&format!("info string failed to load book {}: {}", path, error))
+                    }
+                }
+            },
+            UCICommands::SetEvalParams(path) => {
+                // An empty path goes back to the built in parameters.
+                if path.is_empty() || path == "<empty>" {
+                    searcher.set_eval_params(EvalParams::default());
+                } else {
+                    match EvalParams::load(&path) {
+                        Ok(params) => searcher.set_eval_params(params),
+                        Err(error) => reply(&format!("info string failed to load evaluation parameters {}: {}", path, error))
+                    }
+                }
+            },
+            UCICommands::SetPositionStore(path) => {
+                searcher.position_store = None;
 
-                if let Some(best_move) = searcher.best_move {
-                    board = board.make_move(&best_move, false).unwrap();
+                if !path.is_empty() && path != "<empty>" {
+                    match PositionStore::load(&path) {
+                        Ok(store) => {
+                            reply(&format!("info string loaded position store with {} positions", store.len()));
+                            searcher.position_store = Some(store);
+                        },
+                        Err(error) => reply(&format!("info string failed to load position store {}: {}", path, error))
+                    }
+                }
+            },
+            UCICommands::SetBookLearning(enabled) => book_learning = enabled,
+            UCICommands::SetBookPruneThreshold(threshold) => book_prune_threshold = threshold,
+            UCICommands::PruneBook => {
+                let Some(book) = book.as_mut() else {
+                    reply("info string no book is loaded");
+                    continue;
+                };
+
+                let removed = book.prune(book_prune_threshold);
+                match book.save() {
+                    Ok(()) => reply(&format!("info string pruned {} book moves", removed)),
+                    Err(error) => reply(&format!("info string failed to save book {}: {}", book.path, error))
+                }
+            },
+            UCICommands::StartSearch(limits) => {
+                // Opening books only know standard chess.
+                if board.variant == Variant::Standard && let Some(book) = book.as_mut() && let Some(book_move) = book.probe(&board, searcher.deterministic) {
+                    if book_learning {
+                        book.record(&board, &book_move);
+                    }
+
+                    reply(&format!("info string book move {}", book_move.to_uci()));
+                    state.wait_while_pondering();
+                    reply(&format!("bestmove {}", book_move.to_uci()));
+                    state.finish();
+                    continue;
+                }
+
+                searcher.variety_seed = (game_ply < 2 * variety).then_some(variety_seed.wrapping_add(games));
+                searcher.nps_limit = [(nps_limit > 0).then_some(nps_limit), limit_strength.then(|| Searcher::elo_nps(elo))].into_iter().flatten().min();
+
+                // Refutations are only tracked while analysing, since games have no use for them.
+                searcher.show_refutations = show_refutations && limits.infinite;
+
+                // Infinite searches are analysis, which writes to the position store instead of being biased by it.
+                let analysis_store = if limits.infinite { searcher.position_store.take() } else { None };
+                let eval = searcher.start(&board, &limits);
+
+                if let Some(mut store) = analysis_store {
+                    // A stopped search only finished the iteration before the one it was stopped in.
+                    let depth = if searcher.stopped { searcher.depth - 1 } else { searcher.depth };
+
+                    if depth > 0 && store.store(&board, eval, depth) && let Err(error) = store.save() {
+                        reply(&format!("info string failed to save position store {}: {}", store.path, error));
+                    }
+
+                    searcher.position_store = Some(store);
+                }
+
+                // The first deep enough search after leaving the book judges the book moves which led there.
+                if book_learning && let Some(book) = book.as_mut() && searcher.depth >= BOOK_LEARN_MIN_DEPTH {
+                    let adjusted = book.learn(eval);
 
-                    if board.half_move_counter == 0 {
-                        searcher.past_boards.clear();
+                    if adjusted > 0 && let Err(error) = book.save() {
+                        reply(&format!("info string failed to save book {}: {}", book.path, error));
                     }
-                    
-                    searcher.past_boards.push(board.zobrist_key);
-
-                    if (SHALLOWEST_PROVEN_LOSS..=DEEPEST_PROVEN_LOSS).contains(&eval) {
-                        let mate_in = (SHALLOWEST_PROVEN_LOSS - eval) / 2;
-                        reply(&format!("info depth {} score mate {} time {} nodes {} nps {}", depth, mate_in, ms_time, nodes, nps));
-                    } else if (DEEPEST_PROVEN_WIN..=SHALLOWEST_PROVEN_WIN).contains(&eval) {
-                        let mate_in = (SHALLOWEST_PROVEN_WIN - eval) / 2;
-                        reply(&format!("info depth {} score mate {} time {} nodes {} nps {}", depth, mate_in, ms_time, nodes, nps));
+                }
+
+                let (nodes, depth) = (searcher.nodes, searcher.depth);
+
+                let ms_time = searcher.elapsed().as_millis();
+                let nps = (nodes as u128 * 1000 / ms_time.max(1)) as u64;
+                let pv = searcher.principal_variation.iter().map(|piece_move| piece_move.to_uci()).collect::<Vec<_>>().join(" ");
+                let root_nodes: Vec<_> = searcher.root_moves.iter().map(|root_move| (root_move.piece_move, root_move.nodes)).collect();
+                let refutations: Vec<_> = searcher.root_moves.iter()
+                    .filter(|root_move| searcher.show_refutations && Some(root_move.piece_move) != searcher.best_move && !root_move.refutation.is_empty())
+                    .collect();
+
+                if let Some(best_move) = searcher.best_move {
+                    if json_output {
+                        reply(&search_info_json(&searcher, eval, ms_time, nps).to_string());
+
+                        if SearchStats::ENABLED {
+                            reply(&json!({ "type": "stats", "stats": searcher.stats.to_json(nodes) }).to_string());
+                        }
+
+                        if search_summary {
+                            reply(&json!({ "type": "summary", "summary": searcher.summary.to_json(nodes, &root_nodes) }).to_string());
+                        }
+
+                        for root_move in refutations.iter() {
+                            let line = root_move.refutation.iter().map(|piece_move| piece_move.to_uci()).collect::<Vec<_>>();
+                            reply(&json!({
+                                "type": "refutation", "move": root_move.piece_move.to_uci(), "line": line,
+                                "nodes": root_move.nodes, "time": root_move.time.as_millis() as u64
+                            }).to_string());
+                        }
                     } else {
-                        reply(&format!("info depth {} score cp {} time {} nodes {} nps {}", depth, eval, ms_time, nodes, nps));
+                        reply(&format!("info depth {} score {} time {} nodes {} nps {} pv {}", depth, Searcher::uci_score(eval), ms_time, nodes, nps, pv));
+
+                        if SearchStats::ENABLED {
+                            for line in searcher.stats.summary(nodes) {
+                                reply(&format!("info string stats {}", line));
+                            }
+                        }
+
+                        if search_summary {
+                            for line in searcher.summary.summary(nodes, &root_nodes) {
+                                reply(&format!("info string summary {}", line));
+                            }
+                        }
+
+                        for root_move in refutations.iter() {
+                            let line = root_move.refutation.iter().map(|piece_move| piece_move.to_uci()).collect::<Vec<_>>().join(" ");
+                            reply(&format!("info refutation {}", line));
+                        }
+                    }
+
+                    // The expected reply is checked against the position after the best move, which is only played
+                    // once the GUI sends it back with the next position command.
+                    let child_board = board.make_move(&best_move, false).expect("expected a legal best move");
+                    state.wait_while_pondering();
+                    match searcher.predicted_reply().filter(|ponder_move| child_board.is_legal(*ponder_move)) {
+                        Some(ponder_move) => reply(&format!("bestmove {} ponder {}", best_move.to_uci(), ponder_move.to_uci())),
+                        None => reply(&format!("bestmove {}", best_move.to_uci()))
                     }
 
-                    reply(&format!("bestmove {}", best_move.to_uci()));
+                    state.finish();
                 } else {
-                    panic!("null move");
+                    // Checkmated, stalemated or already decided positions have no move to play.
+                    state.wait_while_pondering();
+                    reply("bestmove 0000");
+                    state.finish();
+                }
+            },
+            UCICommands::PrintBoard(style) => {
+                print!("{}", render(&board, style, last_move));
+                reply(&format!("Fen: {}", board.to_fen()));
+            },
+            UCICommands::ProbeTT => {
+                for (ply, (key, entry)) in searcher.tt_line(&board).into_iter().enumerate() {
+                    let Some(entry) = entry else {
+                        reply(&format!("info string probe ply {} key {:016x} no entry", ply, key));
+                        break;
+                    };
+
+                    let bound = match entry.evaluation_type {
+                        EvaluationType::Exact => "exact",
+                        EvaluationType::LowerBound => "lower",
+                        EvaluationType::UpperBound => "upper"
+                    };
+                    let best_move = entry.best_move.unpack().map_or("none".to_string(), |piece_move| piece_move.to_uci());
+
+                    reply(&format!(
+                        "info string probe ply {} key {:016x} depth {} bound {} score {} move {} generation {} age {}",
+                        ply, key, entry.depth, bound, entry.evaluation, best_move, entry.generation, searcher.transposition_table.age(&entry)
+                    ));
                 }
             },
-            UCICommands::PrintBoard => {
-                dbg!(&board);
+            UCICommands::Solve(max_nodes, mate) => {
+                let max_plies = mate.map(|moves| moves * 2 - 1);
+                let mut solver = ProofNumberSearch::new(max_nodes, max_plies);
+                let start = Instant::now();
+
+                let result = match solver.solve(&board) {
+                    SolveResult::Proven(line) => format!("proven pv {}", line.iter().map(Move::to_uci).collect::<Vec<_>>().join(" ")),
+                    SolveResult::Disproven => "disproven".to_string(),
+                    SolveResult::Unknown => "unknown".to_string()
+                };
+
+                reply(&format!("info string solve {} nodes {} time {}", result, solver.node_count(), start.elapsed().as_millis()));
+            },
+            UCICommands::Analyse(depth, threads) => {
+                let analysis = RootSplit::new(depth, threads);
+                let start = Instant::now();
+
+                let root_moves = analysis.analyse(&searcher, &board);
+                let nodes: usize = root_moves.iter().map(|root_move| root_move.nodes).sum();
+
+                for (index, root_move) in root_moves.iter().enumerate() {
+                    let pv = root_move.pv.iter().map(|piece_move| piece_move.to_uci()).collect::<Vec<_>>().join(" ");
+                    reply(&format!("info multipv {} depth {} score {} nodes {} pv {}", index + 1, analysis.depth, Searcher::uci_score(root_move.score), root_move.nodes, pv));
+                }
+
+                reply(&format!("info string analyse moves {} threads {} nodes {} time {}", root_moves.len(), analysis.threads, nodes, start.elapsed().as_millis()));
+
+                // Every position after a root move was analysed one ply less deep than the root.
+                if let Some(store) = searcher.position_store.as_mut() && !searcher.stop_signal.load(Ordering::Relaxed) && let Some(best) = root_moves.first() {
+                    store.store(&board, best.score, analysis.depth);
+
+                    for root_move in root_moves.iter() {
+                        let child_board = board.make_move(&root_move.piece_move, false).expect("root move should be legal");
+                        store.store(&child_board, -root_move.score, analysis.depth - 1);
+                    }
+
+                    if let Err(error) = store.save() {
+                        reply(&format!("info string failed to save position store {}: {}", store.path, error));
+                    }
+                }
+                state.finish();
             }
         }
     }
 }
 
+/// Describes exactly which build is running: the version, the compiler, the
+/// target features it was compiled with and the node count of its bench.
+pub fn build_info() -> String {
+    let features = [("bmi2", cfg!(target_feature = "bmi2")), ("avx2", cfg!(target_feature = "avx2")), ("popcnt", cfg!(target_feature = "popcnt"))]
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| *feature)
+        .collect::<Vec<_>>();
+
+    format!(
+        "Sacre Dieu {} built with {} features {} bench {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("SACRE_DIEU_RUSTC_VERSION"),
+        if features.is_empty() { "none".to_string() } else { features.join(",") },
+        BENCH_NODES
+    )
+}
+
 pub fn reply(response: &str) {
     println!("{}", response);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{atomic::{AtomicBool, Ordering}, mpsc::channel, Arc}, time::Duration};
+
+    use crate::{engine::{search::{SearchLimits, Searcher}, time_manager::TimeManager}, utils::{board::Board, consts::{MAX_DEPTH, STARTING_FEN}}};
+
+    use super::{handle_board, handle_command, parse_search_limits, search_info_json, EngineState, SearchState};
+
+    #[test]
+    fn test_parse_search_limits() {
+        let limits = parse_search_limits("wtime 60000 btime -25 winc 1000 binc 1000 movestogo 0 ponder".split_whitespace());
+        assert_eq!((limits.time_left, limits.increment, limits.moves_to_go), ([Some(60_000), Some(0)], [1_000, 1_000], None));
+        assert!(limits.ponder && !limits.infinite);
+
+        let limits = parse_search_limits("infinite depth 12 nodes 5000 mate 3".split_whitespace());
+        assert_eq!((limits.depth, limits.nodes, limits.mate), (Some(12), Some(5_000), Some(3)));
+        assert!(limits.infinite && limits.time_left == [None, None]);
+
+        // A mate in zero is no limit, and a mate in more moves than the search can reach is clamped.
+        assert_eq!(parse_search_limits("mate 0".split_whitespace()).mate, None);
+        assert_eq!(parse_search_limits(format!("mate {}", usize::MAX).split_whitespace()).mate, Some(MAX_DEPTH / 2));
+    }
+
+    #[test]
+    fn test_search_info_json() {
+        let mut searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+        let eval = searcher.start(&Board::new(STARTING_FEN), &SearchLimits { depth: Some(4), ..Default::default() });
+
+        // The emitted line is valid JSON, carrying the same numbers as the text output.
+        let info: serde_json::Value = serde_json::from_str(&search_info_json(&searcher, eval, 250, 1234).to_string()).expect("expected valid json");
+        assert_eq!((&info["type"], &info["depth"], &info["time"], &info["nps"]), (&"info".into(), &4.into(), &250.into(), &1234.into()));
+        assert_eq!(info["nodes"], searcher.nodes);
+        assert_eq!(info["score"]["cp"], Searcher::normalized_cp(eval));
+        assert_eq!(info["pv"][0], searcher.best_move.expect("expected a best move").to_uci());
+
+        // Mates are reported in moves instead.
+        let eval = searcher.start(&Board::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"), &SearchLimits { depth: Some(4), ..Default::default() });
+        let info: serde_json::Value = serde_json::from_str(&search_info_json(&searcher, eval, 250, 1234).to_string()).expect("expected valid json");
+        assert_eq!((&info["score"]["mate"], &info["pv"][0]), (&1.into(), &"a1a8".into()));
+        assert!(info["score"].get("cp").is_none());
+    }
+
+    #[test]
+    fn test_variant_positions() {
+        let (stop_signal, state) = (Arc::new(AtomicBool::new(false)), Arc::new(SearchState::default()));
+        let (sender, receiver) = channel();
+        let board_thread = {
+            let (stop_signal, state) = (stop_signal.clone(), state.clone());
+            std::thread::spawn(move || handle_board(receiver, stop_signal, state))
+        };
+        let command = |command: &str| handle_command(command, sender.clone(), stop_signal.clone(), state.clone());
+
+        // An infinite search only ends by itself when the game is already over, which tells which rules the position is played by.
+        let game_over = |position: &str| {
+            command(position);
+            command("go infinite");
+            std::thread::sleep(Duration::from_millis(300));
+
+            let over = state.get() == EngineState::Idle;
+            command("stop");
+            state.wait_until_idle();
+            over
+        };
+
+        // White's king is on the hill, which only ends the game in king of the hill, and only while the option is set.
+        let on_hill = "position fen 4k3/pppppppp/8/8/3K4/8/PPPPPPPP/8 b - - 0 1";
+        assert!(!game_over(on_hill));
+
+        command("setoption name UCI_Variant value kingofthehill");
+        assert!(game_over(on_hill));
+        assert!(game_over("position startpos moves e2e3 e7e6 e1e2 e8e7 e2d3 e7d6 d3e4"));
+
+        // White has given its third check, which the FEN says even when UCI_Variant doesn't.
+        command("setoption name UCI_Variant value chess");
+        assert!(!game_over(on_hill));
+        assert!(game_over("position fen 4k3/pppppppp/8/8/8/3K4/PPPPPPPP/8 b - - 0+3 0 1"));
+
+        drop(sender);
+        board_thread.join().expect("board thread panicked");
+    }
+
+    #[test]
+    fn test_search_state() {
+        let (stop_signal, state) = (Arc::new(AtomicBool::new(false)), Arc::new(SearchState::default()));
+        let (sender, receiver) = channel();
+        let board_thread = {
+            let (stop_signal, state) = (stop_signal.clone(), state.clone());
+            std::thread::spawn(move || handle_board(receiver, stop_signal, state))
+        };
+        let command = |command: &str| handle_command(command, sender.clone(), stop_signal.clone(), state.clone());
+
+        // A finished search on the opponent's time holds its bestmove back until the expected move is played.
+        command("position startpos moves e2e4");
+        command("go ponder depth 2");
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(state.get(), EngineState::Pondering);
+
+        command("ponderhit");
+        state.wait_until_idle();
+
+        // Stopping a ponder search and starting another one from a new position doesn't get stuck.
+        command("go ponder wtime 600000 btime 600000");
+        command("stop");
+        command("position startpos moves e2e4 e7e5");
+        assert_eq!(state.get(), EngineState::Idle);
+
+        command("go depth 1");
+        state.wait_until_idle();
+
+        // Without a search, stop has nothing to stop, so the next search isn't stopped by it either.
+        command("stop");
+        assert!(!stop_signal.load(Ordering::Relaxed));
+        assert!(!state.ponder_hit() && !state.stop());
+
+        drop(sender);
+        board_thread.join().expect("board thread panicked");
+    }
+
+    #[test]
+    fn test_search_without_moves() {
+        let (stop_signal, state) = (Arc::new(AtomicBool::new(false)), Arc::new(SearchState::default()));
+        let (sender, receiver) = channel();
+        let board_thread = {
+            let (stop_signal, state) = (stop_signal.clone(), state.clone());
+            std::thread::spawn(move || handle_board(receiver, stop_signal, state))
+        };
+        let command = |command: &str| handle_command(command, sender.clone(), stop_signal.clone(), state.clone());
+        let wait_until_idle = || {
+            while state.get() != EngineState::Idle {
+                assert!(!board_thread.is_finished(), "board thread died");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        // A checkmated position has no best move, which must not take the board thread down with it.
+        command("position startpos moves f2f3 e7e5 g2g4 d8h4");
+        command("go depth 3");
+        wait_until_idle();
+
+        // Neither does a king of the hill game which is already over.
+        command("setoption name UCI_Variant value kingofthehill");
+        command("position fen 4k3/8/8/8/3K4/8/8/8 b - - 0 1");
+        command("go depth 3");
+        wait_until_idle();
+
+        // A position without a white king is refused, along with its moves, and the previous position is searched.
+        command("position fen 8/8/8/8/8/8/8/7k w - - 0 1 moves h1g1");
+        command("go depth 3");
+        wait_until_idle();
+
+        // The next search still runs.
+        command("setoption name UCI_Variant value chess");
+        command("position startpos");
+        command("go depth 1");
+        wait_until_idle();
+
+        drop(sender);
+        board_thread.join().expect("board thread panicked");
+    }
+}
@@ -1,8 +1,9 @@
 use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
 
 use arrayvec::ArrayVec;
+use strum::{EnumCount, IntoEnumIterator};
 
-use super::{consts::{get_piece_type, MagicEntry, MAX_LEGAL_MOVES, PIECE_INDICES}, piece::*, piece_move::{Move, MoveArray, MoveFlags}, zobrist::{generate_zobrist_hash, ZOBRIST_CASTLING_KEYS, ZOBRIST_EN_PASSANT_KEYS, ZOBRIST_SIDE_TO_MOVE}};
+use super::{consts::{get_bishop_mask, get_piece_type, get_rook_mask, MagicEntry, A_FILE, BISHOP_MAGICS, CASTLING_RIGHTS_MASK, BLACK_PAWN_MASK, EMPTY_TILE, KING_MASKS, KNIGHT_MASKS, KOTH_HILL, MAX_LEGAL_MOVES, MAX_PHASE, PHASE_WEIGHTS, PIECE_INDICES, ROOK_MAGICS, THREE_CHECK_LIMIT, WHITE_PAWN_MASK}, piece::*, piece_move::{Move, MoveArray, MoveFlags}, render::{render, RenderStyle}, zobrist::{castling_key, checks_key, en_passant_key, generate_zobrist_hash, ZOBRIST_SIDE_TO_MOVE}};
 use colored::Colorize;
 
 /// A type representing an array of bitboards for tracking piece/color state.
@@ -18,10 +19,32 @@ pub struct Bitboard {
 
 impl Bitboard {
     /// Instantiates a bitboard.
-    pub fn new(board: u64) -> Self {
+    pub const fn new(board: u64) -> Self {
         Bitboard { board }
     }
 
+    /// Instantiates a bitboard with a set of tiles.
+    pub const fn from_tiles(tiles: &[Tile]) -> Self {
+        let mut board = 0;
+        let mut index = 0;
+        while index < tiles.len() {
+            board |= tiles[index].bit();
+            index += 1;
+        }
+
+        Bitboard { board }
+    }
+
+    /// Instantiates a bitboard with every tile of a rank.
+    pub const fn rank(rank: u8) -> Self {
+        Bitboard { board: 0xFF << (rank * 8) }
+    }
+
+    /// Instantiates a bitboard with every tile of a file.
+    pub const fn file(file: u8) -> Self {
+        Bitboard { board: A_FILE << file }
+    }
+
     /// Instantiates a constant ZERO bitboard.
     pub const ZERO: Self = Bitboard { board: 0 };
 
@@ -36,15 +59,15 @@ impl Bitboard {
     }
 
     /// Checks if a state is set on the board, given a tile.
-    pub fn get_bit(&self, tile: Tile) -> bool {
-        self.board & (1 << tile.index()) != 0
+    pub const fn get_bit(&self, tile: Tile) -> bool {
+        self.board & tile.bit() != 0
     }
 
     /// Renders the bitboard.
     pub fn render_bitboard(&self, tile: Tile) {
         for row in (0..8).rev() {
             for col in 0..8 {
-                let current_tile = Tile::new(row, col).unwrap();
+                let current_tile = Tile::at(row, col);
                 let is_set = self.get_bit(current_tile);
     
                 print!( "| ");
@@ -72,10 +95,7 @@ impl Bitboard {
         let lsb_index = self.board.trailing_zeros();
         self.board &= self.board - 1;
 
-        let r = lsb_index as u8 / 8;
-        let f = lsb_index as u8 % 8;
-        
-        Tile::new(r, f).unwrap()
+        Tile::from(lsb_index as u8)
     }
 }
 
@@ -137,22 +157,111 @@ impl Not for Bitboard {
     }
 }
 
+/// The tiles attacked by one side, broken down by the type of the attacking piece.
+#[derive(Debug, Clone, Copy)]
+pub struct Attacks {
+    /// The tiles attacked by each piece type, indexed by `PieceType as usize`.
+    pub by_piece: [Bitboard; PieceType::COUNT],
+    /// The tiles attacked by any piece.
+    pub all: Bitboard
+}
+
+impl Attacks {
+    /// Returns the tiles attacked by a particular piece type.
+    pub fn piece(&self, piece_type: PieceType) -> Bitboard {
+        self.by_piece[piece_type as usize]
+    }
+}
+
+/// The number of piece types (every piece but the king) tracked by a material key.
+pub const MATERIAL_PIECE_TYPES: usize = 5;
+
+/// Builds a material key from the number of pawns, knights, bishops, rooks, and queens of each side.
+/// 
+/// Each count occupies four bits, so no two distinct (legal) material configurations share a key.
+pub const fn material_key_from_counts(white: [u32; MATERIAL_PIECE_TYPES], black: [u32; MATERIAL_PIECE_TYPES]) -> u64 {
+    let mut key = 0;
+
+    let mut index = 0;
+    while index < MATERIAL_PIECE_TYPES {
+        key |= (white[index] as u64) << (4 * index);
+        key |= (black[index] as u64) << (4 * (index + MATERIAL_PIECE_TYPES));
+        index += 1;
+    }
+
+    key
+}
+
+/// The rules a game is played by. Every variant plays standard chess, but can also end in other ways, see `Board::variant_winner`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Bringing the king to one of the four center tiles wins the game.
+    KingOfTheHill,
+    /// Giving check for the third time wins the game.
+    ThreeCheck
+}
+
+impl Variant {
+    /// Every variant, along with its name as a `UCI_Variant` value.
+    pub const ALL: [(Variant, &'static str); 3] = [(Variant::Standard, "chess"), (Variant::KingOfTheHill, "kingofthehill"), (Variant::ThreeCheck, "3check")];
+
+    /// Parses the name of a variant, as a `UCI_Variant` value.
+    pub fn from_uci(name: &str) -> Option<Variant> {
+        Self::ALL.iter().find(|(_, uci)| uci.eq_ignore_ascii_case(name)).map(|(variant, _)| *variant)
+    }
+
+    /// The name of the variant, as a `UCI_Variant` value.
+    pub fn to_uci(self) -> &'static str {
+        Self::ALL.iter().find(|(variant, _)| *variant == self).map(|(_, uci)| *uci).expect("expected every variant to be named")
+    }
+}
+
+/// A move list which couldn't be replayed in full, see `Board::apply_uci_moves`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayError {
+    /// The moves played before the illegal one.
+    pub played: Vec<Move>,
+    /// The first move which isn't legal.
+    pub illegal_move: String
+}
+
 /// A structure representing the state of an entire chess board.
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Board {
     /// The bitboard of all pieces and colors.
     pub piece_bitboard: PositionalBitboard,
-    /// A mailbox board of pieces.
-    pub board: [Option<Piece>; 64],
+    /// A mailbox board of packed pieces.
+    pub mailbox: [u8; 64],
+    /// A cache of all occupied tiles, updated whenever pieces move.
+    occupancy: Bitboard,
+    /// The tile of each side's king.
+    king_tiles: [Tile; 2],
+    /// A key identifying the material on the board, see `material_key_from_counts`.
+    material_key: u64,
+    /// The total value of each side's pieces, excluding pawns and kings.
+    non_pawn_material: [i32; 2],
+    /// The sum of `PHASE_WEIGHTS` over every piece on the board.
+    phase: u32,
 
     /// The castle rights for both sides.
     pub castle_rights: [CastleRights; 2],
+    /// Whether or not each side has castled during the game. A FEN can't express this, so
+    /// boards set up from one start without it, and it isn't part of the zobrist key.
+    pub has_castled: [bool; 2],
+    /// The rules the game is played by. Boards set up from a FEN play standard chess.
+    pub variant: Variant,
+    /// The number of checks each side has given, which is only counted in three-check.
+    pub checks_given: [u8; 2],
     /// The side who's turn it is to move.
     pub side_to_move: PieceColor,
     /// The location of an en passant square.
     pub en_passant: Option<Tile>,
     /// The half move counter.
     pub half_move_counter: u8,
+    /// The number of plies since the last null move.
+    pub plies_from_null: u8,
 
     /// A zobrist key representing the state of the board.
     pub zobrist_key: u64
@@ -163,17 +272,110 @@ impl Board {
         Board {
             piece_bitboard: std::array::from_fn(|_| Bitboard::default()),
             castle_rights: std::array::from_fn(|_| CastleRights::default()),
+            has_castled: [false; 2],
+            variant: Variant::Standard,
+            checks_given: [0; 2],
             side_to_move: PieceColor::White,
             en_passant: None,
-            board: std::array::from_fn(|_| None),
+            mailbox: [EMPTY_TILE; 64],
+            occupancy: Bitboard::ZERO,
+            king_tiles: [Tile::E1, Tile::E8],
+            material_key: 0,
+            non_pawn_material: [0; 2],
+            phase: 0,
             half_move_counter: 0,
+            plies_from_null: u8::MAX,
             zobrist_key: 0
         }
     }
 
+    /// Returns the piece on a tile, if any.
+    #[inline]
+    pub fn piece_at(&self, tile: Tile) -> Option<Piece> {
+        Piece::unpack(self.mailbox[tile.index()])
+    }
+
+    /// Whether or not a tile has no piece on it.
+    #[inline]
+    pub fn is_empty(&self, tile: Tile) -> bool {
+        self.mailbox[tile.index()] == EMPTY_TILE
+    }
+
+    /// Places a piece on a tile in the mailbox board, or empties it.
+    #[inline]
+    fn set_piece_at(&mut self, tile: Tile, piece: Option<Piece>) {
+        self.mailbox[tile.index()] = piece.map_or(EMPTY_TILE, |piece| piece.pack());
+    }
+
+    /// Adds a piece to the material key, non-pawn material totals and phase.
+    fn add_material(&mut self, piece: Piece) {
+        if piece.piece_type == PieceType::King {
+            return;
+        }
+
+        self.phase += PHASE_WEIGHTS[piece.piece_type as usize];
+
+        self.material_key += 1 << (4 * (piece.piece_type as usize + piece.piece_color.to_index() * MATERIAL_PIECE_TYPES));
+
+        if piece.piece_type != PieceType::Pawn {
+            self.non_pawn_material[piece.piece_color.to_index()] += piece.piece_type.get_value();
+        }
+    }
+
+    /// Removes a piece from the material key, non-pawn material totals and phase.
+    fn remove_material(&mut self, piece: Piece) {
+        if piece.piece_type == PieceType::King {
+            return;
+        }
+
+        self.phase -= PHASE_WEIGHTS[piece.piece_type as usize];
+
+        self.material_key -= 1 << (4 * (piece.piece_type as usize + piece.piece_color.to_index() * MATERIAL_PIECE_TYPES));
+
+        if piece.piece_type != PieceType::Pawn {
+            self.non_pawn_material[piece.piece_color.to_index()] -= piece.piece_type.get_value();
+        }
+    }
+
     /// Returns all occupied squares on the board.
+    #[inline]
     pub fn occupied(&self) -> Bitboard {
-        self.piece_bitboard[PieceColor::White.to_index()] | self.piece_bitboard[PieceColor::Black.to_index()]
+        self.occupancy
+    }
+
+    /// Recomputes the occupancy cache from the color bitboards.
+    fn update_occupancy(&mut self) {
+        self.occupancy = self.piece_bitboard[PieceColor::White.to_index()] | self.piece_bitboard[PieceColor::Black.to_index()];
+    }
+
+    /// Returns the tile of a side's king.
+    #[inline]
+    pub fn king_tile(&self, color: PieceColor) -> Tile {
+        self.king_tiles[color.to_index()]
+    }
+
+    /// Returns a bitboard of the pieces of the side to move.
+    #[inline]
+    pub fn us(&self) -> Bitboard {
+        self.color(self.side_to_move)
+    }
+
+    /// Returns a bitboard of the pieces of the side not to move.
+    #[inline]
+    pub fn them(&self) -> Bitboard {
+        self.color(!self.side_to_move)
+    }
+
+    /// Returns a bitboard for a particular piece of the side to move.
+    #[inline]
+    pub fn our_piece(&self, piece: PieceType) -> Bitboard {
+        self.colored_piece(piece, self.side_to_move)
+    }
+
+    /// Returns a bitboard for a particular piece of the side not to move.
+    #[inline]
+    pub fn their_piece(&self, piece: PieceType) -> Bitboard {
+        self.colored_piece(piece, !self.side_to_move)
     }
 
     /// Returns a bitboard for a particular color.
@@ -191,47 +393,128 @@ impl Board {
         self.piece_bitboard[piece.to_index()] & self.piece_bitboard[color.to_index()]
     }
 
+    /// Returns a key which uniquely identifies the material on the board,
+    /// ignoring where the pieces are.
+    #[inline]
+    pub fn material_key(&self) -> u64 {
+        self.material_key
+    }
+
+    /// Returns the total value of a side's pieces, excluding pawns and kings.
+    #[inline]
+    pub fn non_pawn_material(&self, color: PieceColor) -> i32 {
+        self.non_pawn_material[color.to_index()]
+    }
+
     /// Whether or not the king of a specific color is in check.
     pub fn in_check(&self, color: PieceColor) -> bool {
-        let mut king_bitboard = self.colored_piece(PieceType::King, color);
-        let tile = king_bitboard.pop_lsb();
+        self.king_tile(color).is_under_attack(self, !color)
+    }
+
+    /// Returns every tile attacked by a side, along with which piece types attack them.
+    pub fn attacks_by(&self, color: PieceColor) -> Attacks {
+        let occupied = self.occupied();
+        let mut by_piece = [Bitboard::ZERO; PieceType::COUNT];
+
+        for piece_type in PieceType::iter() {
+            let mut pieces = self.colored_piece(piece_type, color);
+
+            while pieces != Bitboard::ZERO {
+                let tile = pieces.pop_lsb();
+                let index = tile.index();
+
+                by_piece[piece_type as usize] |= match piece_type {
+                    PieceType::Pawn => Bitboard::new(if color == PieceColor::White { WHITE_PAWN_MASK[index].1 } else { BLACK_PAWN_MASK[index].1 }),
+                    PieceType::Knight => Bitboard::new(KNIGHT_MASKS[index]),
+                    PieceType::Bishop => get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[index], &occupied)),
+                    PieceType::Rook => get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[index], &occupied)),
+                    PieceType::Queen => get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[index], &occupied))
+                        | get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[index], &occupied)),
+                    PieceType::King => Bitboard::new(KING_MASKS[index])
+                };
+            }
+        }
+
+        let all = by_piece.iter().fold(Bitboard::ZERO, |all, attacks| all | *attacks);
+        Attacks { by_piece, all }
+    }
+
+    /// Returns the enemy pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        self.king_tile(self.side_to_move).colored_attackers(self, !self.side_to_move)
+    }
 
-        tile.is_under_attack(self, !color)
+    /// Returns the enemy pieces giving check to the side to move, and the pieces
+    /// of the side to move which are pinned to their king.
+    pub fn checkers_and_pinned(&self) -> (Bitboard, Bitboard) {
+        let king_tile = self.king_tile(self.side_to_move);
+        let checkers = king_tile.colored_attackers(self, !self.side_to_move);
+
+        // Enemy sliders which would attack the king if only enemy pieces were on the board.
+        let them = self.them();
+        let diagonal_sliders = self.their_piece(PieceType::Bishop) | self.their_piece(PieceType::Queen);
+        let orthogonal_sliders = self.their_piece(PieceType::Rook) | self.their_piece(PieceType::Queen);
+
+        let mut snipers = (get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[king_tile.index()], &them)) & diagonal_sliders)
+            | (get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[king_tile.index()], &them)) & orthogonal_sliders);
+
+        let mut pinned = Bitboard::ZERO;
+        while snipers != Bitboard::ZERO {
+            let sniper = snipers.pop_lsb();
+            let blockers = king_tile.between(sniper) & self.occupied();
+
+            if blockers.board.count_ones() == 1 && (blockers & self.us()) != Bitboard::ZERO {
+                pinned |= blockers;
+            }
+        }
+
+        (checkers, pinned)
     }
 
+    /// The game phase, from `MAX_PHASE` with every piece on the board down to zero with only pawns
+    /// and kings left. Positions with promoted pieces are capped at `MAX_PHASE`.
     pub fn phase(&self) -> u32 {
-        let knight_bitboard = self.piece(PieceType::Knight);
-        let bishop_bitboard = self.piece(PieceType::Bishop);
-        let rook_bitboard = self.piece(PieceType::Rook);
-        let queen_bitboard = self.piece(PieceType::Queen);
-
-        let knight_score = knight_bitboard.board.count_ones() * 1;
-        let bishop_score = bishop_bitboard.board.count_ones() * 1;
-        let rook_score = rook_bitboard.board.count_ones() * 2;
-        let queen_score = queen_bitboard.board.count_ones() * 4;
-    
-        knight_score + bishop_score + rook_score + queen_score
+        self.phase.min(MAX_PHASE)
     }
 
     /// Initialises a chess board given a FEN string.
     /// 
-    /// Returns an error if the FEN is invalid.
+    /// Panics if the FEN is invalid, see `from_fen` for a fallible version.
     pub fn new(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap_or_else(|error| panic!("invalid fen {}: {}", fen, error))
+    }
+
+    /// Initialises a chess board given a FEN string.
+    /// 
+    /// Returns an error if the FEN is invalid, including when a side doesn't have exactly one king.
+    pub fn from_fen(fen: &str) -> Result<Board, String> {
         let mut chess_board = Board::default();
 
         let (mut rank, mut file) = (7_u8, 0_u8);
 
         let tokens: Vec<&str> = fen.split(' ').collect();
         if tokens.len() < 4 {
-            panic!("invalid fen: at least 4 tokens should be present {}", fen);
+            return Err("at least 4 tokens should be present".to_string());
         }
 
         let pieces = tokens[0];
         let side = tokens[1];
         let castle_rights = tokens[2];
         let en_passant = tokens[3];
-        let half_move_counter = tokens.get(4).unwrap_or(&"0");
-        let _ = tokens.get(5).unwrap_or(&"0"); // full move counter
+        let mut counters = &tokens[4..];
+
+        // Three-check FENs have the number of checks each side has left to give (such as `3+3`) before the move counters.
+        if let Some((white, black)) = counters.first().and_then(|field| field.split_once('+')) {
+            for (checks_given, remaining) in chess_board.checks_given.iter_mut().zip([white, black]) {
+                *checks_given = THREE_CHECK_LIMIT.saturating_sub(remaining.parse::<u8>().map_err(|_| "remaining checks is not a valid u8 number")?);
+            }
+
+            chess_board.variant = Variant::ThreeCheck;
+            counters = &counters[1..];
+        }
+
+        let half_move_counter = counters.first().unwrap_or(&"0");
+        let _ = counters.get(1).unwrap_or(&"0"); // full move counter
 
         for char in pieces.chars() {
             if let Some(advance) = char.to_digit(10) {
@@ -243,26 +526,43 @@ impl Board {
 
             match char.to_ascii_lowercase() {
                 '/' => {
-                    rank -= 1;
+                    rank = rank.checked_sub(1).ok_or("too many ranks")?;
                     file = 0;
                 },
                 'p' | 'n' | 'b' | 'r' | 'q' | 'k' => {
+                    if file >= 8 {
+                        return Err(format!("too many tiles on rank {}", rank + 1));
+                    }
+
                     let piece_type = get_piece_type(char.to_ascii_lowercase());
-                    chess_board.board[(rank * 8 + file) as usize] = Some(Piece::new(piece_type.clone(), piece_color));
+                    chess_board.set_piece_at(Tile::at(rank, file), Some(Piece::new(piece_type, piece_color)));
+                    chess_board.add_material(Piece::new(piece_type, piece_color));
 
-                    chess_board.piece_bitboard[piece_type.to_index()].set_bit(Tile::new(rank, file).expect("invalid coordinate"));
-                    chess_board.piece_bitboard[piece_color.to_index()].set_bit(Tile::new(rank, file).expect("invalid coordinate"));
+                    if piece_type == PieceType::King {
+                        chess_board.king_tiles[piece_color.to_index()] = Tile::at(rank, file);
+                    }
+
+                    chess_board.piece_bitboard[piece_type.to_index()].set_bit(Tile::at(rank, file));
+                    chess_board.piece_bitboard[piece_color.to_index()].set_bit(Tile::at(rank, file));
 
                     file += 1;
                 }
-                c => panic!("invalid board notation {}", c)
+                c => return Err(format!("invalid board notation {}", c))
+            }
+        }
+
+        // The king tiles are cached, so a missing king would silently be looked for on its starting tile.
+        for color in [PieceColor::White, PieceColor::Black] {
+            let kings = chess_board.colored_piece(PieceType::King, color).board.count_ones();
+            if kings != 1 {
+                return Err(format!("{:?} has {} kings instead of one", color, kings));
             }
         }
 
         match side.to_ascii_lowercase().as_str() {
             "w" => chess_board.side_to_move = PieceColor::White,
             "b" => chess_board.side_to_move = PieceColor::Black,
-            _ => panic!("invalid side-to-move notation")
+            _ => return Err("invalid side-to-move notation".to_string())
         };
 
         for (king_side, queen_side, color) in [("K", "Q", PieceColor::White), ("k", "q", PieceColor::Black)].iter() {
@@ -278,20 +578,192 @@ impl Board {
             chess_board.en_passant = Some(Tile::from_code(en_passant));
         }
 
-        chess_board.half_move_counter = half_move_counter.parse::<u8>().expect("half move counter is not a valid u8 number");
+        chess_board.half_move_counter = half_move_counter.parse::<u8>().map_err(|_| "half move counter is not a valid u8 number")?;
+        chess_board.update_occupancy();
         chess_board.zobrist_key = generate_zobrist_hash(&chess_board);
         
-        chess_board
+        Ok(chess_board)
+    }
+
+    /// Converts the board to a FEN string.
+    /// 
+    /// The full move counter is not tracked, so it is always written as 1.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+
+            for file in 0..8 {
+                match self.piece_at(Tile { rank, file }) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen += &empty.to_string();
+                            empty = 0;
+                        }
+
+                        fen.push(piece.to_char());
+                    },
+                    None => empty += 1
+                }
+            }
+
+            if empty > 0 {
+                fen += &empty.to_string();
+            }
+
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen += if self.side_to_move == PieceColor::White { " w " } else { " b " };
+
+        let mut castle_rights = String::new();
+        for (color, king_side, queen_side) in [(PieceColor::White, "K", "Q"), (PieceColor::Black, "k", "q")] {
+            match self.castle_rights[color.to_index()] {
+                CastleRights::Both => { castle_rights += king_side; castle_rights += queen_side; },
+                CastleRights::KingSide => castle_rights += king_side,
+                CastleRights::QueenSide => castle_rights += queen_side,
+                CastleRights::None => {}
+            }
+        }
+
+        fen += if castle_rights.is_empty() { "-" } else { &castle_rights };
+        fen += &format!(" {}", self.en_passant.map_or("-".to_string(), |ep| ep.get_code()));
+
+        if self.variant == Variant::ThreeCheck {
+            let [white, black] = self.checks_given.map(|checks| THREE_CHECK_LIMIT.saturating_sub(checks));
+            fen += &format!(" {}+{}", white, black);
+        }
+
+        fen += &format!(" {} 1", self.half_move_counter);
+
+        fen
+    }
+
+    /// The same position with the colors swapped: the board is flipped vertically, every piece
+    /// changes color, and the castling rights, en passant square and side to move follow.
+    /// 
+    /// The side to move has the same position either way, so a symmetric evaluation scores both the same.
+    pub fn mirror(&self) -> Board {
+        let mut mirrored = Board::default();
+
+        for index in 0..64 {
+            let tile = Tile::from(index);
+            let Some(piece) = self.piece_at(tile) else { continue; };
+
+            let (tile, piece) = (tile.flip(), Piece::new(piece.piece_type, !piece.piece_color));
+            mirrored.set_piece_at(tile, Some(piece));
+            mirrored.add_material(piece);
+
+            if piece.piece_type == PieceType::King {
+                mirrored.king_tiles[piece.piece_color.to_index()] = tile;
+            }
+
+            mirrored.piece_bitboard[piece.piece_type.to_index()].set_bit(tile);
+            mirrored.piece_bitboard[piece.piece_color.to_index()].set_bit(tile);
+        }
+
+        mirrored.castle_rights = [self.castle_rights[1], self.castle_rights[0]];
+        mirrored.has_castled = [self.has_castled[1], self.has_castled[0]];
+        mirrored.variant = self.variant;
+        mirrored.checks_given = [self.checks_given[1], self.checks_given[0]];
+        mirrored.side_to_move = !self.side_to_move;
+        mirrored.en_passant = self.en_passant.map(|tile| tile.flip());
+        mirrored.half_move_counter = self.half_move_counter;
+        mirrored.plies_from_null = self.plies_from_null;
+
+        mirrored.update_occupancy();
+        mirrored.zobrist_key = generate_zobrist_hash(&mirrored);
+
+        mirrored
     }
         
-    /// Generates all legal moves for a given piece.
+    /// Generates all pseudo-legal moves for the side to move.
     pub fn generate_moves(&self, moves: &mut MoveArray, qsearch: bool) {
-        let mut occupied = self.color(self.side_to_move);
+        let (checkers, pinned) = self.checkers_and_pinned();
+        self.generate_moves_with(moves, qsearch, checkers, pinned);
+    }
+
+    /// Generates the moves of a single piece, which are only its captures and promotions in quiescence search.
+    fn generate_piece_moves(&self, piece: Piece, tile: Tile, qsearch: bool, moves: &mut MoveArray) {
+        if qsearch {
+            piece.generate_captures(self, tile, moves);
+        } else {
+            piece.generate_moves(self, tile, moves);
+        }
+    }
+
+    /// Generates all pseudo-legal moves for the side to move, given the checkers
+    /// and pinned pieces from `checkers_and_pinned`.
+    /// 
+    /// When in check, only moves which could possibly resolve the check are generated:
+    /// king moves, captures of the checker, and blocks (unless in double check). Pinned
+    /// pieces only generate moves along the line of the pin.
+    pub fn generate_moves_with(&self, moves: &mut MoveArray, qsearch: bool, checkers: Bitboard, pinned: Bitboard) {
+        // A game won by the rules of the variant is over, so there is nothing left to play.
+        if self.variant_winner().is_some() {
+            return;
+        }
+
+        let king_tile = self.king_tile(self.side_to_move);
+
+        // Only the king can move out of a double check.
+        if checkers.board.count_ones() > 1 {
+            let king = self.piece_at(king_tile).expect("expected king on king tile");
+            self.generate_piece_moves(king, king_tile, qsearch, moves);
+
+            return;
+        }
+
+        let first_move = moves.len();
+
+        let mut occupied = self.us();
         while occupied != Bitboard::ZERO {
             let tile = occupied.pop_lsb();
-            let piece = self.board[tile.index()].as_ref().expect("expected piece on tile in generate_moves");
+            let piece = self.piece_at(tile).expect("expected piece on tile in generate_moves");
 
-            piece.generate_moves(self, tile, qsearch, moves);
+            if pinned.get_bit(tile) {
+                let pinned_first_move = moves.len();
+                self.generate_piece_moves(piece, tile, qsearch, moves);
+
+                // A pinned piece may only move towards the king or the pinning piece.
+                let mut index = pinned_first_move;
+                while index < moves.len() {
+                    let end = moves[index].end;
+
+                    if king_tile.between(end).get_bit(tile) || king_tile.between(tile).get_bit(end) {
+                        index += 1;
+                    } else {
+                        moves.remove(index);
+                    }
+                }
+            } else {
+                self.generate_piece_moves(piece, tile, qsearch, moves);
+            }
+        }
+
+        // Out of a single check, other pieces must capture the checker or block the check.
+        if checkers != Bitboard::ZERO {
+            let mut checker_bitboard = checkers;
+            let checker_tile = checker_bitboard.pop_lsb();
+            let evasion_mask = checkers | king_tile.between(checker_tile);
+
+            let mut index = first_move;
+            while index < moves.len() {
+                let piece_move = moves[index];
+
+                let resolves_check = piece_move.initial == king_tile
+                    || evasion_mask.get_bit(piece_move.end)
+                    || (piece_move.flags == MoveFlags::EnPassant && piece_move.end.transform(if self.side_to_move == PieceColor::White { -1 } else { 1 }, 0) == Some(checker_tile));
+
+                if resolves_check {
+                    index += 1;
+                } else {
+                    moves.remove(index);
+                }
+            }
         }
 
         // for square in 0..64 {
@@ -301,27 +773,47 @@ impl Board {
         // }
     }
     
+    /// Checks if a move could be generated by the side to move in this position,
+    /// without verifying that it leaves the king out of check.
+    /// 
+    /// This should be used to validate moves from tables (hash moves, killers) which
+    /// may not belong to the current position.
+    pub fn is_pseudo_legal(&self, piece_move: Move) -> bool {
+        let Some(piece) = self.piece_at(piece_move.initial) else {
+            return false;
+        };
+
+        if piece.piece_color != self.side_to_move || self.us().get_bit(piece_move.end) {
+            return false;
+        }
+
+        let mut moves = ArrayVec::new();
+        piece.generate_moves(self, piece_move.initial, &mut moves);
+
+        moves.contains(&piece_move)
+    }
+
+    /// Checks if a move is fully legal in this position.
+    pub fn is_legal(&self, piece_move: Move) -> bool {
+        self.is_pseudo_legal(piece_move) && self.make_move(&piece_move, true).is_some()
+    }
+
     /// Applies a move to the board.
     pub fn make_move(&self, piece_move: &Move, perft: bool) -> Option<Board> {
         let mut board = self.clone();
 
         if !perft { // Ignore zobrist hashing.
             // Reset the Zobrist key for castling/en passant/side to move.
-            board.zobrist_key ^= ZOBRIST_CASTLING_KEYS[board.castle_rights[0] as usize + board.castle_rights[1] as usize];
-        
-            if let Some(ep) = board.en_passant {
-                board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[ep.rank as usize + 1];
-            } else {
-                board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[0];
-            }
+            board.zobrist_key ^= castling_key(board.castle_rights);
+            board.zobrist_key ^= en_passant_key(board.en_passant);
         
             if board.side_to_move == PieceColor::Black {
                 board.zobrist_key ^= ZOBRIST_SIDE_TO_MOVE;
             }
         }
 
-        let initial_piece = board.board[piece_move.initial.index()].clone().expect("expected a piece on initial square");
-        let end_piece = board.board[piece_move.end.index()].clone();
+        let initial_piece = board.piece_at(piece_move.initial).expect("expected a piece on initial square");
+        let end_piece = board.piece_at(piece_move.end);
 
         if !perft { // Ignore non-stalemate draws.
             // Update the half move counter.
@@ -330,10 +822,13 @@ impl Board {
             } else {
                 board.half_move_counter += 1;
             }
+
+            board.plies_from_null = board.plies_from_null.saturating_add(1);
         }
 
         // Update the bitboards.
-        if let Some(ref piece) = end_piece {
+        if let Some(piece) = end_piece {
+            board.remove_material(piece);
             board.piece_bitboard[piece.piece_type.to_index()].clear_bit(piece_move.end);
             board.piece_bitboard[piece.piece_color.to_index()].clear_bit(piece_move.end);
 
@@ -353,49 +848,29 @@ impl Board {
         }
 
         // Update the mailbox board.
-        board.board[piece_move.initial.index()] = None;
-        board.board[piece_move.end.index()] = Some(initial_piece.clone());
-
-        let castle_rights = &mut board.castle_rights[initial_piece.piece_color.to_index()];
-        if *castle_rights != CastleRights::None {
-            if initial_piece.piece_type == PieceType::King {
-                *castle_rights = CastleRights::None; // The right to castle has been lost if the king has already moved.
-            } else if initial_piece.piece_type == PieceType::Rook {
-                // The right to castle has been lost with a rook that has already moved.
-                if piece_move.initial.file == 0 {
-                    *castle_rights = if *castle_rights == CastleRights::QueenSide { CastleRights::None } else { CastleRights::KingSide };
-                } else if piece_move.initial.file == 7 {
-                    *castle_rights = if *castle_rights == CastleRights::KingSide { CastleRights::None } else { CastleRights::QueenSide };
-                }
-            }
-        }
-
-        if let Some(ref piece) = end_piece && piece.piece_type == PieceType::Rook {
-            // Can't castle with a dead rook
-            let castle_rights = &mut board.castle_rights[piece.piece_color.to_index()];
-            let correct_rank = if piece.piece_color == PieceColor::White { 0 } else { 7 };
+        board.set_piece_at(piece_move.initial, None);
+        board.set_piece_at(piece_move.end, Some(initial_piece));
 
-            if *castle_rights != CastleRights::None && piece_move.end.rank == correct_rank {
-                if piece_move.end.file == 0 {
-                    *castle_rights = if *castle_rights == CastleRights::QueenSide { CastleRights::None } else { CastleRights::KingSide };
-                } else if piece_move.end.file == 7 {
-                    *castle_rights = if *castle_rights == CastleRights::KingSide { CastleRights::None } else { CastleRights::QueenSide };
-                }
-            }
+        if initial_piece.piece_type == PieceType::King {
+            board.king_tiles[initial_piece.piece_color.to_index()] = piece_move.end;
         }
 
+        // Moving from or onto a king or rook starting tile loses the rights which depend on it.
+        let rights = board.castle_rights[0].bits() | board.castle_rights[1].bits() << 2;
+        let rights = rights & CASTLING_RIGHTS_MASK[piece_move.initial.index()] & CASTLING_RIGHTS_MASK[piece_move.end.index()];
+        board.castle_rights = [CastleRights::from_bits(rights), CastleRights::from_bits(rights >> 2)];
+
         board.en_passant = None;
 
         match piece_move.flags {
             MoveFlags::DoublePush => {
-                let direction = if initial_piece.piece_color == PieceColor::White { -1 } else { 1 };
-                board.en_passant = Some(piece_move.end.transform(direction, 0).unwrap());
+                board.en_passant = Some(Tile::at((piece_move.initial.rank + piece_move.end.rank) / 2, piece_move.end.file));
             },
             MoveFlags::EnPassant => {
-                let direction = if initial_piece.piece_color == PieceColor::White { -1 } else { 1 };
-                let capture_position = piece_move.end.transform(direction * 1, 0).unwrap();
+                let capture_position = Tile::at(piece_move.initial.rank, piece_move.end.file);
 
-                let piece = &board.board[capture_position.index()].as_ref().expect("en passant on a nothing piece");
+                let piece = board.piece_at(capture_position).expect("en passant on a nothing piece");
+                board.remove_material(piece);
 
                 board.piece_bitboard[piece.piece_type.to_index()].clear_bit(capture_position);
                 board.piece_bitboard[piece.piece_color.to_index()].clear_bit(capture_position);
@@ -404,23 +879,20 @@ impl Board {
                     board.zobrist_key ^= piece.zobrist_key(capture_position.index());
                 }
 
-                board.board[capture_position.index()] = None;
+                board.set_piece_at(capture_position, None);
             },
             MoveFlags::Castling => {
-                board.castle_rights[initial_piece.piece_color.to_index()] = CastleRights::None;
                 let king_side = (piece_move.end.file - piece_move.initial.file) == 2;
 
-                let old_rook_tile = Tile::new(
-                    if initial_piece.piece_color == PieceColor::White { 0 } else { 7 }, 
-                    if king_side { 7 } else { 0 }
-                ).unwrap();
-
-                let new_rook_tile = Tile::new(
-                    if initial_piece.piece_color == PieceColor::White { 0 } else { 7 }, 
-                    if king_side { 5 } else { 3 }
-                ).unwrap();
+                let (old_rook_tile, new_rook_tile) = match (initial_piece.piece_color, king_side) {
+                    (PieceColor::White, true) => (Tile::H1, Tile::F1),
+                    (PieceColor::White, false) => (Tile::A1, Tile::D1),
+                    (PieceColor::Black, true) => (Tile::H8, Tile::F8),
+                    (PieceColor::Black, false) => (Tile::A8, Tile::D8)
+                };
 
                 let rook_piece = Piece::new(PieceType::Rook, initial_piece.piece_color);
+                board.has_castled[initial_piece.piece_color.to_index()] = true;
 
                 board.piece_bitboard[rook_piece.piece_type.to_index()].clear_bit(old_rook_tile);
                 board.piece_bitboard[rook_piece.piece_color.to_index()].clear_bit(old_rook_tile);
@@ -432,117 +904,240 @@ impl Board {
                     board.zobrist_key ^= rook_piece.zobrist_key(new_rook_tile.index());
                 }
 
-                board.board[old_rook_tile.index()] = None;
-                board.board[new_rook_tile.index()] = Some(Piece::new(PieceType::Rook, initial_piece.piece_color));
-            },
-            MoveFlags::KnightPromotion => {
-                board.piece_bitboard[initial_piece.piece_type.to_index()].clear_bit(piece_move.end);
-                board.piece_bitboard[PieceType::Knight.to_index()].set_bit(piece_move.end);
-
-                let knight = Piece::new(PieceType::Knight, initial_piece.piece_color);
-
-                if !perft { // Ignore zobrist hashing.
-                    board.zobrist_key ^= initial_piece.zobrist_key(piece_move.end.index());
-                    board.zobrist_key ^= knight.zobrist_key(piece_move.end.index());
-                }
-
-                board.board[piece_move.end.index()] = Some(knight);
-            },
-            MoveFlags::BishopPromotion => {
-                board.piece_bitboard[initial_piece.piece_type.to_index()].clear_bit(piece_move.end);
-                board.piece_bitboard[PieceType::Bishop.to_index()].set_bit(piece_move.end);
-
-                let bishop = Piece::new(PieceType::Bishop, initial_piece.piece_color);
-
-                if !perft { // Ignore zobrist hashing.
-                    board.zobrist_key ^= initial_piece.zobrist_key(piece_move.end.index());
-                    board.zobrist_key ^= bishop.zobrist_key(piece_move.end.index());
-                }
-
-                board.board[piece_move.end.index()] = Some(bishop);
+                board.set_piece_at(old_rook_tile, None);
+                board.set_piece_at(new_rook_tile, Some(rook_piece));
             },
-            MoveFlags::RookPromotion => {
-                board.piece_bitboard[initial_piece.piece_type.to_index()].clear_bit(piece_move.end);
-                board.piece_bitboard[PieceType::Rook.to_index()].set_bit(piece_move.end);
-
-                let rook = Piece::new(PieceType::Rook, initial_piece.piece_color);
+            MoveFlags::KnightPromotion | MoveFlags::BishopPromotion | MoveFlags::RookPromotion | MoveFlags::QueenPromotion => {
+                let promoted = Piece::new(piece_move.get_promotion_type(), initial_piece.piece_color);
 
-                if !perft { // Ignore zobrist hashing.
-                    board.zobrist_key ^= initial_piece.zobrist_key(piece_move.end.index());
-                    board.zobrist_key ^= rook.zobrist_key(piece_move.end.index());
-                }
-
-                board.board[piece_move.end.index()] = Some(rook);
-            },
-            MoveFlags::QueenPromotion => {
                 board.piece_bitboard[initial_piece.piece_type.to_index()].clear_bit(piece_move.end);
-                board.piece_bitboard[PieceType::Queen.to_index()].set_bit(piece_move.end);
+                board.piece_bitboard[promoted.piece_type.to_index()].set_bit(piece_move.end);
 
-                let queen = Piece::new(PieceType::Queen, initial_piece.piece_color);
+                board.remove_material(initial_piece);
+                board.add_material(promoted);
 
                 if !perft { // Ignore zobrist hashing.
                     board.zobrist_key ^= initial_piece.zobrist_key(piece_move.end.index());
-                    board.zobrist_key ^= queen.zobrist_key(piece_move.end.index());
+                    board.zobrist_key ^= promoted.zobrist_key(piece_move.end.index());
                 }
 
-                board.board[piece_move.end.index()] = Some(queen);
+                board.set_piece_at(piece_move.end, Some(promoted));
             },
             MoveFlags::None => {}
         }
 
+        board.update_occupancy();
+
         if board.in_check(board.side_to_move) {
             None
         } else {
             board.side_to_move = !board.side_to_move;
 
-            if !perft { // Ignore zobrist hashing.
-                board.zobrist_key ^= ZOBRIST_CASTLING_KEYS[board.castle_rights[0] as usize + board.castle_rights[1] as usize];
-            
-                if let Some(ep) = board.en_passant {
-                    board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[ep.rank as usize + 1];
-                } else {
-                    board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[0];
+            // Every check given counts towards winning three-check, so the count is part of the position.
+            if board.variant == Variant::ThreeCheck && board.in_check(board.side_to_move) {
+                let old_checks_key = checks_key(board.checks_given);
+                board.checks_given[self.side_to_move.to_index()] += 1;
+
+                if !perft {
+                    board.zobrist_key ^= old_checks_key ^ checks_key(board.checks_given);
                 }
+            }
+
+            if !perft { // Ignore zobrist hashing.
+                board.zobrist_key ^= castling_key(board.castle_rights);
+                board.zobrist_key ^= en_passant_key(board.en_passant);
             
                 if board.side_to_move == PieceColor::Black {
                     board.zobrist_key ^= ZOBRIST_SIDE_TO_MOVE;
                 }
             }
 
+            #[cfg(feature = "paranoid")]
+            {
+                let validation = board.validate(perft);
+                debug_assert!(validation.is_ok(), "{:?} after {}", validation, piece_move.to_uci());
+            }
+
             Some(board)
         }
     }
 
-    /// Makes an empty move.
+    /// Makes an empty move, passing the turn to the opponent.
+    /// 
+    /// Any en passant square is cleared and the half move counter is advanced. Positions
+    /// reached after a null move must not be matched against positions before it when
+    /// detecting repetitions, which is tracked through `plies_from_null`.
     pub fn make_null_move(&self) -> Board {
         let mut board = self.clone();
 
-        if let Some(ep) = board.en_passant {
-            board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[ep.rank as usize + 1];
-        } else {
-            board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[0];
-        }
-    
-        if board.side_to_move == PieceColor::Black {
-            board.zobrist_key ^= ZOBRIST_SIDE_TO_MOVE;
-        }
+        board.zobrist_key ^= en_passant_key(board.en_passant) ^ en_passant_key(None);
+
+        board.zobrist_key ^= ZOBRIST_SIDE_TO_MOVE;
 
         board.side_to_move = !board.side_to_move;
         board.en_passant = None;
+        board.half_move_counter = board.half_move_counter.saturating_add(1);
+        board.plies_from_null = 0;
 
-        if let Some(ep) = board.en_passant {
-            board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[ep.rank as usize + 1];
-        } else {
-            board.zobrist_key ^= ZOBRIST_EN_PASSANT_KEYS[0];
-        }
-    
-        if board.side_to_move == PieceColor::Black {
-            board.zobrist_key ^= ZOBRIST_SIDE_TO_MOVE;
+        #[cfg(feature = "paranoid")]
+        {
+            let validation = board.validate(false);
+            debug_assert!(validation.is_ok(), "{:?} after a null move", validation);
         }
 
         board
     }
 
+    /// Plays a legal move in place, recording the new position in a game history of zobrist keys, which
+    /// repetitions are checked against. After an irreversible move (a capture or a pawn move) no earlier
+    /// position can repeat, so the history starts over.
+    pub fn play(&mut self, piece_move: &Move, history: &mut Vec<u64>) {
+        *self = self.make_move(piece_move, false).expect("expected a legal move");
+
+        if self.half_move_counter == 0 {
+            history.clear();
+        }
+
+        history.push(self.zobrist_key);
+    }
+
+    /// Replays a list of UCI moves (such as those of a `position` command), resolving each one against the legal
+    /// moves of the board (see `Move::from_uci_on`) and recording every position in the game history (see `play`).
+    ///
+    /// Returns the moves played, or the first move which isn't legal along with the moves played before it, after
+    /// which nothing else is played. Null moves (`0000`) are skipped, as are empty tokens from repeated spaces.
+    pub fn apply_uci_moves(&mut self, moves: &str, history: &mut Vec<u64>) -> Result<Vec<Move>, ReplayError> {
+        let mut played = vec![];
+
+        for uci_move in moves.split_whitespace().filter(|uci_move| *uci_move != "0000") {
+            let Some(piece_move) = Move::from_uci_on(self, uci_move) else {
+                return Err(ReplayError { played, illegal_move: uci_move.to_string() });
+            };
+
+            self.play(&piece_move, history);
+            played.push(piece_move);
+        }
+
+        Ok(played)
+    }
+
+    /// Switches the rules the game is played by. Checks given are only counted in three-check, so they are forgotten otherwise.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+
+        if variant != Variant::ThreeCheck {
+            self.zobrist_key ^= checks_key(self.checks_given) ^ checks_key([0; 2]);
+            self.checks_given = [0; 2];
+        }
+    }
+
+    /// The side which won the game by the rules of the variant, such as bringing its king to the hill in king of the hill.
+    /// Checkmate, stalemate and draws end the game the same way in every variant, so they are left to the caller.
+    pub fn variant_winner(&self) -> Option<PieceColor> {
+        let mut colors = [PieceColor::White, PieceColor::Black].into_iter();
+
+        match self.variant {
+            Variant::Standard => None,
+            Variant::KingOfTheHill => colors.find(|color| KOTH_HILL & self.king_tile(*color).bit() != 0),
+            Variant::ThreeCheck => colors.find(|color| self.checks_given[color.to_index()] >= THREE_CHECK_LIMIT)
+        }
+    }
+
+    /// Whether or not a null move has been made within the last `plies` plies.
+    pub fn null_move_within(&self, plies: usize) -> bool {
+        (self.plies_from_null as usize) < plies
+    }
+
+    /// Checks that every piece of redundant board state agrees with the mailbox,
+    /// returning a description of the first inconsistency found.
+    /// 
+    /// This covers the piece bitboards, occupancy, king tiles, material, castle rights (and whether
+    /// a side has castled), the en passant tile, the checks given, and (unless `perft` is set) the zobrist key.
+    pub fn validate(&self, perft: bool) -> Result<(), String> {
+        let mut material = Board::default();
+
+        for index in 0..64 {
+            let tile = Tile::from(index as u8);
+            let piece = self.piece_at(tile);
+
+            for color in [PieceColor::White, PieceColor::Black] {
+                let expected = piece.is_some_and(|piece| piece.piece_color == color);
+                if self.piece_bitboard[color.to_index()].get_bit(tile) != expected {
+                    return Err(format!("{:?} bitboard disagrees with the mailbox on {:?}", color, tile));
+                }
+            }
+
+            for piece_type in PieceType::iter() {
+                let expected = piece.is_some_and(|piece| piece.piece_type == piece_type);
+                if self.piece_bitboard[piece_type.to_index()].get_bit(tile) != expected {
+                    return Err(format!("{:?} bitboard disagrees with the mailbox on {:?}", piece_type, tile));
+                }
+            }
+
+            if let Some(piece) = piece {
+                material.add_material(piece);
+            }
+        }
+
+        let occupancy = self.color(PieceColor::White) | self.color(PieceColor::Black);
+        if self.occupancy != occupancy {
+            return Err("occupancy cache disagrees with the color bitboards".to_string());
+        }
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            let kings = self.colored_piece(PieceType::King, color);
+            if kings.board.count_ones() != 1 {
+                return Err(format!("{:?} has {} kings instead of one", color, kings.board.count_ones()));
+            }
+
+            if !kings.get_bit(self.king_tile(color)) {
+                return Err(format!("{:?} king is not alone on {:?}", color, self.king_tile(color)));
+            }
+
+            // A side may only castle if its king and the relevant rook are untouched.
+            let rank = if color == PieceColor::White { 0 } else { 7 };
+            let rook_on = |file: u8| self.piece_at(Tile { rank, file }) == Some(Piece::new(PieceType::Rook, color));
+            let king_home = self.king_tile(color) == Tile { rank, file: 4 };
+
+            let (king_side, queen_side) = match self.castle_rights[color.to_index()] {
+                CastleRights::None => (false, false),
+                CastleRights::KingSide => (true, false),
+                CastleRights::QueenSide => (false, true),
+                CastleRights::Both => (true, true)
+            };
+
+            if (king_side || queen_side) && !king_home || king_side && !rook_on(7) || queen_side && !rook_on(0) {
+                return Err(format!("{:?} castle rights {:?} disagree with the king and rooks", color, self.castle_rights[color.to_index()]));
+            }
+
+            // Castling gives up every right, so a side which has castled can't castle again.
+            if self.has_castled[color.to_index()] && (king_side || queen_side) {
+                return Err(format!("{:?} has castled but kept castle rights {:?}", color, self.castle_rights[color.to_index()]));
+            }
+        }
+
+        if material.material_key != self.material_key || material.non_pawn_material != self.non_pawn_material || material.phase != self.phase {
+            return Err("material disagrees with the mailbox".to_string());
+        }
+
+        if let Some(ep) = self.en_passant {
+            let rank = if self.side_to_move == PieceColor::White { 5 } else { 2 };
+            if ep.rank != rank || !self.is_empty(ep) {
+                return Err(format!("en passant tile {:?} is impossible", ep));
+            }
+        }
+
+        if self.variant != Variant::ThreeCheck && self.checks_given != [0; 2] {
+            return Err(format!("checks {:?} are counted outside of three-check", self.checks_given));
+        }
+
+        if !perft && self.zobrist_key != generate_zobrist_hash(self) {
+            return Err("zobrist key disagrees with the board".to_string());
+        }
+
+        Ok(())
+    }
+
     /// Performance testing, move path enumerating function.
     pub fn perft(&self, depth: usize) -> u64 {
         if depth == 0 {
@@ -621,44 +1216,16 @@ impl Board {
 
 impl std::fmt::Debug for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let  _ = writeln!(f);
-
-        let board_size = 8;
-        
-        for row in (0..board_size).rev() {
-            for col in 0..board_size {
-                let index = row * board_size + col;
-                write!(f, "| ")?;
-                
-                match &self.board[index] {
-                    Some(piece) => {
-                        let uppercase = piece.piece_color == PieceColor::White;
-                        match piece.piece_type {
-                            PieceType::Pawn => if uppercase { write!(f, "P")? } else { write!(f, "p")? },
-                            PieceType::Rook => if uppercase { write!(f, "R")? } else { write!(f, "r")? },
-                            PieceType::Knight => if uppercase { write!(f, "N")? } else { write!(f, "n")? },
-                            PieceType::Bishop => if uppercase { write!(f, "B")? } else { write!(f, "b")? },
-                            PieceType::Queen => if uppercase { write!(f, "Q")? } else { write!(f, "q")? },
-                            PieceType::King => if uppercase { write!(f, "K")? } else { write!(f, "k")? },
-                        }
-                    },
-                    None => write!(f, " ")?,
-                }
-            }
-            
-            writeln!(f, "|")?;
-        }
-        
-        let _ = writeln!(f);
-        
-
-        std::fmt::Result::Ok(())
+        writeln!(f)?;
+        write!(f, "{}", render(self, RenderStyle::PLAIN, None))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::{board::Board, piece_move::{Move, MoveFlags}};
+    use arrayvec::ArrayVec;
+    use strum::IntoEnumIterator;
+    use crate::utils::{board::{material_key_from_counts, Bitboard, Board, ReplayError, Variant, MATERIAL_PIECE_TYPES}, consts::{CENTER_FILES, EMPTY_TILE, MAX_PHASE, PHASE_WEIGHTS, QUEEN_VALUE, ROOK_VALUE, SPACE_MASK}, piece::{Piece, PieceColor, PieceType, Tile}, piece_move::{Move, MoveFlags}, zobrist::generate_zobrist_hash};
     use colored::Colorize;
 
     const EPD_FILE: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400 ;D3 8902 ;D4 197281 ;D5 4865609 ;D6 119060324
@@ -787,6 +1354,9 @@ n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1 ;D1 24 ;D2 496 ;D3 9483 ;D4 182838 ;D5 3
 n1n5/1Pk5/8/8/8/8/5Kp1/5N1N b - - 0 1 ;D1 24 ;D2 421 ;D3 7421 ;D4 124608 ;D5 2193768 ;D6 37665329
 8/PPPk4/8/8/8/8/4Kppp/8 b - - 0 1 ;D1 18 ;D2 270 ;D3 4699 ;D4 79355 ;D5 1533145 ;D6 28859283
 n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1 ;D1 24 ;D2 496 ;D3 9483 ;D4 182838 ;D5 3605103 ;D6 71179139
+8/P1k5/K7/8/8/8/8/8 w - - 0 1 ;D1 6 ;D2 27 ;D3 273 ;D4 1329 ;D5 18135 ;D6 92683
+2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1 ;D1 11 ;D2 133 ;D3 1442 ;D4 19174 ;D5 266199 ;D6 3821001
+4k3/1P6/8/8/8/8/K7/8 w - - 0 1 ;D1 9 ;D2 40 ;D3 472 ;D4 2661 ;D5 38983 ;D6 217342
 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1 ;D4 43238 ;D5 674624 ;D6 11030083
 rnbqkb1r/ppppp1pp/7n/4Pp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3 ;D5 11139762";
 
@@ -813,6 +1383,392 @@ rnbqkb1r/ppppp1pp/7n/4Pp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3 ;D5 11139762";
         }
     }
 
+    #[test]
+    fn test_mailbox() {
+        for piece_color in [PieceColor::White, PieceColor::Black] {
+            for piece_type in PieceType::iter() {
+                let piece = Piece::new(piece_type, piece_color);
+                assert_eq!(Piece::unpack(piece.pack()), Some(piece));
+            }
+        }
+
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(board.piece_at(Tile::from_code("e1")), Some(Piece::new(PieceType::King, PieceColor::White)));
+        assert_eq!(board.piece_at(Tile::from_code("d8")), Some(Piece::new(PieceType::Queen, PieceColor::Black)));
+        assert!(board.is_empty(Tile::from_code("e4")));
+
+        // King tiles are tracked through king moves and castling.
+        let board = Board::new("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let mut castle = Move::from_uci("e1g1");
+        castle.flags = MoveFlags::Castling;
+
+        let board = board.make_move(&castle, false).expect("should return valid board");
+        assert_eq!(board.king_tile(PieceColor::White), Tile::from_code("g1"));
+        assert_eq!(board.has_castled, [true, false]);
+        assert_eq!(board.mirror().has_castled, [false, true]);
+
+        let board = board.make_move(&Move::from_uci("e8d7"), false).expect("should return valid board");
+        assert_eq!(board.king_tile(PieceColor::Black), Tile::from_code("d7"));
+    }
+
+    #[test]
+    fn test_checkers_and_pinned() {
+        let board = Board::new("4q2k/8/8/8/1b6/8/3N4/r1B1K3 w - - 0 1");
+        let (checkers, pinned) = board.checkers_and_pinned();
+
+        // The queen checks along the e-file, the bishop pins the knight and the rook pins the bishop.
+        assert_eq!(checkers.board.count_ones(), 1);
+        assert!(checkers.get_bit(Tile::from_code("e8")));
+
+        assert_eq!(pinned.board.count_ones(), 2);
+        assert!(pinned.get_bit(Tile::from_code("d2")));
+        assert!(pinned.get_bit(Tile::from_code("c1")));
+
+        // Pinned pieces can't block the check, so only the king can move.
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+        assert!(moves.iter().all(|piece_move| piece_move.initial == Tile::from_code("e1")));
+    }
+
+    #[test]
+    fn test_attacks_by() {
+        let board = Board::new("4k3/8/8/3p4/8/2N5/8/R3K3 w - - 0 1");
+        let attacks = board.attacks_by(PieceColor::White);
+
+        assert!(attacks.piece(PieceType::Knight).get_bit(Tile::from_code("d5")));
+        assert!(attacks.piece(PieceType::Rook).get_bit(Tile::from_code("a8")));
+        assert!(attacks.piece(PieceType::Rook).get_bit(Tile::from_code("d1")));
+        assert!(!attacks.piece(PieceType::Rook).get_bit(Tile::from_code("f1")));
+        assert!(attacks.piece(PieceType::King).get_bit(Tile::from_code("f2")));
+        assert_eq!(attacks.piece(PieceType::Pawn), Bitboard::ZERO);
+
+        let black_attacks = board.attacks_by(PieceColor::Black);
+        assert!(black_attacks.piece(PieceType::Pawn).get_bit(Tile::from_code("c4")));
+        assert!(black_attacks.piece(PieceType::Pawn).get_bit(Tile::from_code("e4")));
+        assert_eq!(black_attacks.all, black_attacks.piece(PieceType::Pawn) | black_attacks.piece(PieceType::King));
+    }
+
+    #[test]
+    fn test_material_tracking() {
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+
+        // The incrementally updated material key should match one computed from scratch.
+        for piece_move in moves.iter() {
+            let Some(new_board) = board.make_move(piece_move, false) else { continue; };
+            let mut counts = [[0; MATERIAL_PIECE_TYPES]; 2];
+
+            for color in [PieceColor::White, PieceColor::Black] {
+                for piece_type in PieceType::iter().take(MATERIAL_PIECE_TYPES) {
+                    counts[color.to_index()][piece_type as usize] = new_board.colored_piece(piece_type, color).board.count_ones();
+                }
+            }
+
+            assert_eq!(new_board.material_key(), material_key_from_counts(counts[0], counts[1]));
+
+            // So should the phase.
+            let phase: u32 = PieceType::iter().map(|piece_type| new_board.piece(piece_type).board.count_ones() * PHASE_WEIGHTS[piece_type as usize]).sum();
+            assert_eq!(new_board.phase(), phase.min(MAX_PHASE));
+        }
+
+        let board = Board::new("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1");
+        assert_eq!(board.non_pawn_material(PieceColor::White), ROOK_VALUE);
+        assert_eq!(board.non_pawn_material(PieceColor::Black), 0);
+
+        let board = board.make_move(&Move::from_uci("b7b8q"), false).expect("should return valid board");
+        assert_eq!(board.non_pawn_material(PieceColor::White), ROOK_VALUE + QUEEN_VALUE);
+        assert_eq!(board.material_key(), material_key_from_counts([0, 0, 0, 1, 1], [0; MATERIAL_PIECE_TYPES]));
+        assert_eq!(board.phase(), PHASE_WEIGHTS[PieceType::Rook as usize] + PHASE_WEIGHTS[PieceType::Queen as usize]);
+
+        assert_eq!(Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").phase(), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_fen() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 3 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "8/8/8/8/8/8/8/4K2k b - - 99 1"
+        ];
+
+        for fen in fens {
+            assert_eq!(Board::new(fen).to_fen(), fen);
+        }
+
+        // Positions without exactly one king per side are rejected instead of having one made up on its starting tile.
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/7k w - - 0 1").err(), Some("White has 0 kings instead of one".to_string()));
+        assert_eq!(Board::from_fen("k7/8/8/8/8/8/8/K6K w - - 0 1").err(), Some("White has 2 kings instead of one".to_string()));
+        assert!(Board::from_fen("8/8/8/8/8/8/8/4K2k x - - 0 1").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/4K2k").is_err());
+
+        // Validation catches a king which went missing after parsing.
+        let mut board = Board::new(fens[3]);
+        board.mailbox[Tile::H1.index()] = EMPTY_TILE;
+        board.piece_bitboard[PieceColor::Black.to_index()].clear_bit(Tile::H1);
+        board.piece_bitboard[PieceType::King.to_index()].clear_bit(Tile::H1);
+        board.update_occupancy();
+        board.remove_material(Piece::new(PieceType::King, PieceColor::Black));
+        assert_eq!(board.validate(false), Err("Black has 0 kings instead of one".to_string()));
+    }
+
+    #[test]
+    fn test_board_validation() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"
+        ];
+
+        // Every position two plies deep should be consistent.
+        for fen in fens {
+            let board = Board::new(fen);
+            assert_eq!(board.validate(false), Ok(()));
+
+            let mut moves = ArrayVec::new();
+            board.generate_moves(&mut moves, false);
+
+            for piece_move in moves.iter() {
+                let Some(child) = board.make_move(piece_move, false) else { continue; };
+                assert_eq!(child.validate(false), Ok(()), "{}", piece_move.to_uci());
+
+                let mut replies = ArrayVec::new();
+                child.generate_moves(&mut replies, false);
+
+                for reply in replies.iter() {
+                    let Some(grandchild) = child.make_move(reply, false) else { continue; };
+                    assert_eq!(grandchild.validate(false), Ok(()), "{} {}", piece_move.to_uci(), reply.to_uci());
+                }
+            }
+        }
+
+        // Corrupted state should be caught.
+        let board = Board::new(fens[0]);
+
+        let mut corrupted = board.clone();
+        corrupted.mailbox[Tile { rank: 1, file: 4 }.index()] = EMPTY_TILE;
+        assert!(corrupted.validate(false).is_err());
+
+        let mut corrupted = board.clone();
+        corrupted.zobrist_key ^= 1;
+        assert!(corrupted.validate(false).is_err());
+        assert!(corrupted.validate(true).is_ok());
+
+        let mut corrupted = board.clone();
+        corrupted.mailbox[Tile { rank: 0, file: 7 }.index()] = EMPTY_TILE;
+        corrupted.piece_bitboard[PieceColor::White.to_index()].clear_bit(Tile { rank: 0, file: 7 });
+        corrupted.piece_bitboard[PieceType::Rook.to_index()].clear_bit(Tile { rank: 0, file: 7 });
+        corrupted.update_occupancy();
+        corrupted.remove_material(Piece::new(PieceType::Rook, PieceColor::White));
+        assert!(corrupted.validate(true).unwrap_err().contains("castle rights"));
+    }
+
+    #[test]
+    fn test_null_move() {
+        {
+            let board = Board::new("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3");
+            let null_board = board.make_null_move();
+
+            assert_eq!(null_board.side_to_move, PieceColor::White);
+            assert_eq!(null_board.en_passant, None);
+            assert_eq!(null_board.half_move_counter, board.half_move_counter + 1);
+            assert_eq!(null_board.castle_rights, board.castle_rights);
+            assert!(null_board.null_move_within(1));
+            assert!(!board.null_move_within(1));
+
+            // The incrementally updated key should match a key generated from scratch.
+            assert_eq!(null_board.zobrist_key, generate_zobrist_hash(&null_board));
+            assert_ne!(null_board.zobrist_key, board.zobrist_key);
+
+            // Two null moves return to the same position, minus the en passant square.
+            let double_null_board = null_board.make_null_move();
+            assert_eq!(double_null_board.side_to_move, board.side_to_move);
+            assert_eq!(double_null_board.zobrist_key, generate_zobrist_hash(&double_null_board));
+        }
+
+        {
+            let board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+            let null_board = board.make_null_move().make_null_move();
+
+            assert_eq!(null_board.zobrist_key, board.zobrist_key);
+            assert!(null_board.null_move_within(2));
+
+            let mv = Move::from_uci("g1f3");
+            let board = null_board.make_move(&mv, false).expect("should return valid board");
+            assert!(board.null_move_within(2));
+            assert!(!board.null_move_within(1));
+        }
+    }
+
+    #[test]
+    fn test_move_legality() {
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+
+        // Every generated move is pseudo-legal, and legal if it doesn't leave the king in check.
+        for piece_move in moves.iter() {
+            assert!(board.is_pseudo_legal(*piece_move));
+            assert_eq!(board.is_legal(*piece_move), board.make_move(piece_move, true).is_some());
+        }
+
+        // Moves from an empty tile, of the wrong side, or with a mismatched flag are rejected.
+        assert!(!board.is_pseudo_legal(Move::from_uci("a4a5")));
+        assert!(!board.is_pseudo_legal(Move::from_uci("a7a6")));
+        assert!(!board.is_pseudo_legal(Move::from_uci("e2e1")));
+        assert!(!board.is_pseudo_legal(Move::from_uci("e1g1")));
+
+        let mut castle = Move::from_uci("e1g1");
+        castle.flags = MoveFlags::Castling;
+        assert!(board.is_legal(castle));
+    }
+
+    #[test]
+    fn test_mirror() {
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K1R1 w Qkq - 3 1");
+        let mirrored = board.mirror();
+
+        assert_eq!(mirrored.to_fen(), "r3k1r1/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQq - 3 1");
+        assert_eq!(mirrored.validate(false), Ok(()));
+        assert_eq!(mirrored.mirror().to_fen(), board.to_fen());
+
+        // The en passant square moves to the other side of the board.
+        let board = Board::new("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(board.mirror().to_fen(), "rnbqkbnr/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 1");
+        assert_eq!(board.mirror().zobrist_key, Board::new("rnbqkbnr/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 1").zobrist_key);
+    }
+
+    #[test]
+    fn test_apply_uci_moves() {
+        // Both sides castle, once in each notation, and then race their pawns to underpromote.
+        let mut board = Board::new("r3k2r/1P6/8/8/8/8/6p1/R3K2R w KQkq - 0 1");
+        let mut history = vec![board.zobrist_key];
+        let played = board.apply_uci_moves("e1c1 e8h8 b7b8r  f8b8 0000 d1d2 g2g1n", &mut history).expect("expected legal moves");
+
+        assert_eq!(played.len(), 6);
+        assert_eq!(played.last().map(|piece_move| piece_move.flags), Some(MoveFlags::KnightPromotion));
+        assert!(board.to_fen().starts_with("rr4k1/8/8/8/8/8/3R4/2K3nR w - - 0 "));
+
+        // The promotion was irreversible, so only the position after it is left in the history.
+        assert_eq!(history, vec![board.zobrist_key]);
+
+        // Nothing is played past an illegal move.
+        let mut board = Board::new("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let mut history = vec![board.zobrist_key];
+        let error = board.apply_uci_moves("e1e2 e8e6 e2e3", &mut history).unwrap_err();
+        assert_eq!(error, ReplayError { played: vec![Move::from_uci_on(&Board::new("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), "e1e2").unwrap()], illegal_move: "e8e6".to_string() });
+        assert!(board.to_fen().starts_with("4k3/8/8/8/8/8/4K3/8 b - - 1 "));
+
+        // Reversible moves keep the history, so the game is seen to repeat.
+        board.apply_uci_moves("e8e7 e2e1 e7e8", &mut history).expect("expected legal moves");
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.first(), history.last());
+    }
+
+    #[test]
+    fn test_variants() {
+        // Reaching the hill ends a game of king of the hill, so nothing is left to play.
+        let mut board = Board::new("4k3/8/8/8/8/3K4/8/8 w - - 0 1");
+        board.set_variant(Variant::KingOfTheHill);
+        board.apply_uci_moves("d3d4", &mut vec![]).expect("expected a legal move");
+        assert_eq!(board.variant_winner(), Some(PieceColor::White));
+        assert_eq!(board.perft(1), 0);
+
+        let mut board = Board::new("4k3/8/8/8/8/3K4/8/8 w - - 0 1");
+        board.apply_uci_moves("d3d4", &mut vec![]).expect("expected a legal move");
+        assert_eq!(board.variant_winner(), None);
+        assert!(board.perft(1) > 0);
+
+        // Three-check counts every check given as part of the position, up to the third one which wins.
+        let mut board = Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1");
+        assert_eq!(board.variant, Variant::ThreeCheck);
+
+        board.apply_uci_moves("e2e4 e7e5 f1c4 d7d6 c4f7 e8f7 d1h5", &mut vec![]).expect("expected legal moves");
+        assert_eq!((board.checks_given, board.variant_winner()), ([2, 0], None));
+        assert!(board.to_fen().starts_with("rnbq1bnr/ppp2kpp/3p4/4p2Q/4P3/8/PPPP1PPP/RNB1K1NR b KQ - 1+3 "));
+        assert_eq!(board.validate(false), Ok(()));
+        assert_ne!(board.zobrist_key, Board::new(&board.to_fen().replace("1+3", "2+3")).zobrist_key);
+
+        board.apply_uci_moves("g7g6 h5g6", &mut vec![]).expect("expected legal moves");
+        assert_eq!(board.variant_winner(), Some(PieceColor::White));
+        assert_eq!(board.mirror().variant_winner(), Some(PieceColor::Black));
+
+        // Other variants forget the checks.
+        board.set_variant(Variant::Standard);
+        assert_eq!((board.checks_given, board.variant_winner()), ([0, 0], None));
+        assert_eq!(board.validate(false), Ok(()));
+        assert!(!board.to_fen().contains('+'));
+
+        assert_eq!(Variant::ALL.map(|(variant, name)| Variant::from_uci(name) == Some(variant) && variant.to_uci() == name), [true; 3]);
+    }
+
+    #[test]
+    fn test_tile_constants() {
+        assert_eq!(Tile::E4, Tile::from_code("e4"));
+        assert_eq!(Tile::from(63), Tile::H8);
+        assert_eq!(usize::from(Tile::C7), 50);
+        assert_eq!((Tile::B2.relative_rank(PieceColor::White), Tile::B2.relative_rank(PieceColor::Black)), (1, 6));
+        assert_eq!(Tile::D2.flip(), Tile::D7);
+
+        // Masks written with the tile and bitboard constructors match the tables they replaced.
+        assert_eq!(Bitboard::from_tiles(&[Tile::A1, Tile::H8]).board, 0x8000_0000_0000_0001);
+        assert_eq!(SPACE_MASK, [0x0000_0000_3C3C_3C00, 0x003C_3C3C_0000_0000]);
+        assert_eq!(CENTER_FILES, 0x3C3C_3C3C_3C3C_3C3C);
+        assert_eq!(Bitboard::rank(7) & Bitboard::file(0), Bitboard::new(Tile::A8.bit()));
+    }
+
+    #[test]
+    fn test_castling_rights() {
+        let board = Board::new("r3k2r/8/8/8/R7/8/8/R3K2R w KQkq - 0 1");
+        let rights_after = |uci: &str| {
+            let piece_move = Move::from_uci_on(&board, uci).expect("expected a legal move");
+            board.make_move(&piece_move, false).unwrap().to_fen().split(' ').nth(2).unwrap().to_string()
+        };
+
+        // Only moves from or onto the king and rook starting tiles lose rights, even on the same files.
+        assert_eq!(rights_after("a4c4"), "KQkq");
+        assert_eq!(rights_after("a1b1"), "Kkq");
+        assert_eq!(rights_after("a4a7"), "KQkq");
+        assert_eq!(rights_after("h1h8"), "Qq");
+        assert_eq!(rights_after("e1f1"), "kq");
+        assert_eq!(rights_after("e1g1"), "kq");
+    }
+
+    #[test]
+    fn test_capture_quiet_split() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "rnbqkb1r/ppppp1pp/7n/4Pp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"
+        ];
+
+        for fen in fens {
+            let board = Board::new(fen);
+
+            let (mut all, mut captures, mut quiets) = (ArrayVec::new(), ArrayVec::new(), ArrayVec::new());
+            let mut pieces = board.us();
+            while pieces != Bitboard::ZERO {
+                let tile = pieces.pop_lsb();
+                let piece = board.piece_at(tile).unwrap();
+
+                piece.generate_moves(&board, tile, &mut all);
+                piece.generate_captures(&board, tile, &mut captures);
+                piece.generate_quiets(&board, tile, &mut quiets);
+            }
+
+            // Captures and quiets never overlap, and together they make up every move.
+            let is_noisy = |piece_move: &Move| !board.is_empty(piece_move.end) || piece_move.flags == MoveFlags::EnPassant || piece_move.flags.is_promotion();
+            assert!(captures.iter().all(is_noisy), "{}", fen);
+            assert!(!quiets.iter().any(is_noisy), "{}", fen);
+            assert_eq!(captures.len() + quiets.len(), all.len(), "{}", fen);
+            assert!(all.iter().all(|piece_move| captures.contains(piece_move) || quiets.contains(piece_move)), "{}", fen);
+        }
+    }
+
     #[test]
     fn test_movegen() {
         let lines = EPD_FILE.split('\n');
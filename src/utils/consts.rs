@@ -1,7 +1,7 @@
 use bytemuck::cast_slice;
 use strum::EnumCount;
 
-use super::{board::Bitboard, piece::{PieceType, PieceColor}};
+use super::{board::Bitboard, piece::{PieceType, PieceColor, Tile}};
 
 pub const MAX_LEGAL_MOVES: usize = 218;
 pub const PIECE_INDICES: usize = PieceType::COUNT + PieceColor::COUNT;
@@ -20,6 +20,11 @@ pub const fn get_piece_type(piece_code: char) -> PieceType {
 
 pub const MAX_DEPTH: usize = 127;
 
+// Mailbox constants.
+pub const EMPTY_TILE: u8 = 0;
+pub const PACKED_COLOR_SHIFT: u8 = 3;
+pub const PACKED_TYPE_MASK: u8 = 0b111;
+
 // Constants which represent evaluation thresholds.
 pub const WORST_EVAL: i32 = -i32::MAX;
 pub const SHALLOWEST_PROVEN_LOSS: i32 = -100000;
@@ -37,17 +42,291 @@ pub const ROOK_VALUE: i32 = 500;
 pub const QUEEN_VALUE: i32 = 900;
 pub const KING_VALUE: i32 = 0;
 
+// Material values (middlegame, endgame) used by the evaluation, indexed by piece type. The values above
+// are used wherever a single value is needed, such as static exchange evaluation and move ordering.
+pub const MATERIAL_VALUES: [(i32, i32); 6] = [(82, 94), (337, 281), (365, 297), (477, 512), (1025, 936), (0, 0)];
+
+// Game phase weights of each piece type, and the phase of the starting position. The phase
+// tapers evaluations from the middlegame (at `MAX_PHASE`) to the endgame (at zero).
+pub const PHASE_WEIGHTS: [u32; 6] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: u32 = 24;
+
+// Mobility weights (middlegame, endgame) per safe tile attacked, indexed by piece type.
+pub const MOBILITY_WEIGHTS: [(i32, i32); 6] = [(0, 0), (4, 4), (5, 5), (2, 4), (1, 2), (0, 0)];
+
+// King safety weights per attacked tile around the king, indexed by the attacking piece type.
+pub const KING_ZONE_ATTACK_WEIGHTS: [i32; 6] = [4, 8, 8, 10, 15, 0];
+
+// King file penalties for the files next to (and including) the king's, in the middlegame. A file is
+// semi-open without friendly pawns, and open without any pawns.
+pub const KING_SEMI_OPEN_FILE_PENALTY: i32 = 15;
+pub const KING_OPEN_FILE_PENALTY: i32 = 25;
+
+// Pawn storm penalties for the most advanced enemy pawn on each king file, indexed by its rank relative
+// to the defending side, for storms which are free to advance and storms blocked by a friendly pawn.
+pub const PAWN_STORM_PENALTY: [i32; 8] = [0, 0, 30, 20, 10, 5, 0, 0];
+pub const BLOCKED_PAWN_STORM_PENALTY: [i32; 8] = [0, 0, 15, 10, 5, 2, 0, 0];
+
+// Outpost bonuses (middlegame, endgame) for knights and bishops on the 4th to 6th ranks, which are
+// protected by a friendly pawn and can't ever be attacked by an enemy pawn.
+pub const KNIGHT_OUTPOST_BONUS: (i32, i32) = (25, 15);
+pub const BISHOP_OUTPOST_BONUS: (i32, i32) = (15, 10);
+
+// Bad bishop penalty (middlegame, endgame) per friendly pawn on the same color complex as a bishop.
+pub const BAD_BISHOP_PENALTY: (i32, i32) = (3, 6);
+
+// King tropism weights (middlegame) per tile a piece is closer to the enemy king than 7 tiles away, indexed by piece type.
+pub const KING_TROPISM_WEIGHTS: [i32; 6] = [0, 3, 1, 1, 4, 0];
+
+// King activity weights in the endgame: a bonus per tile the king is closer to the center than the edge,
+// a penalty per tile the king is from its own passed pawns, and a bonus per tile the enemy king is from them.
+pub const KING_CENTRALIZATION_BONUS: i32 = 8;
+pub const KING_PASSED_PAWN_DISTANCE_PENALTY: i32 = 4;
+pub const ENEMY_KING_PASSED_PAWN_DISTANCE_BONUS: i32 = 6;
+
+// Space weight (middlegame) per unit of space, which is the number of safe tiles in the center of our
+// half (counting tiles behind our pawns twice) times our pieces, divided by `SPACE_PIECE_DIVISOR`.
+pub const SPACE_WEIGHT: i32 = 2;
+pub const SPACE_PIECE_DIVISOR: i32 = 8;
+pub const SPACE_MASK: [u64; 2] = [
+    CENTER_FILES & (Bitboard::rank(1).board | Bitboard::rank(2).board | Bitboard::rank(3).board),
+    CENTER_FILES & (Bitboard::rank(4).board | Bitboard::rank(5).board | Bitboard::rank(6).board)
+];
+
+// Castling weights (middlegame): a bonus per castling right a side keeps, a bonus once it has castled,
+// and a penalty for a king stranded on the central files, which has neither castled nor kept any rights.
+pub const CASTLING_RIGHT_BONUS: i32 = 10;
+pub const CASTLED_BONUS: i32 = 20;
+pub const STRANDED_KING_PENALTY: i32 = 30;
+pub const STRANDED_KING_FILES: u64 = CENTER_FILES;
+
+// King of the hill is won by bringing the king to one of the center tiles, and three-check by giving this many checks.
+pub const KOTH_HILL: u64 = Bitboard::from_tiles(&[Tile::D4, Tile::E4, Tile::D5, Tile::E5]).board;
+pub const THREE_CHECK_LIMIT: u8 = 3;
+
+// The score of an endgame which is won with correct technique, but not yet a proven mate.
+pub const KNOWN_WIN: i32 = 10000;
+
+// Reported scores are normalized so that 100 cp wins as often between equal players as a one pawn advantage. The
+// internal score of that advantage is 100 / K for the sigmoid constant K fitted to game results by the tuner.
+// Normalized scores are then rounded to a multiple of `EVAL_GRAIN`, so they don't claim more precision than they have.
+pub const NORMALIZE_PAWN_VALUE: i32 = 90;
+pub const EVAL_GRAIN: i32 = 2;
+
+// Endgame scaling constants, as fractions of SCALE_NORMAL.
+pub const SCALE_NORMAL: i32 = 128;
+pub const SCALE_DRAW: i32 = 0;
+pub const SCALE_OPPOSITE_BISHOPS: i32 = 64;
+pub const SCALE_OPPOSITE_BISHOPS_WITH_PIECES: i32 = 104;
+pub const SCALE_ROOK_PAWN_UP: i32 = 96;
+pub const FIFTY_MOVE_SCALE_BASE: i32 = 200;
+
+// How far outside the window the material and piece-square score has to be for the rest of the evaluation to be skipped.
+pub const LAZY_EVAL_MARGIN: i32 = 500;
+
+// The half move counter after which moves which reset it are preferred.
+pub const FIFTY_MOVE_PROGRESS_PLIES: u8 = 50;
+
+// The half move counter after which transposition table scores may hide an upcoming draw.
+pub const FIFTY_MOVE_TT_CUTOFF_PLIES: u8 = 90;
+
+// Bitboard masks of tiles.
+pub const LIGHT_TILES: u64 = 0x55AA_55AA_55AA_55AA;
+pub const DARK_TILES: u64 = !LIGHT_TILES;
+pub const A_FILE: u64 = 0x0101_0101_0101_0101;
+pub const H_FILE: u64 = 0x8080_8080_8080_8080;
+pub const CENTER_FILES: u64 = Bitboard::file(2).board | Bitboard::file(3).board | Bitboard::file(4).board | Bitboard::file(5).board;
+
+// The castling rights kept by a move starting or ending on each tile, as bits of `CastleRights::bits`
+// with white in the low two bits. Only the king and rook starting tiles take rights away.
+pub const CASTLING_RIGHTS_MASK: [u8; 64] = {
+    let mut masks = [0b1111; 64];
+    masks[Tile::A1.index()] = 0b1110;
+    masks[Tile::E1.index()] = 0b1100;
+    masks[Tile::H1.index()] = 0b1101;
+    masks[Tile::A8.index()] = 0b1011;
+    masks[Tile::E8.index()] = 0b0011;
+    masks[Tile::H8.index()] = 0b0111;
+    masks
+};
+
+// Aspiration window constants.
+pub const ASPIRATION_MIN_DEPTH: usize = 4;
+pub const ASPIRATION_DELTA: i32 = 25;
+pub const ASPIRATION_GROWTH: f32 = 1.5;
+pub const ASPIRATION_MAX_FAILURES: usize = 5;
+
+// The largest shift (in cp) of a root move score when opening variety is enabled.
+pub const VARIETY_NOISE: i32 = 8;
+// The largest number of moves (per side) of a game which opening variety may apply to.
+pub const VARIETY_MAX_MOVES: usize = 100;
+
 // Reverse Futility Pruning constants.
 pub const RFP_DEPTH: usize = 5;
-pub const RFP_THRESHOLD: usize = 200;
+pub const RFP_MARGIN: i32 = 200;
+pub const RFP_QUADRATIC_MARGIN: i32 = 0;
+pub const RFP_IMPROVING_MARGIN: i32 = 200;
+
+// The number of quiescence search plies in which quiet checks are searched.
+pub const QS_CHECK_PLIES: usize = 1;
+
+// Futility Pruning constants.
+pub const FP_DEPTH: usize = 5;
+pub const FP_BASE: i32 = 100;
+pub const FP_MARGIN: i32 = 80;
+
+// Late Move Pruning constants. Quiet moves are pruned once `LMP_BASE + LMP_SCALE * depth`
+// moves have been searched, or half as many when the position isn't improving.
+pub const LMP_DEPTH: usize = 5;
+pub const LMP_BASE: usize = 0;
+pub const LMP_SCALE: usize = 8;
+
+// History Pruning constants. Quiet moves are pruned when their history is below `-HP_MARGIN * depth`.
+pub const HP_DEPTH: usize = 3;
+pub const HP_MARGIN: i32 = 512;
+
+// Extension constants.
+pub const MAX_EXTENSIONS: usize = 16;
 
 // Late Move Reduction constants.
 pub const LMR_MOVE_THRESHOLD: usize = 3;
 pub const LMR_REDUCTION_BASE: f32 = 0.77;
 pub const LMR_REDUCTION_DIVISOR: f32 = 2.36;
+pub const LMR_PV_ADJUSTMENT: f32 = 1.0;
+pub const LMR_CHECK_ADJUSTMENT: f32 = 1.0;
+pub const LMR_KILLER_ADJUSTMENT: f32 = 1.0;
+pub const LMR_NOT_IMPROVING_ADJUSTMENT: f32 = 1.0;
+pub const LMR_HISTORY_DIVISOR: f32 = 8192.0;
 
 include!("../../consts/lmr.rs");
 
+// The default transposition table size, in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// The bounds of the transposition table size, in megabytes. No allocation may exceed `isize::MAX` bytes,
+// so the upper bound is kept to a quarter of the address space on targets with small pointers.
+pub const MIN_HASH_MB: usize = 1;
+pub const MAX_HASH_MB: usize = if (isize::MAX as usize >> 21) < 65536 { isize::MAX as usize >> 21 } else { 65536 };
+
+// Transposition tables at least this large (in megabytes) are initialized from every core.
+pub const TT_PARALLEL_INIT_MIN_MB: usize = 64;
+
+// The size of a huge page on common platforms, in bytes.
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+// The size of the static evaluation cache, in megabytes.
+pub const EVAL_CACHE_MB: usize = 2;
+
+// Time management constants, in milliseconds.
+pub const MOVE_OVERHEAD: u64 = 10;
+pub const MIN_THINK_TIME: u64 = 1;
+pub const EMERGENCY_TIME: u64 = 1000;
+pub const DEFAULT_MOVES_TO_GO: u64 = 25;
+
+// The number of nodes between polls of the stop signal and the clock.
+pub const STOP_CHECK_INTERVAL: usize = 2048;
+
+// A search limited to a number of nodes per second sleeps whenever it gets ahead of that rate, checking
+// NPS_GOVERNOR_SLICES times per second's worth of nodes, so it keeps answering `stop` at low rates.
+pub const NPS_GOVERNOR_SLICES: usize = 100;
+pub const NPS_MAX_LIMIT: usize = 100_000_000;
+
+// UCI_Elo limits the node rate, which starts at ELO_MIN_NPS for ELO_MIN and doubles every ELO_PER_NPS_DOUBLING Elo.
+pub const ELO_MIN: usize = 1000;
+pub const ELO_MAX: usize = 2800;
+pub const ELO_MIN_NPS: usize = 1000;
+pub const ELO_PER_NPS_DOUBLING: f64 = 150.0;
+
+// The number of nodes allowed per millisecond of allocated time when searching deterministically.
+pub const DETERMINISTIC_NODES_PER_MS: u64 = 1000;
+
+// Soft time scaling constants.
+pub const TM_SCORE_DROP_WEIGHT: f64 = 0.01;
+pub const TM_MAX_SCORE_DROP_SCALE: f64 = 1.0;
+pub const TM_FAIL_LOW_WEIGHT: f64 = 0.25;
+pub const TM_DECIDED_SCALE: f64 = 0.5;
+pub const TM_DECIDED_EVAL: i32 = 1500;
+pub const TM_MIN_SCALE: f64 = 0.3;
+pub const TM_MAX_SCALE: f64 = 2.5;
+
+// Position class constants for time management, see `PositionClass`.
+pub const TM_SIMPLE_PHASE: u32 = 6;
+pub const TM_COMPLEX_PHASE: u32 = 16;
+pub const TM_KING_DANGER_TILES: u32 = 5;
+pub const TM_RECAPTURE_DEFICIT: i32 = KNIGHT_VALUE - PAWN_VALUE;
+pub const TM_SIMPLE_SCALE: f64 = 0.6;
+pub const TM_COMPLEX_SCALE: f64 = 1.3;
+
+// The total node count of the bench, which identifies the search behaviour of a build.
+pub const BENCH_NODES: usize = 160422;
+
+// The position every game starts from.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// The default time (in ms) the test suite subcommand searches each position for, and the size of its table.
+pub const TEST_SUITE_DEFAULT_MOVETIME: u64 = 1000;
+pub const TEST_SUITE_HASH_MB: usize = 16;
+
+// The default number of nodes a proof-number search may allocate.
+pub const PN_SEARCH_DEFAULT_NODES: usize = 1_000_000;
+
+// The default depth of an analysis split across threads by root move, and the size of the table each thread gets.
+pub const ROOT_SPLIT_DEFAULT_DEPTH: usize = 8;
+pub const ROOT_SPLIT_HASH_MB: usize = 8;
+
+// The bias (in cp) of a root move towards the score analysis stored for the position it leads to, which is
+// that score divided by POSITION_STORE_BIAS_DIVISOR, and never more than POSITION_STORE_MAX_BIAS either way.
+pub const POSITION_STORE_BIAS_DIVISOR: i32 = 8;
+pub const POSITION_STORE_MAX_BIAS: i32 = 25;
+
+// Opening book construction, where moves are scored like polyglot: two points for a win and one for a draw.
+pub const BOOK_WIN_SCORE: u32 = 2;
+pub const BOOK_DRAW_SCORE: u32 = 1;
+pub const BOOK_DEFAULT_MAX_PLIES: usize = 40;
+
+// Book learning, where a score of BOOK_LEARN_SCALE (in cp) after leaving the book doubles
+// (or halves) the weight of the book moves which led there.
+pub const BOOK_LEARN_SCALE: f64 = 200.0;
+pub const BOOK_LEARN_MAX_SCORE: i32 = 400;
+pub const BOOK_LEARN_MIN_DEPTH: usize = 8;
+pub const BOOK_IMPORT_MAX_WEIGHT: u16 = 1 << 12;
+pub const BOOK_DEFAULT_PRUNE_THRESHOLD: i32 = 100;
+
+// Texel tuning defaults.
+pub const TUNER_DEFAULT_EPOCHS: usize = 1000;
+pub const TUNER_DEFAULT_LEARNING_RATE: f64 = 1.0;
+pub const TUNER_DEFAULT_REPORT_INTERVAL: usize = 50;
+pub const TUNER_DEFAULT_CHECKPOINT_INTERVAL: usize = 100;
+pub const TUNER_K_MIN: f64 = 0.0;
+pub const TUNER_K_MAX: f64 = 10.0;
+pub const TUNER_K_ITERATIONS: usize = 40;
+pub const TUNER_ADAM_BETA1: f64 = 0.9;
+pub const TUNER_ADAM_BETA2: f64 = 0.999;
+pub const TUNER_ADAM_EPSILON: f64 = 1e-8;
+
+// Data generation defaults.
+pub const DATAGEN_DEFAULT_GAMES: usize = 100;
+pub const DATAGEN_DEFAULT_NODES: usize = 5000;
+pub const DATAGEN_DEFAULT_RANDOM_PLIES: usize = 8;
+
+// Adjudication of data generation games, with scores in centipawns.
+pub const ADJUDICATION_WIN_SCORE: i32 = 1000;
+pub const ADJUDICATION_WIN_PLIES: usize = 4;
+pub const ADJUDICATION_DRAW_SCORE: i32 = 10;
+pub const ADJUDICATION_DRAW_PLIES: usize = 10;
+pub const ADJUDICATION_DRAW_MIN_PLY: usize = 80;
+
+// Training data filtering defaults.
+pub const FILTER_DEFAULT_EXIT_PLIES: usize = 8;
+pub const FILTER_DEFAULT_SCORE_BOUND: i32 = 3000;
+pub const FILTER_DEFAULT_SEED: u64 = 0x5ACE_D1E0;
+
+// The size of a position in the binary training data formats, in bytes.
+pub const PACKED_POSITION_SIZE: usize = 32;
+
+// The random keys of the polyglot book format.
+include!("../../consts/polyglot.rs");
+
 // PSQT table, stolen from Pesto.
 // NOTE: These PSQT tables assume A8 = 0.
 pub const PIECE_SQUARE_TABLE: [[(i32, i32); 64]; PieceType::COUNT] = [
@@ -0,0 +1,83 @@
+use super::{board::Board, piece_move::Move};
+
+/// A position of a test suite, written in EPD: the first four fields of a FEN,
+/// followed by operations such as `bm Nf3; id "WAC.001";`.
+pub struct EpdEntry {
+    /// The position, with the move counters of a FEN.
+    pub board: Board,
+    /// The name of the position, from the `id` operation.
+    pub id: Option<String>,
+    /// The moves which solve the position, from the `bm` operation.
+    pub best_moves: Vec<Move>,
+    /// The moves which fail the position, from the `am` operation.
+    pub avoid_moves: Vec<Move>
+}
+
+impl EpdEntry {
+    /// Parses a line of an EPD file. Returns `None` for empty lines, and an error for moves which
+    /// aren't legal in the position (as a typo would silently make the position unsolvable).
+    pub fn parse(line: &str) -> Option<Result<EpdEntry, String>> {
+        let line = line.trim();
+        let fields: Vec<&str> = line.splitn(5, ' ').collect();
+        if fields.len() < 4 {
+            return None;
+        }
+
+        let board = Board::new(&fields[..4].join(" "));
+        let mut entry = EpdEntry { board, id: None, best_moves: vec![], avoid_moves: vec![] };
+
+        for operation in fields.get(4).unwrap_or(&"").split(';').map(str::trim).filter(|operation| !operation.is_empty()) {
+            let (opcode, operands) = operation.split_once(' ').unwrap_or((operation, ""));
+            let moves = || operands.split_whitespace()
+                .map(|san| Move::from_san(&entry.board, san).ok_or_else(|| format!("illegal move {} in {}", san, line)))
+                .collect::<Result<Vec<Move>, String>>();
+
+            match opcode {
+                "bm" => entry.best_moves = match moves() { Ok(moves) => moves, Err(error) => return Some(Err(error)) },
+                "am" => entry.avoid_moves = match moves() { Ok(moves) => moves, Err(error) => return Some(Err(error)) },
+                "id" => entry.id = Some(operands.trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+
+        Some(Ok(entry))
+    }
+
+    /// Whether or not a move solves the position: it has to be one of the best moves (if any are
+    /// given), and none of the moves to avoid.
+    pub fn is_solved_by(&self, piece_move: Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&piece_move)) && !self.avoid_moves.contains(&piece_move)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::piece_move::Move;
+
+    use super::EpdEntry;
+
+    #[test]
+    fn test_epd_entry() {
+        let entry = EpdEntry::parse("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";")
+            .expect("expected a position")
+            .expect("expected legal moves");
+
+        assert_eq!(entry.id.as_deref(), Some("WAC.001"));
+        assert!(entry.is_solved_by(Move::from_san(&entry.board, "Qg6").unwrap()));
+        assert!(!entry.is_solved_by(Move::from_san(&entry.board, "Qh3").unwrap()));
+
+        let entry = EpdEntry::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am f3 g4;").unwrap().unwrap();
+        assert!(entry.is_solved_by(Move::from_san(&entry.board, "e4").unwrap()));
+        assert!(!entry.is_solved_by(Move::from_san(&entry.board, "g4").unwrap()));
+
+        // Underpromotions are told apart from promotions to other pieces.
+        let entry = EpdEntry::parse("4k3/1P6/8/8/8/8/K7/8 w - - bm b8=Q+ b8=R+; am b8=N;").unwrap().unwrap();
+        assert!(entry.is_solved_by(Move::from_uci_on(&entry.board, "b7b8r").unwrap()));
+        assert!(!entry.is_solved_by(Move::from_uci_on(&entry.board, "b7b8n").unwrap()));
+        assert!(!entry.is_solved_by(Move::from_uci_on(&entry.board, "b7b8b").unwrap()));
+
+        // Moves which aren't legal are an error rather than an unsolvable position, and empty lines are skipped.
+        assert!(EpdEntry::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e5;").unwrap().is_err());
+        assert!(EpdEntry::parse("   ").is_none());
+    }
+}
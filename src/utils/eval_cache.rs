@@ -0,0 +1,82 @@
+use super::board::Board;
+
+/// Spreads the half move counter over the key, since the fifty move scaling of the
+/// evaluation depends on it while the zobrist key does not.
+const HALF_MOVE_MULTIPLIER: u64 = 0x9E37_79B9_7F4A_7C15;
+/// Marks each side which has castled, since the evaluation rewards castling while
+/// the zobrist key does not see it.
+const CASTLED_KEYS: [u64; 2] = [0xD6E8_FEB8_6659_FD93, 0xA076_1D64_78BD_642F];
+
+/// An entry into the evaluation cache.
+#[derive(Debug, Clone, Copy)]
+struct EvalEntry {
+    /// The key of the evaluated position.
+    key: u64,
+    /// The static evaluation of the position, from the side to move's perspective.
+    evaluation: i32
+}
+
+/// A small always-replace table of static evaluations.
+pub struct EvalCache {
+    /// The entries in the table.
+    table: Vec<Option<EvalEntry>>
+}
+
+impl EvalCache {
+    /// Creates a new evaluation cache from a size in megabytes.
+    pub fn from_mb(size: usize) -> Self {
+        let entries = (size * 1024 * 1024 / std::mem::size_of::<Option<EvalEntry>>()).max(1);
+        EvalCache { table: vec![None; entries] }
+    }
+
+    /// The key of a position, which includes everything the evaluation depends on.
+    fn key(board: &Board) -> u64 {
+        let castled = (0..2).filter(|&color| board.has_castled[color]).fold(0, |key, color| key ^ CASTLED_KEYS[color]);
+        board.zobrist_key ^ castled ^ (board.half_move_counter as u64 + 1).wrapping_mul(HALF_MOVE_MULTIPLIER)
+    }
+
+    /// Indexes the internal table given a key.
+    fn index(&self, key: u64) -> usize {
+        ((key as u128).wrapping_mul(self.table.len() as u128) >> 64) as usize
+    }
+
+    /// Gets the cached evaluation of a position.
+    pub fn get(&self, board: &Board) -> Option<i32> {
+        let key = Self::key(board);
+        self.table[self.index(key)].filter(|entry| entry.key == key).map(|entry| entry.evaluation)
+    }
+
+    /// Stores the evaluation of a position.
+    pub fn store(&mut self, board: &Board, evaluation: i32) {
+        let key = Self::key(board);
+        let index = self.index(key);
+
+        self.table[index] = Some(EvalEntry { key, evaluation });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::board::Board;
+
+    use super::EvalCache;
+
+    #[test]
+    fn test_eval_cache() {
+        let mut cache = EvalCache::from_mb(1);
+        let board = Board::new("r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq - 0 14");
+        assert_eq!(cache.get(&board), None);
+
+        cache.store(&board, 42);
+        assert_eq!(cache.get(&board), Some(42));
+
+        // The same position closer to a fifty move draw evaluates differently.
+        let later = Board::new("r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq - 30 29");
+        assert_eq!(cache.get(&later), None);
+
+        // So does the same position after castling.
+        let mut castled = board.clone();
+        castled.has_castled[0] = true;
+        assert_eq!(cache.get(&castled), None);
+    }
+}
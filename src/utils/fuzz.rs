@@ -0,0 +1,94 @@
+//! Round-trip fuzzing of board state, run with `cargo test --features fuzz`.
+//!
+//! Random games are played from a set of starting positions, and every position
+//! along the way is checked for consistency. Set `FUZZ_SEED` to replay a failure.
+
+use arrayvec::ArrayVec;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::{board::Board, piece_move::{Move, MoveArray}};
+
+/// The number of random games played from each starting position.
+const GAMES_PER_POSITION: usize = 64;
+/// The maximum number of plies in a random game.
+const MAX_GAME_PLIES: usize = 200;
+/// The seed used when `FUZZ_SEED` is not set.
+const DEFAULT_SEED: u64 = 0x5ACE_D1E0;
+
+const STARTING_POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"
+];
+
+/// Generates every legal move in a position.
+fn legal_moves(board: &Board) -> MoveArray {
+    let mut moves = ArrayVec::new();
+    board.generate_moves(&mut moves, false);
+    moves.retain(|piece_move| board.is_legal(*piece_move));
+
+    moves
+}
+
+/// Checks every round trip property of a single position.
+fn check_position(board: &Board, moves: &MoveArray, context: &str) {
+    assert_eq!(board.validate(false), Ok(()), "{}", context);
+
+    // FEN round trip, which should restore every piece of state but the null move distance
+    // and whether a side has castled, neither of which FEN records.
+    let fen = board.to_fen();
+    let mut parsed = Board::new(&fen);
+    parsed.plies_from_null = board.plies_from_null;
+    parsed.has_castled = board.has_castled;
+    assert!(parsed == *board, "fen {} does not round trip: {}", fen, context);
+
+    for piece_move in moves.iter() {
+        // UCI round trip, which must identify the move among every legal move.
+        let uci = piece_move.to_uci();
+        let matches: Vec<&Move> = moves.iter().filter(|other| other.to_uci() == uci).collect();
+        assert_eq!(matches, vec![piece_move], "uci {} is ambiguous: {}", uci, context);
+
+        // SAN round trip.
+        let san = piece_move.to_san(board);
+        assert_eq!(Move::from_san(board, &san), Some(*piece_move), "san {} does not round trip: {}", san, context);
+
+        // Making a move must leave the original board untouched, which is what
+        // an in-place make/unmake pair has to reproduce.
+        let before = board.clone();
+        let child = board.make_move(piece_move, false).expect("expected a legal move");
+        assert!(before == *board, "making {} changed the board: {}", uci, context);
+        assert_eq!(child.validate(false), Ok(()), "after {}: {}", uci, context);
+    }
+}
+
+#[test]
+fn fuzz_round_trips() {
+    let seed = std::env::var("FUZZ_SEED").map_or(DEFAULT_SEED, |seed| seed.parse().expect("FUZZ_SEED should be a number"));
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for fen in STARTING_POSITIONS {
+        for game in 0..GAMES_PER_POSITION {
+            let mut board = Board::new(fen);
+            let mut history = Vec::new();
+
+            for _ in 0..rng.gen_range(1..=MAX_GAME_PLIES) {
+                let moves = legal_moves(&board);
+                let context = format!("seed {} game {} from {} after [{}]", seed, game, fen, history.join(" "));
+                check_position(&board, &moves, &context);
+
+                // Null moves are mixed in occasionally, as long as they are legal.
+                if !board.in_check(board.side_to_move) && rng.gen_ratio(1, 16) {
+                    board = board.make_null_move();
+                    history.push("0000".to_string());
+                    continue;
+                }
+
+                let Some(piece_move) = moves.choose(&mut rng) else { break; };
+                history.push(piece_move.to_uci());
+                board = board.make_move(piece_move, false).expect("expected a legal move");
+            }
+        }
+    }
+}
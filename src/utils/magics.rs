@@ -0,0 +1,190 @@
+use std::io::Write;
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use super::consts::MagicEntry;
+
+/// A sliding piece, whose attacks are looked up in a magic table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slider {
+    Rook,
+    Bishop
+}
+
+impl Slider {
+    /// The directions the piece slides in, as rank and file offsets.
+    fn directions(&self) -> [(i8, i8); 4] {
+        match self {
+            Slider::Rook => [(1, 0), (-1, 0), (0, 1), (0, -1)],
+            Slider::Bishop => [(1, 1), (1, -1), (-1, 1), (-1, -1)]
+        }
+    }
+
+    /// The name of the piece, as used by the constants and attack table files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Slider::Rook => "rook",
+            Slider::Bishop => "bishop"
+        }
+    }
+}
+
+/// Walks every ray of a slider from a square, calling `visit` with each square and whether it is the last one of its ray.
+/// A ray stops after the first square in `blockers`.
+fn walk_rays(slider: Slider, square: usize, blockers: u64, mut visit: impl FnMut(u64, bool)) {
+    for (rank_offset, file_offset) in slider.directions() {
+        let (mut rank, mut file) = ((square / 8) as i8, (square % 8) as i8);
+
+        loop {
+            (rank, file) = (rank + rank_offset, file + file_offset);
+            if !(0..8).contains(&rank) || !(0..8).contains(&file) {
+                break;
+            }
+
+            let bit = 1 << (rank * 8 + file);
+            let (next_rank, next_file) = (rank + rank_offset, file + file_offset);
+            visit(bit, !(0..8).contains(&next_rank) || !(0..8).contains(&next_file));
+
+            if blockers & bit != 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// The squares a slider on a square attacks, up to and including the first blocker of every ray.
+pub fn slider_attacks(slider: Slider, square: usize, blockers: u64) -> u64 {
+    let mut attacks = 0;
+    walk_rays(slider, square, blockers, |bit, _| attacks |= bit);
+    attacks
+}
+
+/// The squares whose blockers change the attacks of a slider on a square: every square of its rays but the last.
+pub fn relevant_blockers(slider: Slider, square: usize) -> u64 {
+    let mut mask = 0;
+    walk_rays(slider, square, 0, |bit, last| if !last { mask |= bit });
+    mask
+}
+
+/// Searches random sparse numbers for a magic which maps every blocker configuration of a square to
+/// an index without a destructive collision, returning the magic and its attack table.
+///
+/// The table is as small as the number of relevant blockers allows (one entry per configuration).
+pub fn find_magic(slider: Slider, square: usize, rng: &mut StdRng) -> (MagicEntry, Vec<u64>) {
+    let mask = relevant_blockers(slider, square);
+    let shift = 64 - mask.count_ones() as u8;
+
+    // Every subset of the mask, enumerated with the carry-rippler trick.
+    let mut configurations = vec![];
+    let mut blockers = 0_u64;
+    loop {
+        configurations.push((blockers, slider_attacks(slider, square, blockers)));
+        blockers = blockers.wrapping_sub(mask) & mask;
+        if blockers == 0 {
+            break;
+        }
+    }
+
+    let mut table = vec![None; 1 << (64 - shift)];
+    loop {
+        let magic = rng.next_u64() & rng.next_u64() & rng.next_u64();
+
+        // Magics which spread the mask over few high bits can't index a full table.
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        table.fill(None);
+        let fits = configurations.iter().all(|&(blockers, attacks)| {
+            let index = (blockers.wrapping_mul(magic) >> shift) as usize;
+            match table[index] {
+                Some(existing) => existing == attacks,
+                None => {
+                    table[index] = Some(attacks);
+                    true
+                }
+            }
+        });
+
+        if fits {
+            let entry = MagicEntry { mask, magic, shift, offset: 0 };
+            return (entry, table.into_iter().map(Option::unwrap_or_default).collect());
+        }
+    }
+}
+
+/// Generates the magics of every square, along with the attack table they index into.
+/// Every square's attacks start at its offset, right after the previous square's.
+pub fn generate_magics(slider: Slider, seed: u64) -> (Vec<MagicEntry>, Vec<u64>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut entries, mut attacks) = (vec![], vec![]);
+
+    for square in 0..64 {
+        let (mut entry, table) = find_magic(slider, square, &mut rng);
+        entry.offset = attacks.len() as u32;
+        attacks.extend(table);
+        entries.push(entry);
+    }
+
+    (entries, attacks)
+}
+
+/// Formats magics as a constant, in the same layout as `consts.rs`.
+pub fn format_magics(slider: Slider, entries: &[MagicEntry]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("MagicEntry {{ mask: 0x{:016X}, magic: 0x{:016X}, shift: {}, offset: {} }}", entry.mask, entry.magic, entry.shift, entry.offset))
+        .collect();
+
+    format!("pub const {}_MAGICS: &[MagicEntry; 64] = &[{}];", slider.name().to_uppercase(), entries.join(", "))
+}
+
+/// Writes an attack table in the same layout as the files in `consts`, which the engine reads as native-endian bitboards.
+pub fn write_attacks(path: &str, attacks: &[u64]) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for attack in attacks {
+        writer.write_all(&attack.to_ne_bytes())?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::utils::{board::{Bitboard, Board}, consts::{get_bishop_mask, get_rook_mask, BISHOP_MAGICS, ROOK_MAGICS}};
+
+    use super::{find_magic, relevant_blockers, slider_attacks, Slider};
+
+    #[test]
+    fn test_find_magic() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for square in [0, 9, 27, 36, 63] {
+            for (slider, magics) in [(Slider::Rook, ROOK_MAGICS), (Slider::Bishop, BISHOP_MAGICS)] {
+                let lookup = |blockers: u64| match slider {
+                    Slider::Rook => get_rook_mask(Board::generate_magic_index(&magics[square], &Bitboard::new(blockers))).board,
+                    Slider::Bishop => get_bishop_mask(Board::generate_magic_index(&magics[square], &Bitboard::new(blockers))).board
+                };
+
+                // The generated masks and attacks agree with the engine's tables.
+                let mask = relevant_blockers(slider, square);
+                assert_eq!(mask, magics[square].mask);
+
+                let (entry, table) = find_magic(slider, square, &mut rng);
+                let mut blockers = 0_u64;
+                loop {
+                    let attacks = slider_attacks(slider, square, blockers);
+                    assert_eq!(attacks, lookup(blockers));
+                    assert_eq!(table[Board::generate_magic_index(&entry, &Bitboard::new(blockers))], attacks);
+
+                    blockers = blockers.wrapping_sub(mask) & mask;
+                    if blockers == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -3,4 +3,12 @@ pub mod piece;
 pub mod consts;
 pub mod piece_move;
 pub mod zobrist;
-pub mod transposition_table;
\ No newline at end of file
+pub mod transposition_table;
+pub mod eval_cache;
+pub mod pgn;
+pub mod polyglot;
+pub mod render;
+pub mod epd;
+pub mod magics;
+#[cfg(all(test, feature = "fuzz"))]
+mod fuzz;
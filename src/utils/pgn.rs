@@ -0,0 +1,211 @@
+use std::io::BufRead;
+
+use super::piece::PieceColor;
+
+/// The result of a game, as written in PGN.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+    /// The game is unfinished, or the result is unknown.
+    Unknown
+}
+
+impl GameResult {
+    /// Parses a PGN result token.
+    pub fn from_pgn(token: &str) -> Option<GameResult> {
+        match token {
+            "1-0" => Some(GameResult::WhiteWin),
+            "0-1" => Some(GameResult::BlackWin),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None
+        }
+    }
+
+    /// The result of a game won by a side.
+    pub fn win_for(color: PieceColor) -> GameResult {
+        match color {
+            PieceColor::White => GameResult::WhiteWin,
+            PieceColor::Black => GameResult::BlackWin
+        }
+    }
+
+    /// Converts the result to a PGN result token.
+    pub fn to_pgn(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWin => "1-0",
+            GameResult::BlackWin => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*"
+        }
+    }
+}
+
+/// A game read from a PGN file.
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// The tag pairs of the game, in the order they appear.
+    pub headers: Vec<(String, String)>,
+    /// The moves of the main line, in SAN.
+    pub moves: Vec<String>,
+    /// The comments following the moves of the main line, indexed like `moves`.
+    /// 
+    /// Only written, comments are skipped when reading.
+    pub comments: Vec<Option<String>>,
+    /// The result of the game.
+    pub result: GameResult
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        PgnGame { headers: Vec::new(), moves: Vec::new(), comments: Vec::new(), result: GameResult::Unknown }
+    }
+}
+
+impl PgnGame {
+    /// Finds the value of a tag pair.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Appends a move (in SAN) to the main line, with an optional comment after it.
+    pub fn push_move(&mut self, san: String, comment: Option<String>) {
+        self.moves.push(san);
+        self.comments.resize(self.moves.len() - 1, None);
+        self.comments.push(comment);
+    }
+
+    /// Writes the game as PGN, with the moves numbered from the standard starting position.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in self.headers.iter() {
+            pgn += &format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+        }
+
+        pgn.push('\n');
+
+        let mut movetext = Vec::new();
+        for (index, san) in self.moves.iter().enumerate() {
+            if index % 2 == 0 {
+                movetext.push(format!("{}.", index / 2 + 1));
+            }
+
+            movetext.push(san.clone());
+
+            // A closing brace would end the comment early, so it is the one character comments can't contain.
+            if let Some(Some(comment)) = self.comments.get(index) {
+                movetext.push(format!("{{{}}}", comment.replace('}', ")")));
+
+                if index % 2 == 0 && index + 1 < self.moves.len() {
+                    movetext.push(format!("{}...", index / 2 + 1));
+                }
+            }
+        }
+
+        movetext.push(self.result.to_pgn().to_string());
+
+        // Lines are wrapped before 80 characters, as the export format asks for.
+        let mut line_length = 0;
+        for token in movetext {
+            if line_length > 0 && line_length + 1 + token.len() > 79 {
+                pgn.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                pgn.push(' ');
+                line_length += 1;
+            }
+
+            line_length += token.len();
+            pgn += &token;
+        }
+
+        pgn + "\n"
+    }
+
+    /// Records a token of movetext, returning whether or not it ended the game.
+    fn push_token(&mut self, token: &str) -> bool {
+        if let Some(result) = GameResult::from_pgn(token) {
+            self.result = result;
+            return true;
+        }
+
+        // Move numbers ("12." or "12...") may be glued to the move itself.
+        let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if !san.is_empty() && !san.starts_with('$') && san != "e.p." {
+            self.moves.push(san.to_string());
+        }
+
+        false
+    }
+}
+
+/// A streaming reader of PGN games, which only keeps the main line of each game.
+///
+/// Comments, variations and numeric annotation glyphs are skipped.
+pub struct PgnReader<R: BufRead> {
+    lines: std::io::Lines<R>
+}
+
+impl<R: BufRead> PgnReader<R> {
+    /// Creates a reader over a PGN source.
+    pub fn new(reader: R) -> Self {
+        PgnReader { lines: reader.lines() }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = PgnGame;
+
+    fn next(&mut self) -> Option<PgnGame> {
+        let mut game = PgnGame::default();
+        let (mut started, mut in_comment, mut variation_depth) = (false, false, 0);
+
+        for line in self.lines.by_ref() {
+            let line = line.expect("failed to read PGN line");
+            let line = line.trim();
+
+            if line.starts_with('%') {
+                continue;
+            }
+
+            if !in_comment && variation_depth == 0 && line.starts_with('[') {
+                let (name, value) = line.trim_start_matches('[').trim_end_matches(']').split_once(' ').unwrap_or((line, ""));
+                game.headers.push((name.to_string(), value.trim().trim_matches('"').to_string()));
+                started = true;
+                continue;
+            }
+
+            let mut token = String::new();
+            for c in line.chars().chain(std::iter::once(' ')) {
+                if in_comment {
+                    in_comment = c != '}';
+                    continue;
+                }
+
+                let delimiter = matches!(c, '{' | ';' | '(' | ')') || c.is_whitespace();
+                if delimiter && !token.is_empty() {
+                    if variation_depth == 0 && game.push_token(&token) {
+                        return Some(game);
+                    }
+
+                    started = true;
+                    token.clear();
+                }
+
+                match c {
+                    '{' => in_comment = true,
+                    ';' => break,
+                    '(' => variation_depth += 1,
+                    ')' => variation_depth = (variation_depth - 1).max(0),
+                    c if c.is_whitespace() => {},
+                    c => token.push(c)
+                }
+            }
+        }
+
+        // A game cut off by the end of the file keeps whatever moves it had.
+        started.then_some(game)
+    }
+}
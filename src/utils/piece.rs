@@ -1,8 +1,9 @@
 use std::ops::Not;
 
 use arrayvec::ArrayVec;
+use strum::EnumCount;
 
-use super::{board::{Bitboard, Board}, consts::{get_bishop_mask, get_rook_mask, BISHOP_MAGICS, BISHOP_VALUE, BLACK_PAWN_MASK, KING_MASKS, KING_VALUE, KNIGHT_MASKS, KNIGHT_VALUE, MAX_LEGAL_MOVES, PAWN_VALUE, QUEEN_VALUE, ROOK_MAGICS, ROOK_VALUE, WHITE_PAWN_MASK}, piece_move::{Move, MoveArray, MoveFlags}, zobrist::ZOBRIST_PIECE_KEYS};
+use super::{board::{Bitboard, Board}, consts::{get_bishop_mask, get_rook_mask, BISHOP_MAGICS, BISHOP_VALUE, BLACK_PAWN_MASK, KING_MASKS, KING_VALUE, KNIGHT_MASKS, KNIGHT_VALUE, MAX_LEGAL_MOVES, PACKED_COLOR_SHIFT, PACKED_TYPE_MASK, PAWN_VALUE, QUEEN_VALUE, ROOK_MAGICS, ROOK_VALUE, WHITE_PAWN_MASK}, piece_move::{Move, MoveArray, MoveFlags}, zobrist::ZOBRIST_PIECE_KEYS};
 
 /// An enum representing the type of chess piece.
 #[derive(Debug, Clone, Copy, PartialEq, strum_macros::EnumCount, strum_macros::EnumIter)]
@@ -28,6 +29,18 @@ impl PieceType {
         }
     }
 
+    /// Converts the piece type to its lowercase letter in FEN and SAN.
+    pub fn to_char(&self) -> char {
+        match self {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k'
+        }
+    }
+
     /// Converts the piece type to a weighted value.
     pub fn get_value(&self) -> i32 {
         match self {
@@ -79,17 +92,23 @@ pub struct Tile {
 
 impl Tile {
     /// Instantiates a new tile.
-    pub fn new(rank: u8, file: u8) -> Option<Tile> {
+    pub const fn new(rank: u8, file: u8) -> Option<Tile> {
         if rank > 7 || file > 7 {
-            // paniep!("invalid position {} {}", rank, file);
             return None;
         }
 
         Some(Tile { rank, file })
     }
 
+    /// Instantiates a tile which is known to be valid, panicking otherwise. In constants,
+    /// an invalid tile fails to compile.
+    pub const fn at(rank: u8, file: u8) -> Tile {
+        assert!(rank < 8 && file < 8, "invalid tile");
+        Tile { rank, file }
+    }
+
     /// Instantiates a new tile from an index.
-    pub fn from_index(index: u8) -> Option<Tile> {
+    pub const fn from_index(index: u8) -> Option<Tile> {
         if index > 63 {
             return None;
         }
@@ -103,10 +122,25 @@ impl Tile {
     }
 
     /// Returns the index of the tile.
-    pub fn index(&self) -> usize {
+    pub const fn index(&self) -> usize {
         (self.rank * 8 + self.file) as usize
     }
 
+    /// The tile as a single bit of a bitboard.
+    pub const fn bit(&self) -> u64 {
+        1 << self.index()
+    }
+
+    /// The rank of the tile from a side's point of view, where its pieces start on the first two ranks.
+    pub const fn relative_rank(&self, side: PieceColor) -> u8 {
+        if matches!(side, PieceColor::White) { self.rank } else { 7 - self.rank }
+    }
+
+    /// The tile on the same file, seen from the other side of the board.
+    pub const fn flip(&self) -> Tile {
+        Tile { rank: 7 - self.rank, file: self.file }
+    }
+
     /// Returns a transformed tile.
     pub fn transform(&self, offset_rank: i8, offset_file: i8) -> Option<Self> {
         Tile::new((self.rank as i8 + offset_rank) as u8, (self.file as i8 + offset_file) as u8)
@@ -126,7 +160,7 @@ impl Tile {
         let rank = code.chars().nth(1).expect("Code doesn't have rank").to_digit(10).expect("Rank can't be converted to number") as u8 - 1;
         let file = code.chars().nth(0).expect("Code doesn't have file") as u8 - b'a';
 
-        Tile::new(rank, file).unwrap()
+        Tile::at(rank, file)
     }
 
     /// Whether or not a code is valid.
@@ -134,6 +168,29 @@ impl Tile {
         code.len() == 2 && code.chars().nth(1).and_then(|c| c.to_digit(10)).is_some()
     }
 
+    /// The tiles strictly between two tiles on the same rank, file, or diagonal.
+    /// 
+    /// Returns an empty bitboard if the tiles are not aligned.
+    pub fn between(&self, other: Tile) -> Bitboard {
+        let rank_difference = other.rank as i8 - self.rank as i8;
+        let file_difference = other.file as i8 - self.file as i8;
+
+        let mut between = Bitboard::ZERO;
+        if *self == other || (rank_difference != 0 && file_difference != 0 && rank_difference.abs() != file_difference.abs()) {
+            return between;
+        }
+
+        let (rank_step, file_step) = (rank_difference.signum(), file_difference.signum());
+        let mut tile = self.transform(rank_step, file_step);
+
+        while let Some(current_tile) = tile && current_tile != other {
+            between.set_bit(current_tile);
+            tile = current_tile.transform(rank_step, file_step);
+        }
+
+        between
+    }
+
     /// The attackers of a tile, irrespective of color.
     pub fn attackers(&self, board: &Board, occupied: Bitboard) -> Bitboard {
         let white_pawns = board.colored_piece(PieceType::Pawn, PieceColor::White);
@@ -155,6 +212,8 @@ impl Tile {
 
     /// The attackers for the tile of a specific color.
     pub fn colored_attackers(&self, board: &Board, enemy_side: PieceColor) -> Bitboard {
+        let occupied = board.occupied();
+
         let enemy_pawns = board.colored_piece(PieceType::Pawn, enemy_side);
         let enemy_knights = board.colored_piece(PieceType::Knight, enemy_side);
         let enemy_bishops = board.colored_piece(PieceType::Bishop, enemy_side) | board.colored_piece(PieceType::Queen, enemy_side);
@@ -166,8 +225,8 @@ impl Tile {
             PieceColor::Black => WHITE_PAWN_MASK[self.index()].1,
         }) & enemy_pawns;
         let knight_attacks = Bitboard::new(KNIGHT_MASKS[self.index()]) & enemy_knights;
-        let bishop_attacks = get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[self.index()], &board.occupied())) & enemy_bishops;
-        let rook_attacks = get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[self.index()], &board.occupied())) & enemy_rooks;
+        let bishop_attacks = get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[self.index()], &occupied)) & enemy_bishops;
+        let rook_attacks = get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[self.index()], &occupied)) & enemy_rooks;
         let king_attacks = Bitboard::new(KING_MASKS[self.index()]) & enemy_kings;
 
         pawn_attacks | knight_attacks | bishop_attacks | rook_attacks | king_attacks
@@ -179,8 +238,33 @@ impl Tile {
     }
 }
 
+/// Every tile by name, so tables of tiles can be written (and checked) at compile time.
+impl Tile {
+    pub const A1: Tile = Tile::at(0, 0); pub const B1: Tile = Tile::at(0, 1); pub const C1: Tile = Tile::at(0, 2); pub const D1: Tile = Tile::at(0, 3); pub const E1: Tile = Tile::at(0, 4); pub const F1: Tile = Tile::at(0, 5); pub const G1: Tile = Tile::at(0, 6); pub const H1: Tile = Tile::at(0, 7);
+    pub const A2: Tile = Tile::at(1, 0); pub const B2: Tile = Tile::at(1, 1); pub const C2: Tile = Tile::at(1, 2); pub const D2: Tile = Tile::at(1, 3); pub const E2: Tile = Tile::at(1, 4); pub const F2: Tile = Tile::at(1, 5); pub const G2: Tile = Tile::at(1, 6); pub const H2: Tile = Tile::at(1, 7);
+    pub const A3: Tile = Tile::at(2, 0); pub const B3: Tile = Tile::at(2, 1); pub const C3: Tile = Tile::at(2, 2); pub const D3: Tile = Tile::at(2, 3); pub const E3: Tile = Tile::at(2, 4); pub const F3: Tile = Tile::at(2, 5); pub const G3: Tile = Tile::at(2, 6); pub const H3: Tile = Tile::at(2, 7);
+    pub const A4: Tile = Tile::at(3, 0); pub const B4: Tile = Tile::at(3, 1); pub const C4: Tile = Tile::at(3, 2); pub const D4: Tile = Tile::at(3, 3); pub const E4: Tile = Tile::at(3, 4); pub const F4: Tile = Tile::at(3, 5); pub const G4: Tile = Tile::at(3, 6); pub const H4: Tile = Tile::at(3, 7);
+    pub const A5: Tile = Tile::at(4, 0); pub const B5: Tile = Tile::at(4, 1); pub const C5: Tile = Tile::at(4, 2); pub const D5: Tile = Tile::at(4, 3); pub const E5: Tile = Tile::at(4, 4); pub const F5: Tile = Tile::at(4, 5); pub const G5: Tile = Tile::at(4, 6); pub const H5: Tile = Tile::at(4, 7);
+    pub const A6: Tile = Tile::at(5, 0); pub const B6: Tile = Tile::at(5, 1); pub const C6: Tile = Tile::at(5, 2); pub const D6: Tile = Tile::at(5, 3); pub const E6: Tile = Tile::at(5, 4); pub const F6: Tile = Tile::at(5, 5); pub const G6: Tile = Tile::at(5, 6); pub const H6: Tile = Tile::at(5, 7);
+    pub const A7: Tile = Tile::at(6, 0); pub const B7: Tile = Tile::at(6, 1); pub const C7: Tile = Tile::at(6, 2); pub const D7: Tile = Tile::at(6, 3); pub const E7: Tile = Tile::at(6, 4); pub const F7: Tile = Tile::at(6, 5); pub const G7: Tile = Tile::at(6, 6); pub const H7: Tile = Tile::at(6, 7);
+    pub const A8: Tile = Tile::at(7, 0); pub const B8: Tile = Tile::at(7, 1); pub const C8: Tile = Tile::at(7, 2); pub const D8: Tile = Tile::at(7, 3); pub const E8: Tile = Tile::at(7, 4); pub const F8: Tile = Tile::at(7, 5); pub const G8: Tile = Tile::at(7, 6); pub const H8: Tile = Tile::at(7, 7);
+}
+
+impl From<u8> for Tile {
+    /// The tile of an index, which has to be valid.
+    fn from(index: u8) -> Self {
+        Tile::at(index / 8, index % 8)
+    }
+}
+
+impl From<Tile> for usize {
+    fn from(tile: Tile) -> Self {
+        tile.index()
+    }
+}
+
 /// A struct representing a chess piece.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Piece {
     /// The type of piece.
     pub piece_type: PieceType,
@@ -194,34 +278,125 @@ impl Piece {
         Piece { piece_type, piece_color }
     }
 
+    /// Converts the piece to its FEN letter, which is uppercase for white.
+    pub fn to_char(&self) -> char {
+        match self.piece_color {
+            PieceColor::White => self.piece_type.to_char().to_ascii_uppercase(),
+            PieceColor::Black => self.piece_type.to_char()
+        }
+    }
+
+    /// Converts the piece to its Unicode chess glyph.
+    pub fn to_glyph(&self) -> char {
+        match (self.piece_color, self.piece_type) {
+            (PieceColor::White, PieceType::Pawn) => '♙',
+            (PieceColor::White, PieceType::Knight) => '♘',
+            (PieceColor::White, PieceType::Bishop) => '♗',
+            (PieceColor::White, PieceType::Rook) => '♖',
+            (PieceColor::White, PieceType::Queen) => '♕',
+            (PieceColor::White, PieceType::King) => '♔',
+            (PieceColor::Black, PieceType::Pawn) => '♟',
+            (PieceColor::Black, PieceType::Knight) => '♞',
+            (PieceColor::Black, PieceType::Bishop) => '♝',
+            (PieceColor::Black, PieceType::Rook) => '♜',
+            (PieceColor::Black, PieceType::Queen) => '♛',
+            (PieceColor::Black, PieceType::King) => '♚'
+        }
+    }
+
+    /// Packs the piece into a single byte for the mailbox board.
+    /// 
+    /// The lower bits hold the piece type (offset by one, so an empty tile is zero)
+    /// and the bit above holds the color.
+    pub const fn pack(&self) -> u8 {
+        ((self.piece_color as u8) << PACKED_COLOR_SHIFT) | (self.piece_type as u8 + 1)
+    }
+
+    /// Unpacks a piece from a mailbox byte, or returns None if the tile is empty.
+    pub const fn unpack(packed: u8) -> Option<Piece> {
+        let piece_type = match packed & PACKED_TYPE_MASK {
+            1 => PieceType::Pawn,
+            2 => PieceType::Knight,
+            3 => PieceType::Bishop,
+            4 => PieceType::Rook,
+            5 => PieceType::Queen,
+            6 => PieceType::King,
+            _ => return None
+        };
+
+        let piece_color = if packed >> PACKED_COLOR_SHIFT == 0 { PieceColor::White } else { PieceColor::Black };
+        Some(Piece { piece_type, piece_color })
+    }
+
     /// Generates a XOR-able 64 bit number to toggle a state in a Zobrist key encoded position.
     pub fn zobrist_key(&self, tile_index: usize) -> u64 {
-        let piece_index = self.piece_color.to_index() + self.piece_type.to_index();
+        let piece_index = self.piece_color.to_index() * PieceType::COUNT + self.piece_type as usize;
         ZOBRIST_PIECE_KEYS[piece_index][tile_index]
     }
 
-    /// Generates a list of moves for the piece.
-    pub fn generate_moves(&self, board: &Board, tile_start: Tile, qsearch: bool, moves: &mut MoveArray) {
+    /// Generates every pseudo-legal move for the piece.
+    pub fn generate_moves(&self, board: &Board, tile_start: Tile, moves: &mut MoveArray) {
+        self.generate_captures(board, tile_start, moves);
+        self.generate_quiets(board, tile_start, moves);
+    }
+
+    /// Generates the noisy moves of the piece: captures (including en passant) and promotions.
+    pub fn generate_captures(&self, board: &Board, tile_start: Tile, moves: &mut MoveArray) {
+        match self.piece_type {
+            PieceType::Pawn => Piece::generate_pawn_captures(board, tile_start, self.piece_color, moves),
+            _ => Piece::push_moves(tile_start, self.attacks(board, tile_start) & board.color(!self.piece_color), moves)
+        }
+    }
+
+    /// Generates the quiet moves of the piece: every other move, including castling.
+    pub fn generate_quiets(&self, board: &Board, tile_start: Tile, moves: &mut MoveArray) {
         match self.piece_type {
-            PieceType::Pawn => Piece::generate_pawn_moves(board, tile_start, self.piece_color, qsearch, moves),
-            PieceType::Knight => Piece::generate_knight_moves(board, tile_start, self.piece_color, qsearch, moves),
-            PieceType::Bishop => Piece::generate_bishop_moves(board, tile_start, self.piece_color, qsearch, moves),
-            PieceType::Rook => Piece::generate_rook_moves(board, tile_start, self.piece_color, qsearch, moves),
-            PieceType::Queen => {
-                Piece::generate_bishop_moves(board, tile_start, self.piece_color, qsearch, moves);
-                Piece::generate_rook_moves(board, tile_start, self.piece_color, qsearch, moves);
+            PieceType::Pawn => Piece::generate_pawn_quiets(board, tile_start, self.piece_color, moves),
+            PieceType::King => {
+                Piece::generate_castling_moves(board, tile_start, self.piece_color, moves);
+                Piece::push_moves(tile_start, self.attacks(board, tile_start) & !board.occupied(), moves);
             },
-            PieceType::King => Piece::generate_king_moves(board, tile_start, self.piece_color, qsearch, moves)
-        };
+            _ => Piece::push_moves(tile_start, self.attacks(board, tile_start) & !board.occupied(), moves)
+        }
+    }
+
+    /// The tiles attacked by the piece from a tile.
+    fn attacks(&self, board: &Board, tile: Tile) -> Bitboard {
+        let index = tile.index();
+
+        match self.piece_type {
+            PieceType::Pawn => Piece::pawn_attacks(tile, self.piece_color),
+            PieceType::Knight => Bitboard::new(KNIGHT_MASKS[index]),
+            PieceType::Bishop => get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[index], &board.occupied())),
+            PieceType::Rook => get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[index], &board.occupied())),
+            PieceType::Queen => get_bishop_mask(Board::generate_magic_index(&BISHOP_MAGICS[index], &board.occupied()))
+                | get_rook_mask(Board::generate_magic_index(&ROOK_MAGICS[index], &board.occupied())),
+            PieceType::King => Bitboard::new(KING_MASKS[index])
+        }
     }
 
-    fn generate_pawn_moves(board: &Board, tile_start: Tile, piece_color: PieceColor, qsearch: bool, moves: &mut MoveArray) {
+    /// Adds a move to every tile of a mask.
+    fn push_moves(tile_start: Tile, mut mask: Bitboard, moves: &mut MoveArray) {
+        while mask.board != 0 {
+            moves.push(Move::new(tile_start, mask.pop_lsb(), MoveFlags::None));
+        }
+    }
+
+    /// The tiles a pawn of a color attacks from a tile.
+    fn pawn_attacks(tile: Tile, piece_color: PieceColor) -> Bitboard {
+        match piece_color {
+            PieceColor::White => Bitboard::new(WHITE_PAWN_MASK[tile.index()].1),
+            PieceColor::Black => Bitboard::new(BLACK_PAWN_MASK[tile.index()].1)
+        }
+    }
+
+    /// The empty tiles a pawn can be pushed to, along with the tile of its double push.
+    fn pawn_pushes(board: &Board, tile_start: Tile, piece_color: PieceColor) -> (Bitboard, Option<Tile>) {
         let direction = if piece_color == PieceColor::White { 1 } else { -1 };
 
-        // Get movement and capture masks.
-        let (mut movement, mut captures) = match piece_color {
-            PieceColor::White => (Bitboard::new(WHITE_PAWN_MASK[tile_start.index()].0), Bitboard::new(WHITE_PAWN_MASK[tile_start.index()].1)),
-            PieceColor::Black => (Bitboard::new(BLACK_PAWN_MASK[tile_start.index()].0), Bitboard::new(BLACK_PAWN_MASK[tile_start.index()].1))
+        let mut movement = match piece_color {
+            PieceColor::White => Bitboard::new(WHITE_PAWN_MASK[tile_start.index()].0),
+            PieceColor::Black => Bitboard::new(BLACK_PAWN_MASK[tile_start.index()].0)
         };
 
         let single_push_tile = tile_start.transform(1 * direction, 0);
@@ -246,103 +421,57 @@ impl Piece {
             }
         }
 
-        // movement &= !board.occupied(); // Avoid moving onto pieces.
-        captures &= board.color(!piece_color); // Allow move only if an enemy piece is there.
+        (movement, double_push_tile)
+    }
 
-        // Check for en passant captures.
-        let mut en_passant = None;
-        if let Some(ep) = board.en_passant {
-            if tile_start.transform(1 * direction, 1) == Some(ep) {
-                en_passant = Some(ep);
-                captures.set_bit(ep);
-            }
+    /// Generates the captures of a pawn, including en passant, and all of its promotions.
+    fn generate_pawn_captures(board: &Board, tile_start: Tile, piece_color: PieceColor, moves: &mut MoveArray) {
+        let attacks = Piece::pawn_attacks(tile_start, piece_color);
+        let promotion_rank = if piece_color == PieceColor::White { 7 } else { 0 };
 
-            if tile_start.transform(1 * direction, -1) == Some(ep) {
-                en_passant = Some(ep);
-                captures.set_bit(ep);
-            }
-        }
+        // Allow captures only if an enemy piece is there. Pushes onto the last rank are promotions, so they are noisy as well.
+        let (pushes, _) = Piece::pawn_pushes(board, tile_start, piece_color);
+        let mut mask = (attacks & board.color(!piece_color)) | (pushes & Bitboard::new(0xFF << (promotion_rank * 8)));
 
-        let mut mask = movement | captures;
         while mask.board != 0 {
             let tile_end = mask.pop_lsb();
 
-            if Some(tile_end) == en_passant {
-                moves.push(Move::new(tile_start, tile_end, MoveFlags::EnPassant));
-            } else if tile_end.rank == (if piece_color == PieceColor::White { 7 } else { 0 }) {
+            if tile_end.rank == promotion_rank {
                 moves.push(Move::new(tile_start, tile_end, MoveFlags::KnightPromotion));
                 moves.push(Move::new(tile_start, tile_end, MoveFlags::BishopPromotion));
                 moves.push(Move::new(tile_start, tile_end, MoveFlags::RookPromotion));
                 moves.push(Move::new(tile_start, tile_end, MoveFlags::QueenPromotion));
             } else {
-                if qsearch && board.board[tile_end.index()].is_none() {
-                    continue;
-                }
-    
-                if Some(tile_end) == double_push_tile {
-                    moves.push(Move::new(tile_start, tile_end, MoveFlags::DoublePush));
-                } else {
-                    moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
-                }
-            }
-        }
-    }
-
-    fn generate_knight_moves(board: &Board, tile_start: Tile, piece_color: PieceColor, qsearch: bool, moves: &mut MoveArray) {
-        let mut mask = Bitboard::new(KNIGHT_MASKS[tile_start.index()]);
-        mask &= !board.color(piece_color); // Avoid capturing friendly pieces.
-
-        let mut mask_clone = mask;
-        while mask_clone.board != 0 {
-            let tile_end = mask_clone.pop_lsb();
-            if qsearch && board.board[tile_end.index()].is_none() {
-                continue;
+                moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
             }
-
-            moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
         }
-    }
-
-    fn generate_rook_moves(board: &Board, tile_start: Tile, piece_color: PieceColor, qsearch: bool, moves: &mut MoveArray) {
-        // Retreive the mask through the magic indexing system.
-        let magic = &ROOK_MAGICS[tile_start.index()];
-
-        let mut mask = get_rook_mask(Board::generate_magic_index(magic, &board.occupied()));
-        mask &= !board.color(piece_color); // Avoid capturing friendly pieces.
 
-        let mut mask_clone = mask;
-        while mask_clone.board != 0 {
-            let tile_end = mask_clone.pop_lsb();
-            if qsearch && board.board[tile_end.index()].is_none() {
-                continue;
-            }
-
-            moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
+        // Check for en passant captures.
+        if let Some(ep) = board.en_passant && attacks.get_bit(ep) {
+            moves.push(Move::new(tile_start, ep, MoveFlags::EnPassant));
         }
     }
 
-    fn generate_bishop_moves(board: &Board, tile_start: Tile, piece_color: PieceColor, qsearch: bool, moves: &mut MoveArray) {
-        // Retreive the mask through the magic indexing system.
-        let magic = &BISHOP_MAGICS[tile_start.index()];
+    /// Generates the pushes of a pawn which don't promote.
+    fn generate_pawn_quiets(board: &Board, tile_start: Tile, piece_color: PieceColor, moves: &mut MoveArray) {
+        let promotion_rank = if piece_color == PieceColor::White { 7 } else { 0 };
+        let (mut pushes, double_push_tile) = Piece::pawn_pushes(board, tile_start, piece_color);
 
-        let mut mask = get_bishop_mask(Board::generate_magic_index(magic, &board.occupied()));
-        mask &= !board.color(piece_color); // Avoid capturing friendly pieces.
+        while pushes.board != 0 {
+            let tile_end = pushes.pop_lsb();
 
-        let mut mask_clone = mask;
-        while mask_clone.board != 0 {
-            let tile_end = mask_clone.pop_lsb();
-            if qsearch && board.board[tile_end.index()].is_none() {
+            if tile_end.rank == promotion_rank {
                 continue;
+            } else if Some(tile_end) == double_push_tile {
+                moves.push(Move::new(tile_start, tile_end, MoveFlags::DoublePush));
+            } else {
+                moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
             }
-
-            moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
         }
     }
 
-    fn generate_king_moves(board: &Board, tile_start: Tile, piece_color: PieceColor, qsearch: bool, moves: &mut MoveArray) {
-        let mut mask = Bitboard::new(KING_MASKS[tile_start.index()]);
-        mask &= !board.color(piece_color); // Avoid capturing friendly pieces.
-
+    /// Generates the castling moves of a king.
+    fn generate_castling_moves(board: &Board, tile_start: Tile, piece_color: PieceColor, moves: &mut MoveArray) {
         let occupied = board.occupied();
 
         match board.castle_rights[piece_color.to_index()] {
@@ -355,7 +484,7 @@ impl Piece {
                     || first_tile.is_under_attack(board, !piece_color) || occupied.get_bit(first_tile)
                     || second_tile.is_under_attack(board, !piece_color) || occupied.get_bit(second_tile));
     
-                    if can_castle && !qsearch {
+                    if can_castle {
                         moves.push(Move::new(tile_start, second_tile, MoveFlags::Castling));
                     }
                 }
@@ -371,7 +500,7 @@ impl Piece {
                     || second_tile.is_under_attack(board, !piece_color) || occupied.get_bit(second_tile)
                     || occupied.get_bit(third_tile));
     
-                    if can_castle && !qsearch {
+                    if can_castle {
                         moves.push(Move::new(tile_start, second_tile, MoveFlags::Castling));
                     }
                 }
@@ -386,7 +515,7 @@ impl Piece {
                         || first_tile.is_under_attack(board, !piece_color) || occupied.get_bit(first_tile)
                         || second_tile.is_under_attack(board, !piece_color) || occupied.get_bit(second_tile));
         
-                        if can_castle && !qsearch {
+                        if can_castle {
                             moves.push(Move::new(tile_start, second_tile, MoveFlags::Castling));
                         }
                     }
@@ -403,7 +532,7 @@ impl Piece {
                         || second_tile.is_under_attack(board, !piece_color) || occupied.get_bit(second_tile)
                         || occupied.get_bit(third_tile));
         
-                        if can_castle && !qsearch {
+                        if can_castle {
                             moves.push(Move::new(tile_start, second_tile, MoveFlags::Castling));
                         }
                     }
@@ -411,16 +540,6 @@ impl Piece {
             },
             CastleRights::None => {}
         }
-
-        let mut mask_clone = mask;
-        while mask_clone.board != 0 {
-            let tile_end = mask_clone.pop_lsb();
-            if qsearch && board.board[tile_end.index()].is_none() {
-                continue;
-            }
-
-            moves.push(Move::new(tile_start, tile_end, MoveFlags::None));
-        }
     }
 }
 
@@ -432,4 +551,21 @@ pub enum CastleRights {
     QueenSide,
     KingSide,
     Both
+}
+
+impl CastleRights {
+    /// The rights as bits, with the queen side in the lowest bit.
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Creates rights from the lowest two bits, with the queen side in the lowest bit.
+    pub fn from_bits(bits: u8) -> CastleRights {
+        match bits & 0b11 {
+            0 => CastleRights::None,
+            1 => CastleRights::QueenSide,
+            2 => CastleRights::KingSide,
+            _ => CastleRights::Both
+        }
+    }
 }
\ No newline at end of file
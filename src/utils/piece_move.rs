@@ -1,9 +1,9 @@
 use arrayvec::ArrayVec;
-use strum::IntoEnumIterator;
+use strum::{EnumCount, IntoEnumIterator};
 
 use crate::engine::search::Searcher;
 
-use super::{board::{Bitboard, Board}, consts::{get_bishop_mask, get_rook_mask, BEST_EVAL, BISHOP_MAGICS, BISHOP_VALUE, BLACK_PAWN_MASK, KING_VALUE, KNIGHT_MASKS, KNIGHT_VALUE, MAX_DEPTH, MAX_LEGAL_MOVES, PAWN_VALUE, QUEEN_VALUE, ROOK_MAGICS, ROOK_VALUE, WHITE_PAWN_MASK, WORST_EVAL}, piece::{PieceColor, PieceType, Tile}};
+use super::{board::{Bitboard, Board}, consts::{get_bishop_mask, get_rook_mask, BEST_EVAL, BISHOP_MAGICS, BISHOP_VALUE, BLACK_PAWN_MASK, FIFTY_MOVE_PROGRESS_PLIES, KING_VALUE, KNIGHT_MASKS, KNIGHT_VALUE, MAX_DEPTH, MAX_LEGAL_MOVES, PAWN_VALUE, QUEEN_VALUE, ROOK_MAGICS, ROOK_VALUE, WHITE_PAWN_MASK, WORST_EVAL}, piece::{Piece, PieceColor, PieceType, Tile}};
 
 pub type MoveArray = ArrayVec<Move, MAX_LEGAL_MOVES>;
 
@@ -32,12 +32,47 @@ pub enum MoveFlags {
 }
 
 impl MoveFlags {
+    /// Every flag, indexed by its packed value.
+    const ALL: [MoveFlags; 8] = [
+        MoveFlags::None, MoveFlags::Castling, MoveFlags::DoublePush, MoveFlags::EnPassant,
+        MoveFlags::KnightPromotion, MoveFlags::BishopPromotion, MoveFlags::RookPromotion, MoveFlags::QueenPromotion
+    ];
+
     /// Whether or not the flag is a promotion.
     pub fn is_promotion(&self) -> bool {
         matches!(self, MoveFlags::KnightPromotion | MoveFlags::BishopPromotion | MoveFlags::RookPromotion | MoveFlags::QueenPromotion)
     }
 }
 
+/// A move packed into 16 bits, as stored in the transposition table: the initial tile in the low six bits,
+/// then the end tile, then the flags (which carry the promotion piece, so underpromotions survive the round trip).
+///
+/// Zero is no move, as a move can't start and end on a1.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    /// The absence of a move.
+    pub const NONE: PackedMove = PackedMove(0);
+
+    /// Unpacks the move, if any.
+    pub fn unpack(self) -> Option<Move> {
+        if self == PackedMove::NONE {
+            return None;
+        }
+
+        let initial = Tile::from((self.0 & 0x3F) as u8);
+        let end = Tile::from((self.0 >> 6 & 0x3F) as u8);
+        Some(Move::new(initial, end, MoveFlags::ALL[(self.0 >> 12 & 0x7) as usize]))
+    }
+}
+
+impl From<Option<Move>> for PackedMove {
+    fn from(piece_move: Option<Move>) -> Self {
+        piece_move.map_or(PackedMove::NONE, |piece_move| piece_move.pack())
+    }
+}
+
 impl Move {
     /// Creates a new move.
     pub fn new(initial: Tile, end: Tile, flags: MoveFlags) -> Self {
@@ -72,6 +107,47 @@ impl Move {
         Move::new(initial, end, metadata)
     }
 
+    /// Finds the legal move written in UCI notation, if any.
+    /// 
+    /// Only the tiles and promotion piece are read from the string, with every other
+    /// flag (castling, en passant, double pushes) resolved from the board.
+    pub fn from_uci_on(board: &Board, uci: &str) -> Option<Move> {
+        let uci = uci.trim().to_ascii_lowercase();
+        let valid_tile = |code: &[u8]| (b'a'..=b'h').contains(&code[0]) && (b'1'..=b'8').contains(&code[1]);
+
+        if !(uci.len() == 4 || uci.len() == 5) || !uci.is_ascii() || !valid_tile(&uci.as_bytes()[0..2]) || !valid_tile(&uci.as_bytes()[2..4]) {
+            return None;
+        }
+
+        let parsed = Move::from_uci(&uci);
+        if uci.len() == 5 && !parsed.flags.is_promotion() {
+            return None;
+        }
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+
+        // A king "capturing" its own rook is how castling is written in Chess960 notation,
+        // which some GUIs send regardless. No other king move can cover that distance.
+        let king = board.piece_at(parsed.initial).filter(|piece| piece.piece_type == PieceType::King);
+        let castles_onto_rook = king.is_some_and(|king| {
+            board.piece_at(parsed.end) == Some(Piece { piece_type: PieceType::Rook, piece_color: king.piece_color })
+        });
+
+        moves.into_iter().find(|piece_move| {
+            let end_matches = if castles_onto_rook {
+                piece_move.flags == MoveFlags::Castling && (piece_move.end.file > piece_move.initial.file) == (parsed.end.file > parsed.initial.file)
+            } else {
+                piece_move.end == parsed.end
+            };
+
+            piece_move.initial == parsed.initial
+                && end_matches
+                && (piece_move.flags == parsed.flags || !parsed.flags.is_promotion() && !piece_move.flags.is_promotion())
+                && board.is_legal(*piece_move)
+        })
+    }
+
     /// Converts a move to its UCI equivalent.
     pub fn to_uci(&self) -> String {
         let mut cur_code = format!("{}{}", self.initial.get_code(), self.end.get_code());
@@ -86,6 +162,85 @@ impl Move {
         cur_code
     }
 
+    /// Converts a move to standard algebraic notation, given the board it is played on.
+    pub fn to_san(&self, board: &Board) -> String {
+        let piece = board.piece_at(self.initial).expect("no piece on initial move tile");
+        let is_capture = self.flags == MoveFlags::EnPassant || !board.is_empty(self.end);
+
+        let mut san = if self.flags == MoveFlags::Castling {
+            if self.end.file > self.initial.file { "O-O".to_string() } else { "O-O-O".to_string() }
+        } else if piece.piece_type == PieceType::Pawn {
+            let mut san = String::new();
+            if is_capture {
+                san.push((self.initial.file + b'a') as char);
+                san.push('x');
+            }
+
+            san += &self.end.get_code();
+            if self.flags.is_promotion() {
+                san.push('=');
+                san.push(self.get_promotion_type().to_char().to_ascii_uppercase());
+            }
+
+            san
+        } else {
+            let mut moves = ArrayVec::new();
+            board.generate_moves(&mut moves, false);
+
+            // Other pieces of the same type which can legally reach the same tile.
+            let rivals: Vec<Tile> = moves.iter()
+                .filter(|other| other.end == self.end && other.initial != self.initial && board.piece_at(other.initial) == Some(piece) && board.is_legal(**other))
+                .map(|other| other.initial)
+                .collect();
+
+            let mut san = piece.piece_type.to_char().to_ascii_uppercase().to_string();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|tile| tile.file != self.initial.file) {
+                    san.push((self.initial.file + b'a') as char);
+                } else if rivals.iter().all(|tile| tile.rank != self.initial.rank) {
+                    san.push((self.initial.rank + b'1') as char);
+                } else {
+                    san += &self.initial.get_code();
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+
+            san += &self.end.get_code();
+            san
+        };
+
+        if let Some(new_board) = board.make_move(self, true) && new_board.in_check(new_board.side_to_move) {
+            let mut replies = ArrayVec::new();
+            new_board.generate_moves(&mut replies, false);
+
+            let mated = !replies.iter().any(|reply| new_board.make_move(reply, true).is_some());
+            san.push(if mated { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    /// Finds the legal move written in standard algebraic notation, if any.
+    /// 
+    /// Check, mate, and annotation suffixes are ignored, and castling may be written with zeros.
+    pub fn from_san(board: &Board, san: &str) -> Option<Move> {
+        let strip = |san: &str| san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+        let san = strip(san.trim());
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+
+        moves.into_iter().find(|piece_move| board.is_legal(*piece_move) && strip(&piece_move.to_san(board)) == san)
+    }
+
+    /// Packs the move into 16 bits, see `PackedMove`.
+    pub fn pack(&self) -> PackedMove {
+        PackedMove(self.initial.index() as u16 | (self.end.index() as u16) << 6 | (self.flags as u16) << 12)
+    }
+
     /// Gets the value of the piece the move is promoting to.
     pub fn get_promotion_type(&self) -> PieceType {
         match self.flags {
@@ -98,12 +253,21 @@ impl Move {
     }
 }
 
+/// What move ordering computes once per node and shares between the moves it scores.
+pub struct OrderingContext {
+    /// The best move stored in the transposition table, if it's pseudo-legal here.
+    pub hash_move: Option<Move>,
+    /// The tiles attacked by enemy pawns.
+    pub pawn_threats: Bitboard
+}
+
 /// A struct which sorts necessary move ordering
 /// score constants and tables of vital move ordering
 /// information.
 pub struct MoveSorter {
-    /// A history table which tracks move scores for quiet beta cutoffs.
-    pub history_table: [[[i32; 64]; 64]; 2],
+    /// A history table which tracks move scores for quiet beta cutoffs,
+    /// indexed by side, moving piece, and destination.
+    pub history_table: [[[i32; 64]; PieceType::COUNT]; 2],
     /// A killer table which tracks quiet moves and their plies if they fail high.
     pub killer_table: [Option<Move>; MAX_DEPTH + 4]
 }
@@ -112,14 +276,21 @@ impl MoveSorter {
     /// Creates a new move sorter.
     pub fn new() -> Self {
         Self {
-            history_table: [[[0; 64]; 64]; 2],
+            history_table: [[[0; 64]; PieceType::COUNT]; 2],
             killer_table: [None; MAX_DEPTH as usize + 4]
         }
     }
 
+    /// Gets the history table index of a move, as (side, piece, destination).
+    fn history_index(board: &Board, piece_move: Move) -> (usize, usize, usize) {
+        let piece = board.piece_at(piece_move.initial).expect("expected piece on initial square for history");
+        (board.side_to_move.to_index(), piece.piece_type as usize, piece_move.end.index())
+    }
+
     /// Gets a move score from history.
     pub fn get_history(&self, board: &Board, piece_move: Move) -> i32 {
-        self.history_table[board.side_to_move as usize][piece_move.initial.index()][piece_move.end.index()]
+        let (side, piece, end) = Self::history_index(board, piece_move);
+        self.history_table[side][piece][end]
     }
 
     /// Updates a move score in the history table.
@@ -127,23 +298,36 @@ impl MoveSorter {
         let clamped_bonus = bonus.clamp(-16384, 16384);
         let old_value = self.get_history(board, piece_move);
 
-        self.history_table[board.side_to_move as usize][piece_move.initial.index()][piece_move.end.index()]
-            += clamped_bonus - old_value * clamped_bonus.abs() / 16384;
+        let (side, piece, end) = Self::history_index(board, piece_move);
+        self.history_table[side][piece][end] += clamped_bonus - old_value * clamped_bonus.abs() / 16384;
+    }
+
+    /// Decays all history scores, so information from older searches
+    /// carries less weight than information from newer searches.
+    pub fn age_history(&mut self) {
+        self.history_table
+            .iter_mut()
+            .flatten()
+            .flatten()
+            .for_each(|score| *score /= 2);
     }
 
     /// Orders moves based off guesses.
     pub fn order_moves(&self, board: &Board, searcher: &Searcher, moves: &mut MoveArray, ply: usize, qsearch: bool) {
         let mut scores: ArrayVec<i32, MAX_LEGAL_MOVES> = ArrayVec::new();
-        let hash_move = searcher.transposition_table.get(board.zobrist_key).and_then(|entry| {
-            if entry.zobrist_key == board.zobrist_key { 
-                entry.best_move
-            } else {
-                None
-            }
-        });
+        let context = OrderingContext {
+            hash_move: searcher.transposition_table.get(board.zobrist_key).and_then(|entry| {
+                if entry.zobrist_key == board.zobrist_key { 
+                    entry.best_move.unpack()
+                } else {
+                    None
+                }
+            }).filter(|&hash_move| board.is_pseudo_legal(hash_move)),
+            pawn_threats: if qsearch { Bitboard::ZERO } else { board.attacks_by(!board.side_to_move).piece(PieceType::Pawn) }
+        };
 
         for piece_move in moves.iter() {
-            scores.push(self.score_move(board, searcher, *piece_move, ply, hash_move, qsearch));
+            scores.push(self.score_move(board, searcher, *piece_move, ply, &context, qsearch));
         }
 
         let mut combined: ArrayVec<(_, _), MAX_LEGAL_MOVES> = scores.iter().copied().zip(moves.iter().copied()).collect();
@@ -154,21 +338,30 @@ impl MoveSorter {
         }
     }
 
-    /// Scores a move.
-    pub fn score_move(&self, board: &Board, searcher: &Searcher, piece_move: Move, ply: usize, hash_move: Option<Move>, qsearch: bool) -> i32 {
-        if hash_move == Some(piece_move) {
+    /// Scores a move, given the hash move and the tiles attacked by enemy pawns.
+    pub fn score_move(&self, board: &Board, searcher: &Searcher, piece_move: Move, ply: usize, context: &OrderingContext, qsearch: bool) -> i32 {
+        if context.hash_move == Some(piece_move) {
             // Hash Move
             return Self::HASH_MOVE;
         }
 
-        let initial_piece = board.board[piece_move.initial.index()]
-            .clone()
-            .expect("expected piece on initial square");
+        let initial_piece = board.piece_at(piece_move.initial).expect("expected piece on initial square");
 
-        // Capture Move
-        if let Some(piece) = board.board[piece_move.end.index()].as_ref() { 
+        // Underpromotions are almost never best, whether or not they capture.
+        if piece_move.flags.is_promotion() && piece_move.get_promotion_type() != PieceType::Queen {
+            return Self::UNDERPROMOTION;
+        }
+
+        // Queen promotions which don't capture are ordered right after good captures, unless the queen is simply lost.
+        if piece_move.flags == MoveFlags::QueenPromotion && board.is_empty(piece_move.end) {
+            return if Self::static_exchange_evaluation(board, piece_move, 0) { Self::QUEEN_PROMOTION } else { Self::BAD_CAPTURE };
+        }
+
+        // Capture Move (en passant lands on an empty tile, but still captures a pawn)
+        let captured = if piece_move.flags == MoveFlags::EnPassant { Some(PieceType::Pawn) } else { board.piece_at(piece_move.end).map(|piece| piece.piece_type) };
+        if let Some(captured) = captured {
             // MVV-LVA
-            let mvv_lva = 100 * piece.piece_type.get_value() - initial_piece.piece_type.get_value();
+            let mvv_lva = 100 * captured.get_value() - initial_piece.piece_type.get_value();
 
             // SEE
             let capture_bucket = if Self::static_exchange_evaluation(board, piece_move, -108) { Self::GOOD_CAPTURE } else { Self::BAD_CAPTURE };
@@ -176,7 +369,7 @@ impl MoveSorter {
             return capture_bucket + mvv_lva;
         }
 
-        let is_quiet = !qsearch && piece_move.flags != MoveFlags::EnPassant && board.board[piece_move.end.index()].is_none();
+        let is_quiet = !qsearch && board.is_empty(piece_move.end);
         if is_quiet {
             // History + Killer Heuristics
             let killer_move = searcher.get_search_entry(ply).expect("expected killer move at ply in move ordering").killer_move;
@@ -184,9 +377,15 @@ impl MoveSorter {
 
             if killer_move == Some(piece_move) {
                 return Self::KILLER_MOVE + history_score;
-            } else {
-                return Self::QUIET_MOVE + history_score;
             }
+
+            // Pieces moving onto tiles attacked by enemy pawns are likely to be lost.
+            let unsafe_penalty = if initial_piece.piece_type != PieceType::Pawn && context.pawn_threats.get_bit(piece_move.end) { Self::UNSAFE_QUIET_PENALTY } else { 0 };
+
+            // Pawn moves reset the fifty move counter, so prefer them once it gets high.
+            let progress_bonus = if initial_piece.piece_type == PieceType::Pawn && board.half_move_counter >= FIFTY_MOVE_PROGRESS_PLIES { Self::PROGRESS_BONUS } else { 0 };
+
+            return Self::QUIET_MOVE + history_score - unsafe_penalty + progress_bonus;
         }
 
         0
@@ -196,7 +395,7 @@ impl MoveSorter {
     pub fn capture_move_value(board: &Board, piece_move: Move) -> i32 {
         let mut value = 0;
 
-        if let Some(piece) = board.board[piece_move.end.index()].as_ref() {
+        if let Some(piece) = board.piece_at(piece_move.end) {
             value = Self::SEE_VALUES[piece.piece_type as usize];
         }
         
@@ -217,7 +416,7 @@ impl MoveSorter {
         let mut next_victim = if piece_move.flags.is_promotion() { 
             piece_move.get_promotion_type()
         } else { 
-            board.board[piece_move.initial.index()].as_ref().unwrap().piece_type
+            board.piece_at(piece_move.initial).unwrap().piece_type
         };
 
         // The current balance, where positive values mean winning.
@@ -240,7 +439,8 @@ impl MoveSorter {
         occupied.clear_bit(piece_move.initial);
         occupied.set_bit(piece_move.end);
         if piece_move.flags == MoveFlags::EnPassant {
-            occupied.set_bit(board.en_passant.unwrap());
+            // The captured pawn sits behind the en passant tile, and may have been blocking an attacker.
+            occupied.clear_bit(Tile::at(piece_move.initial.rank, piece_move.end.file));
         }
 
         // Generate all attackers.
@@ -300,18 +500,26 @@ impl MoveSorter {
 impl MoveSorter {
     const HASH_MOVE: i32 = 100_000_000;
     const GOOD_CAPTURE: i32 = 20_000_000;
+    const QUEEN_PROMOTION: i32 = 19_000_000;
     const KILLER_MOVE: i32 = 15_000_000;
     const COUNTER_MOVE: i32 = 10_000_000;
     const QUIET_MOVE: i32 = 5_000_000;
+    const UNSAFE_QUIET_PENALTY: i32 = 32_768;
+    const PROGRESS_BONUS: i32 = 32_768;
     const BAD_CAPTURE: i32 = 0;
+    const UNDERPROMOTION: i32 = -10_000_000;
 
     const SEE_VALUES: [i32; 6] = [PAWN_VALUE, KNIGHT_VALUE, BISHOP_VALUE, ROOK_VALUE, QUEEN_VALUE, KING_VALUE];
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::{board::Board, piece_move::MoveSorter};
-    use super::Move;
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use arrayvec::ArrayVec;
+
+    use crate::{engine::{search::Searcher, time_manager::TimeManager}, utils::{board::Board, piece_move::MoveSorter}};
+    use super::{Move, MoveFlags, PackedMove};
 
     const SEE: &str = "6k1/1pp4p/p1pb4/6q1/3P1pRr/2P4P/PP1Br1P1/5RKN w - - | f1f4 | -100 | P - R + B
 5rk1/1pp2q1p/p1pb4/8/3P1NP1/2P5/1P1BQ1P1/5RK1 b - - | d6f4 | 0 | -N + B
@@ -431,6 +639,85 @@ r1bqk1nr/pppp1ppp/2n5/1B2p3/1b2P3/5N2/PPPP1PPP/RNBQK2R w KQkq - | e1g1 | 0";
         (fen, piece_move, threshold)
     }
 
+    #[test]
+    fn test_see_en_passant() {
+        let suite: Vec<(&str, &str, i32, bool)> = vec![
+            // An undefended pawn is won outright.
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100, true),
+            // A bishop recaptures, so the exchange is only even.
+            ("4k3/2b5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 0, true),
+            ("4k3/2b5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 1, false),
+            // The captured pawn was blocking the rook, which now defends the capturing pawn from the queen.
+            ("4k3/2q5/8/3pP3/8/8/8/3RK3 w - d6 0 1", "e5d6", 100, true),
+            ("4k3/2q5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100, false),
+            // The same from black's side.
+            ("4k3/8/8/8/3Pp3/8/2Q5/3rK3 b - d3 0 1", "e4d3", 100, true),
+        ];
+
+        for (fen, piece_move, threshold, result) in suite.into_iter() {
+            let board = Board::new(fen);
+            let piece_move = Move::from_uci_on(&board, piece_move).expect("expected a legal en passant capture");
+            assert_eq!(piece_move.flags, MoveFlags::EnPassant);
+
+            assert_eq!(MoveSorter::static_exchange_evaluation(&board, piece_move, threshold), result, "{} {}", fen, threshold);
+        }
+    }
+
+    #[test]
+    fn test_en_passant_ordering() {
+        let board = Board::new("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+        searcher.move_sorter.order_moves(&board, &searcher, &mut moves, 0, false);
+
+        // A winning en passant capture is ordered ahead of every quiet move.
+        assert_eq!(moves[0].to_uci(), "e5d6");
+        assert_eq!(moves[0].flags, MoveFlags::EnPassant);
+    }
+
+    #[test]
+    fn test_packed_move() {
+        // Every promotion piece (and every other flag) survives packing, with and without a capture.
+        for fen in ["n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "rnbqkb1r/ppppp1pp/7n/4Pp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"] {
+            let board = Board::new(fen);
+            let mut moves = ArrayVec::new();
+            board.generate_moves(&mut moves, false);
+
+            for piece_move in moves {
+                assert_eq!(piece_move.pack().unpack(), Some(piece_move), "{}", fen);
+            }
+        }
+
+        let underpromotion = Move::from_uci("b7a8n");
+        assert_ne!(underpromotion.pack(), Move::from_uci("b7a8q").pack());
+        assert_eq!(PackedMove::from(None).unpack(), None);
+        assert_eq!(std::mem::size_of::<PackedMove>(), 2);
+    }
+
+    #[test]
+    fn test_promotion_ordering() {
+        let board = Board::new("1n2k3/P1P5/8/8/8/8/5PPP/4K3 w - - 0 1");
+        let searcher = Searcher::new(TimeManager::default(), 1, Arc::new(AtomicBool::new(false)));
+
+        let mut moves = ArrayVec::new();
+        board.generate_moves(&mut moves, false);
+        searcher.move_sorter.order_moves(&board, &searcher, &mut moves, 0, false);
+
+        let position = |uci: &str| moves.iter().position(|piece_move| piece_move.to_uci() == uci).unwrap();
+        let last_quiet = moves.iter().rposition(|piece_move| !piece_move.flags.is_promotion()).unwrap();
+
+        // Capturing the knight while queening comes first, then the queen promotions which don't capture, then quiets.
+        assert!(position("a7b8q") < 2 && position("c7b8q") < 2);
+        assert!(position("a7a8q") < 4 && position("c7c8q") < 4);
+
+        // Underpromotions come after everything else, even when they capture.
+        for uci in ["a7b8n", "c7b8r", "c7c8n", "c7c8b", "a7a8r"] {
+            assert!(position(uci) > last_quiet, "{}", uci);
+        }
+    }
+
     #[test]
     fn test_see_2() {
         // Ensure SEE values are [100, 300, 300, 500, 900, 0] in this case.
@@ -453,4 +740,59 @@ r1bqk1nr/pppp1ppp/2n5/1B2p3/1b2P3/5N2/PPPP1PPP/RNBQK2R w KQkq - | e1g1 | 0";
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_uci_resolution() {
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        assert_eq!(Move::from_uci_on(&board, "e1g1").map(|piece_move| piece_move.flags), Some(MoveFlags::Castling));
+        assert_eq!(Move::from_uci_on(&board, "a2a4").map(|piece_move| piece_move.flags), Some(MoveFlags::DoublePush));
+        assert_eq!(Move::from_uci_on(&board, "E5F7").map(|piece_move| piece_move.to_uci()), Some("e5f7".to_string()));
+        assert_eq!(Move::from_uci_on(&board, "e1e3"), None);
+        assert_eq!(Move::from_uci_on(&board, "0000"), None);
+        assert_eq!(Move::from_uci_on(&board, "z9a1"), None);
+
+        let board = Board::new("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        assert_eq!(Move::from_uci_on(&board, "e5f6").map(|piece_move| piece_move.flags), Some(MoveFlags::EnPassant));
+
+        let board = Board::new("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(Move::from_uci_on(&board, "b7b8N").map(|piece_move| piece_move.flags), Some(MoveFlags::KnightPromotion));
+        assert_eq!(Move::from_uci_on(&board, "b7b8"), None);
+        assert_eq!(Move::from_uci_on(&board, "b7b8x"), None);
+
+        // Castling written as the king taking its own rook.
+        let board = Board::new("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(Move::from_uci_on(&board, "e1h1").map(|piece_move| (piece_move.to_uci(), piece_move.flags)), Some(("e1g1".to_string(), MoveFlags::Castling)));
+        assert_eq!(Move::from_uci_on(&board, "e1a1").map(|piece_move| (piece_move.to_uci(), piece_move.flags)), Some(("e1c1".to_string(), MoveFlags::Castling)));
+
+        let board = Board::new("r3k2r/8/8/8/8/8/8/R3K2R w kq - 0 1");
+        assert_eq!(Move::from_uci_on(&board, "e1h1"), None);
+    }
+
+    #[test]
+    fn test_san() {
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", "Nf3"),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "e1g1", "O-O"),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "e1c1", "O-O-O"),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "e2a6", "Bxa6"),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", "e5f6", "exf6"),
+            ("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", "b8=Q+"),
+            ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", "Ra8#"),
+            ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1", "Rad1"),
+            ("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1", "a1a2", "R1a2"),
+            ("4k3/8/8/1N6/8/1N3N2/8/4K3 w - - 0 1", "b3d4", "Nb3d4")
+        ];
+
+        for (fen, uci, san) in cases {
+            let board = Board::new(fen);
+            let piece_move = Move::from_san(&board, san).unwrap_or_else(|| panic!("{} should be legal", san));
+
+            assert_eq!(piece_move.to_uci(), uci);
+            assert_eq!(piece_move.to_san(&board), san);
+        }
+
+        let board = Board::new(cases[0].0);
+        assert_eq!(Move::from_san(&board, "Nf6"), None);
+        assert_eq!(Move::from_san(&board, "e4!?").map(|piece_move| piece_move.to_uci()), Some("e2e4".to_string()));
+    }
+}
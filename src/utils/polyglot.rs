@@ -0,0 +1,159 @@
+use std::io::Write;
+
+use arrayvec::ArrayVec;
+
+use super::{board::Board, consts::POLYGLOT_RANDOMS, piece::{CastleRights, Piece, PieceColor, PieceType, Tile}, piece_move::{Move, MoveFlags}};
+
+/// The offset of the castling keys in `POLYGLOT_RANDOMS`.
+const CASTLING_OFFSET: usize = 768;
+/// The offset of the en passant keys in `POLYGLOT_RANDOMS`.
+const EN_PASSANT_OFFSET: usize = 772;
+/// The offset of the side to move key in `POLYGLOT_RANDOMS`.
+const TURN_OFFSET: usize = 780;
+
+/// A single entry of a polyglot opening book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolyglotEntry {
+    /// The polyglot key of the position.
+    pub key: u64,
+    /// The move, in polyglot's encoding.
+    pub piece_move: u16,
+    /// The weight of the move relative to the other moves in the position.
+    pub weight: u16,
+    /// Data used for book learning.
+    pub learn: u32
+}
+
+impl PolyglotEntry {
+    /// Serializes the entry as it is stored in a book file (big-endian).
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.piece_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// Computes the polyglot key of a position, which differs from the engine's own zobrist key.
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+
+    for index in 0..64 {
+        let tile = Tile::from(index);
+        if let Some(piece) = board.piece_at(tile) {
+            // Polyglot interleaves colors, starting with black: bp, wp, bn, wn, ...
+            let kind = 2 * (piece.piece_type.to_index() - 2) + (piece.piece_color == PieceColor::White) as usize;
+            key ^= POLYGLOT_RANDOMS[64 * kind + tile.index()];
+        }
+    }
+
+    for (color, offset) in [(PieceColor::White, 0), (PieceColor::Black, 2)] {
+        let rights = board.castle_rights[color.to_index()];
+
+        if matches!(rights, CastleRights::KingSide | CastleRights::Both) {
+            key ^= POLYGLOT_RANDOMS[CASTLING_OFFSET + offset];
+        }
+
+        if matches!(rights, CastleRights::QueenSide | CastleRights::Both) {
+            key ^= POLYGLOT_RANDOMS[CASTLING_OFFSET + offset + 1];
+        }
+    }
+
+    // The en passant file only counts when a pawn of the side to move could capture there.
+    if let Some(en_passant) = board.en_passant {
+        let rank = if board.side_to_move == PieceColor::White { 4 } else { 3 };
+        let pawn = Piece::new(PieceType::Pawn, board.side_to_move);
+
+        let can_capture = [-1, 1].iter()
+            .filter_map(|offset| Tile::new(rank, (en_passant.file as i8 + offset) as u8))
+            .any(|tile| board.piece_at(tile) == Some(pawn));
+
+        if can_capture {
+            key ^= POLYGLOT_RANDOMS[EN_PASSANT_OFFSET + en_passant.file as usize];
+        }
+    }
+
+    if board.side_to_move == PieceColor::White {
+        key ^= POLYGLOT_RANDOMS[TURN_OFFSET];
+    }
+
+    key
+}
+
+/// Encodes a move in polyglot's format, where castling is written as the king capturing its own rook.
+pub fn encode_move(piece_move: &Move) -> u16 {
+    let end = match piece_move.flags {
+        MoveFlags::Castling => Tile { rank: piece_move.end.rank, file: if piece_move.end.file > piece_move.initial.file { 7 } else { 0 } },
+        _ => piece_move.end
+    };
+
+    let promotion = match piece_move.flags {
+        MoveFlags::KnightPromotion => 1,
+        MoveFlags::BishopPromotion => 2,
+        MoveFlags::RookPromotion => 3,
+        MoveFlags::QueenPromotion => 4,
+        _ => 0
+    };
+
+    (end.file as u16) | (end.rank as u16) << 3 | (piece_move.initial.file as u16) << 6 | (piece_move.initial.rank as u16) << 9 | promotion << 12
+}
+
+/// Finds the legal move in a position matching a move in polyglot's encoding.
+pub fn decode_move(board: &Board, piece_move: u16) -> Option<Move> {
+    let mut moves = ArrayVec::new();
+    board.generate_moves(&mut moves, false);
+
+    moves.into_iter().find(|candidate| encode_move(candidate) == piece_move && board.is_legal(*candidate))
+}
+
+/// Writes a book file, given entries sorted by key.
+pub fn write_book(path: &str, entries: &[PolyglotEntry]) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    for entry in entries {
+        writer.write_all(&entry.to_bytes())?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::Board, piece::Tile, piece_move::{Move, MoveFlags}};
+
+    use super::{encode_move, polyglot_key, PolyglotEntry};
+
+    #[test]
+    fn test_polyglot_keys() {
+        // The reference keys from the polyglot format specification.
+        let positions = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+            ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+            ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+            ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+            ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560)
+        ];
+
+        for (fen, key) in positions {
+            assert_eq!(polyglot_key(&Board::new(fen)), key, "wrong key for {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_polyglot_moves() {
+        assert_eq!(encode_move(&Move::from_uci("e2e4")), 0x031C);
+        assert_eq!(encode_move(&Move::new(Tile::from_code("e1"), Tile::from_code("g1"), MoveFlags::Castling)), 0x0107);
+        assert_eq!(encode_move(&Move::new(Tile::from_code("e8"), Tile::from_code("c8"), MoveFlags::Castling)), 0x0F38);
+        assert_eq!(encode_move(&Move::new(Tile::from_code("a7"), Tile::from_code("a8"), MoveFlags::QueenPromotion)), 0x4C38);
+
+        let entry = PolyglotEntry { key: 0x463b96181691fc9c, piece_move: 0x031C, weight: 2, learn: 0 };
+        assert_eq!(entry.to_bytes(), [0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c, 0x03, 0x1C, 0x00, 0x02, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::IsTerminal;
+
+use super::{board::Board, piece::{PieceColor, Tile}, piece_move::Move};
+
+/// The background of light tiles, as RGB.
+const LIGHT_TILE: (u8, u8, u8) = (240, 217, 181);
+/// The background of dark tiles, as RGB.
+const DARK_TILE: (u8, u8, u8) = (181, 136, 99);
+/// The background of light tiles touched by the last move, as RGB.
+const LIGHT_HIGHLIGHT: (u8, u8, u8) = (205, 210, 106);
+/// The background of dark tiles touched by the last move, as RGB.
+const DARK_HIGHLIGHT: (u8, u8, u8) = (170, 162, 58);
+/// The color of white pieces, as RGB.
+const WHITE_PIECE: (u8, u8, u8) = (255, 255, 255);
+/// The color of black pieces, as RGB.
+const BLACK_PIECE: (u8, u8, u8) = (0, 0, 0);
+
+/// How a board is drawn as text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderStyle {
+    /// Whether pieces are drawn as Unicode glyphs rather than FEN letters.
+    pub unicode: bool,
+    /// Whether tiles and pieces are drawn with ANSI colors.
+    pub color: bool
+}
+
+impl RenderStyle {
+    /// Plain ASCII without colors, which displays correctly anywhere.
+    pub const PLAIN: RenderStyle = RenderStyle { unicode: false, color: false };
+
+    /// The richest style the output supports: colors are only used when stdout is an
+    /// interactive terminal, and dumb terminals (or `NO_COLOR`) fall back to plain text.
+    pub fn detect() -> Self {
+        let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+        let no_color = std::env::var_os("NO_COLOR").is_some();
+
+        RenderStyle {
+            unicode: !dumb,
+            color: !dumb && !no_color && std::io::stdout().is_terminal()
+        }
+    }
+}
+
+/// Draws a board from white's perspective with file and rank coordinates,
+/// highlighting the tiles of the last move played (if any).
+///
+/// Without colors, highlighted tiles are wrapped in brackets instead.
+pub fn render(board: &Board, style: RenderStyle, last_move: Option<Move>) -> String {
+    let mut output = String::new();
+
+    for rank in (0..8).rev() {
+        output.push_str(&format!("{} ", rank + 1));
+
+        for file in 0..8 {
+            let tile = Tile::at(rank, file);
+            let highlighted = last_move.is_some_and(|piece_move| piece_move.initial == tile || piece_move.end == tile);
+
+            let piece = board.piece_at(tile);
+
+            let symbol = match piece {
+                Some(piece) if style.unicode => piece.to_glyph(),
+                Some(piece) => piece.to_char(),
+                // Empty tiles are left blank when the background shows the board.
+                None if style.color => ' ',
+                None if style.unicode => '·',
+                None => '.'
+            };
+
+            if style.color {
+                let light = (rank + file) % 2 == 1;
+                let (r, g, b) = match (light, highlighted) {
+                    (true, false) => LIGHT_TILE,
+                    (false, false) => DARK_TILE,
+                    (true, true) => LIGHT_HIGHLIGHT,
+                    (false, true) => DARK_HIGHLIGHT
+                };
+
+                let white = piece.is_some_and(|piece| piece.piece_color == PieceColor::White);
+                let foreground = if white { WHITE_PIECE } else { BLACK_PIECE };
+
+                output.push_str(&paint(&format!(" {} ", symbol), foreground, (r, g, b), white));
+            } else if highlighted {
+                output.push_str(&format!("[{}]", symbol));
+            } else {
+                output.push_str(&format!(" {} ", symbol));
+            }
+        }
+
+        output.push('\n');
+    }
+
+    output.push_str("   a  b  c  d  e  f  g  h\n");
+
+    output
+}
+
+/// Wraps text in the ANSI escape codes for a truecolor foreground and background.
+///
+/// The style has already decided whether colors are wanted, so the codes are
+/// written directly rather than through the global terminal detection of `colored`.
+fn paint(text: &str, (fr, fg, fb): (u8, u8, u8), (br, bg, bb): (u8, u8, u8), bold: bool) -> String {
+    format!("\x1b[{}38;2;{};{};{};48;2;{};{};{}m{}\x1b[0m", if bold { "1;" } else { "" }, fr, fg, fb, br, bg, bb, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{board::Board, piece_move::Move};
+
+    use super::{render, RenderStyle};
+
+    #[test]
+    fn test_render() {
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        let last_move = Move::from_uci("e2e4");
+
+        let plain = render(&board, RenderStyle::PLAIN, Some(last_move));
+        let lines: Vec<&str> = plain.lines().collect();
+
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "8  r  n  b  q  k  b  n  r ");
+        assert_eq!(lines[4], "4  .  .  .  . [P] .  .  . ");
+        assert_eq!(lines[6], "2  P  P  P  P [.] P  P  P ");
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h");
+
+        // Unicode glyphs replace the letters, and no escape codes are written without colors.
+        let unicode = render(&board, RenderStyle { unicode: true, color: false }, None);
+        assert!(unicode.starts_with("8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜ "));
+        assert!(!unicode.contains('\x1b'));
+
+        // Colors are written whenever the style asks for them, terminal or not.
+        let colored = render(&board, RenderStyle { unicode: false, color: true }, Some(last_move));
+        assert!(colored.lines().nth(4).is_some_and(|line| line.contains("\x1b[1;38;2;255;255;255;48;2;205;210;106m P \x1b[0m")));
+        assert!(colored.lines().next().is_some_and(|line| line.contains("\x1b[38;2;0;0;0;48;2;240;217;181m r \x1b[0m")));
+    }
+}
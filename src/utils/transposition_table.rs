@@ -1,4 +1,4 @@
-use super::piece_move::Move;
+use super::{consts::{HUGE_PAGE_SIZE, MAX_HASH_MB, MIN_HASH_MB, TT_PARALLEL_INIT_MIN_MB}, piece_move::PackedMove};
 
 /// An entry into the transposition table.
 #[derive(Debug, Clone, PartialEq)]
@@ -11,8 +11,10 @@ pub struct TTEntry {
     pub evaluation: i32,
     /// The type of evaluation from the search.
     pub evaluation_type: EvaluationType,
-    /// The best move from the search.
-    pub best_move: Option<Move>
+    /// The best move from the search, packed to keep entries small.
+    pub best_move: PackedMove,
+    /// The search generation in which the entry was stored.
+    pub generation: u8
 }
 
 /// The type of evaluation from a search.
@@ -32,7 +34,9 @@ pub struct TranspositionTable {
     /// The entries in the table.
     table: Vec<Option<TTEntry>>,
     /// The number of entries/buckets in the table.
-    buckets: usize
+    buckets: usize,
+    /// The current search generation, incremented for every new search.
+    generation: u8
 }
 
 impl TranspositionTable {
@@ -41,34 +45,94 @@ impl TranspositionTable {
     /// NOTE: Bucket size doesn't need to be a power of two
     /// since the indexer does not use a modulo.
     pub fn new(buckets: usize) -> Self {
+        let table = Self::allocate(buckets);
+
         Self {
-            table: std::iter::repeat_with(|| None).take(buckets).collect(),
-            buckets
+            buckets: table.len(),
+            table,
+            generation: 0
+        }
+    }
+
+    /// The number of threads which should touch a table of a given size.
+    fn init_threads(buckets: usize) -> usize {
+        if buckets * std::mem::size_of::<Option<TTEntry>>() < TT_PARALLEL_INIT_MIN_MB * 1024 * 1024 {
+            return 1;
+        }
+
+        std::thread::available_parallelism().map_or(1, |threads| threads.get())
+    }
+
+    /// Allocates a table of empty entries.
+    ///
+    /// Large tables are backed by huge pages where the OS supports them, and are
+    /// initialized from several threads, so that (with first-touch NUMA policies)
+    /// their pages end up spread across the nodes of the threads which use them.
+    ///
+    /// If the memory isn't available, the size is halved until it is.
+    fn allocate(mut buckets: usize) -> Vec<Option<TTEntry>> {
+        let mut table = Vec::new();
+        while table.try_reserve_exact(buckets).is_err() {
+            assert!(buckets > 1, "failed to allocate a transposition table");
+            buckets /= 2;
         }
+
+        advise_huge_pages(table.as_mut_ptr() as usize, buckets * std::mem::size_of::<Option<TTEntry>>());
+
+        let chunk_size = buckets.div_ceil(Self::init_threads(buckets)).max(1);
+        std::thread::scope(|scope| {
+            for chunk in table.spare_capacity_mut()[..buckets].chunks_mut(chunk_size) {
+                scope.spawn(|| chunk.iter_mut().for_each(|entry| { entry.write(None); }));
+            }
+        });
+
+        // SAFETY: the first `buckets` entries were all initialized above.
+        unsafe { table.set_len(buckets) };
+        table
+    }
+
+    /// Clamps a size in megabytes to the supported range.
+    pub fn clamp_mb(size: usize) -> usize {
+        size.clamp(MIN_HASH_MB, MAX_HASH_MB)
+    }
+
+    /// The number of buckets which fit in a size in megabytes, once clamped.
+    fn buckets_in_mb(size: usize) -> usize {
+        Self::clamp_mb(size) * 1024 * 1024 / std::mem::size_of::<Option<TTEntry>>()
     }
 
     /// Creates a new transposition table from a size in megabytes.
     pub fn from_mb(size: usize) -> Self {
-        let desired_size = size * 1024 * 1024;
-        let entry_size = std::mem::size_of::<TTEntry>();
-        let buckets = desired_size / entry_size;
-
-        TranspositionTable::new(buckets)
+        TranspositionTable::new(Self::buckets_in_mb(size))
     }
 
     /// Resizes the transposition table.
+    /// 
+    /// Every entry is lost, since the table is allocated again.
     pub fn resize(&mut self, buckets: usize) {
-        self.buckets = buckets;
-        self.table.resize_with(buckets, || None);
+        // Free the old table first, so both never have to fit in memory at once.
+        self.table = Vec::new();
+        self.table = Self::allocate(buckets);
+        self.buckets = self.table.len();
     }
 
     /// Resizes the transposition table from megabytes.
+    ///
+    /// GUIs often resend the hash size before every game, so a table which already
+    /// has the right size is only cleared rather than allocated again.
     pub fn resize_mb(&mut self, size: usize) {
-        let desired_size = size * 1024 * 1024;
-        let entry_size = std::mem::size_of::<TTEntry>();
-        let buckets = desired_size / entry_size;
+        let buckets = Self::buckets_in_mb(size);
+
+        if buckets == self.buckets {
+            self.clear();
+        } else {
+            self.resize(buckets);
+        }
+    }
 
-        self.resize(buckets)
+    /// The size of the table, in megabytes.
+    pub fn size_mb(&self) -> usize {
+        (self.buckets * std::mem::size_of::<Option<TTEntry>>()).div_ceil(1024 * 1024)
     }
 
     /// Indexes the internal table given a Zobrist hash.
@@ -83,23 +147,89 @@ impl TranspositionTable {
     }
 
     /// Stores an entry in the transposition table and returns its index.
+    ///
+    /// Exact scores come from PV nodes, which are rare and expensive, so within a search
+    /// they are only replaced by entries for other positions or at least as deep.
     pub fn store(&mut self, key: u64, entry: TTEntry) -> usize {
         // TODO sprt different replacement strategies
 
         let index = self.index(key);
+        if let Some(old) = &mut self.table[index]
+            && old.zobrist_key == entry.zobrist_key && old.generation == entry.generation
+            && old.evaluation_type == EvaluationType::Exact && entry.evaluation_type != EvaluationType::Exact && entry.depth < old.depth {
+            // The exact score stays, but a newer best move is still the better move to try first.
+            if entry.best_move != PackedMove::NONE {
+                old.best_move = entry.best_move;
+            }
+
+            return index;
+        }
+
         self.table[index] = Some(entry);
 
         index
     }
 
+    /// Points the entry of a position at a move, leaving its score alone.
+    ///
+    /// Positions without an entry are left out, since there is no score to store alongside the move.
+    pub fn store_move(&mut self, key: u64, best_move: PackedMove) {
+        let (index, generation) = (self.index(key), self.generation);
+        if let Some(entry) = &mut self.table[index] && entry.zobrist_key == key {
+            entry.best_move = best_move;
+            entry.generation = generation;
+        }
+    }
+
+    /// The current search generation.
+    pub fn generation(&self) -> u8 {
+        self.generation
+    }
+
+    /// Advances the search generation, so entries from older searches can be told apart.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// The number of generations since an entry was stored.
+    pub fn age(&self, entry: &TTEntry) -> u8 {
+        self.generation.wrapping_sub(entry.generation)
+    }
+
     /// Clears out the transposition table.
     pub fn clear(&mut self) {
-        self.table.iter_mut().for_each(|entry| *entry = None);
+        let chunk_size = self.buckets.div_ceil(Self::init_threads(self.buckets)).max(1);
+        std::thread::scope(|scope| {
+            for chunk in self.table.chunks_mut(chunk_size) {
+                scope.spawn(|| chunk.iter_mut().for_each(|entry| *entry = None));
+            }
+        });
+
+        self.generation = 0;
+    }
+}
+
+/// Asks the OS to back the huge page aligned part of an allocation with huge pages,
+/// which cuts down on TLB misses when probing a large table.
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(address: usize, length: usize) {
+    let (start, end) = (address.next_multiple_of(HUGE_PAGE_SIZE), (address + length) / HUGE_PAGE_SIZE * HUGE_PAGE_SIZE);
+
+    if end > start {
+        // SAFETY: the range lies within a live allocation, and the advice never changes its contents.
+        // Failure (such as transparent huge pages being disabled) only means normal pages are used.
+        unsafe { libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_HUGEPAGE) };
     }
 }
 
+/// Huge pages are only requested on Linux, where no special privileges are needed.
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_address: usize, _length: usize) {}
+
 #[cfg(test)]
 mod tests {
+    use crate::utils::piece_move::Move;
+
     use super::*;
 
     #[test]
@@ -111,7 +241,8 @@ mod tests {
             depth: 5,
             evaluation: 100,
             evaluation_type: EvaluationType::Exact,
-            best_move: None
+            best_move: PackedMove::NONE,
+            generation: table.generation()
         };
 
         let index = table.store(entry.zobrist_key, entry.clone());
@@ -122,13 +253,90 @@ mod tests {
             depth: 6,
             evaluation: 200,
             evaluation_type: EvaluationType::Exact,
-            best_move: None
+            best_move: PackedMove::NONE,
+            generation: table.generation()
         };
 
         let index = table.store(entry.zobrist_key, entry.clone());
         assert_eq!(table.get(entry.zobrist_key), Some(&entry));
 
+        table.new_search();
+        assert_eq!(table.age(table.get(entry.zobrist_key).unwrap()), 1);
+
         table.clear();
         assert_eq!(table.get(0x1234567890ABCDEF), None);
     }
+
+    #[test]
+    fn test_exact_priority() {
+        let mut table = TranspositionTable::new(16);
+        let exact = TTEntry { zobrist_key: 7, depth: 6, evaluation: 30, evaluation_type: EvaluationType::Exact, best_move: PackedMove::NONE, generation: 0 };
+        table.store(7, exact.clone());
+
+        // A shallower bound for the same position doesn't replace an exact score.
+        table.store(7, TTEntry { depth: 3, evaluation_type: EvaluationType::LowerBound, ..exact.clone() });
+        assert_eq!(table.get(7), Some(&exact));
+
+        // Its best move is still refreshed, unless the bound has none.
+        let best_move = Move::from_uci("e2e4").pack();
+        table.store(7, TTEntry { depth: 3, evaluation: -80, evaluation_type: EvaluationType::UpperBound, best_move, ..exact.clone() });
+        table.store(7, TTEntry { depth: 2, evaluation_type: EvaluationType::LowerBound, ..exact.clone() });
+        assert_eq!(table.get(7), Some(&TTEntry { best_move, ..exact.clone() }));
+
+        // Storing only a move keeps the score, and never makes up an entry.
+        let other_move = Move::from_uci("d2d4").pack();
+        table.store_move(7, other_move);
+        assert_eq!(table.get(7), Some(&TTEntry { best_move: other_move, ..exact.clone() }));
+
+        table.store_move(8, other_move);
+        assert!(table.get(8).is_none_or(|entry| entry.zobrist_key != 8));
+
+        // Deeper bounds and later searches do.
+        let deeper = TTEntry { depth: 7, evaluation_type: EvaluationType::UpperBound, ..exact.clone() };
+        table.store(7, deeper.clone());
+        assert_eq!(table.get(7), Some(&deeper));
+
+        table.store(7, exact.clone());
+        table.new_search();
+        let later = TTEntry { depth: 1, evaluation_type: EvaluationType::LowerBound, generation: table.generation(), ..exact };
+        table.store(7, later.clone());
+        assert_eq!(table.get(7), Some(&later));
+    }
+
+    #[test]
+    fn test_large_transposition_table() {
+        // Large enough to be initialized (and cleared) from several threads.
+        let mut table = TranspositionTable::from_mb(TT_PARALLEL_INIT_MIN_MB);
+        assert!(table.table.iter().all(Option::is_none));
+
+        let entry = TTEntry { zobrist_key: u64::MAX, depth: 1, evaluation: -50, evaluation_type: EvaluationType::LowerBound, best_move: PackedMove::NONE, generation: 0 };
+        table.store(entry.zobrist_key, entry.clone());
+        assert_eq!(table.get(u64::MAX), Some(&entry));
+
+        table.clear();
+        assert!(table.table.iter().all(Option::is_none));
+
+        table.resize_mb(1);
+        assert_eq!(table.table.len(), table.buckets);
+        assert!(table.table.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_hash_bounds() {
+        assert_eq!(TranspositionTable::clamp_mb(0), MIN_HASH_MB);
+        assert_eq!(TranspositionTable::clamp_mb(usize::MAX), MAX_HASH_MB);
+
+        let mut table = TranspositionTable::from_mb(0);
+        assert_eq!(table.buckets, TranspositionTable::from_mb(MIN_HASH_MB).buckets);
+        assert_eq!(table.size_mb(), MIN_HASH_MB);
+
+        let entry = TTEntry { zobrist_key: 42, depth: 3, evaluation: 10, evaluation_type: EvaluationType::Exact, best_move: PackedMove::NONE, generation: 0 };
+        table.store(entry.zobrist_key, entry);
+
+        // Resizing to the current size keeps the allocation, but still empties it.
+        let buckets = table.buckets;
+        table.resize_mb(MIN_HASH_MB);
+        assert_eq!(table.buckets, buckets);
+        assert_eq!(table.get(42), None);
+    }
 }
\ No newline at end of file